@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use sha2::{Sha256, Digest};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use crate::clock;
+use crate::Blockchain;
+use crate::consensus::Block;
+use crate::franchise_network::FranchiseNetwork;
+use crate::ipfs_storage::{IPFSStorage, PinReason};
+
+// Зашифрованные бэкапы состояния ноды в IPFS хранилище.
+// Компонент (Blockchain, FranchiseNetwork, цепь блоков P2P ноды) делится на части по полям верхнего уровня
+// (цепь — по блокам); каждая часть
+// шифруется ChaCha20-Poly1305 ключом оператора и хранится отдельным объектом. Бэкап — зашифрованный
+// манифест со списком частей; неизмененные с прошлого бэкапа части берутся из его манифеста,
+// поэтому новый бэкап записывает только изменившиеся поля. Закрепляется манифест, части он удерживает ссылками.
+
+const MANIFEST_VERSION: u32 = 1;
+const NONCE_SIZE: usize = 12;
+const MANIFEST_AAD: &str = "backup|manifest";
+pub const BLOCKCHAIN_COMPONENT: &str = "blockchain";
+pub const FRANCHISE_NETWORK_COMPONENT: &str = "franchise_network";
+pub const FRANCHISE_CHAIN_COMPONENT: &str = "franchise_chain";
+
+// Симметричный ключ оператора, 32 байта
+pub struct BackupKey(Key);
+
+impl BackupKey {
+    pub fn from_hex(key_hex: &str) -> Result<Self, String> {
+        let bytes = hex::decode(key_hex.trim())
+            .map_err(|e| format!("Invalid backup key hex: {}", e))?;
+        let key: [u8; 32] = bytes.try_into()
+            .map_err(|_| "Backup key must be 32 bytes".to_string())?;
+        Ok(Self(key.into()))
+    }
+
+    // nonce || шифртекст; aad привязывает шифртекст к его месту в бэкапе
+    fn seal(&self, plaintext: &[u8], aad: &str) -> Result<Vec<u8>, String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = ChaCha20Poly1305::new(&self.0)
+            .encrypt(&nonce, Payload { msg: plaintext, aad: aad.as_bytes() })
+            .map_err(|_| "Backup encryption failed".to_string())?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    fn open(&self, sealed: &[u8], aad: &str) -> Result<Vec<u8>, String> {
+        if sealed.len() < NONCE_SIZE {
+            return Err(format!("Encrypted {} is truncated", aad));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+        ChaCha20Poly1305::new(&self.0)
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| format!("Failed to decrypt {}: wrong key or tampered data", aad))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created_at: u64,
+    pub previous: Option<String>, // CID манифеста предыдущего бэкапа
+    pub components: Vec<BackupComponent>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupComponent {
+    pub name: String,
+    pub sha256: String, // Хеш всего компонента в JSON, сверяется после сборки из частей
+    pub parts: Vec<BackupPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupPart {
+    pub field: String,
+    pub sha256: String, // Хеш открытого текста части
+    pub cid: String,    // Зашифрованная часть в хранилище
+    pub size: usize,
+}
+
+impl BackupManifest {
+    // Новые объекты, записанные этим бэкапом (остальные части взяты из предыдущего)
+    pub fn new_parts(&self, previous: Option<&BackupManifest>) -> usize {
+        let reused = |cid: &str| previous.is_some_and(|manifest| {
+            manifest.components.iter().flat_map(|c| &c.parts).any(|part| part.cid == cid)
+        });
+        self.components.iter().flat_map(|c| &c.parts).filter(|part| !reused(&part.cid)).count()
+    }
+}
+
+// Восстановленное и проверенное состояние ноды
+pub struct RestoredBackup {
+    pub manifest: BackupManifest,
+    pub blockchain: Blockchain,
+    pub franchise_network: FranchiseNetwork, // Состояние сети до первого блока franchise_chain
+    pub franchise_chain: Vec<Block>,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+fn part_aad(component: &str, field: &str) -> String {
+    format!("backup|{}|{}", component, field)
+}
+
+// Последний закрепленный бэкап хранилища
+pub fn latest_backup(storage: &IPFSStorage) -> Option<String> {
+    storage.pins()
+        .filter_map(|(hash, reason)| match reason {
+            PinReason::Backup { created_at } => Some((*created_at, hash.to_string())),
+            _ => None,
+        })
+        .max()
+        .map(|(_, hash)| hash)
+}
+
+pub fn read_manifest(storage: &IPFSStorage, key: &BackupKey, cid: &str) -> Result<BackupManifest, String> {
+    let manifest: BackupManifest = serde_json::from_slice(&key.open(&storage.retrieve_bytes(cid)?, MANIFEST_AAD)?)
+        .map_err(|e| format!("Invalid backup manifest {}: {}", cid, e))?;
+    if manifest.version != MANIFEST_VERSION {
+        return Err(format!("Unsupported backup manifest version {}", manifest.version));
+    }
+    Ok(manifest)
+}
+
+// Ключ блока в компоненте цепи: по одной части на блок, порядок ключей — порядок высот
+fn chain_field(height: usize) -> String {
+    format!("{:020}", height)
+}
+
+// Новый бэкап поверх последнего; возвращает CID манифеста.
+// network — состояние сети до первого блока chain: после восстановления нода заново применяет цепь.
+pub fn create_backup(storage: &mut IPFSStorage, key: &BackupKey, blockchain: &Blockchain, network: &FranchiseNetwork, chain: &[Block]) -> Result<String, String> {
+    let previous_cid = latest_backup(storage);
+    let previous = match &previous_cid {
+        Some(cid) => Some(read_manifest(storage, key, cid)?),
+        None => None,
+    };
+
+    let chain: Result<Map<String, Value>, _> = chain.iter().enumerate()
+        .map(|(height, block)| serde_json::to_value(block).map(|block| (chain_field(height), block)))
+        .collect();
+    let components = [
+        (BLOCKCHAIN_COMPONENT, serde_json::to_value(blockchain)),
+        (FRANCHISE_NETWORK_COMPONENT, serde_json::to_value(network)),
+        (FRANCHISE_CHAIN_COMPONENT, chain.map(Value::Object)),
+    ];
+    let mut manifest = BackupManifest {
+        version: MANIFEST_VERSION,
+        // Метки бэкапов строго растут, чтобы latest_backup однозначно выбирал последний
+        created_at: clock::now_secs().max(previous.as_ref().map_or(0, |manifest| manifest.created_at + 1)),
+        previous: previous_cid,
+        components: Vec::new(),
+    };
+    for (name, value) in components {
+        let value = value.map_err(|e| format!("Failed to serialize {}: {}", name, e))?;
+        manifest.components.push(backup_component(storage, key, name, value, previous.as_ref())?);
+    }
+
+    let references = manifest.components.iter().flat_map(|c| &c.parts).map(|part| part.cid.clone()).collect();
+    let manifest_json = serde_json::to_vec(&manifest)
+        .map_err(|e| format!("Failed to serialize backup manifest: {}", e))?;
+    let sealed = key.seal(&manifest_json, MANIFEST_AAD)?;
+    let metadata = HashMap::from([("backup".to_string(), "manifest".to_string())]);
+    let cid = storage.store_object(&sealed, "application/octet-stream", metadata, references,
+                                   Some(PinReason::Backup { created_at: manifest.created_at }))?;
+
+    println!("🔐 Backup {} stored: {} new parts, previous {:?}", cid, manifest.new_parts(previous.as_ref()), manifest.previous);
+    Ok(cid)
+}
+
+fn backup_component(storage: &mut IPFSStorage, key: &BackupKey, name: &str, value: Value,
+                    previous: Option<&BackupManifest>) -> Result<BackupComponent, String> {
+    let Value::Object(fields) = value else {
+        return Err(format!("Component {} is not a JSON object", name));
+    };
+    let previous_parts: HashMap<&str, &BackupPart> = previous
+        .and_then(|manifest| manifest.components.iter().find(|component| component.name == name))
+        .map(|component| component.parts.iter().map(|part| (part.field.as_str(), part)).collect())
+        .unwrap_or_default();
+
+    let sha256 = sha256_hex(&serde_json::to_vec(&fields).map_err(|e| e.to_string())?);
+    let mut parts = Vec::new();
+    for (field, value) in fields {
+        let plaintext = serde_json::to_vec(&value).map_err(|e| e.to_string())?;
+        let part_sha256 = sha256_hex(&plaintext);
+        // Неизмененная часть, которая все еще есть в хранилище, не перешифровывается
+        let reusable = previous_parts.get(field.as_str())
+            .filter(|part| part.sha256 == part_sha256 && storage.index.contains_key(&part.cid));
+        let cid = match reusable {
+            Some(part) => part.cid.clone(),
+            None => {
+                let sealed = key.seal(&plaintext, &part_aad(name, &field))?;
+                storage.store_object(&sealed, "application/octet-stream", HashMap::new(), Vec::new(), None)?
+            }
+        };
+        parts.push(BackupPart { field, sha256: part_sha256, cid, size: plaintext.len() });
+    }
+    Ok(BackupComponent { name: name.to_string(), sha256, parts })
+}
+
+// Состояние из бэкапа: каждая часть проверяется по CID, тегу AEAD и хешу открытого текста,
+// собранный компонент — по хешу из манифеста
+pub fn restore_from_backup(storage: &IPFSStorage, key: &BackupKey, cid: &str) -> Result<RestoredBackup, String> {
+    let manifest = read_manifest(storage, key, cid)?;
+    let blockchain = serde_json::from_value(Value::Object(restore_component(storage, key, &manifest, BLOCKCHAIN_COMPONENT)?))
+        .map_err(|e| format!("Invalid {} in backup: {}", BLOCKCHAIN_COMPONENT, e))?;
+    let franchise_network = serde_json::from_value(Value::Object(restore_component(storage, key, &manifest, FRANCHISE_NETWORK_COMPONENT)?))
+        .map_err(|e| format!("Invalid {} in backup: {}", FRANCHISE_NETWORK_COMPONENT, e))?;
+    // Бэкапы до появления цепи в бэкапе ее не содержат
+    let mut franchise_chain = Vec::new();
+    if manifest.components.iter().any(|component| component.name == FRANCHISE_CHAIN_COMPONENT) {
+        let blocks = restore_component(storage, key, &manifest, FRANCHISE_CHAIN_COMPONENT)?;
+        for (height, (field, block)) in blocks.into_iter().enumerate() {
+            if field != chain_field(height) {
+                return Err(format!("Block {} is missing from {} in backup", height, FRANCHISE_CHAIN_COMPONENT));
+            }
+            franchise_chain.push(serde_json::from_value(block)
+                .map_err(|e| format!("Invalid block {} in backup: {}", height, e))?);
+        }
+    }
+    Ok(RestoredBackup { manifest, blockchain, franchise_network, franchise_chain })
+}
+
+fn restore_component(storage: &IPFSStorage, key: &BackupKey, manifest: &BackupManifest, name: &str) -> Result<Map<String, Value>, String> {
+    let component = manifest.components.iter().find(|component| component.name == name)
+        .ok_or_else(|| format!("Backup has no {} component", name))?;
+    let mut fields = Map::new();
+    for part in &component.parts {
+        let plaintext = key.open(&storage.retrieve_bytes(&part.cid)?, &part_aad(name, &part.field))?;
+        if sha256_hex(&plaintext) != part.sha256 {
+            return Err(format!("Part {}.{} does not match the manifest", name, part.field));
+        }
+        let value = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Invalid part {}.{}: {}", name, part.field, e))?;
+        fields.insert(part.field.clone(), value);
+    }
+    if sha256_hex(&serde_json::to_vec(&fields).map_err(|e| e.to_string())?) != component.sha256 {
+        return Err(format!("Component {} does not match the manifest", name));
+    }
+    Ok(fields)
+}
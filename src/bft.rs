@@ -0,0 +1,474 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+use crate::chain_sync::ChainSync;
+use crate::clock;
+use crate::consensus::{block_signing_bytes, Block, BlockSignature, ConsensusAlgorithm, SelectionSeed, Transaction};
+use crate::crypto::{self, Keypair};
+use crate::executor;
+use crate::franchise_network::FranchiseNetwork;
+use crate::rewards::{self, EquivocationEvidence};
+use crate::state_tree;
+
+// BFT раунд финализации блока среди выбранных валидаторов (в духе Tendermint):
+//   Proposal — лидер раунда предлагает блок;
+//   Prevote  — валидаторы проверяют блок и голосуют за него;
+//   Commit   — увидев 2/3+1 Prevote за блок, валидатор подписывает сам блок.
+// Блок финален, когда в одном раунде собрано 2/3+1 Commit: эти подписи и есть BlockSignature блока.
+// Если за таймаут раунд не завершился, начинается следующий с другим лидером.
+//
+// Валидатор, подписавший Commit, блокируется на этом блоке и голосует только за него,
+// пока не увидит 2/3+1 Prevote за другой блок в более позднем раунде. Поэтому два разных блока
+// на одной высоте не могут оба собрать кворум, пока честных валидаторов больше 2/3.
+//
+// Движок не делает ввода-вывода: он возвращает сообщения для рассылки и финализированный блок.
+
+pub const ROUND_TIMEOUT_SECS: u64 = 10;
+const ROUND_TIMEOUT_STEP_SECS: u64 = 5; // Каждый следующий раунд ждет дольше
+const MAX_ROUND_TIMEOUT_SECS: u64 = 60;
+pub const MAX_BLOCK_TRANSACTIONS: usize = 500;
+const MAX_CLOCK_DRIFT_SECS: u64 = 15; // Насколько метка предложенного блока может опережать наши часы
+const MAX_FUTURE_MESSAGES: usize = 256; // Сообщения следующей высоты, пришедшие раньше ее блока
+
+// Предложение блока лидером раунда
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Proposal {
+    pub block: Block, // Без подписей валидаторов
+    pub round: u32,
+    pub proposer: u64,
+    pub signature: String,
+}
+
+impl Proposal {
+    pub fn new(block: Block, round: u32, proposer: u64, keypair: &Keypair) -> Self {
+        let signature = keypair.sign(&Self::signing_bytes(block.height, round, &block.hash));
+        Self { block, round, proposer, signature }
+    }
+
+    fn signing_bytes(height: u64, round: u32, hash: &str) -> Vec<u8> {
+        format!("proposal|{}|{}|{}", height, round, hash).into_bytes()
+    }
+
+    fn verify(&self, public_key: &str) -> bool {
+        crypto::verify(public_key, &Self::signing_bytes(self.block.height, self.round, &self.block.hash), &self.signature)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VoteKind {
+    Prevote,
+    Commit,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vote {
+    pub kind: VoteKind,
+    pub height: u64,
+    pub round: u32,
+    pub block_hash: String,
+    pub validator_id: u64,
+    pub signature: String,
+    pub timestamp: u64,
+}
+
+impl Vote {
+    pub fn new(kind: VoteKind, height: u64, round: u32, block_hash: String, validator_id: u64, keypair: &Keypair) -> Self {
+        let signature = keypair.sign(&Self::signing_bytes(kind, height, round, &block_hash, validator_id));
+        Self { kind, height, round, block_hash, validator_id, signature, timestamp: clock::now_secs() }
+    }
+
+    // Commit подписывает байты блока, чтобы подпись годилась для BlockSignature
+    fn signing_bytes(kind: VoteKind, height: u64, round: u32, hash: &str, validator_id: u64) -> Vec<u8> {
+        match kind {
+            VoteKind::Prevote => format!("prevote|{}|{}|{}|{}", height, round, hash, validator_id).into_bytes(),
+            VoteKind::Commit => block_signing_bytes(height, round, hash, validator_id),
+        }
+    }
+
+    fn verify(&self, public_key: &str) -> bool {
+        let bytes = Self::signing_bytes(self.kind, self.height, self.round, &self.block_hash, self.validator_id);
+        crypto::verify(public_key, &bytes, &self.signature)
+    }
+
+    fn block_signature(&self) -> BlockSignature {
+        BlockSignature {
+            validator_id: self.validator_id,
+            round: self.round,
+            signature: self.signature.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BftMessage {
+    Proposal(Box<Proposal>),
+    Vote(Vote),
+}
+
+impl BftMessage {
+    fn height(&self) -> u64 {
+        match self {
+            BftMessage::Proposal(proposal) => proposal.block.height,
+            BftMessage::Vote(vote) => vote.height,
+        }
+    }
+}
+
+// Результат шага движка
+#[derive(Debug, Clone, PartialEq)]
+pub enum BftOutput {
+    Broadcast(BftMessage), // Свое сообщение или новое чужое для пересылки дальше
+    Finalized(Block),      // Блок с подписями кворума, готов к импорту
+}
+
+// Состояние ноды, нужное движку на каждом шаге
+pub struct BftEnv<'a> {
+    pub chain: &'a ChainSync,
+    pub network: &'a FranchiseNetwork,
+    pub committed: &'a FranchiseNetwork, // Состояние до вершины цепи (executor::apply_chain_update)
+    pub consensus: &'a ConsensusAlgorithm,
+    pub pending: &'a [Transaction],
+    pub keypair: &'a Keypair,
+}
+
+#[derive(Debug, Default)]
+pub struct Bft {
+    node_id: u64,
+    height: u64,
+    parent_hash: Option<String>, // Вершина цепи, на которой идет текущая высота; None — еще не начали
+    parent_timestamp: u64,
+    validators: Vec<u64>, // Отсортированы по id, лидер раунда — validators[(height + round) % len]
+    round: u32,
+    round_started: u64,
+    proposals: BTreeMap<u32, Block>, // Раунд -> проверенный предложенный блок
+    prevotes: BTreeMap<u32, HashMap<u64, Vote>>, // Раунд -> валидатор -> голос
+    commits: BTreeMap<u32, HashMap<u64, Vote>>,
+    proposed: HashSet<u32>, // Раунды, в которых мы уже отправили свое предложение/голос
+    prevoted: HashSet<u32>,
+    committed: HashSet<u32>,
+    locked: Option<(u32, Block)>, // Блок, на котором мы заблокированы после своего Commit
+    valid: Option<(u32, Block)>,  // Последний блок с 2/3+1 Prevote — лидер предлагает его повторно
+    finalized: bool,
+    future: Vec<BftMessage>,
+    evidence: Vec<EquivocationEvidence>, // Двойные подписи Commit, еще не переданные ноде
+}
+
+impl Bft {
+    pub fn new(node_id: u64) -> Self {
+        Self { node_id, ..Self::default() }
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn validators(&self) -> &[u64] {
+        &self.validators
+    }
+
+    // Обнаруженные двойные подписи; нода публикует их транзакциями Evidence
+    pub fn take_evidence(&mut self) -> Vec<EquivocationEvidence> {
+        std::mem::take(&mut self.evidence)
+    }
+
+    pub fn leader(&self, round: u32) -> Option<u64> {
+        if self.validators.is_empty() {
+            return None;
+        }
+        let index = (self.height + round as u64) % self.validators.len() as u64;
+        Some(self.validators[index as usize])
+    }
+
+    fn quorum(&self) -> usize {
+        self.validators.len() * 2 / 3 + 1 // Как в validate_block
+    }
+
+    fn round_timeout(round: u32) -> u64 {
+        (ROUND_TIMEOUT_SECS + round as u64 * ROUND_TIMEOUT_STEP_SECS).min(MAX_ROUND_TIMEOUT_SECS)
+    }
+
+    // Периодический шаг: смена высоты после нового блока, таймаут раунда, свои предложения и голоса
+    pub fn tick(&mut self, env: &BftEnv) -> Vec<BftOutput> {
+        let mut outputs = self.follow_chain(env);
+        if clock::now_secs() >= self.round_started + Self::round_timeout(self.round) {
+            if !self.proposals.is_empty() {
+                println!("⏳ Round {} at height {} timed out", self.round, self.height);
+            }
+            self.start_round(self.round + 1);
+        }
+        outputs.extend(self.progress(env));
+        outputs
+    }
+
+    // Сообщение от пира; Err — сообщение невалидно (нарушение протокола)
+    pub fn handle(&mut self, message: BftMessage, env: &BftEnv) -> Result<Vec<BftOutput>, String> {
+        let mut outputs = self.follow_chain(env);
+        outputs.extend(self.receive(message, env)?);
+        outputs.extend(self.progress(env));
+        Ok(outputs)
+    }
+
+    // Новая высота, когда основная цепь сменила вершину (свой финализированный блок, блок от пира или reorg)
+    fn follow_chain(&mut self, env: &BftEnv) -> Vec<BftOutput> {
+        let tip_hash = env.chain.tip_hash();
+        if self.parent_hash.as_ref() == Some(&tip_hash) {
+            return Vec::new();
+        }
+
+        let node_id = self.node_id;
+        let future = std::mem::take(&mut self.future);
+        let evidence = std::mem::take(&mut self.evidence);
+        *self = Self::new(node_id);
+        self.evidence = evidence;
+        self.height = env.chain.height();
+        self.parent_hash = Some(tip_hash);
+        self.parent_timestamp = env.chain.main_chain().last().map(|b| b.timestamp).unwrap_or(0);
+        let seed = env.chain.selection_seed(self.height).unwrap_or_else(SelectionSeed::genesis);
+        self.validators = env.consensus.select_validators(env.network, self.height, &seed).selected_validators;
+        self.validators.sort();
+        self.round_started = clock::now_secs();
+
+        // Сообщения этой высоты, пришедшие до ее блока
+        let mut outputs = Vec::new();
+        let height = self.height;
+        for message in future.into_iter().filter(|m| m.height() == height) {
+            if let Ok(relayed) = self.receive(message, env) {
+                outputs.extend(relayed);
+            }
+        }
+        outputs
+    }
+
+    fn start_round(&mut self, round: u32) {
+        self.round = round;
+        self.round_started = clock::now_secs();
+        self.finalized = false;
+    }
+
+    // Проверка и запись сообщения; новое валидное сообщение возвращается для пересылки
+    fn receive(&mut self, message: BftMessage, env: &BftEnv) -> Result<Vec<BftOutput>, String> {
+        let height = message.height();
+        if height != self.height {
+            // Валидаторов следующей высоты еще не знаем — проверим, когда до нее дойдем
+            if height == self.height + 1 && self.future.len() < MAX_FUTURE_MESSAGES && !self.future.contains(&message) {
+                self.future.push(message);
+            }
+            return Ok(Vec::new());
+        }
+
+        let is_new = match &message {
+            BftMessage::Proposal(proposal) => self.receive_proposal(proposal, env)?,
+            BftMessage::Vote(vote) => self.receive_vote(vote, env)?,
+        };
+        Ok(if is_new { vec![BftOutput::Broadcast(message)] } else { Vec::new() })
+    }
+
+    fn receive_proposal(&mut self, proposal: &Proposal, env: &BftEnv) -> Result<bool, String> {
+        let block = &proposal.block;
+        if self.leader(proposal.round) != Some(proposal.proposer) {
+            return Err(format!("Node {} is not the leader of round {}", proposal.proposer, proposal.round));
+        }
+        if !env.network.node_public_key(proposal.proposer).is_some_and(|key| proposal.verify(key)) {
+            return Err(format!("Invalid proposal signature from node {}", proposal.proposer));
+        }
+        if let Some(known) = self.proposals.get(&proposal.round) {
+            if known.hash == block.hash {
+                return Ok(false);
+            }
+            return Err(format!("Node {} proposed two blocks in round {}", proposal.proposer, proposal.round));
+        }
+
+        // Блок на другой вершине: мы или лидер еще не получили последний блок, это не нарушение
+        if self.parent_hash.as_deref() != Some(block.previous_hash.as_str()) {
+            return Ok(false);
+        }
+        if block.timestamp < self.parent_timestamp || block.timestamp > clock::now_secs() + MAX_CLOCK_DRIFT_SECS {
+            return Ok(false);
+        }
+        if !block.signatures.is_empty() || !env.consensus.validate_block_content(block, env.network) {
+            return Err(format!("Invalid block {} proposed by node {}", block.hash, proposal.proposer));
+        }
+        // Легкие клиенты верят заголовку с подписями кворума, поэтому валидаторы высоты
+        // и корень состояния сверяются со своими до голосования
+        if block.validators != self.validators || block.state_root != Self::state_root_after_parent(block, env)? {
+            return Err(format!("Block {} proposed by node {} commits to another state", block.hash, proposal.proposer));
+        }
+
+        self.proposals.insert(proposal.round, block.clone());
+        // Предложение более позднего раунда — остальные ноды уже там
+        if proposal.round > self.round {
+            self.start_round(proposal.round);
+        }
+        Ok(true)
+    }
+
+    // Корень состояния после родителя с подписями из предложения: наша копия родителя
+    // могла собрать другой кворум Commit, и награды за него у нас другие
+    fn state_root_after_parent(block: &Block, env: &BftEnv) -> Result<String, String> {
+        let parent = match env.chain.main_chain().last() {
+            Some(parent) if block.parent_signatures != parent.signatures => parent.with_signatures(block.parent_signatures.clone()),
+            _ => return Ok(state_tree::root(env.network)),
+        };
+        if !env.consensus.validate_signatures(&parent, &parent.validators, env.network) {
+            return Err(format!("Block {} carries invalid signatures of its parent", block.hash));
+        }
+        let mut network = env.committed.clone();
+        executor::apply_block(&mut network, env.consensus, env.chain, &parent);
+        Ok(state_tree::root(&network))
+    }
+
+    fn receive_vote(&mut self, vote: &Vote, env: &BftEnv) -> Result<bool, String> {
+        if !self.validators.contains(&vote.validator_id) {
+            return Err(format!("Node {} is not a validator at height {}", vote.validator_id, vote.height));
+        }
+        if !env.network.node_public_key(vote.validator_id).is_some_and(|key| vote.verify(key)) {
+            return Err(format!("Invalid {:?} signature from node {}", vote.kind, vote.validator_id));
+        }
+        if !self.record_vote(vote.clone())? {
+            return Ok(false);
+        }
+
+        // Больше 1/3 валидаторов уже в более позднем раунде — догоняем
+        if vote.round > self.round {
+            let ahead: HashSet<u64> = self.prevotes.get(&vote.round).into_iter()
+                .chain(self.commits.get(&vote.round))
+                .flat_map(|votes| votes.keys().copied())
+                .collect();
+            if ahead.len() * 3 > self.validators.len() {
+                self.start_round(vote.round);
+            }
+        }
+        Ok(true)
+    }
+
+    // Запись голоса; false — такой голос уже есть, Err — второй голос валидатора за другой блок
+    fn record_vote(&mut self, vote: Vote) -> Result<bool, String> {
+        let votes = match vote.kind {
+            VoteKind::Prevote => self.prevotes.entry(vote.round).or_default(),
+            VoteKind::Commit => self.commits.entry(vote.round).or_default(),
+        };
+        match votes.get(&vote.validator_id) {
+            Some(existing) if existing.block_hash == vote.block_hash => Ok(false),
+            Some(existing) => {
+                // Два Commit — две подписи под разными блоками одной высоты
+                if vote.kind == VoteKind::Commit {
+                    let first = existing.block_signature();
+                    let second = vote.block_signature();
+                    self.evidence.push(EquivocationEvidence::new((&existing.block_hash, &first), (&vote.block_hash, &second), vote.height));
+                }
+                Err(format!("Node {} sent conflicting {:?} votes in round {}", vote.validator_id, vote.kind, vote.round))
+            }
+            None => {
+                votes.insert(vote.validator_id, vote);
+                Ok(true)
+            }
+        }
+    }
+
+    // Хеш блока, набравшего кворум голосов в раунде
+    fn quorum_hash(&self, votes: Option<&HashMap<u64, Vote>>) -> Option<String> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for vote in votes.into_iter().flat_map(|votes| votes.values()) {
+            *counts.entry(vote.block_hash.as_str()).or_default() += 1;
+        }
+        counts.into_iter()
+            .find(|(_, count)| *count >= self.quorum())
+            .map(|(hash, _)| hash.to_string())
+    }
+
+    // Был ли кворум Prevote за блок в раунде позже указанного
+    fn has_polka_after(&self, hash: &str, round: u32) -> bool {
+        self.prevotes.range(round + 1..)
+            .any(|(_, votes)| self.quorum_hash(Some(votes)).as_deref() == Some(hash))
+    }
+
+    // Действия, ставшие возможными после нового сообщения или смены раунда
+    fn progress(&mut self, env: &BftEnv) -> Vec<BftOutput> {
+        let mut outputs = Vec::new();
+        if self.finalized || self.validators.is_empty() {
+            return outputs;
+        }
+        let round = self.round;
+        let is_validator = self.validators.contains(&self.node_id);
+
+        // Лидер предлагает блок: ранее набравший Prevote или новый из ожидающих транзакций
+        if self.leader(round) == Some(self.node_id) && !self.proposed.contains(&round) && !self.proposals.contains_key(&round) {
+            let block = match &self.valid {
+                Some((_, block)) => Some(block.clone()),
+                None if !env.pending.is_empty() => {
+                    let transactions = env.pending.iter().take(MAX_BLOCK_TRANSACTIONS).cloned().collect();
+                    let parent_hash = self.parent_hash.clone().unwrap_or_default();
+                    let parent_signatures = env.chain.main_chain().last().map(|parent| parent.signatures.clone()).unwrap_or_default();
+                    Some(Block::new(self.height, parent_hash, transactions)
+                        .with_header_commitments(self.validators.clone(), parent_signatures, state_tree::root(env.network)))
+                }
+                None => None,
+            };
+            if let Some(block) = block {
+                println!("📦 Node {} proposes block {} at height {} (round {})", self.node_id, block.hash, self.height, round);
+                self.proposed.insert(round);
+                self.proposals.insert(round, block.clone());
+                outputs.push(BftOutput::Broadcast(BftMessage::Proposal(Box::new(Proposal::new(block, round, self.node_id, env.keypair)))));
+            }
+        }
+
+        // Prevote за предложение раунда, если мы не заблокированы на другом блоке
+        if is_validator && !self.prevoted.contains(&round) {
+            if let Some(block) = self.proposals.get(&round) {
+                let allowed = match &self.locked {
+                    None => true,
+                    Some((locked_round, locked)) => locked.hash == block.hash || self.has_polka_after(&block.hash, *locked_round),
+                };
+                if allowed {
+                    let vote = Vote::new(VoteKind::Prevote, self.height, round, block.hash.clone(), self.node_id, env.keypair);
+                    self.prevoted.insert(round);
+                    let _ = self.record_vote(vote.clone());
+                    outputs.push(BftOutput::Broadcast(BftMessage::Vote(vote)));
+                }
+            }
+        }
+
+        // Блок с кворумом Prevote становится кандидатом на повторное предложение
+        let latest_polka = self.prevotes.iter().rev().find_map(|(polka_round, votes)| {
+            let hash = self.quorum_hash(Some(votes))?;
+            self.proposals.get(polka_round).filter(|b| b.hash == hash).map(|b| (*polka_round, b.clone()))
+        });
+        if let Some((polka_round, block)) = latest_polka {
+            if self.valid.as_ref().is_none_or(|(valid_round, _)| *valid_round < polka_round) {
+                self.valid = Some((polka_round, block));
+            }
+        }
+
+        // Commit: кворум Prevote за блок в текущем раунде
+        if is_validator && !self.committed.contains(&round) {
+            let hash = self.quorum_hash(self.prevotes.get(&round));
+            if let Some(block) = self.proposals.get(&round).filter(|b| Some(&b.hash) == hash.as_ref()).cloned() {
+                let vote = Vote::new(VoteKind::Commit, self.height, round, block.hash.clone(), self.node_id, env.keypair);
+                self.committed.insert(round);
+                self.locked = Some((round, block));
+                let _ = self.record_vote(vote.clone());
+                outputs.push(BftOutput::Broadcast(BftMessage::Vote(vote)));
+            }
+        }
+
+        // Финализация: кворум Commit в одном раунде за известный блок
+        let decided = self.commits.iter().find_map(|(commit_round, votes)| {
+            let hash = self.quorum_hash(Some(votes))?;
+            let mut block = self.proposals.get(commit_round).filter(|b| b.hash == hash)?.clone();
+            let mut signatures: Vec<BlockSignature> = votes.values()
+                .filter(|vote| vote.block_hash == hash)
+                .map(|vote| vote.block_signature())
+                .collect();
+            signatures.sort_by_key(|signature| signature.validator_id);
+            block.validator_rewards = rewards::block_rewards(&signatures);
+            block.signatures = signatures;
+            Some((*commit_round, block))
+        });
+        if let Some((commit_round, block)) = decided {
+            println!("✅ Block {} finalized at height {} (round {})", block.hash, self.height, commit_round);
+            self.finalized = true;
+            outputs.push(BftOutput::Finalized(block));
+        }
+        outputs
+    }
+}
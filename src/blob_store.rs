@@ -0,0 +1,301 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+// Локальное контентно-адресуемое хранилище блоков на диске.
+// Блок адресуется CIDv1 (версия 1, кодек, multihash sha2-256), текстовая форма — multibase base32 ("b...").
+// Данные режутся на куски по CHUNK_SIZE: небольшие данные — один raw блок, большие — raw куски
+// и корневой DAG-JSON блок со ссылками на них. Каждый блок при чтении сверяется со своим CID.
+
+pub const RAW_CODEC: u64 = 0x55;
+pub const DAG_JSON_CODEC: u64 = 0x0129;
+pub const CHUNK_SIZE: usize = 256 * 1024;
+const CID_VERSION: u64 = 1;
+const SHA2_256: u64 = 0x12;
+const BLOCKS_DIR: &str = "blocks";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cid {
+    codec: u64,
+    digest: [u8; 32],
+}
+
+impl Cid {
+    pub fn of(codec: u64, bytes: &[u8]) -> Self {
+        Self { codec, digest: Sha256::digest(bytes).into() }
+    }
+
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    pub fn verify(&self, bytes: &[u8]) -> bool {
+        Sha256::digest(bytes).as_slice() == self.digest
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(36);
+        for value in [CID_VERSION, self.codec, SHA2_256, self.digest.len() as u64] {
+            write_varint(&mut bytes, value);
+        }
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut rest = bytes;
+        let version = read_varint(&mut rest)?;
+        let codec = read_varint(&mut rest)?;
+        let hash_code = read_varint(&mut rest)?;
+        let length = read_varint(&mut rest)?;
+        if version != CID_VERSION {
+            return Err(format!("Unsupported CID version {}", version));
+        }
+        if hash_code != SHA2_256 || length != 32 || rest.len() != 32 {
+            return Err("CID multihash must be a sha2-256 digest".to_string());
+        }
+        Ok(Self { codec, digest: rest.try_into().unwrap() })
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "b{}", base32_encode(&self.to_bytes()))
+    }
+}
+
+impl FromStr for Cid {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let encoded = s.strip_prefix('b').ok_or_else(|| format!("CID {} is not multibase base32", s))?;
+        let bytes = base32_decode(encoded).ok_or_else(|| format!("CID {} is not valid base32", s))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("Truncated CID")?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err("CID varint is too long".to_string())
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+// RFC 4648 base32 строчными буквами без выравнивания
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 8 / 5 + 1);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+// Корневой блок данных из нескольких кусков
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChunkedRoot {
+    size: u64,
+    links: Vec<ChunkLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChunkLink {
+    cid: String,
+    size: u64,
+}
+
+impl ChunkedRoot {
+    // Размер из корня — чужие данные: ему верим, только если он равен сумме ссылок, каждая не больше куска
+    fn checked_size(&self) -> Result<usize, String> {
+        let mut total: u64 = 0;
+        for link in &self.links {
+            if link.size > CHUNK_SIZE as u64 {
+                return Err(format!("Chunk {} claims {} bytes, chunks are at most {}", link.cid, link.size, CHUNK_SIZE));
+            }
+            total += link.size;
+        }
+        if total != self.size {
+            return Err(format!("Chunk links add up to {} bytes, root claims {}", total, self.size));
+        }
+        usize::try_from(total).map_err(|_| format!("Content of {} bytes does not fit in memory", total))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn open(root: &Path) -> Result<Self, String> {
+        fs::create_dir_all(root.join(BLOCKS_DIR))
+            .map_err(|e| format!("Failed to create blob store in {}: {}", root.display(), e))?;
+        Ok(Self { root: root.to_path_buf() })
+    }
+
+    // Сохраняет данные и возвращает CID корня
+    pub fn put(&self, bytes: &[u8]) -> Result<Cid, String> {
+        if bytes.len() <= CHUNK_SIZE {
+            return self.put_block(RAW_CODEC, bytes);
+        }
+        let links = bytes.chunks(CHUNK_SIZE)
+            .map(|chunk| {
+                let cid = self.put_block(RAW_CODEC, chunk)?;
+                Ok(ChunkLink { cid: cid.to_string(), size: chunk.len() as u64 })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let root = serde_json::to_vec(&ChunkedRoot { size: bytes.len() as u64, links })
+            .map_err(|e| format!("Failed to encode chunk links: {}", e))?;
+        self.put_block(DAG_JSON_CODEC, &root)
+    }
+
+    // Данные по CID корня; каждый блок проверяется по хешу
+    pub fn get(&self, cid: &Cid) -> Result<Vec<u8>, String> {
+        let block = self.get_block(cid)?;
+        match cid.codec() {
+            RAW_CODEC => Ok(block),
+            DAG_JSON_CODEC => {
+                let root: ChunkedRoot = serde_json::from_slice(&block)
+                    .map_err(|e| format!("Malformed chunk links in {}: {}", cid, e))?;
+                let mut bytes = Vec::with_capacity(root.checked_size()?);
+                for link in &root.links {
+                    let chunk = self.get_block(&link.cid.parse()?)?;
+                    if chunk.len() as u64 != link.size {
+                        return Err(format!("Chunk {} has {} bytes, expected {}", link.cid, chunk.len(), link.size));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                if bytes.len() as u64 != root.size {
+                    return Err(format!("Content {} has {} bytes, expected {}", cid, bytes.len(), root.size));
+                }
+                Ok(bytes)
+            }
+            codec => Err(format!("Unsupported CID codec 0x{:x}", codec)),
+        }
+    }
+
+    // Куски содержимого по порядку с размерами, без чтения самих кусков: по ним данные отдаются потоком
+    pub fn layout(&self, cid: &Cid) -> Result<Vec<(Cid, usize)>, String> {
+        match cid.codec() {
+            RAW_CODEC => {
+                let size = fs::metadata(self.block_path(cid))
+                    .map_err(|_| format!("Content {} is not in the local store", cid))?
+                    .len();
+                Ok(vec![(*cid, size as usize)])
+            }
+            DAG_JSON_CODEC => {
+                let root: ChunkedRoot = serde_json::from_slice(&self.get_block(cid)?)
+                    .map_err(|e| format!("Malformed chunk links in {}: {}", cid, e))?;
+                root.checked_size()?;
+                root.links.iter()
+                    .map(|link| Ok((link.cid.parse()?, link.size as usize)))
+                    .collect()
+            }
+            codec => Err(format!("Unsupported CID codec 0x{:x}", codec)),
+        }
+    }
+
+    // Блоки, из которых состоит содержимое: корень и его куски
+    pub fn blocks_of(&self, cid: &Cid) -> Result<Vec<Cid>, String> {
+        let mut blocks = vec![*cid];
+        if cid.codec() == DAG_JSON_CODEC {
+            let root: ChunkedRoot = serde_json::from_slice(&self.get_block(cid)?)
+                .map_err(|e| format!("Malformed chunk links in {}: {}", cid, e))?;
+            for link in &root.links {
+                blocks.push(link.cid.parse()?);
+            }
+        }
+        Ok(blocks)
+    }
+
+    // Удаляет все блоки, кроме live, и возвращает освобожденные байты
+    pub fn retain(&self, live: &BTreeSet<Cid>) -> Result<u64, String> {
+        let dir = self.root.join(BLOCKS_DIR);
+        let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?;
+        let mut reclaimed = 0;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?;
+            let keep = entry.file_name().to_str()
+                .and_then(|name| name.parse::<Cid>().ok())
+                .is_some_and(|cid| live.contains(&cid));
+            if keep {
+                continue;
+            }
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            fs::remove_file(entry.path())
+                .map_err(|e| format!("Failed to remove {}: {}", entry.path().display(), e))?;
+            reclaimed += size;
+        }
+        Ok(reclaimed)
+    }
+
+    // Блок пишется во временный файл и переименовывается, чтобы сбой не оставил обрезанный блок
+    pub fn put_block(&self, codec: u64, bytes: &[u8]) -> Result<Cid, String> {
+        let cid = Cid::of(codec, bytes);
+        let path = self.block_path(&cid);
+        if path.exists() {
+            return Ok(cid);
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write block {}: {}", cid, e))?;
+        Ok(cid)
+    }
+
+    pub fn get_block(&self, cid: &Cid) -> Result<Vec<u8>, String> {
+        let bytes = fs::read(self.block_path(cid))
+            .map_err(|_| format!("Content {} is not in the local store", cid))?;
+        if !cid.verify(&bytes) {
+            return Err(format!("Block {} failed integrity check", cid));
+        }
+        Ok(bytes)
+    }
+
+    fn block_path(&self, cid: &Cid) -> PathBuf {
+        self.root.join(BLOCKS_DIR).join(cid.to_string())
+    }
+}
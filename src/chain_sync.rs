@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use crate::consensus::{Block, ConsensusAlgorithm, SelectionSeed, Transaction};
+use crate::executor;
+use crate::franchise_network::FranchiseNetwork;
+
+// Синхронизация цепочки блоков консенсуса между нодами.
+// Хранит все валидные блоки всех веток, выбирает лучшую ветку (fork choice)
+// и перестраивает основную цепь, когда приходит более сильная ветка.
+//
+// Правило выбора: наибольшее суммарное число подписей валидаторов на ветке,
+// при равенстве — большая высота, затем меньший хеш вершины (детерминированно на всех нодах).
+//
+// Блок проверяется по состоянию сети до него на его же ветке: состояние получается исполнением
+// ветки от начального состояния (base), поэтому блок боковой ветки или повторно импортированный
+// старый блок получает тот же набор валидаторов, что и при первой проверке.
+
+pub const GENESIS_PREVIOUS_HASH: &str = "genesis"; // previous_hash первого блока
+const MAX_ORPHANS: usize = 256; // Блоки, ожидающие родителя
+const MAX_CACHED_STATES: usize = 64; // Состояния после последних проверенных блоков
+
+// Результат импорта блока
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockImport {
+    AlreadyKnown,
+    Orphan,     // Родитель неизвестен, блок отложен до его прихода
+    SideBranch, // Блок валиден, но лучшая ветка не изменилась
+    Extended,   // Блок продлил основную цепь
+    Reorganized { reverted: Vec<Block>, applied: Vec<Block> },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChainSync {
+    blocks: HashMap<String, Block>, // hash -> блок, все ветки
+    scores: HashMap<String, u64>,   // hash -> суммарное число подписей от генезиса до блока
+    chain: Vec<Block>,              // Основная цепь от генезиса до лучшей вершины
+    orphans: HashMap<String, Vec<Block>>, // previous_hash -> блоки без родителя
+    states: HashMap<String, (u64, FranchiseNetwork)>, // hash -> высота и состояние сети после блока
+}
+
+impl ChainSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn main_chain(&self) -> &[Block] {
+        &self.chain
+    }
+
+    // Высота следующего блока основной цепи
+    pub fn height(&self) -> u64 {
+        self.chain.len() as u64
+    }
+
+    pub fn tip_hash(&self) -> String {
+        self.chain.last()
+            .map(|b| b.hash.clone())
+            .unwrap_or_else(|| GENESIS_PREVIOUS_HASH.to_string())
+    }
+
+    // Хеш первого блока основной цепи; сравнивается при handshake
+    pub fn genesis_hash(&self) -> Option<String> {
+        self.chain.first().map(|b| b.hash.clone())
+    }
+
+    // Seed выбора валидаторов для блока основной цепи на этой высоте
+    pub fn selection_seed(&self, height: u64) -> Option<SelectionSeed> {
+        match height {
+            0 => Some(SelectionSeed::genesis()),
+            _ => self.chain.get(height as usize - 1).map(SelectionSeed::from_parent),
+        }
+    }
+
+    // Seed выбора валидаторов для блока любой ветки: по его родителю
+    pub fn selection_seed_for(&self, block: &Block) -> SelectionSeed {
+        match self.blocks.get(&block.previous_hash) {
+            Some(parent) => SelectionSeed::from_parent(parent),
+            None => self.selection_seed(block.height).unwrap_or_else(SelectionSeed::genesis),
+        }
+    }
+
+    pub fn blocks_from(&self, height: u64) -> Vec<Block> {
+        self.chain.iter().skip(height as usize).cloned().collect()
+    }
+
+    // Проверка и импорт блока; блоки-сироты, ждавшие его, импортируются следом.
+    // base — состояние сети до первого блока (Executor::base)
+    pub fn import(&mut self, block: Block, consensus: &ConsensusAlgorithm, base: &FranchiseNetwork) -> Result<BlockImport, String> {
+        let old_tip = self.tip_hash();
+        let outcome = self.import_one(block, consensus, base)?;
+
+        if outcome == BlockImport::AlreadyKnown || outcome == BlockImport::Orphan {
+            return Ok(outcome);
+        }
+
+        // Подтягиваем сирот, у которых появился родитель
+        let mut ready: Vec<String> = self.chain_hashes_needing_orphans();
+        while let Some(parent) = ready.pop() {
+            for orphan in self.orphans.remove(&parent).unwrap_or_default() {
+                let hash = orphan.hash.clone();
+                if self.import_one(orphan, consensus, base).is_ok() && self.orphans.contains_key(&hash) {
+                    ready.push(hash);
+                }
+            }
+        }
+
+        Ok(self.switch_to_best_tip(&old_tip))
+    }
+
+    fn chain_hashes_needing_orphans(&self) -> Vec<String> {
+        self.orphans.keys()
+            .filter(|parent| self.blocks.contains_key(*parent))
+            .cloned()
+            .collect()
+    }
+
+    // Проверка одного блока и сохранение его в дереве веток (без смены основной цепи)
+    fn import_one(&mut self, block: Block, consensus: &ConsensusAlgorithm, base: &FranchiseNetwork) -> Result<BlockImport, String> {
+        if self.blocks.contains_key(&block.hash) {
+            return Ok(BlockImport::AlreadyKnown);
+        }
+
+        let (parent_score, seed) = if block.previous_hash == GENESIS_PREVIOUS_HASH {
+            if block.height != 0 {
+                return Err(format!("Block {} builds on genesis at height {}", block.hash, block.height));
+            }
+            (0, SelectionSeed::genesis())
+        } else {
+            let parent = match self.blocks.get(&block.previous_hash) {
+                Some(parent) => parent,
+                None => {
+                    self.add_orphan(block);
+                    return Ok(BlockImport::Orphan);
+                }
+            };
+            if block.height != parent.height + 1 {
+                return Err(format!("Block {} has height {}, parent has {}", block.hash, block.height, parent.height));
+            }
+            if block.timestamp < parent.timestamp {
+                return Err(format!("Block {} is older than its parent", block.hash));
+            }
+            (self.scores[&block.previous_hash], SelectionSeed::from_parent(parent))
+        };
+
+        // Подписи родителя, с которыми его финализировал лидер, заменяют нашу копию;
+        // они проверяются по состоянию до родителя, а награды за них входят в состояние до блока
+        let stale_parent = self.blocks.get(&block.previous_hash)
+            .filter(|parent| !block.parent_signatures.is_empty() && block.parent_signatures != parent.signatures)
+            .cloned();
+        let canonical_parent = match stale_parent {
+            Some(parent) => {
+                let canonical = parent.with_signatures(block.parent_signatures.clone());
+                let before_parent = self.state_after(&parent.previous_hash, consensus, base);
+                if !consensus.validate_signatures(&canonical, &parent.validators, &before_parent) {
+                    return Err(format!("Block {} carries invalid signatures of its parent", block.hash));
+                }
+                Some((canonical, before_parent))
+            }
+            None => None,
+        };
+        let network = match &canonical_parent {
+            Some((parent, before_parent)) => {
+                let mut network = before_parent.clone();
+                executor::apply_block(&mut network, consensus, self, parent);
+                network
+            }
+            None => self.state_after(&block.previous_hash, consensus, base),
+        };
+
+        // Хеш, подписи кворума выбранных валидаторов (выбор зависит от родителя блока и состояния до него) и подписи транзакций
+        let validators = consensus.select_validators(&network, block.height, &seed).selected_validators;
+        if !consensus.validate_block(&block, &validators, &network) {
+            return Err(format!("Block {} at height {} failed validation", block.hash, block.height));
+        }
+
+        if let Some((parent, _)) = canonical_parent {
+            if let Some(stored) = self.chain.get_mut(parent.height as usize).filter(|stored| stored.hash == parent.hash) {
+                *stored = parent.clone();
+            }
+            // Состояния после родителя и его потомков посчитаны с прежними подписями
+            self.states.retain(|_, (height, _)| *height < parent.height);
+            self.cache_state(&parent, network);
+            self.blocks.insert(parent.hash.clone(), parent);
+        }
+
+        self.scores.insert(block.hash.clone(), parent_score + block.signatures.len() as u64);
+        self.blocks.insert(block.hash.clone(), block);
+        Ok(BlockImport::SideBranch)
+    }
+
+    // Состояние сети после блока hash на его ветке: от ближайшего закешированного предка или от base
+    fn state_after(&mut self, hash: &str, consensus: &ConsensusAlgorithm, base: &FranchiseNetwork) -> FranchiseNetwork {
+        let mut branch = Vec::new();
+        let mut cursor = hash;
+        let mut network = loop {
+            if let Some((_, network)) = self.states.get(cursor) {
+                break network.clone();
+            }
+            match self.blocks.get(cursor) {
+                Some(block) => {
+                    branch.push(block);
+                    cursor = &block.previous_hash;
+                }
+                None => break base.clone(),
+            }
+        };
+        let branch: Vec<Block> = branch.into_iter().cloned().collect();
+        for block in branch.iter().rev() {
+            executor::apply_block(&mut network, consensus, self, block);
+            self.cache_state(block, network.clone());
+        }
+        network
+    }
+
+    fn cache_state(&mut self, block: &Block, network: FranchiseNetwork) {
+        if self.states.len() >= MAX_CACHED_STATES {
+            let oldest = self.states.iter().min_by_key(|(_, (height, _))| *height).map(|(hash, _)| hash.clone());
+            if let Some(oldest) = oldest {
+                self.states.remove(&oldest);
+            }
+        }
+        self.states.insert(block.hash.clone(), (block.height, network));
+    }
+
+    fn add_orphan(&mut self, block: Block) {
+        let orphan_count: usize = self.orphans.values().map(|v| v.len()).sum();
+        if orphan_count >= MAX_ORPHANS {
+            return;
+        }
+        let waiting = self.orphans.entry(block.previous_hash.clone()).or_default();
+        if !waiting.iter().any(|b| b.hash == block.hash) {
+            waiting.push(block);
+        }
+    }
+
+    // Лучшая вершина по правилу fork choice
+    fn best_tip(&self) -> Option<&Block> {
+        self.blocks.values().max_by(|a, b| {
+            self.scores[&a.hash].cmp(&self.scores[&b.hash])
+                .then(a.height.cmp(&b.height))
+                .then(b.hash.cmp(&a.hash))
+        })
+    }
+
+    // Перестройка основной цепи на лучшую вершину
+    fn switch_to_best_tip(&mut self, old_tip: &str) -> BlockImport {
+        let best = match self.best_tip() {
+            Some(best) => best.clone(),
+            None => return BlockImport::SideBranch,
+        };
+        if best.hash == old_tip {
+            return BlockImport::SideBranch;
+        }
+
+        // Новая основная цепь: от лучшей вершины назад к генезису
+        let mut new_chain = vec![best];
+        while let Some(parent) = self.blocks.get(&new_chain.last().unwrap().previous_hash) {
+            new_chain.push(parent.clone());
+        }
+        new_chain.reverse();
+
+        // Общий префикс со старой цепью
+        let common = self.chain.iter().zip(new_chain.iter())
+            .take_while(|(old, new)| old.hash == new.hash)
+            .count();
+        let reverted: Vec<Block> = self.chain[common..].to_vec();
+        let applied: Vec<Block> = new_chain[common..].to_vec();
+        self.chain = new_chain;
+
+        if reverted.is_empty() {
+            BlockImport::Extended
+        } else {
+            BlockImport::Reorganized { reverted, applied }
+        }
+    }
+
+    // Обновление пула ожидающих транзакций после импорта:
+    // транзакции из новых блоков убираются, из отмененных — возвращаются (если их нет в новой ветке)
+    pub fn reconcile_pending(outcome: &BlockImport, chain: &[Block], pending: &mut Vec<Transaction>) {
+        let reverted: &[Block] = match outcome {
+            BlockImport::Reorganized { reverted, .. } => reverted,
+            BlockImport::Extended => &[],
+            _ => return,
+        };
+
+        let included: HashSet<&str> = chain.iter()
+            .flat_map(|b| b.transactions.iter().map(|tx| tx.id.as_str()))
+            .collect();
+        pending.retain(|tx| !included.contains(tx.id.as_str()));
+
+        for tx in reverted.iter().flat_map(|b| b.transactions.iter()) {
+            if !included.contains(tx.id.as_str()) && !pending.iter().any(|p| p.id == tx.id) {
+                pending.push(tx.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Источник времени для состояния блокчейна.
+// По умолчанию это системные часы, но на время операции время можно "заморозить":
+// тогда все метки внутри операции совпадают, а при воспроизведении журнала (WAL)
+// операция получает ту же метку, что и при первом выполнении.
+
+thread_local! {
+    static FROZEN_AT: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+// Текущее время в секундах Unix
+pub fn now_secs() -> u64 {
+    FROZEN_AT.with(|frozen| frozen.get()).unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    })
+}
+
+// Заморожено ли время в текущем потоке
+pub fn is_frozen() -> bool {
+    FROZEN_AT.with(|frozen| frozen.get().is_some())
+}
+
+// Замораживает время до уничтожения возвращенного guard'а
+pub fn freeze(timestamp: u64) -> FrozenClock {
+    let previous = FROZEN_AT.with(|frozen| frozen.replace(Some(timestamp)));
+    FrozenClock { previous }
+}
+
+pub struct FrozenClock {
+    previous: Option<u64>,
+}
+
+impl Drop for FrozenClock {
+    fn drop(&mut self) {
+        FROZEN_AT.with(|frozen| frozen.set(self.previous));
+    }
+}
@@ -1,6 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use crate::chain_sync::GENESIS_PREVIOUS_HASH;
+use crate::clock;
+use crate::crypto::{self, Keypair};
 use crate::franchise_network::{FranchiseNetwork, FranchiseNode, NodeType};
+use crate::merkle;
+use crate::rewards::{self, EquivocationEvidence};
 
 // Алгоритм консенсуса: Proof of Sales + Reputation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,7 +17,7 @@ pub struct ConsensusAlgorithm {
     pub geographic_distribution: GeographicDistribution,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReputationWeights {
     pub sales_weight: f64,        // 0.4 - вес продаж
     pub reputation_weight: f64,   // 0.3 - вес репутации
@@ -19,6 +25,21 @@ pub struct ReputationWeights {
     pub stake_weight: f64,        // 0.1 - вес токенов
 }
 
+impl ReputationWeights {
+    // Веса неотрицательны и в сумме дают 1
+    pub fn validate(&self) -> Result<(), String> {
+        let parts = [self.sales_weight, self.reputation_weight, self.geographic_weight, self.stake_weight];
+        if parts.iter().any(|weight| *weight < 0.0) {
+            return Err("Reputation weights must not be negative".to_string());
+        }
+        let total: f64 = parts.iter().sum();
+        if (total - 1.0).abs() > 1e-9 {
+            return Err(format!("Reputation weights sum to {}, must be 1.0", total));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeographicDistribution {
     pub max_nodes_per_city: u32,
@@ -41,8 +62,35 @@ pub struct ValidatorScore {
 pub struct ConsensusResult {
     pub selected_validators: Vec<u64>,
     pub validator_scores: Vec<ValidatorScore>,
-    pub consensus_timestamp: u64,
+    pub consensus_timestamp: u64, // Метка блока h−1, а не время расчета
     pub block_height: u64,
+    pub seed: SelectionSeed,
+}
+
+// Состояние цепи на высоте h−1, от которого зависит выбор валидаторов высоты h.
+// Выбор — чистая функция (сеть, высота, seed): любая нода может пересчитать и проверить его.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionSeed {
+    pub previous_hash: String,
+    pub timestamp: u64, // "Текущее время" для расчета репутации
+}
+
+impl SelectionSeed {
+    // Seed первого блока цепи
+    pub fn genesis() -> Self {
+        Self { previous_hash: GENESIS_PREVIOUS_HASH.to_string(), timestamp: 0 }
+    }
+
+    pub fn from_parent(parent: &Block) -> Self {
+        Self { previous_hash: parent.hash.clone(), timestamp: parent.timestamp }
+    }
+
+    // Порядок нод с равным score: хеш seed и id ноды, свой на каждой высоте
+    fn tie_breaker(&self, node_id: u64) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("validator|{}|{}", self.previous_hash, node_id).as_bytes());
+        hasher.finalize().into()
+    }
 }
 
 impl Default for ConsensusAlgorithm {
@@ -83,24 +131,28 @@ impl ConsensusAlgorithm {
     }
 
     // Основная функция выбора валидаторов
-    pub fn select_validators(&self, network: &FranchiseNetwork, block_height: u64) -> ConsensusResult {
+    pub fn select_validators(&self, network: &FranchiseNetwork, block_height: u64, seed: &SelectionSeed) -> ConsensusResult {
         let mut candidates = Vec::new();
+        // Веса, принятые управлением сети, важнее параметров алгоритма
+        let weights = network.reputation_weights.as_ref().unwrap_or(&self.reputation_weights);
         
-        // Рассчитываем score для каждой ноды
-        for (node_id, node) in &network.nodes {
+        // Рассчитываем score для каждой ноды; ноды обходятся по возрастанию id
+        let mut nodes: Vec<(&u64, &FranchiseNode)> = network.nodes.iter().collect();
+        nodes.sort_by_key(|(node_id, _)| **node_id);
+        for (node_id, node) in nodes {
             if !node.active {
                 continue;
             }
 
             let sales_score = self.calculate_sales_score(*node_id, network);
-            let reputation_score = self.calculate_reputation_score(*node_id, network);
+            let reputation_score = self.calculate_reputation_score(*node_id, network, block_height, seed.timestamp);
             let geographic_score = self.calculate_geographic_score(node, network);
             let stake_score = self.calculate_stake_score(&node.owner_address, network);
 
-            let total_score = sales_score * self.reputation_weights.sales_weight +
-                            reputation_score * self.reputation_weights.reputation_weight +
-                            geographic_score * self.reputation_weights.geographic_weight +
-                            stake_score * self.reputation_weights.stake_weight;
+            let total_score = sales_score * weights.sales_weight +
+                            reputation_score * weights.reputation_weight +
+                            geographic_score * weights.geographic_weight +
+                            stake_score * weights.stake_weight;
 
             // Минимальный порог для участия в консенсусе
             if total_score > 0.3 {
@@ -116,8 +168,9 @@ impl ConsensusAlgorithm {
             }
         }
 
-        // Сортируем по общему score
-        candidates.sort_by(|a, b| b.total_score.partial_cmp(&a.total_score).unwrap());
+        // Сортируем по общему score, равные — в порядке, заданном seed
+        candidates.sort_by(|a, b| b.total_score.total_cmp(&a.total_score)
+            .then_with(|| seed.tie_breaker(a.node_id).cmp(&seed.tie_breaker(b.node_id))));
 
         // Применяем географическое распределение
         let selected_validators = self.apply_geographic_distribution(candidates.clone(), network);
@@ -125,11 +178,24 @@ impl ConsensusAlgorithm {
         ConsensusResult {
             selected_validators: selected_validators.clone(),
             validator_scores: candidates,
-            consensus_timestamp: chrono::Utc::now().timestamp() as u64,
+            consensus_timestamp: seed.timestamp,
             block_height,
+            seed: seed.clone(),
         }
     }
 
+    // Проверка чужого ConsensusResult пересчетом с тем же seed
+    pub fn verify_result(&self, result: &ConsensusResult, network: &FranchiseNetwork) -> Result<(), String> {
+        let expected = self.select_validators(network, result.block_height, &result.seed);
+        if expected.selected_validators != result.selected_validators {
+            return Err(format!(
+                "Validators {:?} at height {} do not match recomputed {:?}",
+                result.selected_validators, result.block_height, expected.selected_validators
+            ));
+        }
+        Ok(())
+    }
+
     // Расчет score по продажам
     fn calculate_sales_score(&self, node_id: u64, network: &FranchiseNetwork) -> f64 {
         let sales_count = network.sales.iter()
@@ -153,14 +219,13 @@ impl ConsensusAlgorithm {
     }
 
     // Расчет репутационного score
-    fn calculate_reputation_score(&self, node_id: u64, network: &FranchiseNetwork) -> f64 {
+    fn calculate_reputation_score(&self, node_id: u64, network: &FranchiseNetwork, block_height: u64, current_time: u64) -> f64 {
         let node = match network.nodes.get(&node_id) {
             Some(n) => n,
             None => return 0.0,
         };
 
-        let current_time = chrono::Utc::now().timestamp() as u64;
-        let days_active = (current_time - node.registered_at) / 86400;
+        let days_active = current_time.saturating_sub(node.registered_at) / 86400;
 
         // Базовый score по времени работы
         let time_score = (days_active as f64 / 365.0).min(1.0);
@@ -169,8 +234,12 @@ impl ConsensusAlgorithm {
         let consistency_score = self.calculate_consistency_score(node_id, network);
         let quality_score = self.calculate_quality_score(node_id, network);
 
+        // Штраф за пропущенные слоты валидатора в последних блоках
+        let missed_slots = network.missed_slot_count(node_id, block_height.saturating_sub(rewards::MISSED_SLOT_WINDOW));
+        let missed_penalty = missed_slots as f64 * rewards::MISSED_SLOT_PENALTY;
+
         // Комбинированный репутационный score
-        time_score * 0.5 + consistency_score * 0.3 + quality_score * 0.2
+        (time_score * 0.5 + consistency_score * 0.3 + quality_score * 0.2 - missed_penalty).max(0.0)
     }
 
     // Расчет консистентности (регулярность продаж)
@@ -183,8 +252,8 @@ impl ConsensusAlgorithm {
             return 0.5; // Нейтральный score для новых нод
         }
 
-        // Группируем продажи по дням
-        let mut daily_sales: HashMap<u64, u32> = HashMap::new();
+        // Группируем продажи по дням (BTreeMap — одинаковый порядок суммирования на всех нодах)
+        let mut daily_sales: BTreeMap<u64, u32> = BTreeMap::new();
         for sale in &node_sales {
             let day = sale.timestamp / 86400;
             *daily_sales.entry(day).or_insert(0) += 1;
@@ -310,30 +379,41 @@ impl ConsensusAlgorithm {
 
     // Валидация блока
     pub fn validate_block(&self, block: &Block, validators: &[u64], network: &FranchiseNetwork) -> bool {
-        // Проверяем, что блок подписан достаточным количеством валидаторов
-        let required_signatures = (validators.len() * 2 / 3) + 1; // 2/3 + 1
-        let actual_signatures = block.signatures.len();
-
-        if actual_signatures < required_signatures {
-            return false;
-        }
+        self.validate_signatures(block, validators, network) && self.validate_block_content(block, network)
+    }
 
-        // Проверяем, что все подписи от валидных валидаторов
+    // Подписи блока: кворум выбранных валидаторов и награда по подписавшим
+    pub fn validate_signatures(&self, block: &Block, validators: &[u64], network: &FranchiseNetwork) -> bool {
+        // Проверяем, что все подписи от валидных валидаторов, без повторов и сделаны их ключами
+        let mut signers = HashSet::new();
         for signature in &block.signatures {
-            if !validators.contains(&signature.validator_id) {
+            if !validators.contains(&signature.validator_id) || !signers.insert(signature.validator_id) {
+                return false;
+            }
+
+            let verified = network.node_public_key(signature.validator_id)
+                .map(|key| block.verify_signature(signature, key))
+                .unwrap_or(false);
+            if !verified {
                 return false;
             }
         }
 
-        // Дополнительные проверки блока
-        self.validate_block_content(block, network)
+        // Проверяем, что блок подписан достаточным количеством валидаторов
+        let required_signatures = (validators.len() * 2 / 3) + 1; // 2/3 + 1
+        if signers.len() < required_signatures {
+            return false;
+        }
+
+        // Награда поделена между подписавшими по правилам rewards
+        block.validator_rewards == rewards::block_rewards(&block.signatures)
     }
 
     // Валидация содержимого блока
-    fn validate_block_content(&self, block: &Block, network: &FranchiseNetwork) -> bool {
-        // Проверяем хеш блока
+    pub fn validate_block_content(&self, block: &Block, network: &FranchiseNetwork) -> bool {
+        // Проверяем хеш блока и корень дерева транзакций
         let calculated_hash = block.calculate_hash();
-        if calculated_hash != block.hash {
+        if calculated_hash != block.hash || block.calculate_merkle_root() != block.merkle_root {
             return false;
         }
 
@@ -348,34 +428,65 @@ impl ConsensusAlgorithm {
     }
 
     // Валидация транзакции
-    fn validate_transaction(&self, transaction: &Transaction, network: &FranchiseNetwork) -> bool {
+    pub fn validate_transaction(&self, transaction: &Transaction, network: &FranchiseNetwork) -> bool {
         // Проверяем, что нода существует и активна
-        if let Some(node) = network.nodes.get(&transaction.node_id) {
-            if !node.active {
-                return false;
-            }
-        } else {
-            return false;
-        }
+        let node = match network.nodes.get(&transaction.node_id) {
+            Some(node) if node.active => node,
+            _ => return false,
+        };
+
+        // Проверяем подпись транзакции ключом ноды; нода без ключа подписывать не может
+        let signed = match &node.public_key {
+            Some(public_key) => transaction.verify_signature(public_key),
+            None => false,
+        };
 
-        // Проверяем подпись транзакции
-        transaction.verify_signature()
+        // Доказательство двойной подписи должно содержать две настоящие подписи нарушителя
+        if signed && transaction.transaction_type == TransactionType::Evidence {
+            return EquivocationEvidence::from_transaction(transaction)
+                .and_then(|evidence| evidence.verify(network))
+                .is_ok();
+        }
+        signed
     }
 }
 
 // Структуры для блокчейна
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub height: u64,
     pub timestamp: u64,
     pub previous_hash: String,
     pub hash: String,
+    pub merkle_root: String, // Корень дерева Меркла транзакций (merkle)
+    // Для легких клиентов: выбранные валидаторы высоты (по возрастанию id) и корень дерева
+    // состояния сети, на котором построен блок (state_tree). Проверяются валидаторами до голосования.
+    pub validators: Vec<u64>,
+    pub state_root: String,
+    // Подписи родителя, с которыми его финализировал лидер этой высоты. Каждая нода собирает
+    // свой кворум голосов Commit, а награды зависят от подписавших, поэтому реплики заменяют
+    // подписи родителя этими и получают одинаковое состояние (пусто у блоков вне BFT)
+    pub parent_signatures: Vec<BlockSignature>,
     pub transactions: Vec<Transaction>,
     pub signatures: Vec<BlockSignature>,
     pub validator_rewards: HashMap<u64, u64>, // validator_id -> reward
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Заголовок блока без транзакций: его скачивает легкий клиент (light_client)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub height: u64,
+    pub timestamp: u64,
+    pub previous_hash: String,
+    pub hash: String,
+    pub merkle_root: String,
+    pub validators: Vec<u64>,
+    pub state_root: String,
+    pub parent_signatures: Vec<BlockSignature>,
+    pub signatures: Vec<BlockSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: String,
     pub node_id: u64,
@@ -385,86 +496,167 @@ pub struct Transaction {
     pub timestamp: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TransactionType {
     Sale,
     NodeRegistration,
     TokenTransfer,
     Governance,
+    Evidence, // Доказательство двойной подписи валидатора (rewards::EquivocationEvidence)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockSignature {
     pub validator_id: u64,
+    pub round: u32, // Раунд BFT, в котором подписан Commit; вне BFT — 0
     pub signature: String,
     pub timestamp: u64,
 }
 
 impl Block {
     pub fn new(height: u64, previous_hash: String, transactions: Vec<Transaction>) -> Self {
-        let timestamp = chrono::Utc::now().timestamp() as u64;
+        let timestamp = clock::now_secs();
         let mut block = Self {
             height,
             timestamp,
             previous_hash,
             hash: String::new(),
+            merkle_root: String::new(),
+            validators: Vec::new(),
+            state_root: String::new(),
+            parent_signatures: Vec::new(),
             transactions,
             signatures: Vec::new(),
             validator_rewards: HashMap::new(),
         };
         
+        block.merkle_root = block.calculate_merkle_root();
         block.hash = block.calculate_hash();
         block
     }
 
+    // Валидаторы, подписи родителя и корень состояния после него; хеш пересчитывается
+    pub fn with_header_commitments(mut self, validators: Vec<u64>, parent_signatures: Vec<BlockSignature>, state_root: String) -> Self {
+        self.validators = validators;
+        self.parent_signatures = parent_signatures;
+        self.state_root = state_root;
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    // Тот же блок с другим набором подписей (parent_signatures дочернего блока)
+    pub fn with_signatures(&self, signatures: Vec<BlockSignature>) -> Self {
+        let mut block = self.clone();
+        block.validator_rewards = rewards::block_rewards(&signatures);
+        block.signatures = signatures;
+        block
+    }
+
     pub fn calculate_hash(&self) -> String {
-        use sha2::{Sha256, Digest};
-        
-        let mut hasher = Sha256::new();
-        hasher.update(self.height.to_string().as_bytes());
-        hasher.update(self.timestamp.to_string().as_bytes());
-        hasher.update(self.previous_hash.as_bytes());
-        
-        for transaction in &self.transactions {
-            hasher.update(transaction.id.as_bytes());
+        header_hash(self.height, self.timestamp, &self.previous_hash, &self.merkle_root, &self.validators, &self.parent_signatures, &self.state_root)
+    }
+
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            height: self.height,
+            timestamp: self.timestamp,
+            previous_hash: self.previous_hash.clone(),
+            hash: self.hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            validators: self.validators.clone(),
+            state_root: self.state_root.clone(),
+            parent_signatures: self.parent_signatures.clone(),
+            signatures: self.signatures.clone(),
         }
-        
-        hex::encode(hasher.finalize())
     }
 
-    pub fn add_signature(&mut self, validator_id: u64, signature: String) {
+    // Листья — транзакции целиком, с подписями, а не только их id
+    fn merkle_leaves(&self) -> Vec<Vec<u8>> {
+        self.transactions.iter()
+            .map(|transaction| serde_json::to_vec(transaction).expect("transaction fields are always serializable"))
+            .collect()
+    }
+
+    pub fn calculate_merkle_root(&self) -> String {
+        merkle::root(&self.merkle_leaves())
+    }
+
+    pub fn signing_bytes(&self, round: u32, validator_id: u64) -> Vec<u8> {
+        block_signing_bytes(self.height, round, &self.hash, validator_id)
+    }
+
+    // Подпись блока ключом валидатора вне BFT раунда (раунд 0); награда пересчитывается по новому набору подписавших
+    pub fn sign(&mut self, validator_id: u64, keypair: &Keypair) {
+        let signature = keypair.sign(&self.signing_bytes(0, validator_id));
         self.signatures.push(BlockSignature {
             validator_id,
+            round: 0,
             signature,
-            timestamp: chrono::Utc::now().timestamp() as u64,
+            timestamp: clock::now_secs(),
         });
+        self.validator_rewards = rewards::block_rewards(&self.signatures);
+    }
+
+    pub fn verify_signature(&self, signature: &BlockSignature, public_key: &str) -> bool {
+        crypto::verify(public_key, &self.signing_bytes(signature.round, signature.validator_id), &signature.signature)
     }
 }
 
+impl BlockHeader {
+    pub fn calculate_hash(&self) -> String {
+        header_hash(self.height, self.timestamp, &self.previous_hash, &self.merkle_root, &self.validators, &self.parent_signatures, &self.state_root)
+    }
+}
+
+// Хеш заголовка; транзакции входят в него через корень дерева Меркла
+fn header_hash(height: u64, timestamp: u64, previous_hash: &str, merkle_root: &str, validators: &[u64], parent_signatures: &[BlockSignature], state_root: &str) -> String {
+    let validators: Vec<String> = validators.iter().map(u64::to_string).collect();
+    let parent_signatures: Vec<String> = parent_signatures.iter()
+        .map(|s| format!("{}:{}", s.validator_id, s.signature))
+        .collect();
+    let mut hasher = Sha256::new();
+    hasher.update(height.to_string().as_bytes());
+    hasher.update(timestamp.to_string().as_bytes());
+    hasher.update(previous_hash.as_bytes());
+    hasher.update(merkle_root.as_bytes());
+    hasher.update(validators.join(",").as_bytes());
+    hasher.update(parent_signatures.join(",").as_bytes());
+    hasher.update(state_root.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+// Байты, которые подписывает валидатор: высота, раунд, хеш блока и id валидатора.
+// Голос Commit в BFT раунде подписывает те же байты и становится подписью блока.
+// Раунд входит в подпись, чтобы честная смена блокировки в позднем раунде не выглядела двойной подписью.
+pub fn block_signing_bytes(height: u64, round: u32, hash: &str, validator_id: u64) -> Vec<u8> {
+    format!("block|{}|{}|{}|{}", height, round, hash, validator_id).into_bytes()
+}
+
 impl Transaction {
     pub fn new(node_id: u64, transaction_type: TransactionType, data: serde_json::Value) -> Self {
-        let id = format!("tx_{}_{}", node_id, chrono::Utc::now().timestamp());
+        let id = format!("tx_{}_{}", node_id, clock::now_secs());
         Self {
             id,
             node_id,
             transaction_type,
             data,
             signature: String::new(),
-            timestamp: chrono::Utc::now().timestamp() as u64,
+            timestamp: clock::now_secs(),
         }
     }
 
-    pub fn sign(&mut self, private_key: &str) {
-        // Упрощенная подпись (в реальности используйте криптографические библиотеки)
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(self.id.as_bytes());
-        hasher.update(private_key.as_bytes());
-        self.signature = hex::encode(hasher.finalize());
+    // Канонические байты транзакции для подписи (все поля, кроме самой подписи).
+    // serde_json сериализует объекты с отсортированными ключами, поэтому байты однозначны.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&(&self.id, self.node_id, &self.transaction_type, &self.data, self.timestamp))
+            .expect("transaction fields are always serializable")
+    }
+
+    pub fn sign(&mut self, keypair: &Keypair) {
+        self.signature = keypair.sign(&self.signing_bytes());
     }
 
-    pub fn verify_signature(&self) -> bool {
-        // Упрощенная проверка подписи
-        !self.signature.is_empty()
+    pub fn verify_signature(&self, public_key: &str) -> bool {
+        crypto::verify(public_key, &self.signing_bytes(), &self.signature)
     }
 }
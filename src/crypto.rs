@@ -0,0 +1,78 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand_core::{OsRng, RngCore};
+use sha2::{Sha256, Digest};
+
+// Ключи Ed25519 для нод франшизной сети и кошельков покупателей.
+// Публичный ключ ноды хранится в FranchiseNetwork, адрес кошелька выводится из его публичного ключа;
+// приватный ключ есть только у владельца. Ключи и подписи передаются в hex, как и хеши в остальном коде.
+
+pub struct Keypair {
+    signing_key: SigningKey,
+}
+
+impl Keypair {
+    // Новая случайная пара ключей
+    pub fn generate() -> Self {
+        Self { signing_key: SigningKey::generate(&mut OsRng) }
+    }
+
+    // Восстановление пары из 32-байтного seed в hex
+    pub fn from_secret_hex(secret_hex: &str) -> Result<Self, String> {
+        let bytes = hex::decode(secret_hex)
+            .map_err(|e| format!("Invalid secret key hex: {}", e))?;
+        let secret: [u8; 32] = bytes.try_into()
+            .map_err(|_| "Secret key must be 32 bytes".to_string())?;
+        Ok(Self { signing_key: SigningKey::from_bytes(&secret) })
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().to_bytes())
+    }
+
+    // Подпись сообщения, результат в hex
+    pub fn sign(&self, message: &[u8]) -> String {
+        hex::encode(self.signing_key.sign(message).to_bytes())
+    }
+}
+
+// Проверка подписи по публичному ключу; любой некорректный ввод — false
+pub fn verify(public_key_hex: &str, message: &[u8], signature_hex: &str) -> bool {
+    let public_key = match parse_public_key(public_key_hex) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let signature: [u8; 64] = match hex::decode(signature_hex).ok().and_then(|b| b.try_into().ok()) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    public_key.verify(message, &Signature::from_bytes(&signature)).is_ok()
+}
+
+// Случайные байты из системного генератора в hex (challenge handshake)
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Шестизначный код подтверждения (активация чека, телефон) из системного генератора
+pub fn random_code() -> String {
+    format!("{:06}", 100_000 + OsRng.next_u32() % 900_000)
+}
+
+// Адрес кошелька: "0x" + первые 20 байт SHA-256 от публичного ключа
+pub fn wallet_address(public_key_hex: &str) -> Result<String, String> {
+    let public_key = parse_public_key(public_key_hex)?;
+    let hash = Sha256::digest(public_key.to_bytes());
+    Ok(format!("0x{}", hex::encode(&hash[..20])))
+}
+
+// Проверка формата публичного ключа перед регистрацией
+pub fn parse_public_key(public_key_hex: &str) -> Result<VerifyingKey, String> {
+    let bytes = hex::decode(public_key_hex)
+        .map_err(|e| format!("Invalid public key hex: {}", e))?;
+    let bytes: [u8; 32] = bytes.try_into()
+        .map_err(|_| "Public key must be 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes)
+        .map_err(|e| format!("Invalid public key: {}", e))
+}
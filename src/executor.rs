@@ -0,0 +1,195 @@
+use serde::{Serialize, Deserialize};
+use crate::chain_sync::{BlockImport, ChainSync};
+use crate::clock;
+use crate::consensus::{Block, ConsensusAlgorithm, ReputationWeights, Transaction, TransactionType};
+use crate::franchise_network::{FranchiseNetwork, NodeType, SaleItem};
+use crate::ledger::TokenLedger;
+use crate::rewards;
+use crate::tokenomics::TokenomicsPolicy;
+
+// Исполнение финализированных блоков над FranchiseNetwork.
+// Состояние сети — начальное состояние (base) плюс транзакции всех блоков основной цепи по порядку.
+// Транзакция видит только свои данные и метку времени блока (часы заморожены на время блока),
+// поэтому все реплики получают одинаковые балансы.
+// Транзакция, которую нельзя применить, одинаково пропускается всеми репликами; ошибка попадает в Receipt.
+// При reorg состояние пересчитывается с base по новой основной цепи.
+
+// Данные транзакций в Transaction.data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalePayload {
+    pub sale_id: String,
+    pub price_gel: f64,
+    pub buyer_meta: String,
+    pub pos_id: String,
+    pub items: Vec<SaleItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeRegistrationPayload {
+    pub owner_address: String,
+    pub node_type: NodeType,
+    pub city: String,
+    pub public_key: Option<String>,
+}
+
+// Перевод с кошелька владельца ноды, подписавшей транзакцию
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenTransferPayload {
+    pub to: String,
+    pub units: u64,
+}
+
+// Изменение параметров сети; подписывается собственной нодой основателя (NodeType::OWNER)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GovernancePayload {
+    WhitelistPos { pos_id: String },
+    SetNodeActive { node_id: u64, active: bool },
+    // Новая политика токеномики с effective_from_height после блока транзакции
+    ScheduleTokenomics { policy: TokenomicsPolicy },
+    // Веса выбора валидаторов; действуют с выбора для следующего блока
+    SetReputationWeights { weights: ReputationWeights },
+}
+
+// Результат исполнения транзакции
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub tx_id: String,
+    pub height: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Executor {
+    base: FranchiseNetwork, // Состояние сети до первого блока
+}
+
+impl Executor {
+    pub fn new(base: FranchiseNetwork) -> Self {
+        Self { base }
+    }
+
+    pub fn base(&self) -> &FranchiseNetwork {
+        &self.base
+    }
+
+    // Состояние сети после всех блоков основной цепи
+    pub fn replay(&self, consensus: &ConsensusAlgorithm, chain: &ChainSync) -> (FranchiseNetwork, Vec<Receipt>) {
+        let mut network = self.base.clone();
+        let mut receipts = Vec::new();
+        for block in chain.main_chain() {
+            receipts.extend(apply_block(&mut network, consensus, chain, block));
+        }
+        log_skipped(&receipts);
+        (network, receipts)
+    }
+
+    // Состояние сети вслед за основной цепью после импорта; old_height — высота цепи до импорта.
+    // committed получает состояние до вершины цепи: его фиксирует state_root вершины,
+    // по нему нода строит доказательства состояния для легких клиентов.
+    pub fn apply_chain_update(&self, network: &mut FranchiseNetwork, committed: &mut FranchiseNetwork, consensus: &ConsensusAlgorithm, chain: &ChainSync, outcome: &BlockImport, old_height: u64) -> Vec<Receipt> {
+        let blocks = match outcome {
+            // Прежняя вершина могла получить подписи лидера (parent_signatures) — применяем ее заново
+            BlockImport::Extended if old_height > 0 => {
+                *network = committed.clone();
+                &chain.main_chain()[old_height as usize - 1..]
+            }
+            BlockImport::Extended => &chain.main_chain()[old_height as usize..],
+            BlockImport::Reorganized { .. } => {
+                *network = self.base.clone();
+                chain.main_chain()
+            }
+            _ => return Vec::new(),
+        };
+        let Some((tip, before_tip)) = blocks.split_last() else {
+            return Vec::new();
+        };
+
+        let mut receipts: Vec<Receipt> = before_tip.iter()
+            .flat_map(|block| apply_block(network, consensus, chain, block))
+            .collect();
+        *committed = network.clone();
+        receipts.extend(apply_block(network, consensus, chain, tip));
+        log_skipped(&receipts);
+        receipts
+    }
+}
+
+fn log_skipped(receipts: &[Receipt]) {
+    for receipt in receipts {
+        if let Some(e) = &receipt.error {
+            println!("⚠️ Transaction {} at height {} skipped: {}", receipt.tx_id, receipt.height, e);
+        }
+    }
+}
+
+// Блок любой ветки: транзакции по порядку, затем награды и пропуски слотов.
+// Валидаторы блока выбираются по состоянию до него — так же, как при проверке блока.
+pub fn apply_block(network: &mut FranchiseNetwork, consensus: &ConsensusAlgorithm, chain: &ChainSync, block: &Block) -> Vec<Receipt> {
+    let _clock = clock::freeze(block.timestamp);
+    let seed = chain.selection_seed_for(block);
+    let validators = consensus.select_validators(network, block.height, &seed).selected_validators;
+    network.height = block.height;
+
+    let receipts = block.transactions.iter()
+        .map(|transaction| {
+            let error = execute_transaction(network, transaction, block.height).err();
+            Receipt { tx_id: transaction.id.clone(), height: block.height, error }
+        })
+        .collect();
+
+    rewards::apply_block(network, block, &validators);
+    receipts
+}
+
+// Одна транзакция; при ошибке состояние сети не меняется
+pub fn execute_transaction(network: &mut FranchiseNetwork, transaction: &Transaction, height: u64) -> Result<(), String> {
+    match transaction.transaction_type {
+        TransactionType::Sale => {
+            let sale: SalePayload = payload(transaction)?;
+            if network.sales.iter().any(|s| s.sale_id == sale.sale_id) {
+                return Err(format!("Sale {} is already recorded", sale.sale_id));
+            }
+            network.record_sale(transaction.node_id, sale.sale_id, sale.price_gel, sale.buyer_meta, sale.pos_id, sale.items)?;
+        }
+        TransactionType::NodeRegistration => {
+            require_owner_node(network, transaction.node_id)?;
+            let registration: NodeRegistrationPayload = payload(transaction)?;
+            network.register_node_with_key(registration.owner_address, registration.node_type, registration.city, registration.public_key)?;
+        }
+        TransactionType::TokenTransfer => {
+            let transfer: TokenTransferPayload = payload(transaction)?;
+            let from = network.nodes.get(&transaction.node_id)
+                .map(|node| node.owner_address.clone())
+                .ok_or("Node not found")?;
+            network.transfer(&from, &transfer.to, transfer.units)?;
+        }
+        TransactionType::Governance => {
+            require_owner_node(network, transaction.node_id)?;
+            match payload(transaction)? {
+                GovernancePayload::WhitelistPos { pos_id } => network.whitelist_pos(pos_id),
+                GovernancePayload::SetNodeActive { node_id, active } => network.set_node_active(node_id, active)?,
+                GovernancePayload::ScheduleTokenomics { policy } => network.tokenomics.schedule(policy, height)?,
+                GovernancePayload::SetReputationWeights { weights } => {
+                    weights.validate()?;
+                    network.reputation_weights = Some(weights);
+                }
+            }
+        }
+        TransactionType::Evidence => {
+            rewards::apply_evidence(network, transaction, height)?;
+        }
+    }
+    Ok(())
+}
+
+fn payload<T: for<'de> Deserialize<'de>>(transaction: &Transaction) -> Result<T, String> {
+    serde_json::from_value(transaction.data.clone())
+        .map_err(|e| format!("Malformed {:?} data: {}", transaction.transaction_type, e))
+}
+
+fn require_owner_node(network: &FranchiseNetwork, node_id: u64) -> Result<(), String> {
+    match network.nodes.get(&node_id) {
+        Some(node) if node.node_type == NodeType::OWNER => Ok(()),
+        _ => Err(format!("Node {} is not an owner node", node_id)),
+    }
+}
@@ -1,9 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use serde::{Serialize, Deserialize};
 use sha2::{Sha256, Digest};
 use hex;
+use crate::clock;
+use crate::consensus::ReputationWeights;
+use crate::crypto;
+use crate::ledger::{SharedLedger, TokenLedger};
+use crate::tokenomics::{self, TokenomicsSchedule, CHARITY_INDEX};
 
-// Константы токеномики
+// Константы токеномики; доли — генезис-политика сети (TokenomicsPolicy::franchise_network)
 pub const SCALE: u64 = 100; // 1 токен = 100 subunits
 pub const OWNER_OWNER_SHARE: u64 = 51; // 0.51 токена владельцу собственной точки
 pub const OWNER_BUYER_SHARE: u64 = 49; // 0.49 токена покупателю
@@ -28,6 +34,7 @@ pub struct FranchiseNode {
     pub active: bool,
     pub registered_at: u64,
     pub pos_systems: Vec<String>, // Whitelisted POS systems
+    pub public_key: Option<String>, // Ed25519 ключ ноды (hex), которым подписываются транзакции и блоки
 }
 
 // Структура продажи
@@ -59,6 +66,8 @@ pub struct TokenMinting {
     pub owner_units: u64,
     pub buyer_units: u64,
     pub royalty_units: u64,
+    #[serde(default)]
+    pub policy_height: u64, // effective_from_height политики, по которой распределена эмиссия
 }
 
 // Структура кошелька
@@ -71,6 +80,15 @@ pub struct Wallet {
     pub balance: u64, // в subunits
 }
 
+// Штраф ноды за двойную подпись
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Slashing {
+    pub node_id: u64,
+    pub evidence_height: u64, // Высота, на которой нода подписала два блока
+    pub block_height: u64,    // Блок с транзакцией-доказательством
+    pub slashed_units: u64,
+}
+
 // Основная сеть франшиз
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FranchiseNetwork {
@@ -82,6 +100,18 @@ pub struct FranchiseNetwork {
     pub whitelisted_pos: HashMap<String, bool>,
     pub total_supply: u64,
     pub next_node_id: u64,
+    #[serde(default)]
+    pub missed_slots: BTreeMap<u64, Vec<u64>>, // Высота блока -> выбранные валидаторы, не подписавшие его
+    #[serde(default)]
+    pub slashings: Vec<Slashing>,
+    #[serde(default = "TokenomicsSchedule::franchise_network")]
+    pub tokenomics: TokenomicsSchedule,
+    #[serde(default)]
+    pub height: u64, // Высота исполняемого блока; по ней выбирается политика токеномики
+    #[serde(default)]
+    pub reputation_weights: Option<ReputationWeights>, // Веса выбора валидаторов, принятые управлением сети
+    #[serde(skip)]
+    pub ledger: Option<SharedLedger>, // Единый реестр токенов, если подключен; копии сети делят его
 }
 
 impl FranchiseNetwork {
@@ -95,6 +125,12 @@ impl FranchiseNetwork {
             whitelisted_pos: HashMap::new(),
             total_supply: SCALE, // Генезис: 1 токен
             next_node_id: 1,
+            missed_slots: BTreeMap::new(),
+            slashings: Vec::new(),
+            tokenomics: TokenomicsSchedule::franchise_network(),
+            height: 0,
+            reputation_weights: None,
+            ledger: None,
         };
         
         // Создаем генезис кошелек для master owner
@@ -109,6 +145,27 @@ impl FranchiseNetwork {
         network
     }
 
+    // Сохраненная сеть (DATA_DIR/franchise_network.json); None, если файла нет
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    // Запись через временный файл, чтобы сбой не оставил сеть наполовину записанной
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, bytes)
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
     // Регистрация новой ноды
     pub fn register_node(&mut self, owner_address: String, node_type: NodeType, city: String) -> Result<u64, String> {
         let node_id = self.next_node_id;
@@ -120,8 +177,9 @@ impl FranchiseNetwork {
             node_type,
             city,
             active: true,
-            registered_at: chrono::Utc::now().timestamp() as u64,
+            registered_at: clock::now_secs(),
             pos_systems: Vec::new(),
+            public_key: None,
         };
 
         self.nodes.insert(node_id, node);
@@ -132,7 +190,7 @@ impl FranchiseNetwork {
                 address: owner_address,
                 owner_type: "franchise".to_string(),
                 owner_id: node_id.to_string(),
-                created_at: chrono::Utc::now().timestamp() as u64,
+                created_at: clock::now_secs(),
                 balance: 0,
             });
         }
@@ -140,6 +198,43 @@ impl FranchiseNetwork {
         Ok(node_id)
     }
 
+    // Привязка публичного ключа к ноде (реестр ключей сети)
+    pub fn register_node_key(&mut self, node_id: u64, public_key: String) -> Result<(), String> {
+        self.check_node_key(Some(node_id), &public_key)?;
+
+        let node = self.nodes.get_mut(&node_id)
+            .ok_or("Node not found")?;
+        node.public_key = Some(public_key);
+        Ok(())
+    }
+
+    // Регистрация ноды вместе с ключом: ключ проверяется до регистрации, чтобы не оставить ноду без ключа
+    pub fn register_node_with_key(&mut self, owner_address: String, node_type: NodeType, city: String,
+                                  public_key: Option<String>) -> Result<u64, String> {
+        if let Some(public_key) = &public_key {
+            self.check_node_key(None, public_key)?;
+        }
+        let node_id = self.register_node(owner_address, node_type, city)?;
+        if let Some(public_key) = public_key {
+            self.register_node_key(node_id, public_key)?;
+        }
+        Ok(node_id)
+    }
+
+    // Ключ корректен и не принадлежит другой ноде
+    fn check_node_key(&self, node_id: Option<u64>, public_key: &str) -> Result<(), String> {
+        crypto::parse_public_key(public_key)?;
+        if self.nodes.values().any(|n| Some(n.node_id) != node_id && n.public_key.as_deref() == Some(public_key)) {
+            return Err("Public key is already registered for another node".to_string());
+        }
+        Ok(())
+    }
+
+    // Публичный ключ ноды, если он зарегистрирован
+    pub fn node_public_key(&self, node_id: u64) -> Option<&str> {
+        self.nodes.get(&node_id).and_then(|n| n.public_key.as_deref())
+    }
+
     // Создание детерминированного чек-адреса
     pub fn create_check_address(&mut self, sale_id: &str) -> String {
         let mut hasher = Sha256::new();
@@ -154,7 +249,7 @@ impl FranchiseNetwork {
                 address: address.clone(),
                 owner_type: "buyer".to_string(),
                 owner_id: sale_id.to_string(),
-                created_at: chrono::Utc::now().timestamp() as u64,
+                created_at: clock::now_secs(),
                 balance: 0,
             });
         }
@@ -186,7 +281,7 @@ impl FranchiseNetwork {
         let sale = Sale {
             sale_id: sale_id.clone(),
             node_id,
-            timestamp: chrono::Utc::now().timestamp() as u64,
+            timestamp: clock::now_secs(),
             price_gel,
             check_address: check_address.clone(),
             buyer_meta,
@@ -209,41 +304,31 @@ impl FranchiseNetwork {
             .ok_or("Node not found")?;
 
         let minted_units = SCALE; // 1 токен = 100 subunits
-        self.total_supply += minted_units;
-
-        let (owner_units, buyer_units, royalty_units) = match node.node_type {
-            NodeType::OWNER => {
-                // Собственная точка: 51% владельцу, 49% покупателю
-                (OWNER_OWNER_SHARE, OWNER_BUYER_SHARE, 0)
-            },
-            NodeType::FRANCHISE => {
-                // Франшиза: 48% франчайзи, 3% роялти, 49% покупателю
-                (FRANCHISE_OWNER_SHARE, FRANCHISE_BUYER_SHARE, FRANCHISE_ROYALTY_SHARE)
-            }
-        };
-
-        // Обновляем балансы
-        if let Some(owner_wallet) = self.wallets.get_mut(&node.owner_address) {
-            owner_wallet.balance += owner_units;
-        }
+        let owner_address = node.owner_address.clone();
 
-        if let Some(buyer_wallet) = self.wallets.get_mut(buyer_address) {
-            buyer_wallet.balance += buyer_units;
-        }
+        // Доли по политике высоты блока; фонда у сети нет, его доля уходит основателю вместе с роялти
+        let policy = self.tokenomics.policy_at(self.height);
+        let split = policy.split_for(&node.node_type, &node_id.to_string());
+        let parts = tokenomics::split_units(minted_units, &split.weights(), CHARITY_INDEX);
+        let (owner_units, buyer_units, royalty_units) = (parts[1], parts[3], parts[0] + parts[2]);
+        let policy_height = policy.effective_from_height;
 
+        // Обновляем балансы
+        self.mint(&owner_address, owner_units)?;
+        self.mint(buyer_address, buyer_units)?;
         if royalty_units > 0 {
-            if let Some(master_wallet) = self.wallets.get_mut(&self.master_owner) {
-                master_wallet.balance += royalty_units;
-            }
+            let master_owner = self.master_owner.clone();
+            self.mint(&master_owner, royalty_units)?;
         }
 
         let minting = TokenMinting {
-            mint_id: format!("mint_{}_{}", node_id, chrono::Utc::now().timestamp()),
+            mint_id: format!("mint_{}_{}", node_id, clock::now_secs()),
             sale_id: format!("sale_{}", node_id),
             minted_units,
             owner_units,
             buyer_units,
             royalty_units,
+            policy_height,
         };
 
         Ok(minting)
@@ -259,6 +344,64 @@ impl FranchiseNetwork {
         self.wallets.get(address).map(|w| w.balance).unwrap_or(0)
     }
 
+    // Подключение единого реестра: раздел сети в нем должен совпадать с ее балансами
+    pub fn attach_ledger(&mut self, ledger: SharedLedger) -> Result<(), String> {
+        ledger.lock().unwrap().ledger().verify_source(self)?;
+        self.ledger = Some(ledger);
+        Ok(())
+    }
+
+    // Включение или отключение ноды; неактивная нода не продает и не участвует в консенсусе
+    pub fn set_node_active(&mut self, node_id: u64, active: bool) -> Result<(), String> {
+        let node = self.nodes.get_mut(&node_id)
+            .ok_or("Node not found")?;
+        node.active = active;
+        Ok(())
+    }
+
+    // Эмиссия токенов на кошелек владельца ноды (награда валидатора)
+    pub fn mint_to_node_owner(&mut self, node_id: u64, units: u64) -> Result<(), String> {
+        let node = self.nodes.get(&node_id)
+            .ok_or("Node not found")?;
+        if !self.wallets.contains_key(&node.owner_address) {
+            return Err("Owner wallet not found".to_string());
+        }
+        let owner_address = node.owner_address.clone();
+        self.mint(&owner_address, units)
+    }
+
+    // Сжигание токенов с кошелька владельца ноды; возвращает фактически списанное
+    pub fn burn_from_node_owner(&mut self, node_id: u64, units: u64) -> Result<u64, String> {
+        let node = self.nodes.get(&node_id)
+            .ok_or("Node not found")?;
+        if !self.wallets.contains_key(&node.owner_address) {
+            return Err("Owner wallet not found".to_string());
+        }
+        let owner_address = node.owner_address.clone();
+        self.burn(&owner_address, units)
+    }
+
+    // Штраф за двойную подпись на высоте evidence_height: сжигается percent баланса владельца.
+    // За одно нарушение нода штрафуется один раз.
+    pub fn slash_node(&mut self, node_id: u64, evidence_height: u64, block_height: u64, percent: u64) -> Result<u64, String> {
+        if self.slashings.iter().any(|s| s.node_id == node_id && s.evidence_height == evidence_height) {
+            return Err(format!("Node {} is already slashed for height {}", node_id, evidence_height));
+        }
+        let node = self.nodes.get(&node_id)
+            .ok_or("Node not found")?;
+        let balance = self.get_wallet_balance(&node.owner_address);
+        let slashed_units = self.burn_from_node_owner(node_id, balance * percent / 100)?;
+        self.slashings.push(Slashing { node_id, evidence_height, block_height, slashed_units });
+        Ok(slashed_units)
+    }
+
+    // Пропуски слотов ноды в блоках начиная с высоты since_height
+    pub fn missed_slot_count(&self, node_id: u64, since_height: u64) -> usize {
+        self.missed_slots.range(since_height..)
+            .filter(|(_, missed)| missed.contains(&node_id))
+            .count()
+    }
+
     // Получение информации о ноде
     pub fn get_node_info(&self, node_id: u64) -> Option<&FranchiseNode> {
         self.nodes.get(&node_id)
@@ -281,6 +424,62 @@ impl FranchiseNetwork {
     }
 }
 
+// Кошельки сети в едином реестре; ноды адресуются строкой своего node_id.
+// Кошелек, впервые получающий токены, заводится как кошелек покупателя.
+impl TokenLedger for FranchiseNetwork {
+    fn namespace(&self) -> &'static str {
+        "franchise_network"
+    }
+
+    fn balance(&self, address: &str) -> u64 {
+        self.get_wallet_balance(address)
+    }
+
+    fn total_supply(&self) -> u64 {
+        self.total_supply
+    }
+
+    fn balances(&self) -> BTreeMap<String, u64> {
+        self.wallets.iter().map(|(address, wallet)| (address.clone(), wallet.balance)).collect()
+    }
+
+    fn franchise_nodes(&self) -> BTreeMap<String, String> {
+        self.nodes.values().map(|node| (node.node_id.to_string(), node.owner_address.clone())).collect()
+    }
+
+    fn mint(&mut self, address: &str, units: u64) -> Result<(), String> {
+        if let Some(ledger) = &self.ledger {
+            ledger.lock().unwrap().credit(self.namespace(), address, units)?;
+        }
+        self.wallets.entry(address.to_string())
+            .or_insert_with(|| Wallet {
+                address: address.to_string(),
+                owner_type: "buyer".to_string(),
+                owner_id: address.to_string(),
+                created_at: clock::now_secs(),
+                balance: 0,
+            })
+            .balance += units;
+        self.total_supply += units;
+        Ok(())
+    }
+
+    fn burn(&mut self, address: &str, units: u64) -> Result<u64, String> {
+        let burned = units.min(self.get_wallet_balance(address));
+        if burned == 0 {
+            return Ok(0);
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.lock().unwrap().debit(self.namespace(), address, burned)?;
+        }
+        if let Some(wallet) = self.wallets.get_mut(address) {
+            wallet.balance -= burned;
+        }
+        self.total_supply -= burned;
+        Ok(burned)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct NetworkStats {
     pub total_nodes: usize,
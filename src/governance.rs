@@ -0,0 +1,255 @@
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use crate::clock;
+use crate::consensus::ReputationWeights;
+
+// Предложения держателей токенов по параметрам протокола (голосование по меню — MenuItem).
+// Голосуют utility токенами, один кошелек — один бюллетень (BallotBox); голоса считаются по стратегии
+// предложения (VotingStrategy). Предложение принято, если после окна голосования проголосовали кошельки
+// с долей utility токенов снимка не меньше кворума и доля "за" больше порога.
+// Принятое изменение применяется один раз при финализации (Blockchain::finalize_proposal).
+
+pub const DEFAULT_VOTING_DAYS: u64 = 7;
+pub const DEFAULT_QUORUM_PERCENTAGE: f64 = 20.0;
+pub const DEFAULT_THRESHOLD_PERCENTAGE: f64 = 50.0;
+pub const CONVICTION_STEP_SECS: u64 = 24 * 60 * 60; // Каждые сутки без смены голоса добавляют его вес
+pub const CONVICTION_MAX_MULTIPLIER: u64 = 8;
+
+// Подсчет голосов, выбирается при открытии голосования
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum VotingStrategy {
+    #[default]
+    Linear,     // Вес = utility токены
+    Quadratic,  // Вес = квадратный корень из utility токенов
+    Conviction, // Вес растет на 1x за сутки, пока голос не меняется, до CONVICTION_MAX_MULTIPLIER
+    Delegated,  // Токены кошелька с делегатом голосуют через конечного делегата цепочки
+}
+
+impl VotingStrategy {
+    // Вес бюллетеня по utility токенам кошелька в снимке
+    fn weight(self, tokens: u64) -> u64 {
+        match self {
+            VotingStrategy::Quadratic => tokens.isqrt(),
+            _ => tokens,
+        }
+    }
+
+    // Множитель голоса, простоявшего held_secs
+    fn multiplier(self, held_secs: u64) -> u64 {
+        match self {
+            VotingStrategy::Conviction => (1 + held_secs / CONVICTION_STEP_SECS).min(CONVICTION_MAX_MULTIPLIER),
+            _ => 1,
+        }
+    }
+}
+
+// Utility токены, заблокированные голосом с убежденностью до конца окна голосования: множитель растет,
+// только пока токены остаются на кошельке
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtilityLock {
+    pub units: u64,
+    pub until: u64,
+}
+
+// Изменение, которое применяется при принятии предложения
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProposalChange {
+    MaxOwnerPercentage { percentage: f64 },
+    MinStake { units: u64 },
+    BlockReward { units: u64 },
+    // Роялти основателя с франшизных продаж, в процентах; разница переходит владельцу франшизы
+    RoyaltyShare { percentage: u64 },
+    // Веса выбора валидаторов франшизной сети; принятое решение уходит в ее цепь транзакцией Governance
+    ReputationWeights { weights: ReputationWeights },
+    AdmitFranchise { node_id: String, franchise_owner: String },
+}
+
+impl ProposalChange {
+    // Проверки, не зависящие от состояния; они же повторяются при исполнении
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ProposalChange::MaxOwnerPercentage { percentage } if !(0.0..=100.0).contains(percentage) => {
+                Err("Max owner percentage must be between 0% and 100%".to_string())
+            }
+            ProposalChange::RoyaltyShare { percentage } if *percentage > 100 => {
+                Err(format!("Royalty share {}% exceeds 100%", percentage))
+            }
+            ProposalChange::ReputationWeights { weights } => weights.validate(),
+            ProposalChange::AdmitFranchise { node_id, franchise_owner } if node_id.is_empty() || franchise_owner.is_empty() => {
+                Err("Franchise node and owner must not be empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Правила голосования по предложению
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VotingRules {
+    pub voting_days: u64,
+    pub quorum_percentage: f64,    // Проголосовавшие utility токены от снимка
+    pub threshold_percentage: f64, // Доля "за" среди поданных голосов должна быть больше
+    #[serde(default)]
+    pub strategy: VotingStrategy,
+}
+
+impl Default for VotingRules {
+    fn default() -> Self {
+        Self {
+            voting_days: DEFAULT_VOTING_DAYS,
+            quorum_percentage: DEFAULT_QUORUM_PERCENTAGE,
+            threshold_percentage: DEFAULT_THRESHOLD_PERCENTAGE,
+            strategy: VotingStrategy::Linear,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProposalStatus {
+    Voting,
+    Rejected,
+    Executed,
+    Failed { reason: String }, // Принято, но изменение не применилось
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ballot {
+    pub vote_for: bool,
+    pub weight: u64, // Вес по стратегии без множителя убежденности
+    #[serde(default)]
+    pub cast_at: u64,
+}
+
+// Бюллетени одного голосования (предложение или позиция меню). Вес кошелька — его utility токены
+// в снимке на момент открытия голосования, поэтому токены, переведенные после открытия, второй раз
+// не голосуют. До конца окна голос можно изменить; итоги всегда пересчитываются из бюллетеней.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BallotBox {
+    pub opened: bool,
+    #[serde(default)]
+    pub strategy: VotingStrategy,
+    pub snapshot: BTreeMap<String, u64>, // Кошелек -> utility токены на момент открытия (с делегированными)
+    pub ballots: BTreeMap<String, Ballot>,
+}
+
+impl BallotBox {
+    pub fn open(snapshot: BTreeMap<String, u64>, strategy: VotingStrategy) -> Self {
+        Self { opened: true, strategy, snapshot, ballots: BTreeMap::new() }
+    }
+
+    // Все utility токены снимка (база кворума)
+    pub fn total_power(&self) -> u64 {
+        self.snapshot.values().sum()
+    }
+
+    // Utility токены проголосовавших кошельков, независимо от стратегии
+    pub fn participation(&self) -> u64 {
+        self.ballots.keys().map(|voter| self.snapshot.get(voter).copied().unwrap_or(0)).sum()
+    }
+
+    // Новый или измененный голос; возвращает вес бюллетеня
+    pub fn cast(&mut self, voter: &str, vote_for: bool) -> Result<u64, String> {
+        let tokens = self.snapshot.get(voter).copied().unwrap_or(0);
+        if tokens == 0 {
+            return Err(format!("Wallet {} has no voting power in the snapshot", voter));
+        }
+        if self.ballots.get(voter).is_some_and(|previous| previous.vote_for == vote_for) {
+            return Err(format!("Wallet {} has already voted {}", voter, if vote_for { "for" } else { "against" }));
+        }
+        let weight = self.strategy.weight(tokens);
+        self.ballots.insert(voter.to_string(), Ballot { vote_for, weight, cast_at: clock::now_secs() });
+        Ok(weight)
+    }
+
+    // Голоса "за" и "против" на момент at
+    pub fn tally(&self, at: u64) -> (u64, u64) {
+        self.ballots.values().fold((0, 0), |(votes_for, votes_against), ballot| {
+            let weight = ballot.weight * self.strategy.multiplier(at.saturating_sub(ballot.cast_at));
+            if ballot.vote_for {
+                (votes_for + weight, votes_against)
+            } else {
+                (votes_for, votes_against + weight)
+            }
+        })
+    }
+}
+
+// Итоги голосования для истории голосований
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VotingTally {
+    pub subject_id: String, // Позиция меню или предложение
+    pub subject_name: String,
+    pub strategy: VotingStrategy,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub voters: usize,
+    pub is_final: bool, // Окно голосования закрыто
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    pub id: String,
+    pub proposer: String,
+    pub description: String,
+    pub change: ProposalChange,
+    pub rules: VotingRules,
+    pub created_timestamp: u64,
+    pub voting_ends: u64,
+    pub votes_for: u64,
+    pub votes_against: u64,
+    pub ballot_box: BallotBox,
+    pub status: ProposalStatus,
+}
+
+impl Proposal {
+    // Голосование открывается сразу; snapshot — utility токены кошельков на этот момент
+    pub fn new(proposer: String, description: String, change: ProposalChange, rules: VotingRules, snapshot: BTreeMap<String, u64>) -> Self {
+        let timestamp = clock::now_secs();
+        let mut hasher = Sha256::new();
+        hasher.update(format!("proposal|{}|{}|{}", proposer, description, timestamp).as_bytes());
+        Self {
+            id: hex::encode(&hasher.finalize()[..8]),
+            proposer,
+            description,
+            change,
+            voting_ends: timestamp + rules.voting_days * 24 * 60 * 60,
+            ballot_box: BallotBox::open(snapshot, rules.strategy),
+            rules,
+            created_timestamp: timestamp,
+            votes_for: 0,
+            votes_against: 0,
+            status: ProposalStatus::Voting,
+        }
+    }
+
+    pub fn vote(&mut self, voter: &str, vote_for: bool) -> Result<u64, String> {
+        if self.status != ProposalStatus::Voting {
+            return Err(format!("Proposal {} is not open for voting", self.id));
+        }
+        if clock::now_secs() > self.voting_ends {
+            return Err("Voting period has ended".to_string());
+        }
+        let weight = self.ballot_box.cast(voter, vote_for)?;
+        (self.votes_for, self.votes_against) = self.ballot_box.tally(clock::now_secs());
+        Ok(weight)
+    }
+
+    // Итоги после окна голосования; возвращает, принято ли предложение. Статус выставляет вызывающий.
+    pub fn close(&mut self) -> Result<bool, String> {
+        if self.status != ProposalStatus::Voting {
+            return Err(format!("Proposal {} is already finalized", self.id));
+        }
+        if clock::now_secs() <= self.voting_ends {
+            return Err(format!("Voting on proposal {} is open until {}", self.id, self.voting_ends));
+        }
+        // Кворум — по токенам проголосовавших, порог — по голосам стратегии на конец окна
+        (self.votes_for, self.votes_against) = self.ballot_box.tally(self.voting_ends);
+        let total_power = self.ballot_box.total_power();
+        let participation = self.ballot_box.participation();
+        let quorum = total_power > 0 && participation as f64 * 100.0 >= self.rules.quorum_percentage * total_power as f64;
+        let cast = self.votes_for + self.votes_against;
+        let threshold = self.votes_for as f64 * 100.0 > self.rules.threshold_percentage * cast as f64;
+        Ok(quorum && threshold)
+    }
+}
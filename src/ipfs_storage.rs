@@ -1,13 +1,42 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 use serde::{Serialize, Deserialize};
+use crate::blob_store::{BlobStore, Cid, CHUNK_SIZE};
+use crate::clock;
 use crate::franchise_network::{FranchiseNetwork, Sale, SaleItem};
 
-// IPFS интеграция для децентрализованного хранения
+// IPFS интеграция для децентрализованного хранения.
+// Содержимое лежит в локальном контентно-адресуемом хранилище (BlobStore) и адресуется CIDv1;
+// индекс index.json хранит тип и размер каждого сохраненного объекта, его закрепления и ссылки.
+// Сборка мусора оставляет закрепленные объекты и все, на что они ссылаются (изображения меню),
+// остальные блоки удаляются с диска.
+// Между нодами содержимое передается поблочно (P2PMessage::WantList/Have/ContentBlock): каждый
+// полученный блок сверяется со своим CID, собранное содержимое попадает в индекс.
+const INDEX_FILE: &str = "index.json";
+pub const DEFAULT_REPLICATION_FACTOR: usize = 2; // Скольким пирам предлагать собственное закрепленное содержимое
+pub const MAX_WANTED_BLOCKS: usize = 64; // CID в одном WantList или Have
+pub const MAX_REPLICAS_PER_PEER: usize = 256; // Объектов, которые нода хранит как реплики одного пира
+pub const MAX_REPLICA_BYTES_PER_PEER: u64 = 256 * 1024 * 1024;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IPFSStorage {
     pub gateway_url: String,
-    pub local_cache: HashMap<String, String>, // hash -> content
-    pub pinned_hashes: Vec<String>,
+    pub root: PathBuf,
+    pub index: BTreeMap<String, StoredData>, // CID -> описание содержимого
+    pub reclaimed_bytes: u64,                // Освобождено сборкой мусора с момента открытия
+    pub replication_factor: usize,
+    store: BlobStore,
+    #[serde(skip)]
+    wants: BTreeMap<String, String>, // Запрошенный у пиров блок -> CID содержимого, которому он нужен
+    #[serde(skip)]
+    fetches: BTreeMap<String, Fetch>, // Содержимое, которое собирается из блоков пиров
+}
+
+#[derive(Debug, Clone, Default)]
+struct Fetch {
+    replica_of: Option<u64>,      // Нода, которая предложила реплику; None — запрошено этой нодой
+    content_type: Option<String>, // Приходит вместе с корневым блоком
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +46,19 @@ pub struct StoredData {
     pub size: usize,
     pub created_at: u64,
     pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub pins: Vec<PinReason>, // Пока список не пуст, объект не удаляется
+    #[serde(default)]
+    pub references: Vec<String>, // CID, которые нужны этому объекту
+}
+
+// Зачем содержимое закреплено; один объект может быть закреплен по нескольким причинам
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PinReason {
+    MenuVersion { version: u32 },
+    SalesReport { node_id: u64, period_end: u64 },
+    Backup { created_at: u64 },
+    Replica { node_id: u64 }, // Копия закрепленного содержимого другой ноды
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,55 +117,167 @@ pub struct DailySales {
 }
 
 impl IPFSStorage {
-    pub fn new(gateway_url: String) -> Self {
-        Self {
+    // Хранилище в каталоге root; индекс прошлых запусков загружается с диска
+    pub fn open(root: &Path, gateway_url: String) -> Result<Self, String> {
+        let store = BlobStore::open(root)?;
+        let index_path = root.join(INDEX_FILE);
+        let index = if index_path.exists() {
+            let bytes = fs::read(&index_path)
+                .map_err(|e| format!("Failed to read {}: {}", index_path.display(), e))?;
+            serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Invalid storage index {}: {}", index_path.display(), e))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
             gateway_url,
-            local_cache: HashMap::new(),
-            pinned_hashes: Vec::new(),
-        }
+            root: root.to_path_buf(),
+            index,
+            reclaimed_bytes: 0,
+            replication_factor: DEFAULT_REPLICATION_FACTOR,
+            store,
+            wants: BTreeMap::new(),
+            fetches: BTreeMap::new(),
+        })
     }
 
-    // Сохранение данных в IPFS (симуляция)
-    pub fn store_data(&mut self, content: &str, content_type: &str) -> Result<String, String> {
-        // В реальной реализации здесь был бы вызов IPFS API
-        let hash = self.generate_hash(content);
-        
-        let stored_data = StoredData {
+    // Сохранение байтов; возвращает CID. Незакрепленное содержимое живет до сборки мусора
+    pub fn store_bytes(&mut self, bytes: &[u8], content_type: &str, metadata: HashMap<String, String>) -> Result<String, String> {
+        self.store_object(bytes, content_type, metadata, Vec::new(), None)
+    }
+
+    // Повторное сохранение тех же байтов добавляет ссылки и закрепление к уже записанному объекту
+    pub fn store_object(&mut self, bytes: &[u8], content_type: &str, metadata: HashMap<String, String>,
+                    references: Vec<String>, pin: Option<PinReason>) -> Result<String, String> {
+        let hash = self.store.put(bytes)?.to_string();
+
+        let stored_data = self.index.entry(hash.clone()).or_insert_with(|| StoredData {
             hash: hash.clone(),
             content_type: content_type.to_string(),
-            size: content.len(),
-            created_at: chrono::Utc::now().timestamp() as u64,
-            metadata: HashMap::new(),
-        };
-        
-        // Сохраняем в локальный кеш
-        self.local_cache.insert(hash.clone(), content.to_string());
-        self.pinned_hashes.push(hash.clone());
-        
+            size: bytes.len(),
+            created_at: clock::now_secs(),
+            metadata,
+            pins: Vec::new(),
+            references: Vec::new(),
+        });
+        for reference in references {
+            if !stored_data.references.contains(&reference) {
+                stored_data.references.push(reference);
+            }
+        }
+        if let Some(reason) = pin {
+            if !stored_data.pins.contains(&reason) {
+                stored_data.pins.push(reason);
+            }
+        }
+        self.save_index()?;
+
         println!("📦 Stored data in IPFS: {}", hash);
         Ok(hash)
     }
 
-    // Получение данных из IPFS
-    pub fn retrieve_data(&self, hash: &str) -> Result<String, String> {
-        // Сначала проверяем локальный кеш
-        if let Some(content) = self.local_cache.get(hash) {
-            return Ok(content.clone());
+    // Снимает одно закрепление; остальные причины продолжают удерживать объект
+    pub fn unpin(&mut self, hash: &str, reason: &PinReason) -> Result<(), String> {
+        let stored_data = self.index.get_mut(hash).ok_or_else(|| format!("Content {} is not in the local store", hash))?;
+        let position = stored_data.pins.iter().position(|pin| pin == reason)
+            .ok_or_else(|| format!("Content {} is not pinned as {:?}", hash, reason))?;
+        stored_data.pins.remove(position);
+        self.save_index()
+    }
+
+    // Закрепленные CID с причинами
+    pub fn pins(&self) -> impl Iterator<Item = (&str, &PinReason)> {
+        self.index.values().flat_map(|data| data.pins.iter().map(move |reason| (data.hash.as_str(), reason)))
+    }
+
+    // Закрепленные объекты и все объекты, достижимые от них по ссылкам
+    fn live_objects(&self) -> BTreeSet<String> {
+        self.reachable_from(|pins| !pins.is_empty())
+    }
+
+    // Объекты, закрепленные по причине, подходящей под is_root, и все, на что они ссылаются
+    fn reachable_from(&self, is_root: impl Fn(&[PinReason]) -> bool) -> BTreeSet<String> {
+        let mut live = BTreeSet::new();
+        let mut pending: Vec<String> = self.index.values()
+            .filter(|data| is_root(&data.pins))
+            .map(|data| data.hash.clone())
+            .collect();
+        while let Some(hash) = pending.pop() {
+            if let Some(data) = self.index.get(&hash) {
+                if live.insert(hash) {
+                    pending.extend(data.references.iter().cloned());
+                }
+            }
         }
-        
-        // В реальной реализации здесь был бы запрос к IPFS gateway
-        println!("🌐 Retrieving data from IPFS: {}", hash);
-        
-        // Симуляция получения данных
-        Ok(format!("Retrieved data for hash: {}", hash))
+        live
+    }
+
+    // Удаляет незакрепленные объекты без ссылок на них и возвращает освобожденные байты.
+    // Блоки содержимого, которое еще собирается у пиров, живые: иначе сборка удалила бы уже полученные куски.
+    pub fn collect_garbage(&mut self) -> Result<u64, String> {
+        let live = self.live_objects();
+        let mut live_blocks = BTreeSet::new();
+        for hash in live.iter().chain(self.fetches.keys()) {
+            let cid: Cid = hash.parse()?;
+            match self.store.blocks_of(&cid) {
+                Ok(blocks) => live_blocks.extend(blocks),
+                // Корень живого объекта пропал с диска: его запросит у пиров sync_with_network
+                Err(e) if live.contains(hash) => println!("⚠️ Garbage collection skipped {}: {}", hash, e),
+                Err(_) => {}
+            }
+            live_blocks.insert(cid);
+        }
+        for hash in self.wants.keys() {
+            live_blocks.insert(hash.parse()?);
+        }
+        let reclaimed = self.store.retain(&live_blocks)?;
+        self.index.retain(|hash, _| live.contains(hash));
+        self.save_index()?;
+        self.reclaimed_bytes += reclaimed;
+
+        println!("🧹 Garbage collection reclaimed {} bytes", reclaimed);
+        Ok(reclaimed)
+    }
+
+    // Хранилище блоков для чтения без блокировки IPFSStorage: блоки неизменны и адресуются CID
+    pub fn blob_store(&self) -> BlobStore {
+        self.store.clone()
+    }
+
+    // Байты по CID с проверкой целостности; отсутствующие данные — ошибка
+    pub fn retrieve_bytes(&self, hash: &str) -> Result<Vec<u8>, String> {
+        let cid: Cid = hash.parse()?;
+        self.store.get(&cid)
+    }
+
+    pub fn store_data(&mut self, content: &str, content_type: &str) -> Result<String, String> {
+        self.store_bytes(content.as_bytes(), content_type, HashMap::new())
+    }
+
+    pub fn retrieve_data(&self, hash: &str) -> Result<String, String> {
+        String::from_utf8(self.retrieve_bytes(hash)?)
+            .map_err(|_| format!("Content {} is not UTF-8 text", hash))
+    }
+
+    // Индекс пишется во временный файл и переименовывается
+    fn save_index(&self) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(&self.index)
+            .map_err(|e| format!("Failed to serialize storage index: {}", e))?;
+        let path = self.root.join(INDEX_FILE);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, bytes)
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
     }
 
-    // Сохранение меню в IPFS
+    // Сохранение меню в IPFS; версия закрепляется и удерживает изображения своих блюд
     pub fn store_menu(&mut self, menu_data: &MenuData) -> Result<String, String> {
         let content = serde_json::to_string(menu_data)
             .map_err(|e| format!("Failed to serialize menu: {}", e))?;
+        let images = menu_data.items.iter().filter_map(|item| item.image_hash.clone()).collect();
         
-        self.store_data(&content, "application/json")
+        self.store_object(content.as_bytes(), "application/json", HashMap::new(), images,
+                          Some(PinReason::MenuVersion { version: menu_data.version }))
     }
 
     // Получение меню из IPFS
@@ -137,8 +291,9 @@ impl IPFSStorage {
     pub fn store_sales_report(&mut self, report: &SalesReport) -> Result<String, String> {
         let content = serde_json::to_string(report)
             .map_err(|e| format!("Failed to serialize sales report: {}", e))?;
+        let reason = PinReason::SalesReport { node_id: report.node_id, period_end: report.period_end };
         
-        self.store_data(&content, "application/json")
+        self.store_object(content.as_bytes(), "application/json", HashMap::new(), Vec::new(), Some(reason))
     }
 
     // Получение отчета о продажах
@@ -148,76 +303,175 @@ impl IPFSStorage {
             .map_err(|e| format!("Failed to deserialize sales report: {}", e))
     }
 
-    // Сохранение изображения (симуляция)
+    // Сохранение изображения; тип содержимого — по расширению файла
     pub fn store_image(&mut self, image_data: &[u8], filename: &str) -> Result<String, String> {
-        // В реальной реализации здесь был бы вызов IPFS API для изображений
-        let hash = self.generate_hash(&format!("{:?}", image_data));
-        
+        let metadata = HashMap::from([("filename".to_string(), filename.to_string())]);
+        let hash = self.store_bytes(image_data, image_content_type(filename), metadata)?;
+
         println!("🖼️  Stored image in IPFS: {} ({} bytes)", hash, image_data.len());
         Ok(hash)
     }
 
-    // Получение изображения
     pub fn retrieve_image(&self, hash: &str) -> Result<Vec<u8>, String> {
-        // Симуляция получения изображения
-        println!("🖼️  Retrieving image from IPFS: {}", hash);
-        Ok(vec![0u8; 1024]) // Заглушка
+        self.retrieve_bytes(hash)
     }
 
-    // Генерация хеша (упрощенная версия)
-    fn generate_hash(&self, content: &str) -> String {
-        use sha2::{Sha256, Digest};
-        let mut hasher = Sha256::new();
-        hasher.update(content.as_bytes());
-        format!("Qm{}", hex::encode(&hasher.finalize()[..20]))
-    }
-
-    // Синхронизация с IPFS сетью
+    // Проверка закрепленного содержимого; возвращает CID, которых нет локально целиком
     pub fn sync_with_network(&mut self) -> Result<Vec<String>, String> {
         println!("🔄 Syncing with IPFS network...");
         
-        // В реальной реализации здесь была бы синхронизация с IPFS
-        let new_hashes = vec![
-            "QmNewHash1".to_string(),
-            "QmNewHash2".to_string(),
-        ];
-        
-        for hash in &new_hashes {
-            self.pinned_hashes.push(hash.clone());
-        }
-        
-        Ok(new_hashes)
+        let missing = self.live_objects().into_iter()
+            .filter(|hash| self.retrieve_bytes(hash).is_err())
+            .collect();
+        Ok(missing)
     }
 
     // Получение статистики хранилища
     pub fn get_storage_stats(&self) -> StorageStats {
+        let live = self.live_objects();
         StorageStats {
-            total_files: self.local_cache.len(),
-            total_size: self.local_cache.values().map(|v| v.len()).sum(),
-            pinned_hashes: self.pinned_hashes.len(),
+            total_files: self.index.len(),
+            total_size: self.index.values().map(|data| data.size).sum(),
+            pinned_hashes: self.index.values().filter(|data| !data.pins.is_empty()).count(),
+            garbage_files: self.index.len() - live.len(),
+            reclaimed_bytes: self.reclaimed_bytes,
             gateway_url: self.gateway_url.clone(),
         }
     }
 }
 
+fn image_content_type(filename: &str) -> &'static str {
+    let extension = filename.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StorageStats {
     pub total_files: usize,
     pub total_size: usize,
     pub pinned_hashes: usize,
+    pub garbage_files: usize,   // Будут удалены следующей сборкой мусора
+    pub reclaimed_bytes: u64,
     pub gateway_url: String,
 }
 
-// Интеграция с франшизной сетью
+// Обмен содержимым с пирами
 impl IPFSStorage {
-    // Сохранение всех данных франшизной сети в IPFS
-    pub fn backup_franchise_network(&mut self, network: &FranchiseNetwork) -> Result<String, String> {
-        let backup_data = serde_json::to_string(network)
-            .map_err(|e| format!("Failed to serialize network: {}", e))?;
-        
-        self.store_data(&backup_data, "application/json")
+    // Собственное закрепленное содержимое и то, на что оно ссылается; реплики чужого дальше не раздаются
+    pub fn replication_set(&self) -> Vec<String> {
+        let own = |pins: &[PinReason]| pins.iter().any(|pin| !matches!(pin, PinReason::Replica { .. }));
+        self.reachable_from(own).into_iter().collect()
+    }
+
+    // Реплики пира: хранимые и собираемые объекты и байты хранимых
+    fn replica_usage(&self, node_id: u64) -> (usize, u64) {
+        let pin = PinReason::Replica { node_id };
+        let stored = self.index.values().filter(|data| data.pins.contains(&pin));
+        let (count, bytes) = stored.fold((0, 0), |(count, bytes), data| (count + 1, bytes + data.size as u64));
+        let fetching = self.fetches.values().filter(|fetch| fetch.replica_of == Some(node_id)).count();
+        (count + fetching, bytes)
+    }
+
+    // Начинает сбор содержимого у пиров; false — оно уже целиком есть локально.
+    // Реплика пира сверх MAX_REPLICAS_PER_PEER или MAX_REPLICA_BYTES_PER_PEER — ошибка.
+    pub fn want(&mut self, hash: &str, replica_of: Option<u64>) -> Result<bool, String> {
+        if self.retrieve_bytes(hash).is_ok() {
+            return Ok(false);
+        }
+        if let Some(node_id) = replica_of {
+            let (count, bytes) = self.replica_usage(node_id);
+            if count >= MAX_REPLICAS_PER_PEER || bytes >= MAX_REPLICA_BYTES_PER_PEER {
+                return Err(format!("Node {} has used its replica quota ({} objects, {} bytes)", node_id, count, bytes));
+            }
+        }
+        self.fetches.entry(hash.to_string()).or_insert_with(|| Fetch { replica_of, content_type: None });
+        self.wants.insert(hash.to_string(), hash.to_string());
+        Ok(true)
+    }
+
+    // Блоки, которые еще ждем от пиров
+    pub fn wanted(&self) -> Vec<String> {
+        self.wants.keys().take(MAX_WANTED_BLOCKS).cloned().collect()
     }
 
+    // Проверенный блок для пира и тип содержимого, если это корень известного объекта
+    pub fn provide(&self, hash: &str) -> Option<(Vec<u8>, Option<String>)> {
+        let block = self.store.get_block(&hash.parse().ok()?).ok()?;
+        Some((block, self.index.get(hash).map(|data| data.content_type.clone())))
+    }
+
+    // Блок от пира. Незапрошенные блоки игнорируются, подмененные — ошибка.
+    // Возвращает блоки, которые нужно запросить дальше (куски большого содержимого).
+    pub fn receive_block(&mut self, hash: &str, data: &[u8], content_type: Option<String>) -> Result<Vec<String>, String> {
+        let Some(root) = self.wants.get(hash).cloned() else {
+            return Ok(Vec::new());
+        };
+        let cid: Cid = hash.parse()?;
+        if !cid.verify(data) {
+            return Err(format!("Block {} failed integrity check", hash));
+        }
+        self.store.put_block(cid.codec(), data)?;
+        self.wants.remove(hash);
+
+        let mut next = Vec::new();
+        if root == hash {
+            let chunks: Vec<Cid> = self.store.blocks_of(&cid)?.into_iter().skip(1).collect();
+            let replica_of = self.fetches.get(&root).and_then(|fetch| fetch.replica_of);
+            if let Some(node_id) = replica_of {
+                // Размер реплики известен только по корню: куски сверх квоты не запрашиваем
+                let size = if chunks.is_empty() { data.len() as u64 } else { (chunks.len() * CHUNK_SIZE) as u64 };
+                let (_, bytes) = self.replica_usage(node_id);
+                if bytes + size > MAX_REPLICA_BYTES_PER_PEER {
+                    println!("⚠️ Dropped replica {} of node {}: {} bytes exceed its quota", root, node_id, size);
+                    self.abandon_fetch(&root);
+                    return Ok(Vec::new());
+                }
+            }
+            if let Some(fetch) = self.fetches.get_mut(&root) {
+                fetch.content_type = content_type;
+            }
+            for chunk in chunks {
+                if self.store.get_block(&chunk).is_err() {
+                    self.wants.insert(chunk.to_string(), root.clone());
+                    next.push(chunk.to_string());
+                }
+            }
+        }
+        if !self.wants.values().any(|wanted_for| *wanted_for == root) {
+            self.complete_fetch(&root)?;
+        }
+        Ok(next)
+    }
+
+    // Сбор прекращен: уже полученные блоки удалит сборка мусора
+    fn abandon_fetch(&mut self, root: &str) {
+        self.fetches.remove(root);
+        self.wants.retain(|_, wanted_for| wanted_for != root);
+    }
+
+    // Все блоки на месте: содержимое проверяется целиком и попадает в индекс
+    fn complete_fetch(&mut self, root: &str) -> Result<(), String> {
+        let fetch = self.fetches.remove(root).unwrap_or_default();
+        let bytes = self.retrieve_bytes(root)?;
+        let content_type = fetch.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+        let pin = fetch.replica_of.map(|node_id| PinReason::Replica { node_id });
+        let hash = self.store_object(&bytes, &content_type, HashMap::new(), Vec::new(), pin)?;
+        if hash != root {
+            return Err(format!("Content {} was reassembled as {}", root, hash));
+        }
+        Ok(())
+    }
+}
+
+// Интеграция с франшизной сетью
+impl IPFSStorage {
     // Создание отчета о продажах для ноды
     pub fn create_sales_report(&self, node_id: u64, network: &FranchiseNetwork, 
                               period_days: u32) -> Result<SalesReport, String> {
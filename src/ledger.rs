@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+use crate::storage;
+
+// Единый реестр security токенов: одно пространство кошельков, одна эмиссия и один реестр франшизных нод.
+// Blockchain (чеки, фонд, годовые распределения) и FranchiseNetwork (ноды, продажи с POS) начисляют и
+// списывают токены через TokenLedger; подключенный реестр (SharedLedger) проводит каждое начисление и
+// списание до того, как модель изменит свои балансы, и дописывает проводку в свой журнал на диске.
+// Проводки операции Blockchain помечены ее номером в WAL, поэтому после сбоя посреди операции
+// воспроизведение WAL дописывает недостающие проводки и не повторяет уже записанные. Счета и ноды в реестре
+// адресуются ключом "<модель>:<id>": числовые id нод сети и строковые ключи нод Blockchain не пересекаются.
+// Баланс кошелька — сумма его счетов во всех моделях. Все суммы в subunits (SCALE на токен).
+
+pub trait TokenLedger {
+    // Пространство имен модели в едином реестре
+    fn namespace(&self) -> &'static str;
+    fn balance(&self, address: &str) -> u64;
+    fn total_supply(&self) -> u64;
+    fn balances(&self) -> BTreeMap<String, u64>;
+    // Ключ франшизной ноды -> адрес владельца
+    fn franchise_nodes(&self) -> BTreeMap<String, String>;
+    // Эмиссия на кошелек; кошелек создается при первом начислении
+    fn mint(&mut self, address: &str, units: u64) -> Result<(), String>;
+    // Сжигание с кошелька; возвращает фактически списанное
+    fn burn(&mut self, address: &str, units: u64) -> Result<u64, String>;
+
+    fn transfer(&mut self, from: &str, to: &str, units: u64) -> Result<(), String> {
+        if from == to {
+            return Err("Cannot transfer to the same wallet".to_string());
+        }
+        let balance = self.balance(from);
+        if balance < units {
+            return Err(format!("Insufficient balance: {} < {}", balance, units));
+        }
+        self.burn(from, units)?;
+        self.mint(to, units)
+    }
+
+    // Сумма балансов равна эмиссии
+    fn audit(&self) -> Result<(), String> {
+        let balances: u64 = self.balances().values().sum();
+        if balances != self.total_supply() {
+            return Err(format!("ledger mismatch: minted {} subunits, balances hold {}", self.total_supply(), balances));
+        }
+        Ok(())
+    }
+}
+
+// Ключ счета или ноды модели в едином реестре
+pub fn account_key(namespace: &str, id: &str) -> String {
+    format!("{}:{}", namespace, id)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ledger {
+    pub accounts: BTreeMap<String, u64>, // "<модель>:<адрес>" -> subunits
+    pub supplies: BTreeMap<String, u64>, // модель -> выпущено ею
+    pub franchise_nodes: BTreeMap<String, String>, // "<модель>:<нода>" -> владелец
+    #[serde(default)]
+    pub seq: u64, // Номер последней проводки журнала реестра, вошедшей в файл
+    #[serde(default)]
+    pub origins: BTreeMap<String, (u64, u32)>, // модель -> последняя проводка из ее WAL: номер операции и проводки в ней
+}
+
+// Проводка единого реестра
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Posting {
+    Credit { namespace: String, address: String, units: u64 },
+    Debit { namespace: String, address: String, units: u64 },
+}
+
+impl Posting {
+    fn namespace(&self) -> &str {
+        match self {
+            Posting::Credit { namespace, .. } | Posting::Debit { namespace, .. } => namespace,
+        }
+    }
+}
+
+// Запись журнала реестра: номер, операция WAL модели с номером проводки в ней (если есть) и проводка
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LedgerRecord {
+    seq: u64,
+    origin: Option<(u64, u32)>,
+    posting: Posting,
+}
+
+impl Ledger {
+    pub fn total_supply(&self) -> u64 {
+        self.supplies.values().sum()
+    }
+
+    // Баланс кошелька во всех моделях
+    pub fn balance(&self, address: &str) -> u64 {
+        self.accounts.iter()
+            .filter(|(key, _)| key.split_once(':').is_some_and(|(_, owner)| owner == address))
+            .map(|(_, units)| units)
+            .sum()
+    }
+
+    pub fn credit(&mut self, namespace: &str, address: &str, units: u64) {
+        *self.accounts.entry(account_key(namespace, address)).or_insert(0) += units;
+        *self.supplies.entry(namespace.to_string()).or_insert(0) += units;
+    }
+
+    fn check_debit(&self, namespace: &str, address: &str, units: u64) -> Result<(), String> {
+        let key = account_key(namespace, address);
+        let balance = self.accounts.get(&key).copied().unwrap_or(0);
+        if balance < units {
+            return Err(format!("Ledger account {} holds {} subunits, cannot debit {}", key, balance, units));
+        }
+        Ok(())
+    }
+
+    pub fn debit(&mut self, namespace: &str, address: &str, units: u64) -> Result<(), String> {
+        self.check_debit(namespace, address, units)?;
+        let key = account_key(namespace, address);
+        if let Some(account) = self.accounts.get_mut(&key) {
+            *account -= units;
+        }
+        if let Some(supply) = self.supplies.get_mut(namespace) {
+            *supply -= units;
+        }
+        Ok(())
+    }
+
+    fn apply(&mut self, record: &LedgerRecord) -> Result<(), String> {
+        match &record.posting {
+            Posting::Credit { namespace, address, units } => self.credit(namespace, address, *units),
+            Posting::Debit { namespace, address, units } => self.debit(namespace, address, *units)?,
+        }
+        if let Some(origin) = record.origin {
+            self.origins.insert(record.posting.namespace().to_string(), origin);
+        }
+        self.seq = record.seq;
+        Ok(())
+    }
+
+    // Сумма счетов каждой модели равна ее эмиссии
+    pub fn audit(&self) -> Result<(), String> {
+        let mut held: BTreeMap<&str, u64> = BTreeMap::new();
+        for (key, units) in &self.accounts {
+            let namespace = key.split_once(':').map(|(namespace, _)| namespace).unwrap_or(key);
+            *held.entry(namespace).or_insert(0) += units;
+        }
+        for (namespace, units) in &held {
+            let supply = self.supplies.get(*namespace).copied().unwrap_or(0);
+            if supply != *units {
+                return Err(format!("ledger mismatch: {} minted {} subunits, its accounts hold {}", namespace, supply, units));
+            }
+        }
+        if let Some((namespace, supply)) = self.supplies.iter().find(|(namespace, supply)| **supply > 0 && !held.contains_key(namespace.as_str())) {
+            return Err(format!("ledger mismatch: {} minted {} subunits, its accounts hold 0", namespace, supply));
+        }
+        Ok(())
+    }
+
+    // Миграция: каждая модель должна сходиться сама с собой и попадает в свой раздел реестра
+    pub fn migrate(sources: &[&dyn TokenLedger]) -> Result<Self, String> {
+        let mut ledger = Ledger::default();
+        for source in sources {
+            source.audit()?;
+            let namespace = source.namespace();
+            if ledger.supplies.contains_key(namespace) {
+                return Err(format!("Model {} is migrated twice", namespace));
+            }
+            ledger.supplies.insert(namespace.to_string(), 0);
+            for (address, units) in source.balances() {
+                if units > 0 {
+                    ledger.credit(namespace, &address, units);
+                }
+            }
+            for (node, owner) in source.franchise_nodes() {
+                ledger.franchise_nodes.insert(account_key(namespace, &node), owner);
+            }
+        }
+        ledger.check_consistency(sources)?;
+        Ok(ledger)
+    }
+
+    // Сверка с исходными моделями: каждая покрывает свой раздел, других разделов нет
+    pub fn check_consistency(&self, sources: &[&dyn TokenLedger]) -> Result<(), String> {
+        self.audit()?;
+        for source in sources {
+            self.verify_source(*source)?;
+        }
+        if let Some(namespace) = self.supplies.keys().find(|namespace| !sources.iter().any(|source| source.namespace() == namespace.as_str())) {
+            return Err(format!("Ledger section {} has no source model", namespace));
+        }
+        Ok(())
+    }
+
+    // Раздел модели совпадает с ее эмиссией и балансом каждого кошелька
+    pub fn verify_source(&self, source: &dyn TokenLedger) -> Result<(), String> {
+        let namespace = source.namespace();
+        let supply = self.supplies.get(namespace).copied().unwrap_or(0);
+        if supply != source.total_supply() {
+            return Err(format!("Supply mismatch: ledger section {} has {} subunits, the model minted {}", namespace, supply, source.total_supply()));
+        }
+
+        let balances = source.balances();
+        for (address, units) in &balances {
+            let held = self.accounts.get(&account_key(namespace, address)).copied().unwrap_or(0);
+            if held != *units {
+                return Err(format!("Wallet {} holds {} subunits in ledger section {}, the model holds {}", address, held, namespace, units));
+            }
+        }
+        let prefix = account_key(namespace, "");
+        if let Some((key, _)) = self.accounts.iter()
+            .filter(|(key, units)| **units > 0 && key.starts_with(&prefix))
+            .find(|(key, _)| !balances.contains_key(&key[prefix.len()..])) {
+            return Err(format!("Ledger account {} is missing from the model", key));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    // Запись через временный файл, чтобы сбой не оставил реестр наполовину записанным
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let tmp_path = path.with_extension("json.tmp");
+        File::create(&tmp_path)
+            .and_then(|mut tmp| tmp.write_all(&bytes).and_then(|_| tmp.sync_all()))
+            .and_then(|_| std::fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        storage::sync_dir(path.parent().unwrap_or(Path::new(".")))
+    }
+}
+
+// Журнал проводок рядом с файлом реестра (ledger.json -> ledger.log)
+fn log_path(path: &Path) -> PathBuf {
+    path.with_extension("log")
+}
+
+fn read_log(path: &Path) -> Result<Vec<LedgerRecord>, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        return Err(format!("{} has a torn record at the tail", path.display()));
+    }
+    content.lines().enumerate().map(|(index, line)| {
+        let (sum, json) = line.split_once(' ')
+            .ok_or(format!("Ledger record {} is malformed", index + 1))?;
+        if storage::checksum(json.as_bytes()) != sum {
+            return Err(format!("Ledger record {} is corrupted", index + 1));
+        }
+        serde_json::from_str(json).map_err(|e| format!("Ledger record {} is unreadable: {}", index + 1, e))
+    }).collect()
+}
+
+// Реестр, подключенный к моделям процесса. Каждая проводка дописывается в журнал и синхронизируется
+// на диск до изменения реестра в памяти; проведение, которое не удалось сохранить, отменяется.
+// Раз в COMPACT_INTERVAL проводок реестр переписывается в файл целиком, а журнал обрезается.
+#[derive(Debug)]
+pub struct LedgerStore {
+    ledger: Ledger,
+    path: Option<PathBuf>,
+    log: Option<File>,
+    operations: BTreeMap<String, (u64, u32)>, // модель -> текущая операция ее WAL и номер следующей проводки
+}
+
+pub type SharedLedger = Arc<Mutex<LedgerStore>>;
+
+const COMPACT_INTERVAL: u64 = 1000;
+
+impl LedgerStore {
+    pub fn new(ledger: Ledger, path: Option<PathBuf>) -> SharedLedger {
+        Arc::new(Mutex::new(LedgerStore { ledger, path, log: None, operations: BTreeMap::new() }))
+    }
+
+    // Реестр из файла и его журнала; None, если миграция еще не проводилась
+    pub fn open(path: &Path) -> Result<Option<SharedLedger>, String> {
+        let Some(mut ledger) = Ledger::load(path)? else { return Ok(None) };
+        for record in read_log(&log_path(path))? {
+            if record.seq <= ledger.seq {
+                continue; // Уже в файле реестра: сбой между записью файла и обрезкой журнала
+            }
+            if record.seq != ledger.seq + 1 {
+                return Err(format!("Ledger log gap: expected record {}, found {}", ledger.seq + 1, record.seq));
+            }
+            ledger.apply(&record)?;
+        }
+        ledger.audit()?;
+        Ok(Some(Self::new(ledger, Some(path.to_path_buf()))))
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    // Проводки модели до end_operation относятся к операции seq ее WAL
+    pub fn begin_operation(&mut self, namespace: &str, seq: u64) {
+        self.operations.insert(namespace.to_string(), (seq, 0));
+    }
+
+    pub fn end_operation(&mut self, namespace: &str) {
+        self.operations.remove(namespace);
+    }
+
+    pub fn credit(&mut self, namespace: &str, address: &str, units: u64) -> Result<(), String> {
+        self.post(Posting::Credit { namespace: namespace.to_string(), address: address.to_string(), units })
+    }
+
+    pub fn debit(&mut self, namespace: &str, address: &str, units: u64) -> Result<(), String> {
+        self.post(Posting::Debit { namespace: namespace.to_string(), address: address.to_string(), units })
+    }
+
+    fn post(&mut self, posting: Posting) -> Result<(), String> {
+        let origin = self.operations.get_mut(posting.namespace()).map(|(seq, index)| {
+            *index += 1;
+            (*seq, *index - 1)
+        });
+        // Проводка, записанная до сбоя, при воспроизведении WAL не повторяется
+        let last = self.ledger.origins.get(posting.namespace()).copied();
+        if origin.is_some_and(|origin| last.is_some_and(|last| origin <= last)) {
+            return Ok(());
+        }
+        if let Posting::Debit { namespace, address, units } = &posting {
+            self.ledger.check_debit(namespace, address, *units)?;
+        }
+        let record = LedgerRecord { seq: self.ledger.seq + 1, origin, posting };
+        self.append(&record)?;
+        self.ledger.apply(&record)?;
+        if self.ledger.seq.is_multiple_of(COMPACT_INTERVAL) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, record: &LedgerRecord) -> Result<(), String> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let log_path = log_path(path);
+        if self.log.is_none() {
+            let log = OpenOptions::new().create(true).append(true).open(&log_path)
+                .map_err(|e| format!("Failed to open {}: {}", log_path.display(), e))?;
+            self.log = Some(log);
+        }
+        let json = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        let line = format!("{} {}\n", storage::checksum(json.as_bytes()), json);
+        let log = self.log.as_mut().expect("ledger log is open");
+        log.write_all(line.as_bytes())
+            .and_then(|_| log.sync_data())
+            .map_err(|e| format!("Failed to append to {}: {}", log_path.display(), e))
+    }
+
+    // Файл реестра с последней проводкой и пустой журнал
+    fn compact(&mut self) -> Result<(), String> {
+        let Some(path) = &self.path else { return Ok(()) };
+        self.ledger.save(path)?;
+        if let Some(log) = &self.log {
+            log.set_len(0)
+                .and_then(|_| log.sync_all())
+                .map_err(|e| format!("Failed to truncate {}: {}", log_path(path).display(), e))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use crate::chain_sync::GENESIS_PREVIOUS_HASH;
+use crate::consensus::{block_signing_bytes, BlockHeader};
+use crate::crypto;
+use crate::p2p_network::{P2PMessage, MAX_SYNC_HEADERS};
+use crate::p2p_protocol::{self, Hello};
+use crate::state_tree::{StateEntry, StateKey, StateProof};
+
+// Легкий клиент кошелька (телефон, киоск): скачивает только заголовки блоков и доказательства
+// отдельных записей состояния, без транзакций и FranchiseNetwork.sales.
+// Модель доверия: клиент стартует с известных ключей нод и принимает заголовок, только если
+// его подписали 2/3 + 1 валидаторов, которым клиент уже доверяет: набора последнего проверенного
+// заголовка, а для генезиса — нод стартовых ключей. Набор из самого заголовка для кворума не используется,
+// подписи нод с неизвестным ключом не считаются.
+// Ключи новых нод клиент узнает из доказательств StateKey::Node против уже проверенного заголовка.
+// Если набор валидаторов сменился сильнее, чем на треть, синхронизация останавливается —
+// нужны свежие доверенные ключи.
+
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct LightClient {
+    node_keys: BTreeMap<u64, String>, // node_id -> публичный ключ
+    genesis_validators: Vec<u64>, // Ноды стартовых ключей: кворум для заголовка генезиса
+    headers: Vec<BlockHeader>,
+    watched: Vec<StateKey>, // Записи, доказательства которых запрашиваются после синхронизации заголовков
+    entries: BTreeMap<StateKey, (u64, StateEntry)>, // Проверенная запись и высота ее заголовка
+}
+
+impl LightClient {
+    pub fn new(trusted_keys: BTreeMap<u64, String>) -> Self {
+        let genesis_validators = trusted_keys.keys().copied().collect();
+        Self { node_keys: trusted_keys, genesis_validators, headers: Vec::new(), watched: Vec::new(), entries: BTreeMap::new() }
+    }
+
+    pub fn watch(&mut self, key: StateKey) {
+        if !self.watched.contains(&key) {
+            self.watched.push(key);
+        }
+    }
+
+    pub fn height(&self) -> u64 {
+        self.headers.len() as u64
+    }
+
+    pub fn tip(&self) -> Option<&BlockHeader> {
+        self.headers.last()
+    }
+
+    // Заголовок должен продолжать последний проверенный и нести кворум подписей известных ключей
+    pub fn accept_header(&mut self, header: BlockHeader) -> Result<(), String> {
+        let (height, previous_hash, parent_timestamp) = match self.headers.last() {
+            Some(parent) => (parent.height + 1, parent.hash.as_str(), parent.timestamp),
+            None => (0, GENESIS_PREVIOUS_HASH, 0),
+        };
+        if header.height != height || header.previous_hash != previous_hash {
+            return Err(format!("Header {} does not extend verified header chain at height {}", header.hash, height));
+        }
+        if header.hash != header.calculate_hash() {
+            return Err(format!("Header at height {} has invalid hash", header.height));
+        }
+        if header.timestamp < parent_timestamp {
+            return Err(format!("Header at height {} is older than its parent", header.height));
+        }
+        if header.validators.is_empty() {
+            return Err(format!("Header at height {} does not list its validators", header.height));
+        }
+
+        let trusted = match self.headers.last() {
+            Some(parent) => &parent.validators,
+            None => &self.genesis_validators,
+        };
+        let added = header.validators.iter().filter(|id| !trusted.contains(id)).count();
+        let removed = trusted.iter().filter(|id| !header.validators.contains(id)).count();
+        if added.max(removed) > trusted.len() / 3 {
+            return Err(format!("Validator set at height {} changed by more than a third, fresh trusted keys are needed", header.height));
+        }
+
+        let signed = trusted.iter()
+            .filter(|validator_id| {
+                let Some(public_key) = self.node_keys.get(validator_id) else { return false };
+                header.signatures.iter().any(|s| {
+                    let message = block_signing_bytes(header.height, s.round, &header.hash, **validator_id);
+                    s.validator_id == **validator_id && crypto::verify(public_key, &message, &s.signature)
+                })
+            })
+            .count();
+        let required = trusted.len() * 2 / 3 + 1; // Как в ConsensusAlgorithm::validate_block
+        if signed < required {
+            return Err(format!("Header at height {} has {} verifiable signatures, {} required", header.height, signed, required));
+        }
+
+        self.headers.push(header);
+        Ok(())
+    }
+
+    // Доказательство проверяется по state_root заголовка своей высоты; проверенные ключи нод запоминаются
+    pub fn accept_proof(&mut self, height: u64, proof: StateProof) -> Result<(), String> {
+        let header = self.headers.get(height as usize)
+            .ok_or_else(|| format!("State proof for unverified height {}", height))?;
+        if !proof.verify(&header.state_root) {
+            return Err(format!("State proof for {:?} does not match state root at height {}", proof.entry.key(), height));
+        }
+
+        if let StateEntry::Node { node_id, public_key, active } = &proof.entry {
+            match public_key {
+                Some(public_key) if *active => { self.node_keys.insert(*node_id, public_key.clone()); }
+                _ => { self.node_keys.remove(node_id); }
+            }
+        }
+        let key = proof.entry.key();
+        if self.entries.get(&key).is_none_or(|(verified_at, _)| *verified_at <= height) {
+            self.entries.insert(key, (height, proof.entry));
+        }
+        Ok(())
+    }
+
+    pub fn entry(&self, key: &StateKey) -> Option<(u64, &StateEntry)> {
+        self.entries.get(key).map(|(height, entry)| (*height, entry))
+    }
+
+    // Проверенный баланс кошелька и высота, на которую он подтвержден
+    pub fn balance(&self, address: &str) -> Option<(u64, u64)> {
+        match self.entry(&StateKey::Wallet(address.to_string()))? {
+            (height, StateEntry::Wallet { balance, .. }) => Some((height, *balance)),
+            _ => None,
+        }
+    }
+
+    // Ответ полной ноды; возвращает следующие запросы к ней
+    pub fn handle(&mut self, message: P2PMessage) -> Result<Vec<P2PMessage>, String> {
+        match message {
+            P2PMessage::Headers { headers } => {
+                let full_batch = headers.len() == MAX_SYNC_HEADERS;
+                for header in headers {
+                    self.accept_header(header)?;
+                }
+                if full_batch {
+                    return Ok(vec![P2PMessage::GetHeaders { from_height: self.height() }]);
+                }
+
+                // Заголовки догнаны: запрашиваем отслеживаемые записи и ключи незнакомых валидаторов вершины
+                let unknown_validators = self.tip().map(|tip| tip.validators.clone()).unwrap_or_default()
+                    .into_iter()
+                    .filter(|node_id| !self.node_keys.contains_key(node_id))
+                    .map(StateKey::Node);
+                Ok(self.watched.iter().cloned().chain(unknown_validators)
+                    .map(|key| P2PMessage::GetStateProof { key })
+                    .collect())
+            }
+            P2PMessage::StateProof { height, proof } => {
+                // Отсутствие записи доказать нельзя, такой ответ просто не дает проверенной записи
+                if let Some(proof) = proof {
+                    self.accept_proof(height, proof)?;
+                }
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    // Синхронизация с одной полной нодой: Hello, заголовки с текущей высоты, затем доказательства
+    pub fn sync(&mut self, address: SocketAddr) -> Result<(), String> {
+        let mut stream = TcpStream::connect_timeout(&address, SYNC_TIMEOUT)
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        let _ = stream.set_read_timeout(Some(SYNC_TIMEOUT));
+        let _ = stream.set_write_timeout(Some(SYNC_TIMEOUT));
+
+        let our_hello = Hello::light(self.height(), self.headers.first().map(|genesis| genesis.hash.clone()));
+        p2p_protocol::write_frame(&mut stream, &P2PMessage::Hello { hello: our_hello.clone() })?;
+        match p2p_protocol::read_frame(&mut stream)? {
+            Some(P2PMessage::Hello { hello }) => our_hello.check_peer(&hello)?,
+            Some(_) => return Err(format!("{} sent a message before Hello", address)),
+            None => return Err(format!("{} closed connection during handshake", address)),
+        }
+
+        // На каждый запрос полная нода отвечает ровно одним сообщением
+        p2p_protocol::write_frame(&mut stream, &P2PMessage::GetHeaders { from_height: self.height() })?;
+        let mut outstanding = 1;
+        while outstanding > 0 {
+            let message: P2PMessage = p2p_protocol::read_frame(&mut stream)?
+                .ok_or_else(|| format!("{} closed connection during sync", address))?;
+            if matches!(message, P2PMessage::Headers { .. } | P2PMessage::StateProof { .. }) {
+                outstanding -= 1;
+            }
+            for request in self.handle(message)? {
+                p2p_protocol::write_frame(&mut stream, &request)?;
+                outstanding += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Доверенные ключи из строки "node_id:public_key_hex,..."
+pub fn parse_trusted_keys(value: &str) -> Result<BTreeMap<u64, String>, String> {
+    value.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (node_id, public_key) = item.split_once(':')
+                .ok_or_else(|| format!("Trusted key '{}' must look like node_id:public_key_hex", item))?;
+            let node_id = node_id.parse::<u64>().map_err(|_| format!("Invalid node id '{}'", node_id))?;
+            crypto::parse_public_key(public_key)?;
+            Ok((node_id, public_key.to_string()))
+        })
+        .collect()
+}
@@ -1,8 +1,9 @@
  #[cfg(test)]
 use std::fmt;
 use std::env;
+#[cfg(test)]
 use std::time::{SystemTime, UNIX_EPOCH};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use sha2::{Sha256, Digest};
 use serde::{Serialize, Deserialize};
 #[cfg(test)]
@@ -12,40 +13,107 @@ use std::sync::{Arc, Mutex};
 use std::net::{TcpListener, TcpStream};
 use std::io::{Read, Write};
 use std::thread;
+use std::path::Path;
 
-mod simple_server;
-use simple_server::SimpleServer;
 mod web_server;
 use web_server::WebServer;
 mod franchise_network;
-use franchise_network::{FranchiseNetwork, NodeType, SaleItem};
+use franchise_network::{FranchiseNetwork, NodeType, SaleItem, SCALE};
+mod ledger;
+use ledger::{Ledger, LedgerStore, SharedLedger, TokenLedger};
+mod tokenomics;
+use tokenomics::{split_units, TokenomicsConfig, TokenomicsPolicy, TokenomicsSchedule, CHARITY_INDEX};
+mod governance;
+use governance::{BallotBox, Proposal, ProposalChange, ProposalStatus, UtilityLock, VotingRules, VotingStrategy, VotingTally};
 mod pos_api;
 use pos_api::PosApiServer;
+mod crypto;
+use crypto::Keypair;
+mod merkle;
+mod state_tree;
+use merkle::MerkleProof;
 mod consensus;
 use consensus::{ConsensusAlgorithm, Block as ConsensusBlock, Transaction as ConsensusTransaction, TransactionType};
+mod chain_sync;
+mod rewards;
+mod executor;
+use executor::GovernancePayload;
+mod bft;
+mod p2p_protocol;
+mod peer_book;
+use peer_book::PeerBook;
+#[cfg(test)]
+mod network_simulator;
 mod p2p_network;
-use p2p_network::P2PNode;
+use p2p_network::{NodeContext, P2PNode};
+mod light_client;
+use light_client::LightClient;
+mod blob_store;
 mod ipfs_storage;
 use ipfs_storage::IPFSStorage;
+mod backup;
+use backup::BackupKey;
+mod clock;
+mod storage;
+use storage::WalOperation;
+
+// Все балансы токенов хранятся в целых subunits (тетри), как во франшизной сети: 1 токен = SCALE subunits.
+// Суммы в лари переводятся в subunits один раз, на входе операции.
+
+// Доли эмиссии, utility токены и лимиты владения задает TokenomicsPolicy (src/tokenomics.rs).
+// Остаток от округления (пыль) при любом делении эмиссии получает благотворительный фонд.
+const INVESTOR_CHARITY_INDEX: usize = 1; // Фонд в долях инвесторской эмиссии: владелец сети, фонд, инвестор
+
+// Производство блоков (proof of stake): время делится на слоты по BLOCK_TIME_SECS, в слоте не больше
+// одного блока. Лидер слота — держатель security токенов, выбранный пропорционально доле по хешу
+// предыдущего блока и номеру слота. Блоки до PoS (slot: None) проверяются по старому хешу с nonce.
+const BLOCK_TIME_SECS: u64 = 30;
+const MAX_BLOCK_TIME_DRIFT_SECS: u64 = 60; // Допустимое опережение метки блока над часами узла
+
+// Бэкапы ноды FULL_DECENTRALIZED (BACKUP_KEY): по расписанию и по файлу-запросу в DATA_DIR
+const BACKUP_INTERVAL_SECS: u64 = 60 * 60;
+const BACKUP_REQUEST_FILE: &str = "backup.request";
+const FRANCHISE_CHAIN_FILE: &str = "franchise_chain.json"; // Цепь P2P ноды, восстановленная из бэкапа
+
+// Перевод суммы в лари (токенах) в subunits с округлением до тетри
+fn to_units(amount: f64) -> u64 {
+    (amount * SCALE as f64).round() as u64
+}
+
+// Subunits в виде "12.34" для вывода
+fn format_units(units: u64) -> String {
+    format!("{}.{:02}", units / SCALE, units % SCALE)
+}
+
+// Доля в процентах (для отчетов и алертов)
+fn percentage_of(units: u64, total: u64) -> f64 {
+    (units as f64 / total as f64) * 100.0
+}
+
+// Точная проверка: превышает ли доля units/total лимит в процентах (сравнение в базисных пунктах, без погрешности f64)
+fn exceeds_percentage(units: u64, total: u64, max_percentage: f64) -> bool {
+    let max_basis_points = (max_percentage * 100.0).round() as u128;
+    units as u128 * 10_000 > max_basis_points * total as u128
+}
 
 // Utility Token for voting
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct UtilityToken {
     symbol: String,
-    total_supply: f64,
-    voting_power_per_token: f64,
+    total_supply: u64,
+    voting_power_per_token: u64,
 }
 
 impl UtilityToken {
     fn new(symbol: String) -> Self {
         UtilityToken {
             symbol,
-            total_supply: 0.0,
-            voting_power_per_token: 1.0,
+            total_supply: 0,
+            voting_power_per_token: 1,
         }
     }
 
-    fn issue_voting_tokens(&mut self, amount: f64) -> f64 {
+    fn issue_voting_tokens(&mut self, amount: u64) -> u64 {
         self.total_supply += amount;
         amount * self.voting_power_per_token
     }
@@ -78,7 +146,7 @@ struct Check {
     check_id: String,
     qr_code: String,
     activation_code: String,
-    amount: f64,
+    amount: u64, // сумма в subunits
     food_items: Vec<String>,
     timestamp: u64,
     is_activated: bool,
@@ -88,14 +156,10 @@ struct Check {
 }
 
 impl Check {
-    fn new(amount: f64, food_items: Vec<String>) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    fn new(amount: u64, food_items: Vec<String>, activation_code: String) -> Self {
+        let timestamp = clock::now_secs();
         
         let check_id = Self::generate_check_id(amount, &food_items, timestamp);
-        let activation_code = Self::generate_activation_code();
         let blockchain_account = Self::generate_blockchain_account();
         
         // Generate QR code data
@@ -116,24 +180,19 @@ impl Check {
         }
     }
 
-    fn new_with_phone(amount: f64, food_items: Vec<String>, phone_number: String) -> Self {
-        let mut check = Self::new(amount, food_items);
+    fn new_with_phone(amount: u64, food_items: Vec<String>, phone_number: String) -> Self {
+        let mut check = Self::new(amount, food_items, crypto::random_code());
         check.phone_number = Some(phone_number);
         check
     }
 
-    fn generate_check_id(amount: f64, food_items: &[String], timestamp: u64) -> String {
+    fn generate_check_id(amount: u64, food_items: &[String], timestamp: u64) -> String {
         let data = format!("{}{}{}", amount, food_items.join(""), timestamp);
         let mut hasher = Sha256::new();
         hasher.update(data.as_bytes());
         hex::encode(&hasher.finalize()[..16])
     }
 
-    fn generate_activation_code() -> String {
-        let mut rng = fastrand::Rng::new();
-        format!("{:06}", rng.u32(100000..999999))
-    }
-
     fn generate_blockchain_account() -> String {
         let mut rng = fastrand::Rng::new();
         let mut account = String::new();
@@ -155,8 +214,8 @@ impl Check {
 struct BlockchainAccount {
     address: String,
     status: AccountStatus,
-    security_tokens: f64,
-    utility_tokens: f64,
+    security_tokens: u64,
+    utility_tokens: u64,
     personal_data: Option<PersonalData>,
     created_timestamp: u64,
     activated_timestamp: Option<u64>,
@@ -182,13 +241,10 @@ impl BlockchainAccount {
         BlockchainAccount {
             address,
             status: AccountStatus::Sleep,
-            security_tokens: 0.0,
-            utility_tokens: 0.0,
+            security_tokens: 0,
+            utility_tokens: 0,
             personal_data: None,
-            created_timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            created_timestamp: clock::now_secs(),
             activated_timestamp: None,
         }
     }
@@ -200,12 +256,7 @@ impl BlockchainAccount {
         
         self.status = AccountStatus::Active;
         self.personal_data = Some(personal_data);
-        self.activated_timestamp = Some(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        );
+        self.activated_timestamp = Some(clock::now_secs());
         Ok(())
     }
 
@@ -230,35 +281,21 @@ struct AuthorizedUser {
 }
 
 impl AuthorizedUser {
-    fn new(phone_number: String, wallet_address: String) -> Self {
-        let verification_code = Self::generate_verification_code();
+    fn new(phone_number: String, wallet_address: String, verification_code: String) -> Self {
         AuthorizedUser {
             phone_number,
             wallet_address,
             verification_code,
             is_verified: false,
-            created_timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            created_timestamp: clock::now_secs(),
             last_login_timestamp: None,
         }
     }
 
-    fn generate_verification_code() -> String {
-        let mut rng = fastrand::Rng::new();
-        format!("{:06}", rng.u32(100000..999999))
-    }
-
     fn verify(&mut self, code: &str) -> Result<(), String> {
         if self.verification_code == code {
             self.is_verified = true;
-            self.last_login_timestamp = Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs()
-            );
+            self.last_login_timestamp = Some(clock::now_secs());
             Ok(())
         } else {
             Err("Invalid verification code".to_string())
@@ -272,7 +309,7 @@ struct CharityFund {
     fund_id: String,
     fund_name: String,
     owner_family: String,
-    total_donations: f64,
+    total_donations: u64,
     created_timestamp: u64,
     is_active: bool,
 }
@@ -284,16 +321,13 @@ impl CharityFund {
             fund_id,
             fund_name: format!("Благотворительный фонд семьи {}", owner_family),
             owner_family,
-            total_donations: 0.0,
-            created_timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            total_donations: 0,
+            created_timestamp: clock::now_secs(),
             is_active: true,
         }
     }
 
-    fn add_donation(&mut self, amount: f64) {
+    fn add_donation(&mut self, amount: u64) {
         self.total_donations += amount;
     }
 }
@@ -302,8 +336,8 @@ impl CharityFund {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TokenHolder {
     address: String,
-    security_tokens: f64,
-    utility_tokens: f64,
+    security_tokens: u64, // в subunits
+    utility_tokens: u64, // в subunits
     role: UserRole,
     is_main_owner: bool,
     is_charity_fund: bool, // Является ли благотворительным фондом
@@ -313,14 +347,18 @@ struct TokenHolder {
     phone_number: Option<String>, // Номер телефона для авторизации
     is_authorized: bool, // Авторизован ли пользователь по телефону
     franchise_nodes: Vec<String>, // Список нод франшизы (если применимо)
+    #[serde(default)]
+    delegate: Option<String>, // Кому передан голос в голосованиях VotingStrategy::Delegated
+    #[serde(default)]
+    utility_locks: BTreeMap<String, UtilityLock>, // Голосование -> токены, заблокированные голосом Conviction
 }
 
 impl TokenHolder {
     fn new(address: String, is_main_owner: bool) -> Self {
         TokenHolder {
             address,
-            security_tokens: 0.0,
-            utility_tokens: 0.0,
+            security_tokens: 0,
+            utility_tokens: 0,
             role: if is_main_owner { UserRole::MainOwner } else { UserRole::Unauthorized },
             is_main_owner,
             is_charity_fund: false,
@@ -330,14 +368,16 @@ impl TokenHolder {
             phone_number: None,
             is_authorized: false,
             franchise_nodes: vec![],
+            delegate: None,
+            utility_locks: BTreeMap::new(),
         }
     }
 
     fn new_charity_fund(address: String, fund_name: String) -> Self {
         TokenHolder {
             address,
-            security_tokens: 0.0,
-            utility_tokens: 0.0,
+            security_tokens: 0,
+            utility_tokens: 0,
             role: UserRole::MainOwner, // Благотворительный фонд имеет особый статус
             is_main_owner: false,
             is_charity_fund: true,
@@ -347,14 +387,16 @@ impl TokenHolder {
             phone_number: None,
             is_authorized: true, // Фонд всегда авторизован
             franchise_nodes: vec![],
+            delegate: None,
+            utility_locks: BTreeMap::new(),
         }
     }
 
     fn new_franchise_owner(address: String, franchise_nodes: Vec<String>) -> Self {
         TokenHolder {
             address,
-            security_tokens: 0.0,
-            utility_tokens: 0.0,
+            security_tokens: 0,
+            utility_tokens: 0,
             role: UserRole::Unauthorized,
             is_main_owner: false,
             is_charity_fund: false,
@@ -364,6 +406,8 @@ impl TokenHolder {
             phone_number: None,
             is_authorized: false,
             franchise_nodes,
+            delegate: None,
+            utility_locks: BTreeMap::new(),
         }
     }
 
@@ -372,15 +416,24 @@ impl TokenHolder {
         self.is_authorized = true;
     }
 
-    fn add_security_tokens(&mut self, amount: f64) {
+    fn add_security_tokens(&mut self, amount: u64) {
         self.security_tokens += amount;
         self.update_role();
     }
 
-    fn add_utility_tokens(&mut self, amount: f64) {
+    fn add_utility_tokens(&mut self, amount: u64) {
         self.utility_tokens += amount;
     }
 
+    // Заблокировано голосами Conviction, чьи окна еще открыты: одни и те же токены стоят за каждым голосом
+    fn locked_utility_tokens(&self, now: u64) -> u64 {
+        self.utility_locks.values()
+            .filter(|lock| lock.until > now)
+            .map(|lock| lock.units)
+            .max()
+            .unwrap_or(0)
+    }
+
     fn update_role(&mut self) {
         if self.is_main_owner {
             self.role = UserRole::MainOwner;
@@ -442,12 +495,14 @@ struct MenuItem {
     ingredients: Vec<Ingredient>,
     total_calories: f64,
     suggested_by: String,
-    votes_for: f64,
-    votes_against: f64,
+    votes_for: u64,
+    votes_against: u64,
     status: MenuItemStatus,
     created_timestamp: u64,
     voting_ends: u64,
     is_available_for_voting: bool, // доступно ли для голосования
+    #[serde(default)]
+    ballot_box: BallotBox, // Бюллетени и снимок utility токенов на момент открытия голосования
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -481,22 +536,19 @@ struct Order {
     id: String,
     customer_wallet: String,
     items: Vec<OrderItem>,
-    total_amount: f64,
+    total_amount: u64, // в subunits
     delivery_time_minutes: u32, // когда может приехать курьер
     status: OrderStatus,
     created_timestamp: u64,
     confirmed_timestamp: Option<u64>,
     cancellation_reason: Option<String>,
-    tokens_issued: f64, // количество токенов, выданных за заказ
+    tokens_issued: u64, // количество токенов (в subunits), выданных за заказ
 }
 
 #[cfg_attr(test, allow(dead_code))]
 impl MenuItem {
     fn new(name: String, description: String, price: f64, suggested_by: String, voting_duration_days: u64) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = clock::now_secs();
         
         MenuItem {
             id: Self::generate_id(&name, &suggested_by, timestamp),
@@ -509,12 +561,13 @@ impl MenuItem {
             ingredients: vec![],
             total_calories: 0.0,
             suggested_by,
-            votes_for: 0.0,
-            votes_against: 0.0,
+            votes_for: 0,
+            votes_against: 0,
             status: MenuItemStatus::Proposed,
             created_timestamp: timestamp,
             voting_ends: timestamp + (voting_duration_days * 24 * 60 * 60),
             is_available_for_voting: false,
+            ballot_box: BallotBox::default(),
         }
     }
 
@@ -529,10 +582,7 @@ impl MenuItem {
         suggested_by: String, 
         voting_duration_days: u64
     ) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = clock::now_secs();
         
         let total_calories: f64 = ingredients.iter().map(|i| i.calories).sum();
         
@@ -547,12 +597,13 @@ impl MenuItem {
             ingredients,
             total_calories,
             suggested_by,
-            votes_for: 0.0,
-            votes_against: 0.0,
+            votes_for: 0,
+            votes_against: 0,
             status: MenuItemStatus::Proposed,
             created_timestamp: timestamp,
             voting_ends: timestamp + (voting_duration_days * 24 * 60 * 60),
             is_available_for_voting: false,
+            ballot_box: BallotBox::default(),
         }
     }
 
@@ -563,39 +614,36 @@ impl MenuItem {
         hex::encode(&hasher.finalize()[..8])
     }
 
-    fn start_voting(&mut self) {
+    // snapshot — utility токены кошельков на момент открытия голосования
+    fn start_voting(&mut self, snapshot: BTreeMap<String, u64>, strategy: VotingStrategy) {
         self.status = MenuItemStatus::Voting;
+        self.ballot_box = BallotBox::open(snapshot, strategy);
     }
 
-    fn vote(&mut self, utility_tokens: f64, vote_for: bool) -> Result<(), String> {
+    // Голос кошелька с весом по снимку и стратегии; повторный голос меняет прежний. Возвращает вес.
+    fn vote(&mut self, voter: &str, vote_for: bool) -> Result<u64, String> {
         if self.status != MenuItemStatus::Voting {
             return Err("Voting is not active for this item".to_string());
         }
         
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let current_time = clock::now_secs();
         
         if current_time > self.voting_ends {
             return Err("Voting period has ended".to_string());
         }
         
-        if vote_for {
-            self.votes_for += utility_tokens;
-        } else {
-            self.votes_against += utility_tokens;
-        }
-        Ok(())
+        let weight = self.ballot_box.cast(voter, vote_for)?;
+        (self.votes_for, self.votes_against) = self.ballot_box.tally(current_time);
+        Ok(weight)
     }
 
     fn finalize_vote(&mut self) {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let current_time = clock::now_secs();
         
         if current_time > self.voting_ends {
+            if self.ballot_box.opened {
+                (self.votes_for, self.votes_against) = self.ballot_box.tally(self.voting_ends);
+            }
             if self.votes_for > self.votes_against {
                 self.status = MenuItemStatus::Approved;
             } else {
@@ -604,22 +652,19 @@ impl MenuItem {
         }
     }
 
-    fn make_available_for_voting(&mut self) {
+    fn make_available_for_voting(&mut self, snapshot: BTreeMap<String, u64>, strategy: VotingStrategy) {
         self.is_available_for_voting = true;
-        self.status = MenuItemStatus::Voting;
+        self.start_voting(snapshot, strategy);
     }
 }
 
 impl Order {
     fn new(customer_wallet: String, items: Vec<OrderItem>, delivery_time_minutes: u32) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = clock::now_secs();
         
-        let total_amount: f64 = items.iter().map(|item| {
+        let total_amount: u64 = items.iter().map(|item| {
             // Здесь нужно будет получить цену из меню
-            item.quantity as f64 * 10.0 // временная заглушка
+            item.quantity as u64 * 10 * SCALE // временная заглушка
         }).sum();
         
         Order {
@@ -632,7 +677,7 @@ impl Order {
             created_timestamp: timestamp,
             confirmed_timestamp: None,
             cancellation_reason: None,
-            tokens_issued: 0.0,
+            tokens_issued: 0,
         }
     }
 
@@ -643,14 +688,9 @@ impl Order {
         format!("ORDER_{}", hex::encode(&hasher.finalize()[..8]))
     }
 
-    fn confirm(&mut self, tokens_issued: f64) {
+    fn confirm(&mut self, tokens_issued: u64) {
         self.status = OrderStatus::Confirmed;
-        self.confirmed_timestamp = Some(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-        );
+        self.confirmed_timestamp = Some(clock::now_secs());
         self.tokens_issued = tokens_issued;
     }
 
@@ -665,27 +705,24 @@ impl Order {
 struct Transaction {
     from: String,
     to: String,
-    amount: f64,
+    amount: u64, // в subunits
     food_items: Vec<String>,
     timestamp: u64,
     transaction_id: String,
     check: Option<Check>,
-    security_tokens_issued: f64,
-    utility_tokens_issued: f64,
+    security_tokens_issued: u64,
+    utility_tokens_issued: u64,
 }
 
 impl Transaction {
-    fn new(from: String, to: String, amount: f64, food_items: Vec<String>, 
-           security_tokens: f64, utility_tokens: f64) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    fn new(from: String, to: String, amount: u64, food_items: Vec<String>, 
+           security_tokens: u64, utility_tokens: u64, activation_code: String) -> Self {
+        let timestamp = clock::now_secs();
         
         let transaction_id = Self::generate_transaction_id(&from, &to, amount, &food_items, timestamp);
         
         // Generate check for the transaction
-        let check = Check::new(amount, food_items.clone());
+        let check = Check::new(amount, food_items.clone(), activation_code);
         
         Transaction {
             from,
@@ -700,21 +737,59 @@ impl Transaction {
         }
     }
 
-    fn generate_transaction_id(from: &str, to: &str, amount: f64, food_items: &[String], timestamp: u64) -> String {
+    fn generate_transaction_id(from: &str, to: &str, amount: u64, food_items: &[String], timestamp: u64) -> String {
         let data = format!("{}{}{}{}{}", from, to, amount, food_items.join(""), timestamp);
         let mut hasher = Sha256::new();
         hasher.update(data.as_bytes());
         hex::encode(hasher.finalize())
     }
+
+    // Публичное представление транзакции — лист дерева Меркла блока
+    fn leaf(&self) -> TransactionLeaf {
+        TransactionLeaf {
+            transaction_id: self.transaction_id.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+            amount: self.amount,
+            food_items: self.food_items.clone(),
+            timestamp: self.timestamp,
+            check_hash: self.check.as_ref().map(|check| {
+                let bytes = serde_json::to_vec(check).expect("check fields are always serializable");
+                hex::encode(Sha256::digest(bytes))
+            }),
+            security_tokens_issued: self.security_tokens_issued,
+            utility_tokens_issued: self.utility_tokens_issued,
+        }
+    }
+
+    fn leaf_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(&self.leaf()).expect("transaction fields are always serializable")
+    }
+}
+
+// Лист дерева Меркла: транзакция, в которой чек заменен его хешем.
+// Код активации и телефон из чека не попадают в доказательство включения, но подменить чек нельзя.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransactionLeaf {
+    transaction_id: String,
+    from: String,
+    to: String,
+    amount: u64,
+    food_items: Vec<String>,
+    timestamp: u64,
+    check_hash: Option<String>,
+    security_tokens_issued: u64,
+    utility_tokens_issued: u64,
 }
 
 // Enhanced Blockchain with new token distribution rules
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 struct Blockchain {
     chain: Vec<Block>,
     token_holders: HashMap<String, TokenHolder>,
     pending_transactions: Vec<Transaction>,
     utility_token: UtilityToken,
+    security_supply: u64, // Всего выпущено security токенов (subunits)
     menu_items: Vec<MenuItem>,
     orders: Vec<Order>,
     #[allow(dead_code)]
@@ -725,19 +800,34 @@ struct Blockchain {
     balance_transfer_history: Vec<BalanceTransferRecord>,
     charity_fund: CharityFund, // Благотворительный фонд семьи владельца
     main_owner: String,
-    difficulty: usize,
-    min_stake: f64,
-    block_reward: f64,
-    // Новые ограничения на владение токенами
-    max_owner_percentage: f64, // Максимум 48% для владельца
-    max_franchise_percentage: f64, // Максимум 24% для владельцев франшиз (все вместе)
-    max_customer_percentage: f64, // Максимум 49% для покупателей
-    charity_percentage: f64, // 3% для благотворительного фонда
+    min_stake: u64, // в subunits, минимальная доля лидера слота
+    block_reward: u64, // в subunits
+    // Ограничения на владение токенами; берутся из политики токеномики, действующей на вершине цепи
+    max_owner_percentage: f64, // Максимум для владельца
+    max_franchise_percentage: f64, // Максимум для владельцев франшиз (все вместе)
+    max_customer_percentage: f64, // Максимум для покупателей
+    charity_percentage: f64, // Ожидаемая доля благотворительного фонда
+    #[serde(default = "TokenomicsSchedule::restaurant")]
+    tokenomics: TokenomicsSchedule,
     franchise_nodes: HashMap<String, String>, // node_id -> franchise_owner_address
     monitoring_alerts: Vec<MonitoringAlert>, // Система мониторинга
     unclaimed_tokens: Vec<UnclaimedTokensRecord>, // Невостребованные токены
     annual_distributions: Vec<AnnualDistribution>, // История годовых распределений
     current_year: u32, // Текущий год для отслеживания
+    #[serde(default)]
+    wallet_nonces: HashMap<String, u64>, // wallet -> последний nonce подписанного API запроса
+    #[serde(default)]
+    proposals: Vec<Proposal>, // Предложения по параметрам протокола
+    #[serde(default)]
+    franchise_governance: Vec<(String, GovernancePayload)>, // Принятые решения для цепи франшизной сети: id предложения и изменение
+    #[serde(default)]
+    slot_stakes: HashMap<u32, SlotStakes>, // индекс PoS блока -> доли, из которых выбран его лидер
+    #[serde(skip)]
+    journal: Option<Arc<Mutex<storage::Journal>>>, // Журнал операций (WAL), если включена персистентность
+    #[serde(skip)]
+    ledger: Option<SharedLedger>, // Единый реестр токенов, если он подключен
+    #[serde(skip)]
+    validator_keys: HashMap<String, Arc<Keypair>>, // адрес держателя -> ключ, которым нода подписывает его блоки
 }
 
 #[cfg_attr(test, allow(dead_code))]
@@ -748,7 +838,7 @@ impl Blockchain {
             vec![],
             "0".to_string(),
             "Genesis".to_string(),
-            0.0,
+            0,
         );
         
         let utility_token = UtilityToken::new("VOTE".to_string());
@@ -757,6 +847,9 @@ impl Blockchain {
         let charity_fund = CharityFund::new(main_owner.clone());
         let charity_address = charity_fund.fund_id.clone();
         
+        let tokenomics = TokenomicsSchedule::restaurant();
+        let limits = tokenomics.policy_at(0).limits.clone();
+
         let mut token_holders = HashMap::new();
         token_holders.insert(main_owner.clone(), TokenHolder::new(main_owner.clone(), true));
         token_holders.insert(charity_address.clone(), TokenHolder::new_charity_fund(charity_address, charity_fund.fund_name.clone()));
@@ -766,6 +859,7 @@ impl Blockchain {
             token_holders,
             pending_transactions: vec![],
             utility_token,
+            security_supply: 0,
             menu_items: vec![],
             orders: vec![],
             smart_contracts: vec![],
@@ -775,122 +869,116 @@ impl Blockchain {
             balance_transfer_history: vec![],
             charity_fund,
             main_owner,
-            difficulty: 4,
-            min_stake: 10.0,
-            block_reward: 5.0,
-            // Новые ограничения
-            max_owner_percentage: 48.0, // Максимум 48% для владельца сети
-            max_franchise_percentage: 24.0, // Максимум 24% для владельцев франшиз (все вместе)
-            max_customer_percentage: 49.0, // Максимум 49% для покупателей
-            charity_percentage: 3.0, // 3% для благотворительного фонда
+            min_stake: 10 * SCALE,
+            block_reward: 5 * SCALE,
+            max_owner_percentage: limits.max_owner_percentage,
+            max_franchise_percentage: limits.max_franchise_percentage,
+            max_customer_percentage: limits.max_customer_percentage,
+            charity_percentage: limits.charity_percentage,
+            tokenomics,
             franchise_nodes: HashMap::new(),
             monitoring_alerts: vec![],
             unclaimed_tokens: vec![],
             annual_distributions: vec![],
             current_year: 2024, // Текущий год
+            wallet_nonces: HashMap::new(),
+            proposals: vec![],
+            franchise_governance: vec![],
+            slot_stakes: HashMap::new(),
+            journal: None,
+            ledger: None,
+            validator_keys: HashMap::new(),
         }
     }
 
-    fn process_purchase(&mut self, customer: String, food_truck: String, amount: f64, food_items: Vec<String>) -> Check {
-        // Новая логика распределения токенов:
-        // Нода владельца сети: 48% владелец сети, 3% фонд, 49% покупатель
-        // Нода франчайзи: 25% владелец сети, 24% франчайзи, 3% фонд, 49% покупатель
-        
+    fn process_purchase(&mut self, customer: String, food_truck: String, amount: f64, food_items: Vec<String>) -> Result<Check, String> {
+        self.process_purchase_with_code(customer, food_truck, amount, food_items, crypto::random_code())
+    }
+
+    // Код активации чека создается до операции и журналируется вместе с ней
+    fn process_purchase_with_code(&mut self, customer: String, food_truck: String, amount: f64, food_items: Vec<String>, activation_code: String) -> Result<Check, String> {
+        // Покупка не должна быть принята, если ее нельзя сохранить
+        let _op = self.begin_operation(WalOperation::ProcessPurchase {
+            customer: customer.clone(),
+            food_truck: food_truck.clone(),
+            amount,
+            food_items: food_items.clone(),
+            activation_code: activation_code.clone(),
+        })?;
+
+        // Доли по политике токеномики на высоте блока, в который попадет покупка:
+        // владелец сети, франчайзи, фонд, покупатель (по типу ноды или по договору франшизы)
         let is_franchise_node = self.franchise_nodes.contains_key(&food_truck);
+        let amount_units = to_units(amount);
+        
+        let policy = self.tokenomics.policy_at(self.chain.len() as u64);
+        let node_type = if is_franchise_node { NodeType::FRANCHISE } else { NodeType::OWNER };
+        let mut parts = split_units(amount_units, &policy.split_for(&node_type, &food_truck).weights(), CHARITY_INDEX);
+        if !is_franchise_node {
+            // Своя точка принадлежит владельцу сети
+            parts[0] += parts[1];
+            parts[1] = 0;
+        }
+        let (main_owner_tokens, franchise_owner_tokens, charity_tokens, customer_tokens) = (parts[0], parts[1], parts[2], parts[3]);
         
-        let (main_owner_tokens, franchise_owner_tokens, charity_tokens, customer_tokens) = if is_franchise_node {
-            // Для франшизной ноды: 25% + 24% + 3% + 49% = 101% (ошибка в требованиях, используем 25% + 24% + 3% + 48% = 100%)
-            let main_owner_tokens = amount * 0.25; // 25% владельцу сети
-            let franchise_owner_tokens = amount * 0.24; // 24% владельцу франшизы
-            let charity_tokens = amount * 0.03; // 3% фонду
-            let customer_tokens = amount * 0.48; // 48% покупателю
-            (main_owner_tokens, franchise_owner_tokens, charity_tokens, customer_tokens)
-        } else {
-            // Для ноды владельца сети: 48% + 3% + 49% = 100%
-            let main_owner_tokens = amount * 0.48; // 48% владельцу сети
-            let franchise_owner_tokens = 0.0; // 0% для франшизы
-            let charity_tokens = amount * 0.03; // 3% фонду
-            let customer_tokens = amount * 0.49; // 49% покупателю
-            (main_owner_tokens, franchise_owner_tokens, charity_tokens, customer_tokens)
-        };
-        
-        let utility_tokens = amount * 0.1; // 10% utility токенов для голосования
+        let utility_tokens = amount_units * policy.utility_percentage / 100; // utility токены для голосования
         
         // Create transaction with check
         let transaction = Transaction::new(
             customer.clone(),
             food_truck.clone(),
-            amount,
+            amount_units,
             food_items.clone(),
             customer_tokens, // Покупатель получает свою долю
             utility_tokens,
+            activation_code,
         );
         
         let check = transaction.check.as_ref().unwrap().clone();
         
         // Распределяем токены согласно новым правилам
         
-        // 1. Владелец сети получает свою долю (48% для своих нод, 25% для франшизных)
-        if !self.token_holders.contains_key(&self.main_owner) {
-            let mut new_holder = TokenHolder::new(self.main_owner.clone(), true);
-            new_holder.add_security_tokens(main_owner_tokens);
-            new_holder.add_check(check.clone());
-            self.token_holders.insert(self.main_owner.clone(), new_holder);
-        } else {
-            if let Some(holder) = self.token_holders.get_mut(&self.main_owner) {
-                holder.add_security_tokens(main_owner_tokens);
-                holder.add_check(check.clone());
-            }
+        // Распределение проводится через реестр (mint); новые держатели заводятся заранее со своей ролью
+        
+        // 1. Владелец сети получает свою долю
+        let main_owner = self.main_owner.clone();
+        self.token_holders.entry(main_owner.clone())
+            .or_insert_with(|| TokenHolder::new(main_owner.clone(), true));
+        self.mint(&main_owner, main_owner_tokens)?;
+        if let Some(holder) = self.token_holders.get_mut(&main_owner) {
+            holder.add_check(check.clone());
         }
         
         // 2. Владелец франшизы получает свою долю (только для франшизных нод)
-        if is_franchise_node && franchise_owner_tokens > 0.0 {
+        if is_franchise_node && franchise_owner_tokens > 0 {
             let franchise_owner = self.franchise_nodes.get(&food_truck).unwrap().clone();
-            if !self.token_holders.contains_key(&franchise_owner) {
-                let mut new_holder = TokenHolder::new_franchise_owner(franchise_owner.clone(), vec![food_truck.clone()]);
-                new_holder.add_security_tokens(franchise_owner_tokens);
-                self.token_holders.insert(franchise_owner.clone(), new_holder);
-            } else {
-                if let Some(holder) = self.token_holders.get_mut(&franchise_owner) {
-                    holder.add_security_tokens(franchise_owner_tokens);
-                }
-            }
+            self.token_holders.entry(franchise_owner.clone())
+                .or_insert_with(|| TokenHolder::new_franchise_owner(franchise_owner.clone(), vec![food_truck.clone()]));
+            self.mint(&franchise_owner, franchise_owner_tokens)?;
         }
         
-        // 3. Благотворительный фонд получает 3%
+        // 3. Благотворительный фонд получает свою долю
         let charity_address = self.charity_fund.fund_id.clone();
-        if let Some(charity_holder) = self.token_holders.get_mut(&charity_address) {
-            charity_holder.add_security_tokens(charity_tokens);
-        }
+        self.mint(&charity_address, charity_tokens)?;
         self.charity_fund.add_donation(charity_tokens);
         
         // 4. Покупатель получает свою долю
-        if !self.token_holders.contains_key(&customer) {
-            let mut new_holder = TokenHolder::new(customer.clone(), false);
-            new_holder.add_security_tokens(customer_tokens);
-            self.token_holders.insert(customer.clone(), new_holder);
-        } else {
-            if let Some(holder) = self.token_holders.get_mut(&customer) {
-                holder.add_security_tokens(customer_tokens);
-            }
-        }
+        self.mint(&customer, customer_tokens)?;
         
         // Issue utility tokens for voting
         let voting_power = self.utility_token.issue_voting_tokens(utility_tokens);
         
         // Utility токены распределяются пропорционально security токенам
-        let total_security = main_owner_tokens + franchise_owner_tokens + charity_tokens + customer_tokens;
-        let main_owner_utility = (main_owner_tokens / total_security) * voting_power;
-        let franchise_owner_utility = (franchise_owner_tokens / total_security) * voting_power;
-        let charity_utility = (charity_tokens / total_security) * voting_power;
-        let customer_utility = (customer_tokens / total_security) * voting_power;
+        let utility_parts = split_units(voting_power, &parts, CHARITY_INDEX);
+        let (main_owner_utility, franchise_owner_utility, charity_utility, customer_utility) =
+            (utility_parts[0], utility_parts[1], utility_parts[2], utility_parts[3]);
         
         // Добавляем utility токены
         if let Some(main_owner_holder) = self.token_holders.get_mut(&self.main_owner) {
             main_owner_holder.add_utility_tokens(main_owner_utility);
         }
         
-        if is_franchise_node && franchise_owner_tokens > 0.0 {
+        if is_franchise_node && franchise_owner_tokens > 0 {
             let franchise_owner = self.franchise_nodes.get(&food_truck).unwrap().clone();
             if let Some(franchise_holder) = self.token_holders.get_mut(&franchise_owner) {
                 franchise_holder.add_utility_tokens(franchise_owner_utility);
@@ -913,18 +1001,12 @@ impl Blockchain {
         let is_customer_registered = self.authorized_users.values()
             .any(|user| user.wallet_address == customer);
         if !is_customer_registered {
-            let expiry_timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs() + (365 * 24 * 60 * 60); // 1 год до истечения
+            let expiry_timestamp = clock::now_secs() + (365 * 24 * 60 * 60); // 1 год до истечения
             
             let unclaimed_record = UnclaimedTokensRecord {
                 check_id: check.check_id.clone(),
                 amount: customer_tokens,
-                created_timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
+                created_timestamp: clock::now_secs(),
                 expiry_timestamp,
                 is_distributed: false,
                 distributed_timestamp: None,
@@ -934,10 +1016,16 @@ impl Blockchain {
         }
         
         self.add_transaction(transaction);
-        check
+        Ok(check)
     }
 
     fn activate_account(&mut self, check_id: &str, activation_code: &str, personal_data: PersonalData) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::ActivateAccount {
+            check_id: check_id.to_string(),
+            activation_code: activation_code.to_string(),
+            personal_data: personal_data.clone(),
+        })?;
+
         // Find the holder who has this check
         for holder in self.token_holders.values_mut() {
             if let Ok(()) = holder.activate_account(check_id, activation_code, personal_data.clone()) {
@@ -949,6 +1037,13 @@ impl Blockchain {
     }
 
     fn suggest_menu_item(&mut self, name: String, description: String, price: f64, suggested_by: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::SuggestMenuItem {
+            name: name.clone(),
+            description: description.clone(),
+            price,
+            suggested_by: suggested_by.clone(),
+        })?;
+
         // Only main owner and big stacks can suggest menu items
         if let Some(holder) = self.token_holders.get(&suggested_by) {
             if holder.role != UserRole::MainOwner && holder.role != UserRole::BigStack {
@@ -966,6 +1061,17 @@ impl Blockchain {
     fn add_menu_item_with_details(&mut self, name: String, description: String, price: f64, 
                                  availability: u32, priority_rank: u32, cooking_time_minutes: u32,
                                  ingredients: Vec<Ingredient>, suggested_by: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::AddMenuItemWithDetails {
+            name: name.clone(),
+            description: description.clone(),
+            price,
+            availability,
+            priority_rank,
+            cooking_time_minutes,
+            ingredients: ingredients.clone(),
+            suggested_by: suggested_by.clone(),
+        })?;
+
         // Only main owner can add detailed menu items
         if let Some(holder) = self.token_holders.get(&suggested_by) {
             if holder.role != UserRole::MainOwner {
@@ -983,16 +1089,44 @@ impl Blockchain {
         Ok(())
     }
 
-    fn make_menu_item_available_for_voting(&mut self, menu_item_id: String) -> Result<(), String> {
+    fn make_menu_item_available_for_voting(&mut self, menu_item_id: String, strategy: VotingStrategy) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::MakeMenuItemAvailableForVoting { menu_item_id: menu_item_id.clone(), strategy })?;
+
+        let snapshot = self.voting_snapshot(strategy);
         if let Some(menu_item) = self.menu_items.iter_mut().find(|item| item.id == menu_item_id) {
-            menu_item.make_available_for_voting();
+            menu_item.make_available_for_voting(snapshot, strategy);
             Ok(())
         } else {
             Err("Menu item not found".to_string())
         }
     }
 
+    // Добавление или замена позиции меню целиком (используется HTTP API)
+    fn upsert_menu_item(&mut self, item: MenuItem) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::UpsertMenuItem { item: item.clone() })?;
+
+        if let Some(pos) = self.menu_items.iter().position(|existing| existing.id == item.id) {
+            self.menu_items[pos] = item;
+        } else {
+            self.menu_items.push(item);
+        }
+        Ok(())
+    }
+
+    fn remove_menu_item(&mut self, menu_item_id: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::RemoveMenuItem { menu_item_id: menu_item_id.clone() })?;
+
+        self.menu_items.retain(|item| item.id != menu_item_id);
+        Ok(())
+    }
+
     fn create_order(&mut self, customer_wallet: String, items: Vec<OrderItem>, delivery_time_minutes: u32) -> Result<Order, String> {
+        let _op = self.begin_operation(WalOperation::CreateOrder {
+            customer_wallet: customer_wallet.clone(),
+            items: items.clone(),
+            delivery_time_minutes,
+        })?;
+
         // Проверяем доступность товаров
         for order_item in &items {
             if let Some(menu_item) = self.menu_items.iter().find(|item| item.id == order_item.menu_item_id) {
@@ -1008,10 +1142,10 @@ impl Blockchain {
         let mut order = Order::new(customer_wallet, items, delivery_time_minutes);
         
         // Рассчитываем правильную сумму заказа
-        let mut total_amount = 0.0;
+        let mut total_amount = 0;
         for order_item in &order.items {
             if let Some(menu_item) = self.menu_items.iter().find(|item| item.id == order_item.menu_item_id) {
-                total_amount += menu_item.price * order_item.quantity as f64;
+                total_amount += to_units(menu_item.price) * order_item.quantity as u64;
             }
         }
         order.total_amount = total_amount;
@@ -1020,7 +1154,17 @@ impl Blockchain {
         Ok(order)
     }
 
+    // Добавление готового заказа (используется HTTP API)
+    fn insert_order(&mut self, order: Order) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::InsertOrder { order: order.clone() })?;
+
+        self.orders.push(order);
+        Ok(())
+    }
+
     fn confirm_order(&mut self, order_id: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::ConfirmOrder { order_id: order_id.clone() })?;
+
         let idx = self.orders.iter().position(|o| o.id == order_id).ok_or("Order not found".to_string())?;
         let (security_tokens, utility_tokens, customer_wallet, items_clone);
         {
@@ -1029,21 +1173,17 @@ impl Blockchain {
                 return Err("Order is not pending".to_string());
             }
             security_tokens = order.total_amount;
-            utility_tokens = order.total_amount * 0.1;
+            utility_tokens = order.total_amount * self.tokenomics.policy_at(self.chain.len() as u64).utility_percentage / 100;
             customer_wallet = order.customer_wallet.clone();
             items_clone = order.items.clone();
         }
 
         // update balances
+        self.mint(&customer_wallet, security_tokens)?;
+        self.utility_token.issue_voting_tokens(utility_tokens);
         if let Some(holder) = self.token_holders.get_mut(&customer_wallet) {
-                holder.add_security_tokens(security_tokens);
-                holder.add_utility_tokens(utility_tokens);
-            } else {
-            let mut new_holder = TokenHolder::new(customer_wallet.clone(), false);
-                new_holder.add_security_tokens(security_tokens);
-                new_holder.add_utility_tokens(utility_tokens);
-            self.token_holders.insert(customer_wallet.clone(), new_holder);
-            }
+            holder.add_utility_tokens(utility_tokens);
+        }
 
         // update availability
         for order_item in &items_clone {
@@ -1081,6 +1221,8 @@ impl Blockchain {
     }
 
     fn cancel_order(&mut self, order_id: String, reason: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::CancelOrder { order_id: order_id.clone(), reason: reason.clone() })?;
+
         if let Some(order) = self.orders.iter_mut().find(|o| o.id == order_id) {
             if order.status != OrderStatus::Pending {
                 return Err("Order is not pending".to_string());
@@ -1094,13 +1236,12 @@ impl Blockchain {
 
     // Децентрализованные смарт-контракты
     fn create_purchase_contract(&mut self, customer: String, amount: f64) -> Result<String, String> {
+        let _op = self.begin_operation(WalOperation::CreatePurchaseContract { customer: customer.clone(), amount })?;
+
         let conditions = ContractConditions {
-            min_tokens_required: 0.0,
+            min_tokens_required: 0,
             expiration_time: Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() + 3600 // 1 час
+                clock::now_secs() + 3600 // 1 час
             ),
             required_participants: vec![customer.clone(), self.main_owner.clone()],
             auto_execute: true,
@@ -1118,23 +1259,27 @@ impl Blockchain {
     }
 
     fn create_voting_contract(&mut self, voter: String, menu_item_id: String) -> Result<String, String> {
+        let _op = self.begin_operation(WalOperation::CreateVotingContract { voter: voter.clone(), menu_item_id: menu_item_id.clone() })?;
+
         let conditions = ContractConditions {
-            min_tokens_required: 1.0, // Минимум 1 utility токен
+            min_tokens_required: SCALE, // Минимум 1 utility токен
             expiration_time: Some(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() + 86400 // 24 часа
+                clock::now_secs() + 86400 // 24 часа
             ),
             required_participants: vec![voter.clone()],
             auto_execute: false,
         };
 
-        let contract = SmartContract::new(
+        if !self.menu_items.iter().any(|item| item.id == menu_item_id) {
+            return Err("Menu item not found".to_string());
+        }
+
+        let mut contract = SmartContract::new(
             ContractType::VotingContract,
             voter.clone(),
             conditions
         );
+        contract.subject = Some(menu_item_id);
 
         let contract_id = contract.contract_id.clone();
         self.smart_contracts.push(contract);
@@ -1142,40 +1287,135 @@ impl Blockchain {
     }
 
     fn execute_voting_contract(&mut self, contract_id: String, voter: String, vote_for: bool) -> Result<(), String> {
-        if let Some(contract) = self.smart_contracts.iter_mut().find(|c| c.contract_id == contract_id) {
-            // Проверяем баланс utility токенов
-            if let Some(holder) = self.token_holders.get(&voter) {
-                if holder.utility_tokens < contract.conditions.min_tokens_required {
-                    return Err("Insufficient utility tokens for voting".to_string());
-                }
-            } else {
-                return Err("Voter not found".to_string());
+        let _op = self.begin_operation(WalOperation::ExecuteVotingContract {
+            contract_id: contract_id.clone(),
+            voter: voter.clone(),
+            vote_for,
+        })?;
+
+        let index = self.smart_contracts.iter()
+            .position(|c| c.contract_id == contract_id)
+            .ok_or("Contract not found")?;
+        let mut contract = self.smart_contracts[index].clone();
+
+        // Проверяем баланс utility токенов
+        if let Some(holder) = self.token_holders.get(&voter) {
+            if holder.utility_tokens < contract.conditions.min_tokens_required {
+                return Err("Insufficient utility tokens for voting".to_string());
             }
+        } else {
+            return Err("Voter not found".to_string());
+        }
+        let menu_item_id = contract.subject.clone().ok_or("Voting contract has no menu item")?;
+
+        // Выполняем контракт; он сохраняется, только если бюллетень принят
+        let action = if vote_for { "vote_for" } else { "vote_against" };
+        let tokens_used = contract.conditions.min_tokens_required;
+        contract.execute(voter.clone(), action.to_string(), tokens_used)?;
+        self.cast_menu_ballot(voter, menu_item_id, vote_for)?;
+        self.smart_contracts[index] = contract;
+        Ok(())
+    }
 
-            // Выполняем контракт
-            let action = if vote_for { "vote_for" } else { "vote_against" };
-            let tokens_used = contract.conditions.min_tokens_required;
-            
-            contract.execute(voter.clone(), action.to_string(), tokens_used)?;
-
-            // Записываем в историю голосований
-            let voting_record = VotingRecord {
-                voter_wallet: voter.clone(),
-                menu_item_id: "menu_item_from_contract".to_string(), // В реальной реализации это будет из контракта
-                menu_item_name: "Menu Item".to_string(),
-                vote_weight: tokens_used,
-                vote_for,
-                timestamp: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs(),
-            };
+    // Бюллетень по позиции меню с записью в историю голосований.
+    // Голосование, открытое без снимка (позиция из HTTP API или из старого состояния), получает снимок при первом голосе.
+    fn cast_menu_ballot(&mut self, voter: String, menu_item_id: String, vote_for: bool) -> Result<(), String> {
+        let snapshot = self.voting_snapshot(VotingStrategy::Linear);
+        let lock = match self.menu_items.iter().find(|item| item.id == menu_item_id) {
+            Some(item) => self.conviction_lock(&voter, &item.ballot_box, item.voting_ends)?,
+            None => None,
+        };
+        let menu_item = self.menu_items.iter_mut()
+            .find(|item| item.id == menu_item_id)
+            .ok_or("Menu item not found")?;
+        if menu_item.status == MenuItemStatus::Voting && !menu_item.ballot_box.opened {
+            menu_item.ballot_box = BallotBox::open(snapshot, VotingStrategy::Linear);
+        }
+        let vote_weight = menu_item.vote(&voter, vote_for)?;
+        let menu_item_name = menu_item.name.clone();
+        let strategy = menu_item.ballot_box.strategy;
+        self.lock_utility_tokens(&voter, &menu_item_id, lock);
+
+        let voting_record = VotingRecord {
+            voter_wallet: voter,
+            menu_item_id,
+            menu_item_name,
+            vote_weight,
+            vote_for,
+            timestamp: clock::now_secs(),
+            strategy,
+        };
+        self.voting_history.push(voting_record);
+        Ok(())
+    }
 
-            self.voting_history.push(voting_record);
-            Ok(())
-        } else {
-            Err("Contract not found".to_string())
+    // Utility токены кошельков — снимок весов для нового голосования.
+    // При делегировании токены кошелька переходят конечному делегату его цепочки.
+    fn voting_snapshot(&self, strategy: VotingStrategy) -> BTreeMap<String, u64> {
+        let mut snapshot = BTreeMap::new();
+        for (address, holder) in &self.token_holders {
+            if holder.utility_tokens == 0 {
+                continue;
+            }
+            let voter = if strategy == VotingStrategy::Delegated { self.final_delegate(address) } else { address.clone() };
+            *snapshot.entry(voter).or_insert(0) += holder.utility_tokens;
+        }
+        snapshot
+    }
+
+    fn final_delegate(&self, address: &str) -> String {
+        let mut current = address.to_string();
+        // Циклы запрещены при делегировании; ограничение длины — страховка для старого состояния
+        for _ in 0..self.token_holders.len() {
+            match self.token_holders.get(&current).and_then(|holder| holder.delegate.clone()) {
+                Some(delegate) => current = delegate,
+                None => break,
+            }
+        }
+        current
+    }
+
+    // Передача голоса другому кошельку (None — отзыв); действует в голосованиях, открытых после нее
+    fn delegate_voting_power(&mut self, wallet: String, delegate: Option<String>) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::DelegateVotingPower { wallet: wallet.clone(), delegate: delegate.clone() })?;
+
+        if !self.token_holders.contains_key(&wallet) {
+            return Err("Wallet not found".to_string());
+        }
+        if let Some(delegate) = &delegate {
+            if !self.token_holders.contains_key(delegate) {
+                return Err("Delegate not found".to_string());
+            }
+            if self.final_delegate(delegate) == wallet {
+                return Err(format!("Delegating to {} would create a delegation cycle", delegate));
+            }
         }
+        self.token_holders.get_mut(&wallet).unwrap().delegate = delegate;
+        Ok(())
+    }
+
+    // Итоги открытых и завершенных голосований по меню и предложениям
+    fn voting_tallies(&self) -> Vec<VotingTally> {
+        let now = clock::now_secs();
+        let menu = self.menu_items.iter()
+            .filter(|item| item.ballot_box.opened)
+            .map(|item| (item.id.clone(), item.name.clone(), &item.ballot_box, item.voting_ends));
+        let proposals = self.proposals.iter()
+            .map(|proposal| (proposal.id.clone(), proposal.description.clone(), &proposal.ballot_box, proposal.voting_ends));
+        menu.chain(proposals)
+            .map(|(subject_id, subject_name, ballot_box, voting_ends)| {
+                let (votes_for, votes_against) = ballot_box.tally(now.min(voting_ends));
+                VotingTally {
+                    subject_id,
+                    subject_name,
+                    strategy: ballot_box.strategy,
+                    votes_for,
+                    votes_against,
+                    voters: ballot_box.ballots.len(),
+                    is_final: now > voting_ends,
+                }
+            })
+            .collect()
     }
 
     // Методы для получения истории
@@ -1187,6 +1427,17 @@ impl Blockchain {
         history
     }
 
+    // Доказательство включения транзакции в блок цепи
+    fn inclusion_proof(&self, tx_id: &str) -> Result<InclusionProof, String> {
+        let block = self.chain.iter()
+            .find(|block| block.transactions.iter().any(|t| t.transaction_id == tx_id))
+            .ok_or_else(|| format!("Transaction {} is not in the chain", tx_id))?;
+        if block.merkle_root.is_empty() {
+            return Err(format!("Block {} has no Merkle root", block.index));
+        }
+        block.inclusion_proof(tx_id).ok_or_else(|| format!("Transaction {} is not in the chain", tx_id))
+    }
+
     fn get_voting_history(&self) -> Vec<VotingRecord> {
         let mut history = self.voting_history.clone();
         history.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
@@ -1211,96 +1462,360 @@ impl Blockchain {
     }
 
     fn vote_on_menu_item(&mut self, voter: String, menu_item_id: String, vote_for: bool) -> Result<(), String> {
-        let voting_power = if let Some(holder) = self.token_holders.get(&voter) {
-            holder.utility_tokens
-        } else {
+        let _op = self.begin_operation(WalOperation::VoteOnMenuItem {
+            voter: voter.clone(),
+            menu_item_id: menu_item_id.clone(),
+            vote_for,
+        })?;
+
+        if !self.token_holders.contains_key(&voter) {
             return Err("Voter not found".to_string());
-        };
-        
-        if voting_power <= 0.0 {
-            return Err("No voting power available".to_string());
-        }
-        
-        if let Some(menu_item) = self.menu_items.iter_mut().find(|item| item.id == menu_item_id) {
-            menu_item.vote(voting_power, vote_for)
-        } else {
-            Err("Menu item not found".to_string())
         }
+        self.cast_menu_ballot(voter, menu_item_id, vote_for)
     }
 
     fn add_transaction(&mut self, transaction: Transaction) {
         self.pending_transactions.push(transaction);
     }
 
-    fn select_validator(&self) -> Option<String> {
-        if self.token_holders.is_empty() {
-            return None;
-        }
+    // Держатели с долей не меньше min_stake, отсортированные по адресу: из них выбирается лидер слота
+    fn eligible_stakes(&self) -> Vec<(String, u64)> {
+        let mut holders: Vec<(String, u64)> = self.token_holders.iter()
+            .filter(|(_, holder)| holder.security_tokens > 0 && holder.security_tokens >= self.min_stake)
+            .map(|(address, holder)| (address.clone(), holder.security_tokens))
+            .collect();
+        holders.sort();
+        holders
+    }
+
+    // Лидер слота среди держателей с долей не меньше min_stake; выбор зависит только от хеша
+    // предыдущего блока, номера слота и балансов, поэтому повторяется при воспроизведении журнала
+    fn slot_leader(&self, prev_hash: &str, slot: u64) -> Option<String> {
+        Self::pick_slot_leader(&self.eligible_stakes(), prev_hash, slot)
+    }
 
-        let total_security_tokens: f64 = self.token_holders.values().map(|v| v.security_tokens).sum();
-        if total_security_tokens == 0.0 {
+    fn pick_slot_leader(stakes: &[(String, u64)], prev_hash: &str, slot: u64) -> Option<String> {
+        let total_stake: u64 = stakes.iter().map(|(_, stake)| stake).sum();
+        if total_stake == 0 {
             return None;
         }
-        
-        let mut rng = fastrand::Rng::new();
-        let random_value = rng.f64() * total_security_tokens;
-        
-        let mut current_sum = 0.0;
-        for (address, holder) in &self.token_holders {
-            current_sum += holder.security_tokens;
-            if random_value <= current_sum {
+        let digest = Sha256::digest(format!("{}|{}", prev_hash, slot).as_bytes());
+        let point = u64::from_be_bytes(digest[..8].try_into().unwrap()) % total_stake;
+
+        let mut current_sum = 0;
+        for (address, stake) in stakes {
+            current_sum += stake;
+            if point < current_sum {
                 return Some(address.clone());
             }
         }
-        
         None
     }
 
-    fn mine_block(&mut self) -> Result<(), String> {
+    // Ключ держателя, от имени которого нода производит блоки в его слотах
+    fn add_validator_key(&mut self, keypair: Keypair) -> String {
+        let address = crypto::wallet_address(&keypair.public_key_hex()).expect("own public key is always valid");
+        self.validator_keys.insert(address.clone(), Arc::new(keypair));
+        address
+    }
+
+    // Блок текущего слота: нода собирает его, если у нее есть ключ лидера слота, подписывает
+    // и добавляет в цепь через append_block, как блок, полученный от лидера
+    fn produce_block(&mut self) -> Result<(), String> {
         if self.pending_transactions.is_empty() {
-            return Err("No pending transactions to mine".to_string());
+            return Err("No pending transactions for a new block".to_string());
         }
 
-        let validator_address = self.select_validator()
-            .ok_or("No validators available")?;
-        
-        let validator = self.token_holders.get(&validator_address)
-            .ok_or("Validator not found")?;
-        
-        if validator.security_tokens < self.min_stake {
-            return Err("Validator security tokens too low".to_string());
+        let previous = self.chain.last().unwrap();
+        let timestamp = clock::now_secs();
+        if timestamp < previous.timestamp {
+            return Err("Clock is behind the previous block".to_string());
+        }
+        let slot = timestamp / BLOCK_TIME_SECS;
+        if previous.slot.is_some_and(|previous_slot| slot <= previous_slot) {
+            return Err(format!("Slot {} already has a block, next slot starts in {}s", slot, (slot + 1) * BLOCK_TIME_SECS - timestamp));
         }
 
-        let transactions = self.pending_transactions.drain(..).collect();
-        let prev_hash = self.chain.last().unwrap().hash.clone();
-        
-        let mut new_block = Block::new(
-            self.chain.len() as u32,
-            transactions,
-            prev_hash,
-            validator_address.clone(),
-            validator.security_tokens,
-        );
-
-        // Add block reward before mining so hash includes it
-        let reward_transaction = Transaction::new(
+        let prev_hash = previous.hash.clone();
+        let stakes = SlotStakes { min_stake: self.min_stake, stakes: self.eligible_stakes() };
+        let validator_address = Self::pick_slot_leader(&stakes.stakes, &prev_hash, slot)
+            .ok_or("No holder has enough stake to produce blocks")?;
+        let keypair = self.validator_keys.get(&validator_address)
+            .ok_or(format!("Slot {} leader {} has no validator key on this node", slot, validator_address))?;
+        let stake_used = self.token_holders[&validator_address].security_tokens;
+
+        // Награда входит в блок до вычисления хеша
+        let mut transactions = self.pending_transactions.clone();
+        transactions.push(Transaction::new(
             "Blockchain".to_string(),
             validator_address.clone(),
             self.block_reward,
             vec!["Block Reward".to_string()],
-            0.0,
-            0.0,
-        );
-        new_block.transactions.push(reward_transaction);
+            0,
+            0,
+            crypto::random_code(),
+        ));
+        let mut new_block = Block::new(self.chain.len() as u32, transactions, prev_hash, validator_address, stake_used);
+        new_block.timestamp = timestamp;
+        new_block.slot = Some(slot);
+        new_block.stakes_root = stakes.root();
+        new_block.update_merkle_root();
+        new_block.sign(keypair);
+
+        self.append_block(new_block)
+    }
+
+    // Подписанный блок лидера слота. Журналируется целиком, поэтому воспроизведение не требует ключа лидера.
+    // Блок проверяется по долям держателей на вершине цепи и должен содержать ожидающие транзакции и награду лидера.
+    fn append_block(&mut self, block: Block) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::AppendBlock { block: block.clone() })?;
 
-        new_block.mine(self.difficulty);
+        let stakes = SlotStakes { min_stake: self.min_stake, stakes: self.eligible_stakes() };
+        Self::verify_pos_block(self.chain.last().unwrap(), &block, &stakes)?;
+
+        let Some((reward, transactions)) = block.transactions.split_last() else {
+            return Err("Block has no transactions".to_string());
+        };
+        if transactions.iter().map(|t| &t.transaction_id).ne(self.pending_transactions.iter().map(|t| &t.transaction_id)) {
+            return Err("Block transactions differ from the pending transactions".to_string());
+        }
+        if reward.from != "Blockchain" || reward.to != block.validator || reward.amount != self.block_reward {
+            return Err("Block reward does not match the slot leader and block_reward".to_string());
+        }
 
         // Update validator rewards
-        if let Some(validator) = self.token_holders.get_mut(&validator_address) {
-            validator.add_security_tokens(self.block_reward);
+        self.mint(&block.validator, self.block_reward)?;
+
+        self.pending_transactions.clear();
+        self.slot_stakes.insert(block.index, stakes);
+        self.chain.push(block);
+        self.apply_tokenomics_limits();
+        Ok(())
+    }
+
+    // PoS блок: без nonce, слот по метке времени, один блок на слот, время не идет назад.
+    // Доли, из которых выбран лидер, закреплены в хеше блока корнем, а блок подписан ключом лидера.
+    fn verify_pos_block(previous: &Block, block: &Block, stakes: &SlotStakes) -> Result<(), String> {
+        let slot = block.slot.ok_or("Block has no slot")?;
+        if block.index != previous.index + 1 || block.prev_hash != previous.hash {
+            return Err("Block does not extend the chain tip".to_string());
+        }
+        let merkle_root_matches = block.merkle_root.is_empty() || block.merkle_root == block.calculate_merkle_root();
+        if !merkle_root_matches || block.hash != block.calculate_hash() {
+            return Err("Block hash does not match its contents".to_string());
+        }
+        if block.nonce != 0 || slot != block.timestamp / BLOCK_TIME_SECS {
+            return Err("Block slot does not match its timestamp".to_string());
+        }
+        if block.timestamp < previous.timestamp || previous.slot.is_some_and(|previous_slot| slot <= previous_slot) {
+            return Err(format!("Slot {} is not after the previous block", slot));
+        }
+        if block.timestamp > clock::now_secs() + MAX_BLOCK_TIME_DRIFT_SECS {
+            return Err("Block timestamp is too far in the future".to_string());
+        }
+        if block.stakes_root != stakes.root() {
+            return Err("Block stakes root does not match the slot stakes".to_string());
+        }
+        if Self::pick_slot_leader(&stakes.stakes, &previous.hash, slot).as_deref() != Some(block.validator.as_str()) {
+            return Err(format!("{} is not the leader of slot {}", block.validator, slot));
+        }
+        if block.stake_used < stakes.min_stake
+            || !stakes.stakes.iter().any(|(address, stake)| *address == block.validator && *stake == block.stake_used) {
+            return Err(format!("Leader stake {} is not the recorded stake", block.stake_used));
+        }
+        if crypto::wallet_address(&block.validator_key).ok().as_deref() != Some(block.validator.as_str())
+            || !crypto::verify(&block.validator_key, &block.signing_bytes(), &block.signature) {
+            return Err("Block is not signed by the slot leader".to_string());
+        }
+        Ok(())
+    }
+
+    // Новая политика токеномики; действует с effective_from_height, уже принятые покупки не пересчитываются
+    fn schedule_tokenomics(&mut self, policy: TokenomicsPolicy) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::ScheduleTokenomics { policy: policy.clone() })?;
+
+        // Покупки, ожидающие блока, уже распределены по политике его высоты
+        self.tokenomics.schedule(policy, self.chain.len() as u64)
+    }
+
+    // Генезис с расписанием токеномики из конфигурации
+    fn with_tokenomics(mut self, tokenomics: TokenomicsSchedule) -> Self {
+        self.tokenomics = tokenomics;
+        let limits = self.tokenomics.policy_at(self.chain.len() as u64).limits.clone();
+        self.max_owner_percentage = limits.max_owner_percentage;
+        self.max_franchise_percentage = limits.max_franchise_percentage;
+        self.max_customer_percentage = limits.max_customer_percentage;
+        self.charity_percentage = limits.charity_percentage;
+        self
+    }
+
+    // Лимиты владения из политики на высоте следующего блока; меняются только когда политика вступает в силу
+    fn apply_tokenomics_limits(&mut self) {
+        let height = self.chain.len() as u64;
+        let policy = self.tokenomics.policy_at(height);
+        if policy.effective_from_height != height {
+            return;
+        }
+        let limits = policy.limits.clone();
+        self.max_owner_percentage = limits.max_owner_percentage;
+        self.max_franchise_percentage = limits.max_franchise_percentage;
+        self.max_customer_percentage = limits.max_customer_percentage;
+        self.charity_percentage = limits.charity_percentage;
+    }
+
+    // Предложение по параметру протокола; выдвигать может держатель с долей не меньше min_stake
+    fn submit_proposal(&mut self, proposer: String, description: String, change: ProposalChange, strategy: VotingStrategy) -> Result<String, String> {
+        let _op = self.begin_operation(WalOperation::SubmitProposal {
+            proposer: proposer.clone(),
+            description: description.clone(),
+            change: change.clone(),
+            strategy,
+        })?;
+
+        let stake = self.token_holders.get(&proposer).map(|holder| holder.security_tokens).unwrap_or(0);
+        if stake == 0 || stake < self.min_stake {
+            return Err("Only holders with at least min_stake security tokens can submit proposals".to_string());
+        }
+        change.validate()?;
+
+        let rules = VotingRules { strategy, ..VotingRules::default() };
+        let proposal = Proposal::new(proposer, description, change, rules, self.voting_snapshot(strategy));
+        if self.proposals.iter().any(|existing| existing.id == proposal.id) {
+            return Err("Proposal already exists".to_string());
+        }
+        let id = proposal.id.clone();
+        self.proposals.push(proposal);
+        Ok(id)
+    }
+
+    fn vote_on_proposal(&mut self, voter: String, proposal_id: String, vote_for: bool) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::VoteOnProposal {
+            voter: voter.clone(),
+            proposal_id: proposal_id.clone(),
+            vote_for,
+        })?;
+
+        let proposal = self.proposals.iter()
+            .find(|proposal| proposal.id == proposal_id)
+            .ok_or("Proposal not found")?;
+        let lock = self.conviction_lock(&voter, &proposal.ballot_box, proposal.voting_ends)?;
+        let proposal = self.proposals.iter_mut()
+            .find(|proposal| proposal.id == proposal_id)
+            .ok_or("Proposal not found")?;
+        proposal.vote(&voter, vote_for)?;
+        self.lock_utility_tokens(&voter, &proposal_id, lock);
+        Ok(())
+    }
+
+    // Голос Conviction блокирует utility токены кошелька из снимка до конца окна: вес растет за время
+    // удержания, поэтому токены не должны уйти с кошелька, пока голос копит множитель
+    fn conviction_lock(&self, voter: &str, ballot_box: &BallotBox, voting_ends: u64) -> Result<Option<UtilityLock>, String> {
+        if !ballot_box.opened || ballot_box.strategy != VotingStrategy::Conviction {
+            return Ok(None);
+        }
+        let units = ballot_box.snapshot.get(voter).copied().unwrap_or(0);
+        let held = self.token_holders.get(voter).map(|holder| holder.utility_tokens).unwrap_or(0);
+        if held < units {
+            return Err(format!("Conviction vote locks {} utility tokens until {}, wallet {} holds {}", units, voting_ends, voter, held));
+        }
+        Ok(Some(UtilityLock { units, until: voting_ends }))
+    }
+
+    fn lock_utility_tokens(&mut self, voter: &str, subject_id: &str, lock: Option<UtilityLock>) {
+        if let (Some(lock), Some(holder)) = (lock, self.token_holders.get_mut(voter)) {
+            holder.utility_locks.insert(subject_id.to_string(), lock);
+        }
+    }
+
+    // Подсчет после окна голосования; принятое изменение применяется сразу.
+    // Если изменение не применилось (например, нода уже есть), предложение остается Failed.
+    fn finalize_proposal(&mut self, proposal_id: String) -> Result<ProposalStatus, String> {
+        let _op = self.begin_operation(WalOperation::FinalizeProposal { proposal_id: proposal_id.clone() })?;
+
+        let index = self.proposals.iter()
+            .position(|proposal| proposal.id == proposal_id)
+            .ok_or("Proposal not found")?;
+        let status = if self.proposals[index].close()? {
+            let change = self.proposals[index].change.clone();
+            match self.apply_proposal_change(&proposal_id, change) {
+                Ok(()) => ProposalStatus::Executed,
+                Err(reason) => ProposalStatus::Failed { reason },
+            }
+        } else {
+            ProposalStatus::Rejected
+        };
+        self.proposals[index].status = status.clone();
+        Ok(status)
+    }
+
+    fn apply_proposal_change(&mut self, proposal_id: &str, change: ProposalChange) -> Result<(), String> {
+        change.validate()?;
+        match change {
+            ProposalChange::MaxOwnerPercentage { percentage } => {
+                self.amend_tokenomics(|policy| {
+                    policy.limits.max_owner_percentage = percentage;
+                    Ok(())
+                })?;
+            }
+            ProposalChange::MinStake { units } => self.min_stake = units,
+            ProposalChange::BlockReward { units } => self.block_reward = units,
+            ProposalChange::RoyaltyShare { percentage } => {
+                self.amend_tokenomics(|policy| {
+                    let pool = policy.franchise_node.network_owner + policy.franchise_node.node_owner;
+                    if percentage > pool {
+                        return Err(format!("Royalty share {}% exceeds the franchise pool of {}%", percentage, pool));
+                    }
+                    policy.franchise_node.network_owner = percentage;
+                    policy.franchise_node.node_owner = pool - percentage;
+                    Ok(())
+                })?;
+            }
+            ProposalChange::ReputationWeights { weights } => {
+                self.franchise_governance.push((proposal_id.to_string(), GovernancePayload::SetReputationWeights { weights }));
+            }
+            ProposalChange::AdmitFranchise { node_id, franchise_owner } => {
+                self.add_franchise_node(node_id, franchise_owner)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Транзакции Governance с решениями держателей, подписанные нодой основателя (NodeType::OWNER).
+    // Id транзакции зависит только от предложения, поэтому повторная отправка после перезапуска
+    // не попадает в цепь второй раз.
+    fn franchise_governance_transactions(&self, node_id: u64, keypair: &Keypair) -> Vec<ConsensusTransaction> {
+        self.franchise_governance.iter()
+            .map(|(proposal_id, payload)| {
+                let data = serde_json::to_value(payload).expect("governance payload is always serializable");
+                let mut transaction = ConsensusTransaction::new(node_id, TransactionType::Governance, data);
+                transaction.id = format!("governance_{}", proposal_id);
+                transaction.sign(keypair);
+                transaction
+            })
+            .collect()
+    }
+
+    // Изменение токеномики голосованием: политика со следующего блока получает изменение, как и
+    // запланированные после нее, поэтому несколько принятых предложений не отменяют друг друга
+    fn amend_tokenomics(&mut self, amend: impl Fn(&mut TokenomicsPolicy) -> Result<(), String>) -> Result<(), String> {
+        let height = self.chain.len() as u64;
+        let mut next = self.tokenomics.policy_at(height + 1).clone();
+        next.effective_from_height = height + 1;
+        let mut later: Vec<TokenomicsPolicy> = self.tokenomics.policies().iter()
+            .filter(|policy| policy.effective_from_height > height + 1)
+            .cloned()
+            .collect();
+        amend(&mut next)?;
+        for policy in &mut later {
+            amend(policy)?;
         }
 
-        self.chain.push(new_block);
+        let mut schedule = self.tokenomics.clone();
+        schedule.schedule(next, height)?;
+        for policy in later {
+            schedule.schedule(policy, height)?;
+        }
+        self.tokenomics = schedule;
         Ok(())
     }
 
@@ -1312,33 +1827,60 @@ impl Blockchain {
             if current.hash != current.calculate_hash() {
                 return false;
             }
+
+            if !current.merkle_root.is_empty() && current.merkle_root != current.calculate_merkle_root() {
+                return false;
+            }
             
             if current.prev_hash != previous.hash {
                 return false;
             }
+
+            // PoS блок подписал лидер слота, выбранный по долям из корня в его хеше
+            if current.slot.is_some() {
+                let Some(recorded) = self.slot_stakes.get(&current.index) else {
+                    return false;
+                };
+                if Self::verify_pos_block(previous, current, recorded).is_err() {
+                    return false;
+                }
+            }
         }
         true
     }
 
-    fn update_roles(&mut self) {
-        let total_security_tokens: f64 = self.token_holders.values().map(|v| v.security_tokens).sum();
+    fn update_roles(&mut self) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::UpdateRoles)?;
+
+        let total_security_tokens: u64 = self.token_holders.values().map(|v| v.security_tokens).sum();
         
         for holder in self.token_holders.values_mut() {
             if !holder.is_main_owner {
-                let percentage = (holder.security_tokens / total_security_tokens) * 100.0;
+                let percentage = percentage_of(holder.security_tokens, total_security_tokens);
                 holder.role = UserRole::from_percentage(percentage);
             }
         }
+        Ok(())
     }
 
     // Авторизация пользователя по номеру телефона
     fn register_user_with_phone(&mut self, phone_number: String, wallet_address: String) -> Result<String, String> {
+        self.register_user_with_code(phone_number, wallet_address, crypto::random_code())
+    }
+
+    // Код подтверждения создается до операции и журналируется вместе с ней
+    fn register_user_with_code(&mut self, phone_number: String, wallet_address: String, verification_code: String) -> Result<String, String> {
+        let _op = self.begin_operation(WalOperation::RegisterUserWithPhone {
+            phone_number: phone_number.clone(),
+            wallet_address: wallet_address.clone(),
+            verification_code: verification_code.clone(),
+        })?;
+
         if self.authorized_users.contains_key(&phone_number) {
             return Err("Phone number already registered".to_string());
         }
 
-        let authorized_user = AuthorizedUser::new(phone_number.clone(), wallet_address.clone());
-        let verification_code = authorized_user.verification_code.clone();
+        let authorized_user = AuthorizedUser::new(phone_number.clone(), wallet_address.clone(), verification_code.clone());
         
         self.authorized_users.insert(phone_number.clone(), authorized_user);
         
@@ -1356,6 +1898,11 @@ impl Blockchain {
 
     // Подтверждение номера телефона
     fn verify_phone_number(&mut self, phone_number: String, verification_code: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::VerifyPhoneNumber {
+            phone_number: phone_number.clone(),
+            verification_code: verification_code.clone(),
+        })?;
+
         if let Some(user) = self.authorized_users.get_mut(&phone_number) {
             user.verify(&verification_code)?;
             Ok(())
@@ -1365,7 +1912,28 @@ impl Blockchain {
     }
 
     // Перенос баланса с неавторизованного кошелька на авторизованный
+    // Защита от повтора подписанных запросов: nonce кошелька должен строго расти
+    fn use_wallet_nonce(&mut self, wallet: String, nonce: u64) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::UseWalletNonce {
+            wallet: wallet.clone(),
+            nonce,
+        })?;
+
+        let last_nonce = self.wallet_nonces.get(&wallet).copied().unwrap_or(0);
+        if nonce <= last_nonce {
+            return Err(format!("Nonce {} is not greater than last used nonce {} for wallet {}", nonce, last_nonce, wallet));
+        }
+
+        self.wallet_nonces.insert(wallet, nonce);
+        Ok(())
+    }
+
     fn transfer_balance_from_check(&mut self, check_id: String, to_phone_number: String) -> Result<String, String> {
+        let _op = self.begin_operation(WalOperation::TransferBalanceFromCheck {
+            check_id: check_id.clone(),
+            to_phone_number: to_phone_number.clone(),
+        })?;
+
         // Проверяем, что получатель авторизован
         let authorized_user = self.authorized_users.get(&to_phone_number)
             .ok_or("Phone number not authorized")?;
@@ -1396,17 +1964,17 @@ impl Blockchain {
         let from_wallet = from_wallet.unwrap();
 
         // Проверяем ограничения на владение токенами
-        let total_security_tokens: f64 = self.token_holders.values().map(|v| v.security_tokens).sum();
+        let total_security_tokens: u64 = self.token_holders.values().map(|v| v.security_tokens).sum();
         let total_utility_tokens = self.utility_token.total_supply;
         
         let security_tokens_to_transfer = check.amount;
-        let utility_tokens_to_transfer = check.amount * 0.1;
+        let utility_tokens_to_transfer = check.amount * self.tokenomics.policy_at(self.chain.len() as u64).utility_percentage / 100;
 
         // Проверяем, не превысит ли перенос максимальную долю владения
         if let Some(to_holder) = self.token_holders.get(&authorized_user.wallet_address) {
             // Токены уже существуют в системе, поэтому общее количество не меняется
-            let new_security_percentage = ((to_holder.security_tokens + security_tokens_to_transfer) / total_security_tokens) * 100.0;
-            let new_utility_percentage = ((to_holder.utility_tokens + utility_tokens_to_transfer) / total_utility_tokens) * 100.0;
+            let new_security_tokens = to_holder.security_tokens + security_tokens_to_transfer;
+            let new_utility_tokens = to_holder.utility_tokens + utility_tokens_to_transfer;
             
             // Определяем максимальный лимит в зависимости от роли пользователя
             let max_percentage = if to_holder.is_main_owner {
@@ -1417,11 +1985,23 @@ impl Blockchain {
                 self.max_customer_percentage
             };
             
-            if new_security_percentage > max_percentage + 0.01 || new_utility_percentage > max_percentage + 0.01 {
+            if exceeds_percentage(new_security_tokens, total_security_tokens, max_percentage)
+                || exceeds_percentage(new_utility_tokens, total_utility_tokens, max_percentage) {
                 return Err(format!("Transfer would exceed maximum ownership percentage of {}%", max_percentage));
             }
         }
 
+        // Перенос не создает токены: на исходном кошельке должно хватать баланса
+        let from_holder = &self.token_holders[&from_wallet];
+        if from_holder.security_tokens < security_tokens_to_transfer || from_holder.utility_tokens < utility_tokens_to_transfer {
+            return Err("Insufficient balance on check wallet".to_string());
+        }
+        // Токены, заблокированные голосами Conviction, остаются на кошельке до конца окна
+        let locked = from_holder.locked_utility_tokens(clock::now_secs());
+        if from_holder.utility_tokens - utility_tokens_to_transfer < locked {
+            return Err(format!("{} utility tokens of {} are locked by conviction votes", locked, from_wallet));
+        }
+
         // Создаем запись о переносе
         let transfer_id = Self::generate_transfer_id(&check_id, &to_phone_number);
         let transfer_record = BalanceTransferRecord {
@@ -1432,17 +2012,22 @@ impl Blockchain {
             to_phone: to_phone_number.clone(),
             security_tokens_transferred: security_tokens_to_transfer,
             utility_tokens_transferred: utility_tokens_to_transfer,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: clock::now_secs(),
             status: TransferStatus::Pending,
         };
 
-        // Выполняем перенос
-        // Удаляем токены с исходного кошелька
-        if let Some(from_holder) = self.token_holders.get_mut(&from_wallet) {
-            from_holder.security_tokens -= security_tokens_to_transfer;
+        // Выполняем перенос: security токены проводятся через реестр
+        let to_wallet = authorized_user.wallet_address.clone();
+        self.token_holders.entry(to_wallet.clone()).or_insert_with(|| {
+            let mut new_holder = TokenHolder::new(to_wallet.clone(), false);
+            new_holder.authorize_with_phone(to_phone_number.clone());
+            new_holder
+        });
+        self.burn(&from_wallet, security_tokens_to_transfer)?;
+        self.mint(&to_wallet, security_tokens_to_transfer)?;
+
+        // Удаляем utility токены с исходного кошелька
+        if let Some(from_holder) = self.token_holders.get_mut(&from_wallet) {
             from_holder.utility_tokens -= utility_tokens_to_transfer;
             
             // Помечаем чек как использованный
@@ -1451,16 +2036,9 @@ impl Blockchain {
             }
         }
 
-        // Добавляем токены на целевой кошелек
-        if let Some(to_holder) = self.token_holders.get_mut(&authorized_user.wallet_address) {
-            to_holder.add_security_tokens(security_tokens_to_transfer);
+        // Добавляем utility токены на целевой кошелек
+        if let Some(to_holder) = self.token_holders.get_mut(&to_wallet) {
             to_holder.add_utility_tokens(utility_tokens_to_transfer);
-        } else {
-            let mut new_holder = TokenHolder::new(authorized_user.wallet_address.clone(), false);
-            new_holder.authorize_with_phone(to_phone_number.clone());
-            new_holder.add_security_tokens(security_tokens_to_transfer);
-            new_holder.add_utility_tokens(utility_tokens_to_transfer);
-            self.token_holders.insert(authorized_user.wallet_address.clone(), new_holder);
         }
 
         // Обновляем статус переноса
@@ -1469,16 +2047,13 @@ impl Blockchain {
         self.balance_transfer_history.push(final_record);
 
         // Обновляем роли
-        self.update_roles();
+        self.update_roles()?;
 
         Ok(transfer_id)
     }
 
     fn generate_transfer_id(check_id: &str, phone_number: &str) -> String {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = clock::now_secs();
         let data = format!("{}{}{}", check_id, phone_number, timestamp);
         let mut hasher = Sha256::new();
         hasher.update(data.as_bytes());
@@ -1496,18 +2071,18 @@ impl Blockchain {
 
     // Проверка ограничений токенов и создание алертов
     fn check_token_limits_and_create_alerts(&mut self) {
-        let total_security_tokens: f64 = self.token_holders.values().map(|v| v.security_tokens).sum();
+        let total_security_tokens: u64 = self.token_holders.values().map(|v| v.security_tokens).sum();
         let total_utility_tokens = self.utility_token.total_supply;
         
         // Собираем информацию о держателях токенов
         let mut alerts_to_create = Vec::new();
         
         for (address, holder) in &self.token_holders {
-            let security_percentage = (holder.security_tokens / total_security_tokens) * 100.0;
-            let utility_percentage = (holder.utility_tokens / total_utility_tokens) * 100.0;
+            let security_percentage = percentage_of(holder.security_tokens, total_security_tokens);
+            let utility_percentage = percentage_of(holder.utility_tokens, total_utility_tokens);
             
             // Проверяем ограничения для владельца
-            if holder.is_main_owner && security_percentage > self.max_owner_percentage + 0.01 {
+            if holder.is_main_owner && exceeds_percentage(holder.security_tokens, total_security_tokens, self.max_owner_percentage) {
                 alerts_to_create.push((
                     AlertType::OwnerExceedsLimit,
                     AlertSeverity::Critical,
@@ -1518,7 +2093,7 @@ impl Blockchain {
             }
             
             // Проверяем ограничения для владельцев франшиз
-            if holder.is_franchise_owner && security_percentage > self.max_franchise_percentage + 0.01 {
+            if holder.is_franchise_owner && exceeds_percentage(holder.security_tokens, total_security_tokens, self.max_franchise_percentage) {
                 alerts_to_create.push((
                     AlertType::FranchiseExceedsLimit,
                     AlertSeverity::High,
@@ -1529,7 +2104,8 @@ impl Blockchain {
             }
             
             // Проверяем ограничения для покупателей
-            if !holder.is_main_owner && !holder.is_charity_fund && !holder.is_franchise_owner && security_percentage > self.max_customer_percentage + 0.01 {
+            if !holder.is_main_owner && !holder.is_charity_fund && !holder.is_franchise_owner
+                && exceeds_percentage(holder.security_tokens, total_security_tokens, self.max_customer_percentage) {
                 alerts_to_create.push((
                     AlertType::CustomerExceedsLimit,
                     AlertSeverity::High,
@@ -1540,7 +2116,7 @@ impl Blockchain {
             }
             
             // Проверяем концентрацию utility токенов
-            if utility_percentage > 30.0 {
+            if exceeds_percentage(holder.utility_tokens, total_utility_tokens, 30.0) {
                 alerts_to_create.push((
                     AlertType::TokenConcentration,
                     AlertSeverity::Medium,
@@ -1552,7 +2128,7 @@ impl Blockchain {
         }
         
         // Проверяем благотворительный фонд
-        let charity_percentage = (self.charity_fund.total_donations / total_security_tokens) * 100.0;
+        let charity_percentage = percentage_of(self.charity_fund.total_donations, total_security_tokens);
         if charity_percentage < self.charity_percentage * 0.8 { // Если меньше 80% от ожидаемого
             alerts_to_create.push((
                 AlertType::CharityFundLow,
@@ -1571,7 +2147,7 @@ impl Blockchain {
     
     // Создание алерта мониторинга
     fn create_alert(&mut self, alert_type: AlertType, severity: AlertSeverity, message: String, affected_wallet: Option<String>, percentage: Option<f64>) {
-        let alert_id = format!("ALERT_{}", SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs());
+        let alert_id = format!("ALERT_{}", clock::now_secs());
         let alert = MonitoringAlert {
             alert_id,
             alert_type,
@@ -1579,7 +2155,7 @@ impl Blockchain {
             message,
             affected_wallet,
             percentage,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            timestamp: clock::now_secs(),
             is_resolved: false,
         };
         self.monitoring_alerts.push(alert);
@@ -1587,6 +2163,11 @@ impl Blockchain {
     
     // Добавление франшизной ноды
     fn add_franchise_node(&mut self, node_id: String, franchise_owner: String) -> Result<(), String> {
+        let _op = self.begin_operation(WalOperation::AddFranchiseNode {
+            node_id: node_id.clone(),
+            franchise_owner: franchise_owner.clone(),
+        })?;
+
         if self.franchise_nodes.contains_key(&node_id) {
             return Err("Node already exists".to_string());
         }
@@ -1609,60 +2190,51 @@ impl Blockchain {
     
     // Эмиссия токенов для привлечения китов-инвесторов
     fn emit_tokens_for_investors(&mut self, amount: f64, investor_address: String) -> Result<(), String> {
-        // Новая логика эмиссии для "китов": 48% владелец сети, 3% фонд, 49% инвестор
-        let main_owner_tokens = amount * 0.48; // 48% владельцу сети
-        let charity_tokens = amount * 0.03; // 3% фонду
-        let investor_tokens = amount * 0.49; // 49% инвестору
+        let _op = self.begin_operation(WalOperation::EmitTokensForInvestors {
+            amount,
+            investor_address: investor_address.clone(),
+        })?;
+
+        // Эмиссия для "китов" по инвесторским долям политики: владелец сети, фонд, инвестор
+        let amount_units = to_units(amount);
+        let policy = self.tokenomics.policy_at(self.chain.len() as u64);
+        let split = split_units(amount_units, &policy.investor.weights(), CHARITY_INDEX);
+        let parts = [split[0] + split[1], split[2], split[3]];
+        let (main_owner_tokens, charity_tokens, investor_tokens) = (parts[0], parts[1], parts[2]);
         
-        let utility_tokens = amount * 0.1; // 10% utility токенов для голосования
+        let utility_tokens = amount_units * policy.utility_percentage / 100; // utility токены для голосования
         
         // Проверяем, что владелец не превысит лимит после эмиссии
-        let current_owner_tokens = self.token_holders.get(&self.main_owner).map(|h| h.security_tokens).unwrap_or(0.0);
-        let total_tokens: f64 = self.token_holders.values().map(|h| h.security_tokens).sum();
-        let new_total = total_tokens + main_owner_tokens + charity_tokens + investor_tokens;
-        let new_owner_percentage = ((current_owner_tokens + main_owner_tokens) / new_total) * 100.0;
+        let current_owner_tokens = self.token_holders.get(&self.main_owner).map(|h| h.security_tokens).unwrap_or(0);
+        let total_tokens: u64 = self.token_holders.values().map(|h| h.security_tokens).sum();
+        let new_total = total_tokens + amount_units;
+        let new_owner_tokens = current_owner_tokens + main_owner_tokens;
+        let new_owner_percentage = percentage_of(new_owner_tokens, new_total);
         
-        if new_owner_percentage > self.max_owner_percentage + 0.01 {
+        if exceeds_percentage(new_owner_tokens, new_total, self.max_owner_percentage) {
             return Err(format!("Эмиссия приведет к превышению лимита владельца: {:.2}% > {:.2}%", new_owner_percentage, self.max_owner_percentage));
         }
         
-        // 1. Владелец сети получает 48%
-        if !self.token_holders.contains_key(&self.main_owner) {
-            let mut new_holder = TokenHolder::new(self.main_owner.clone(), true);
-            new_holder.add_security_tokens(main_owner_tokens);
-            self.token_holders.insert(self.main_owner.clone(), new_holder);
-        } else {
-            if let Some(holder) = self.token_holders.get_mut(&self.main_owner) {
-                holder.add_security_tokens(main_owner_tokens);
-            }
-        }
+        // 1. Владелец сети получает свою долю
+        let main_owner = self.main_owner.clone();
+        self.token_holders.entry(main_owner.clone())
+            .or_insert_with(|| TokenHolder::new(main_owner.clone(), true));
+        self.mint(&main_owner, main_owner_tokens)?;
         
-        // 2. Благотворительный фонд получает 3%
+        // 2. Благотворительный фонд получает свою долю
         let charity_address = self.charity_fund.fund_id.clone();
-        if let Some(charity_holder) = self.token_holders.get_mut(&charity_address) {
-            charity_holder.add_security_tokens(charity_tokens);
-        }
+        self.mint(&charity_address, charity_tokens)?;
         self.charity_fund.add_donation(charity_tokens);
         
-        // 3. Инвестор получает 49%
-        if !self.token_holders.contains_key(&investor_address) {
-            let mut investor_holder = TokenHolder::new(investor_address.clone(), false);
-            investor_holder.add_security_tokens(investor_tokens);
-            self.token_holders.insert(investor_address.clone(), investor_holder);
-        } else {
-            if let Some(holder) = self.token_holders.get_mut(&investor_address) {
-                holder.add_security_tokens(investor_tokens);
-            }
-        }
+        // 3. Инвестор получает свою долю
+        self.mint(&investor_address, investor_tokens)?;
         
         // Issue utility tokens for voting
         let voting_power = self.utility_token.issue_voting_tokens(utility_tokens);
         
         // Utility токены распределяются пропорционально security токенам
-        let total_security = main_owner_tokens + charity_tokens + investor_tokens;
-        let main_owner_utility = (main_owner_tokens / total_security) * voting_power;
-        let charity_utility = (charity_tokens / total_security) * voting_power;
-        let investor_utility = (investor_tokens / total_security) * voting_power;
+        let utility_parts = split_units(voting_power, &parts, INVESTOR_CHARITY_INDEX);
+        let (main_owner_utility, charity_utility, investor_utility) = (utility_parts[0], utility_parts[1], utility_parts[2]);
         
         // Добавляем utility токены
         if let Some(main_owner_holder) = self.token_holders.get_mut(&self.main_owner) {
@@ -1694,7 +2266,9 @@ impl Blockchain {
     
     // Распределение невостребованных токенов в конце года
     fn distribute_unclaimed_tokens_annually(&mut self) -> Result<AnnualDistribution, String> {
-        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let _op = self.begin_operation(WalOperation::DistributeUnclaimedTokensAnnually)?;
+
+        let current_timestamp = clock::now_secs();
         
         // Находим все невостребованные токены, которые истекли
         let mut unclaimed_to_distribute: Vec<UnclaimedTokensRecord> = self.unclaimed_tokens
@@ -1707,20 +2281,27 @@ impl Blockchain {
             return Err("Нет невостребованных токенов для распределения".to_string());
         }
         
-        let total_unclaimed: f64 = unclaimed_to_distribute.iter().map(|r| r.amount).sum();
+        let total_unclaimed: u64 = unclaimed_to_distribute.iter().map(|r| r.amount).sum();
         
         // Вычисляем общее количество токенов для пропорционального распределения
-        let total_security_tokens: f64 = self.token_holders.values().map(|h| h.security_tokens).sum();
+        let total_security_tokens: u64 = self.token_holders.values().map(|h| h.security_tokens).sum();
         
         let mut distributions = Vec::new();
         
+        // Делим пропорционально балансам в фиксированном порядке адресов; пыль получает фонд
+        let mut addresses: Vec<String> = self.token_holders.keys().cloned().collect();
+        addresses.sort();
+        let weights: Vec<u64> = addresses.iter().map(|a| self.token_holders[a].security_tokens).collect();
+        let charity_index = addresses.iter().position(|a| *a == self.charity_fund.fund_id).unwrap_or(0);
+        let amounts = split_units(total_unclaimed, &weights, charity_index);
+        
         // Собираем информацию о держателях токенов
         let mut holder_info = Vec::new();
-        for (address, holder) in &self.token_holders {
-            let holder_percentage = (holder.security_tokens / total_security_tokens) * 100.0;
-            let distribution_amount = (holder_percentage / 100.0) * total_unclaimed;
+        for (address, distribution_amount) in addresses.iter().zip(amounts) {
+            let holder = &self.token_holders[address];
+            let holder_percentage = percentage_of(holder.security_tokens, total_security_tokens);
             
-            if distribution_amount > 0.0 {
+            if distribution_amount > 0 {
                 let recipient_type = if holder.is_main_owner {
                     RecipientType::MainOwner
                 } else if holder.is_charity_fund {
@@ -1744,9 +2325,7 @@ impl Blockchain {
         
         // Добавляем токены держателям
         for (address, amount) in holder_info {
-            if let Some(holder_mut) = self.token_holders.get_mut(&address) {
-                holder_mut.add_security_tokens(amount);
-            }
+            self.mint(&address, amount)?;
         }
         
         // Создаем запись о годовом распределении
@@ -1793,7 +2372,7 @@ impl Blockchain {
     
     // Проверка истечения невостребованных токенов
     fn check_expired_unclaimed_tokens(&mut self) -> Vec<String> {
-        let current_timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let current_timestamp = clock::now_secs();
         let mut expired_checks = Vec::new();
         
         for record in &self.unclaimed_tokens {
@@ -1805,17 +2384,37 @@ impl Blockchain {
         expired_checks
     }
     
+    // Подключение единого реестра: раздел Blockchain в нем должен совпадать с балансами держателей
+    fn attach_ledger(&mut self, ledger: SharedLedger) -> Result<(), String> {
+        ledger.lock().unwrap().ledger().verify_source(self)?;
+        self.ledger = Some(ledger);
+        Ok(())
+    }
+
+    // Сверка учета: выпущенное количество токенов должно совпадать с суммой балансов до тетри
+    fn audit_ledger(&self) -> Result<(), String> {
+        self.audit().map_err(|e| format!("Security {}", e))?;
+
+        let utility_balances: u64 = self.token_holders.values().map(|h| h.utility_tokens).sum();
+        if utility_balances != self.utility_token.total_supply {
+            return Err(format!("Utility ledger mismatch: minted {} subunits, balances hold {}",
+                self.utility_token.total_supply, utility_balances));
+        }
+
+        Ok(())
+    }
+
     // Проверка безопасности сети
     fn check_network_security(&self) -> NetworkSecurityReport {
-        let total_security_tokens: f64 = self.token_holders.values().map(|v| v.security_tokens).sum();
+        let total_security_tokens: u64 = self.token_holders.values().map(|v| v.security_tokens).sum();
         let total_utility_tokens = self.utility_token.total_supply;
         
         let mut security_risks = Vec::new();
         let mut utility_risks = Vec::new();
         
         for (address, holder) in &self.token_holders {
-            let security_percentage = (holder.security_tokens / total_security_tokens) * 100.0;
-            let utility_percentage = (holder.utility_tokens / total_utility_tokens) * 100.0;
+            let security_percentage = percentage_of(holder.security_tokens, total_security_tokens);
+            let utility_percentage = percentage_of(holder.utility_tokens, total_utility_tokens);
             
             // Определяем максимальный лимит в зависимости от роли пользователя
             let max_percentage = if holder.is_main_owner {
@@ -1826,7 +2425,7 @@ impl Blockchain {
                 self.max_customer_percentage
             };
             
-            if security_percentage > max_percentage + 0.01 {
+            if exceeds_percentage(holder.security_tokens, total_security_tokens, max_percentage) {
                 security_risks.push(OwnershipRisk {
                     wallet: address.clone(),
                     percentage: security_percentage,
@@ -1834,7 +2433,7 @@ impl Blockchain {
                 });
             }
             
-            if utility_percentage > max_percentage + 0.01 {
+            if exceeds_percentage(holder.utility_tokens, total_utility_tokens, max_percentage) {
                 utility_risks.push(OwnershipRisk {
                     wallet: address.clone(),
                     percentage: utility_percentage,
@@ -1854,8 +2453,58 @@ impl Blockchain {
     }
 }
 
+// Security токены держателей в едином реестре; ключи нод — строковые id франшизных точек.
+// Utility токены для голосования в реестр не входят.
+impl TokenLedger for Blockchain {
+    fn namespace(&self) -> &'static str {
+        "blockchain"
+    }
+
+    fn balance(&self, address: &str) -> u64 {
+        self.token_holders.get(address).map(|h| h.security_tokens).unwrap_or(0)
+    }
+
+    fn total_supply(&self) -> u64 {
+        self.security_supply
+    }
+
+    fn balances(&self) -> BTreeMap<String, u64> {
+        self.token_holders.iter().map(|(address, holder)| (address.clone(), holder.security_tokens)).collect()
+    }
+
+    fn franchise_nodes(&self) -> BTreeMap<String, String> {
+        self.franchise_nodes.iter().map(|(node_id, owner)| (node_id.clone(), owner.clone())).collect()
+    }
+
+    fn mint(&mut self, address: &str, units: u64) -> Result<(), String> {
+        if let Some(ledger) = &self.ledger {
+            ledger.lock().unwrap().credit(self.namespace(), address, units)?;
+        }
+        self.token_holders.entry(address.to_string())
+            .or_insert_with(|| TokenHolder::new(address.to_string(), false))
+            .add_security_tokens(units);
+        self.security_supply += units;
+        Ok(())
+    }
+
+    fn burn(&mut self, address: &str, units: u64) -> Result<u64, String> {
+        let burned = units.min(self.balance(address));
+        if burned == 0 {
+            return Ok(0);
+        }
+        if let Some(ledger) = &self.ledger {
+            ledger.lock().unwrap().debit(self.namespace(), address, burned)?;
+        }
+        if let Some(holder) = self.token_holders.get_mut(address) {
+            holder.security_tokens -= burned;
+        }
+        self.security_supply -= burned;
+        Ok(burned)
+    }
+}
+
 // Block structure (simplified for this example)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Block {
     index: u32,
     transactions: Vec<Transaction>,
@@ -1863,16 +2512,87 @@ struct Block {
     hash: String,
     timestamp: u64,
     validator: String,
-    stake_used: f64,
+    stake_used: u64,
+    nonce: u64, // Только у блоков, добытых перебором (до PoS); у PoS блоков 0
+    // Корень дерева Меркла листьев транзакций. Пустой у блоков, записанных до его появления:
+    // их хеш считается по старому правилу, из id транзакций.
+    #[serde(default)]
+    merkle_root: String,
+    #[serde(default)]
+    slot: Option<u64>, // Слот PoS блока; None у генезиса и блоков, добытых перебором nonce
+    #[serde(default)]
+    stakes_root: String, // Корень долей (SlotStakes), из которых выбран лидер; входит в хеш PoS блока
+    #[serde(default)]
+    validator_key: String, // Публичный ключ лидера слота; адрес validator выводится из него
+    #[serde(default)]
+    signature: String, // Подпись лидера над хешем блока
+}
+
+// Доли держателей на момент производства PoS блока и действовавший тогда min_stake
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SlotStakes {
+    min_stake: u64,
+    stakes: Vec<(String, u64)>,
+}
+
+impl SlotStakes {
+    fn root(&self) -> String {
+        let bytes = serde_json::to_vec(self).expect("slot stakes are always serializable");
+        hex::encode(Sha256::digest(&bytes))
+    }
+}
+
+// Заголовок блока без транзакций: по нему кошелек проверяет доказательство включения
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BlockHeader {
+    index: u32,
+    prev_hash: String,
+    hash: String,
+    timestamp: u64,
+    validator: String,
+    stake_used: u64,
     nonce: u64,
+    merkle_root: String,
+    #[serde(default)]
+    slot: Option<u64>,
+    #[serde(default)]
+    stakes_root: String,
+}
+
+impl BlockHeader {
+    fn calculate_hash(&self) -> String {
+        let mut data = format!(
+            "{}{}{}{}{}{}{}",
+            self.index,
+            self.merkle_root,
+            self.prev_hash,
+            self.timestamp,
+            self.validator,
+            self.stake_used,
+            self.nonce
+        );
+        // Слот и корень долей дописываются только у PoS блоков, хеши блоков до PoS не меняются
+        if let Some(slot) = self.slot {
+            data.push_str(&format!("|{}|{}", slot, self.stakes_root));
+        }
+        hex::encode(Sha256::digest(data.as_bytes()))
+    }
+}
+
+// Доказательство включения транзакции для GetInclusionProof.
+// leaf — байты листа (JSON TransactionLeaf) ровно в том виде, в котором они хешируются.
+// Кошелек проверяет без доступа к цепи: хеш заголовка сходится, а лист через proof ведет к merkle_root.
+// Что сам заголовок принадлежит цепи, кошелек сверяет по хешу блока из доверенного источника.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct InclusionProof {
+    header: BlockHeader,
+    leaf: String,
+    proof: MerkleProof,
 }
 
 impl Block {
-    fn new(index: u32, transactions: Vec<Transaction>, prev_hash: String, validator: String, stake_used: f64) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    fn new(index: u32, transactions: Vec<Transaction>, prev_hash: String, validator: String, stake_used: u64) -> Self {
+        let timestamp = clock::now_secs();
         
         let mut block = Block {
             index,
@@ -1883,13 +2603,68 @@ impl Block {
             validator,
             stake_used,
             nonce: 0,
+            merkle_root: String::new(),
+            slot: None,
+            stakes_root: String::new(),
+            validator_key: String::new(),
+            signature: String::new(),
         };
         
-        block.hash = block.calculate_hash();
+        block.update_merkle_root();
         block
     }
 
+    // Байты, которые подписывает лидер слота: хеш блока покрывает все поля заголовка
+    fn signing_bytes(&self) -> Vec<u8> {
+        format!("pos-block|{}", self.hash).into_bytes()
+    }
+
+    fn sign(&mut self, keypair: &Keypair) {
+        self.validator_key = keypair.public_key_hex();
+        self.signature = keypair.sign(&self.signing_bytes());
+    }
+
+    fn calculate_merkle_root(&self) -> String {
+        let leaves: Vec<Vec<u8>> = self.transactions.iter().map(Transaction::leaf_bytes).collect();
+        merkle::root(&leaves)
+    }
+
+    // Пересчет корня и хеша после изменения списка транзакций
+    fn update_merkle_root(&mut self) {
+        self.merkle_root = self.calculate_merkle_root();
+        self.hash = self.calculate_hash();
+    }
+
+    fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            prev_hash: self.prev_hash.clone(),
+            hash: self.hash.clone(),
+            timestamp: self.timestamp,
+            validator: self.validator.clone(),
+            stake_used: self.stake_used,
+            nonce: self.nonce,
+            merkle_root: self.merkle_root.clone(),
+            slot: self.slot,
+            stakes_root: self.stakes_root.clone(),
+        }
+    }
+
+    fn inclusion_proof(&self, tx_id: &str) -> Option<InclusionProof> {
+        let index = self.transactions.iter().position(|t| t.transaction_id == tx_id)?;
+        let leaves: Vec<Vec<u8>> = self.transactions.iter().map(Transaction::leaf_bytes).collect();
+        Some(InclusionProof {
+            header: self.header(),
+            leaf: String::from_utf8(leaves[index].clone()).expect("JSON is always valid UTF-8"),
+            proof: merkle::proof(&leaves, index)?,
+        })
+    }
+
     fn calculate_hash(&self) -> String {
+        if !self.merkle_root.is_empty() {
+            return self.header().calculate_hash();
+        }
+
         let mut hasher = Sha256::new();
         let tx_concat: String = self.transactions.iter().map(|t| t.transaction_id.as_str()).collect();
         let data = format!(
@@ -1905,18 +2680,10 @@ impl Block {
         hasher.update(data.as_bytes());
         hex::encode(hasher.finalize())
     }
-
-    fn mine(&mut self, difficulty: usize) {
-        let target = "0".repeat(difficulty);
-        while !self.hash.starts_with(&target) {
-            self.nonce += 1;
-            self.hash = self.calculate_hash();
-        }
-    }
 }
 
 // API Request/Response structures
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 enum ApiRequest {
     GetMenu,
     GetMenuItem { id: String },
@@ -1933,6 +2700,7 @@ enum ApiRequest {
     GetOrderStatus { order_id: String },
     GetWalletBalance { wallet: String },
     GetBlockchainHistory { limit: Option<u32> },
+    GetInclusionProof { tx_id: String },
     GetVotingHistory,
     VoteOnMenuItem { 
         voter_wallet: String, 
@@ -1949,11 +2717,16 @@ enum ApiRequest {
         ingredients: Vec<Ingredient>,
         suggested_by: String,
     },
-    MakeItemAvailableForVoting { menu_item_id: String },
+    MakeItemAvailableForVoting {
+        menu_item_id: String,
+        #[serde(default)]
+        strategy: VotingStrategy,
+    },
     ConfirmOrder { order_id: String },
     RegisterUserWithPhone { phone_number: String, wallet_address: String },
     VerifyPhoneNumber { phone_number: String, verification_code: String },
-    TransferBalanceFromCheck { check_id: String, to_phone_number: String },
+    // Код активации с чека подтверждает, что отправитель держит чек в руках
+    TransferBalanceFromCheck { check_id: String, activation_code: String, to_phone_number: String },
     GetBalanceTransferHistory { limit: Option<u32> },
     GetNetworkSecurityReport,
     AddFranchiseNode { node_id: String, franchise_owner: String },
@@ -1964,6 +2737,70 @@ enum ApiRequest {
     GetUnclaimedTokens { limit: Option<u32> },
     GetAnnualDistributions { limit: Option<u32> },
     CheckExpiredUnclaimedTokens,
+    GetProposals,
+    SubmitProposal {
+        proposer: String,
+        description: String,
+        change: ProposalChange,
+        #[serde(default)]
+        strategy: VotingStrategy,
+    },
+    VoteOnProposal { voter_wallet: String, proposal_id: String, vote_for: bool },
+    FinalizeProposal { proposal_id: String },
+    DelegateVotingPower { wallet: String, delegate: Option<String> },
+    // Запрос от имени кошелька: payload — JSON вложенного ApiRequest,
+    // подпись ключом кошелька над signed_request_bytes(nonce, payload)
+    Signed { payload: String, public_key: String, nonce: u64, signature: String },
+}
+
+// Байты, которые подписывает кошелек
+fn signed_request_bytes(nonce: u64, payload: &str) -> Vec<u8> {
+    format!("hotpot-api|{}|{}", nonce, payload).into_bytes()
+}
+
+// Кто должен подписать запрос
+#[derive(Debug, Clone, PartialEq)]
+enum RequestAuthority {
+    Public,         // Чтение и действия, которые подтверждаются своим кодом
+    Wallet(String), // Ключ кошелька, от имени которого действует запрос
+    Operator,       // Ключ кошелька владельца сети (main_owner)
+}
+
+impl ApiRequest {
+    // Изменяющие запросы принимаются только с подписью кошелька или оператора.
+    // Если кошелек определить нельзя, запрос отклоняется.
+    fn authority(&self, blockchain: &Blockchain) -> Result<RequestAuthority, String> {
+        let authority = match self {
+            ApiRequest::CreateOrder { customer_wallet, .. } => RequestAuthority::Wallet(customer_wallet.clone()),
+            ApiRequest::CancelOrder { customer_wallet, .. } => RequestAuthority::Wallet(customer_wallet.clone()),
+            ApiRequest::VoteOnMenuItem { voter_wallet, .. } => RequestAuthority::Wallet(voter_wallet.clone()),
+            ApiRequest::AddMenuItem { suggested_by, .. } => RequestAuthority::Wallet(suggested_by.clone()),
+            ApiRequest::SubmitProposal { proposer, .. } => RequestAuthority::Wallet(proposer.clone()),
+            ApiRequest::VoteOnProposal { voter_wallet, .. } => RequestAuthority::Wallet(voter_wallet.clone()),
+            ApiRequest::DelegateVotingPower { wallet, .. } => RequestAuthority::Wallet(wallet.clone()),
+            ApiRequest::RegisterUserWithPhone { wallet_address, .. } => RequestAuthority::Wallet(wallet_address.clone()),
+            // Токены с чека уходят на кошелек, привязанный к телефону
+            ApiRequest::TransferBalanceFromCheck { check_id, activation_code, to_phone_number } => {
+                let check = blockchain.token_holders.values()
+                    .flat_map(|holder| &holder.checks)
+                    .find(|check| &check.check_id == check_id)
+                    .ok_or("Check not found")?;
+                if &check.activation_code != activation_code {
+                    return Err("Invalid activation code".to_string());
+                }
+                let user = blockchain.authorized_users.get(to_phone_number)
+                    .ok_or("Phone number not authorized")?;
+                RequestAuthority::Wallet(user.wallet_address.clone())
+            }
+            ApiRequest::MakeItemAvailableForVoting { .. }
+            | ApiRequest::ConfirmOrder { .. }
+            | ApiRequest::AddFranchiseNode { .. }
+            | ApiRequest::EmitTokensForInvestors { .. }
+            | ApiRequest::DistributeUnclaimedTokensAnnually => RequestAuthority::Operator,
+            _ => RequestAuthority::Public,
+        };
+        Ok(authority)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -1975,14 +2812,16 @@ enum ApiResponse {
     OrderStatus { order: Order },
     WalletBalance { 
         wallet: String, 
-        security_tokens: f64, 
-        utility_tokens: f64 
+        security_tokens: u64, // в subunits
+        utility_tokens: u64 // в subunits
     },
     BlockchainHistory { 
         orders: Vec<BlockchainOrderRecord> 
     },
+    InclusionProof { proof: InclusionProof },
     VotingHistory { 
-        votes: Vec<VotingRecord> 
+        votes: Vec<VotingRecord>,
+        tallies: Vec<VotingTally>,
     },
     VoteResult { success: bool },
     MenuItemAdded { success: bool },
@@ -2001,6 +2840,10 @@ enum ApiResponse {
     UnclaimedTokens { tokens: Vec<UnclaimedTokensRecord> },
     AnnualDistributions { distributions: Vec<AnnualDistribution> },
     ExpiredUnclaimedTokens { expired_checks: Vec<String> },
+    Proposals { proposals: Vec<Proposal> },
+    ProposalSubmitted { proposal_id: String },
+    ProposalFinalized { status: ProposalStatus },
+    VotingPowerDelegated { success: bool },
     Error { message: String },
 }
 
@@ -2009,8 +2852,8 @@ enum ApiResponse {
 struct BlockchainOrderRecord {
     order_id: String,
     customer_wallet: String,
-    total_amount: f64,
-    tokens_issued: f64,
+    total_amount: u64,
+    tokens_issued: u64,
     timestamp: u64,
     status: String,
 }
@@ -2020,9 +2863,11 @@ struct VotingRecord {
     voter_wallet: String,
     menu_item_id: String,
     menu_item_name: String,
-    vote_weight: f64,
+    vote_weight: u64,
     vote_for: bool,
     timestamp: u64,
+    #[serde(default)]
+    strategy: VotingStrategy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -2032,8 +2877,8 @@ struct BalanceTransferRecord {
     from_wallet: String,
     to_wallet: String,
     to_phone: String,
-    security_tokens_transferred: f64,
-    utility_tokens_transferred: f64,
+    security_tokens_transferred: u64,
+    utility_tokens_transferred: u64,
     timestamp: u64,
     status: TransferStatus,
 }
@@ -2047,8 +2892,8 @@ enum TransferStatus {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct NetworkSecurityReport {
-    total_security_tokens: f64,
-    total_utility_tokens: f64,
+    total_security_tokens: u64,
+    total_utility_tokens: u64,
     max_owner_percentage: f64,
     security_risks: Vec<OwnershipRisk>,
     utility_risks: Vec<OwnershipRisk>,
@@ -2077,7 +2922,7 @@ struct MonitoringAlert {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct UnclaimedTokensRecord {
     check_id: String,
-    amount: f64,
+    amount: u64,
     created_timestamp: u64,
     expiry_timestamp: u64,
     is_distributed: bool,
@@ -2087,7 +2932,7 @@ struct UnclaimedTokensRecord {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct AnnualDistribution {
     year: u32,
-    total_unclaimed_tokens: f64,
+    total_unclaimed_tokens: u64,
     distribution_timestamp: u64,
     distributions: Vec<TokenDistribution>,
 }
@@ -2095,7 +2940,7 @@ struct AnnualDistribution {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TokenDistribution {
     recipient_address: String,
-    amount: f64,
+    amount: u64,
     percentage: f64,
     recipient_type: RecipientType,
 }
@@ -2139,6 +2984,8 @@ struct SmartContract {
     participants: Vec<String>,
     conditions: ContractConditions,
     execution_history: Vec<ContractExecution>,
+    #[serde(default)]
+    subject: Option<String>, // Позиция меню голосующего контракта
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -2159,7 +3006,7 @@ enum ContractStatus {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ContractConditions {
-    min_tokens_required: f64,
+    min_tokens_required: u64,
     expiration_time: Option<u64>,
     required_participants: Vec<String>,
     auto_execute: bool,
@@ -2171,16 +3018,13 @@ struct ContractExecution {
     action: String,
     timestamp: u64,
     result: String,
-    tokens_used: f64,
+    tokens_used: u64,
 }
 
 #[cfg_attr(test, allow(dead_code))]
 impl SmartContract {
     fn new(contract_type: ContractType, creator: String, conditions: ContractConditions) -> Self {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let timestamp = clock::now_secs();
         
         let contract_id = Self::generate_contract_id(&creator, timestamp);
         
@@ -2193,6 +3037,7 @@ impl SmartContract {
             participants: vec![creator],
             conditions,
             execution_history: vec![],
+            subject: None,
         }
     }
 
@@ -2203,7 +3048,7 @@ impl SmartContract {
         format!("CONTRACT_{}", hex::encode(&hasher.finalize()[..8]))
     }
 
-    fn execute(&mut self, executor: String, action: String, tokens_used: f64) -> Result<String, String> {
+    fn execute(&mut self, executor: String, action: String, tokens_used: u64) -> Result<String, String> {
         if self.status != ContractStatus::Active {
             return Err("Contract is not active".to_string());
         }
@@ -2218,10 +3063,7 @@ impl SmartContract {
         let execution = ContractExecution {
             executor: executor.clone(),
             action,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp: clock::now_secs(),
             result: result.clone(),
             tokens_used,
         };
@@ -2293,7 +3135,8 @@ impl ApiServer {
                 let api_result = match serde_json::from_str::<ApiRequest>(body) {
                     Ok(req) => Self::process_request(req, blockchain),
                     Err(_) => {
-                        // Fallback compatibility: accept {"Variant": {...}} and unit variants as {"Variant": {}}
+                        // Fallback compatibility: accept unit variants as {"Variant": {}}.
+                        // Изменяющие запросы разбираются только строго: они все равно требуют обертки Signed
                         match serde_json::from_str::<serde_json::Value>(body) {
                             Ok(val) => {
                                 if let Some(obj) = val.as_object() {
@@ -2304,60 +3147,8 @@ impl ApiServer {
                                         Self::process_request(ApiRequest::GetBlockchainHistory { limit }, blockchain)
                                     } else if obj.contains_key("GetVotingHistory") {
                                         Self::process_request(ApiRequest::GetVotingHistory, blockchain)
-                                    } else if let Some(params) = obj.get("MakeItemAvailableForVoting") {
-                                        if let Some(menu_item_id) = params.get("menu_item_id").and_then(|v| v.as_str()) {
-                                            Self::process_request(ApiRequest::MakeItemAvailableForVoting { menu_item_id: menu_item_id.to_string() }, blockchain)
-                                        } else {
-                                            ApiResponse::Error { message: "Invalid MakeItemAvailableForVoting payload".to_string() }
-                                        }
-                                    } else if let Some(params) = obj.get("ConfirmOrder") {
-                                        if let Some(order_id) = params.get("order_id").and_then(|v| v.as_str()) {
-                                            Self::process_request(ApiRequest::ConfirmOrder { order_id: order_id.to_string() }, blockchain)
-                                        } else {
-                                            ApiResponse::Error { message: "Invalid ConfirmOrder payload".to_string() }
-                                        }
-                                    } else if let Some(params) = obj.get("CancelOrder") {
-                                        let order_id = params.get("order_id").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let reason = params.get("reason").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let customer_wallet = params.get("customer_wallet").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        if order_id.is_empty() || customer_wallet.is_empty() {
-                                            ApiResponse::Error { message: "Invalid CancelOrder payload".to_string() }
-                                        } else {
-                                            Self::process_request(ApiRequest::CancelOrder { order_id, reason, customer_wallet }, blockchain)
-                                        }
-                                    } else if let Some(params) = obj.get("AddMenuItem") {
-                                        // Map incoming ingredient fields amount -> amount_grams
-                                        let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let description = params.get("description").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let price = params.get("price").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                        let availability = params.get("availability").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
-                                        let priority_rank = params.get("priority_rank").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
-                                        let cooking_time_minutes = params.get("cooking_time_minutes").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
-                                        let suggested_by = params.get("suggested_by").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let ingredients = params.get("ingredients").and_then(|v| v.as_array()).map(|arr| {
-                                            arr.iter().filter_map(|ing| {
-                                                let name = ing.get("name").and_then(|v| v.as_str())?.to_string();
-                                                let amount = ing.get("amount").or_else(|| ing.get("amount_grams")).and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                                let calories = ing.get("calories").and_then(|v| v.as_f64()).unwrap_or(0.0);
-                                                Some(Ingredient { name, amount_grams: amount, calories })
-                                            }).collect::<Vec<_>>()
-                                        }).unwrap_or_default();
-
-                                        Self::process_request(
-                                            ApiRequest::AddMenuItem {
-                                                name, description, price, availability, priority_rank, cooking_time_minutes, ingredients, suggested_by
-                                            },
-                                            blockchain,
-                                        )
-                                    } else if let Some(params) = obj.get("VoteOnMenuItem") {
-                                        let voter_wallet = params.get("voter_wallet").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let menu_item_id = params.get("menu_item_id").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                        let vote_for = params.get("vote_for").and_then(|v| v.as_bool()).unwrap_or(true);
-                                        if voter_wallet.is_empty() || menu_item_id.is_empty() {
-                                            ApiResponse::Error { message: "Invalid VoteOnMenuItem payload".to_string() }
-                                        } else {
-                                            Self::process_request(ApiRequest::VoteOnMenuItem { voter_wallet, menu_item_id, vote_for }, blockchain)
-                                        }
+                                    } else if obj.contains_key("GetProposals") {
+                                        Self::process_request(ApiRequest::GetProposals, blockchain)
                                     } else {
                                         ApiResponse::Error { message: "Unknown API request".to_string() }
                                     }
@@ -2386,10 +3177,54 @@ impl ApiServer {
         }
     }
 
+    // Проверка подписи и nonce; возвращает вложенный запрос
+    fn authenticate(blockchain: &mut Blockchain, payload: &str, public_key: &str, nonce: u64, signature: &str) -> Result<ApiRequest, String> {
+        let signer = crypto::wallet_address(public_key)?;
+        if !crypto::verify(public_key, &signed_request_bytes(nonce, payload), signature) {
+            return Err("Invalid request signature".to_string());
+        }
+
+        let request: ApiRequest = serde_json::from_str(payload)
+            .map_err(|e| format!("Invalid signed payload: {}", e))?;
+        match request.authority(blockchain)? {
+            RequestAuthority::Wallet(wallet) if wallet != signer => {
+                return Err(format!("Request on behalf of wallet {} is signed by {}", wallet, signer));
+            }
+            RequestAuthority::Operator if blockchain.main_owner != signer => {
+                return Err(format!("Operator request is signed by {}", signer));
+            }
+            _ => {}
+        }
+
+        // Nonce расходуется до выполнения: повтор того же запроса будет отклонен, даже если он завершился ошибкой
+        blockchain.use_wallet_nonce(signer, nonce)?;
+        Ok(request)
+    }
+
     fn process_request(request: ApiRequest, blockchain: Arc<Mutex<Blockchain>>) -> ApiResponse {
         let mut blockchain_guard = blockchain.lock().unwrap();
         
-        match request {
+        // Подпись проверяется до любого изменения состояния
+        let request = match request {
+            ApiRequest::Signed { payload, public_key, nonce, signature } => {
+                match Self::authenticate(&mut blockchain_guard, &payload, &public_key, nonce, &signature) {
+                    Ok(request) => request,
+                    Err(e) => return ApiResponse::Error { message: e },
+                }
+            }
+            request => match request.authority(&blockchain_guard) {
+                Ok(RequestAuthority::Public) => request,
+                Ok(RequestAuthority::Wallet(wallet)) => {
+                    return ApiResponse::Error { message: format!("Request on behalf of wallet {} must be signed", wallet) };
+                }
+                Ok(RequestAuthority::Operator) => {
+                    return ApiResponse::Error { message: "Operator request must be signed by the operator wallet".to_string() };
+                }
+                Err(e) => return ApiResponse::Error { message: e },
+            },
+        };
+        
+        let response = match request {
             ApiRequest::GetMenu => {
                 let items = blockchain_guard.menu_items.clone();
                 ApiResponse::Menu { items }
@@ -2444,8 +3279,8 @@ impl ApiServer {
                 } else {
                     ApiResponse::WalletBalance {
                         wallet,
-                        security_tokens: 0.0,
-                        utility_tokens: 0.0,
+                        security_tokens: 0,
+                        utility_tokens: 0,
                     }
                 }
             }
@@ -2454,10 +3289,18 @@ impl ApiServer {
                 let orders = blockchain_guard.get_blockchain_history(limit);
                 ApiResponse::BlockchainHistory { orders }
             }
+
+            ApiRequest::GetInclusionProof { tx_id } => {
+                match blockchain_guard.inclusion_proof(&tx_id) {
+                    Ok(proof) => ApiResponse::InclusionProof { proof },
+                    Err(e) => ApiResponse::Error { message: e },
+                }
+            }
             
             ApiRequest::GetVotingHistory => {
                 let votes = blockchain_guard.get_voting_history();
-                ApiResponse::VotingHistory { votes }
+                let tallies = blockchain_guard.voting_tallies();
+                ApiResponse::VotingHistory { votes, tallies }
             }
             
             ApiRequest::VoteOnMenuItem { voter_wallet, menu_item_id, vote_for } => {
@@ -2467,6 +3310,38 @@ impl ApiServer {
                 }
             }
             
+            ApiRequest::GetProposals => {
+                ApiResponse::Proposals { proposals: blockchain_guard.proposals.clone() }
+            }
+
+            ApiRequest::SubmitProposal { proposer, description, change, strategy } => {
+                match blockchain_guard.submit_proposal(proposer, description, change, strategy) {
+                    Ok(proposal_id) => ApiResponse::ProposalSubmitted { proposal_id },
+                    Err(e) => ApiResponse::Error { message: e },
+                }
+            }
+
+            ApiRequest::VoteOnProposal { voter_wallet, proposal_id, vote_for } => {
+                match blockchain_guard.vote_on_proposal(voter_wallet, proposal_id, vote_for) {
+                    Ok(()) => ApiResponse::VoteResult { success: true },
+                    Err(e) => ApiResponse::Error { message: e },
+                }
+            }
+
+            ApiRequest::DelegateVotingPower { wallet, delegate } => {
+                match blockchain_guard.delegate_voting_power(wallet, delegate) {
+                    Ok(()) => ApiResponse::VotingPowerDelegated { success: true },
+                    Err(e) => ApiResponse::Error { message: e },
+                }
+            }
+
+            ApiRequest::FinalizeProposal { proposal_id } => {
+                match blockchain_guard.finalize_proposal(proposal_id) {
+                    Ok(status) => ApiResponse::ProposalFinalized { status },
+                    Err(e) => ApiResponse::Error { message: e },
+                }
+            }
+            
             ApiRequest::AddMenuItem { name, description, price, availability, priority_rank, cooking_time_minutes, ingredients, suggested_by } => {
                 match blockchain_guard.add_menu_item_with_details(
                     name, description, price, availability, priority_rank, 
@@ -2477,8 +3352,8 @@ impl ApiServer {
                 }
             }
             
-            ApiRequest::MakeItemAvailableForVoting { menu_item_id } => {
-                match blockchain_guard.make_menu_item_available_for_voting(menu_item_id) {
+            ApiRequest::MakeItemAvailableForVoting { menu_item_id, strategy } => {
+                match blockchain_guard.make_menu_item_available_for_voting(menu_item_id, strategy) {
                     Ok(()) => ApiResponse::ItemAvailableForVoting { success: true },
                     Err(e) => ApiResponse::Error { message: e },
                 }
@@ -2505,7 +3380,7 @@ impl ApiServer {
                 }
             }
             
-            ApiRequest::TransferBalanceFromCheck { check_id, to_phone_number } => {
+            ApiRequest::TransferBalanceFromCheck { check_id, to_phone_number, .. } => {
                 match blockchain_guard.transfer_balance_from_check(check_id, to_phone_number) {
                     Ok(transfer_id) => ApiResponse::BalanceTransferred { transfer_id },
                     Err(e) => ApiResponse::Error { message: e },
@@ -2567,7 +3442,16 @@ impl ApiServer {
                 let expired_checks = blockchain_guard.check_expired_unclaimed_tokens();
                 ApiResponse::ExpiredUnclaimedTokens { expired_checks }
             }
+            
+            ApiRequest::Signed { .. } => {
+                ApiResponse::Error { message: "Nested signed requests are not allowed".to_string() }
+            }
+        };
+
+        if let Err(e) = blockchain_guard.checkpoint_if_due() {
+            eprintln!("Error writing snapshot: {}", e);
         }
+        response
     }
 }
 
@@ -2594,8 +3478,8 @@ impl UI {
             if let Some(holder) = self.blockchain.token_holders.get(user) {
                 println!("Logged in as: {}", user);
                 println!("Role: {:?}", holder.role);
-                println!("Security Tokens: {:.2}", holder.security_tokens);
-                println!("Utility Tokens: {:.2}", holder.utility_tokens);
+                println!("Security Tokens: {}", format_units(holder.security_tokens));
+                println!("Utility Tokens: {}", format_units(holder.utility_tokens));
                 println!("Checks: {}", holder.checks.len());
                 println!("Blockchain Accounts: {}", holder.blockchain_accounts.len());
             }
@@ -2685,7 +3569,7 @@ impl UI {
         println!("3. Vote on Menu Items");
         println!("4. View All Checks");
         println!("5. View Blockchain Status");
-        println!("6. Mine Block");
+        println!("6. Produce Block");
         println!("7. Back to Main Menu");
     }
 
@@ -2764,7 +3648,7 @@ impl UI {
             println!("{}. {} - {}", i + 1, item.name, item.description);
             println!("   Status: {}", status_str);
             println!("   Suggested by: {}", item.suggested_by);
-            println!("   Votes: For {:.2}, Against {:.2}", item.votes_for, item.votes_against);
+            println!("   Votes: For {}, Against {}", format_units(item.votes_for), format_units(item.votes_against));
             println!();
         }
     }
@@ -2892,7 +3776,7 @@ impl UI {
         // Simulate selecting first item
         if let Some(item) = proposed_items.first() {
             println!("Making {} available for voting...", item.name);
-            match self.blockchain.make_menu_item_available_for_voting(item.id.clone()) {
+            match self.blockchain.make_menu_item_available_for_voting(item.id.clone(), VotingStrategy::Linear) {
                 Ok(()) => println!("✅ Item is now available for voting!"),
                 Err(e) => println!("❌ Error: {}", e),
             }
@@ -2930,7 +3814,7 @@ impl UI {
             
             println!("{}. Order ID: {}", i + 1, order.id);
             println!("   Customer: {}", order.customer_wallet);
-            println!("   Amount: ${:.2}", order.total_amount);
+            println!("   Amount: ${}", format_units(order.total_amount));
             println!("   Status: {}", status_str);
             println!("   Delivery Time: {} minutes", order.delivery_time_minutes);
             println!("   Items: {} items", order.items.len());
@@ -2967,8 +3851,8 @@ impl UI {
         }
         
         for (i, order) in pending_orders.iter().enumerate() {
-            println!("{}. Order ID: {} - Customer: {} - Amount: ${:.2}", 
-                i + 1, order.id, order.customer_wallet, order.total_amount);
+            println!("{}. Order ID: {} - Customer: {} - Amount: ${}", 
+                i + 1, order.id, order.customer_wallet, format_units(order.total_amount));
         }
         
         // Simulate confirming first order
@@ -2984,7 +3868,7 @@ impl UI {
     fn vote_on_menu_items(&mut self) {
         if let Some(user) = &self.current_user {
             if let Some(holder) = self.blockchain.token_holders.get(user) {
-                if holder.utility_tokens <= 0.0 {
+                if holder.utility_tokens == 0 {
                     println!("❌ You don't have any voting power");
                     return;
                 }
@@ -3034,7 +3918,7 @@ impl UI {
                 
                 for (i, check) in holder.checks.iter().enumerate() {
                     println!("{}. Check ID: {}", i + 1, check.check_id);
-                    println!("   Amount: ${:.2}", check.amount);
+                    println!("   Amount: ${}", format_units(check.amount));
                     println!("   Food Items: {}", check.food_items.join(", "));
                     println!("   Activation Code: {}", check.activation_code);
                     println!("   Status: {}", if check.is_activated { "Activated" } else { "Not Activated" });
@@ -3124,25 +4008,26 @@ impl UI {
         println!("\n🔗 Blockchain Status");
         println!("===================");
         println!("Chain valid: {}", self.blockchain.is_chain_valid());
+        println!("Ledger reconciled: {}", self.blockchain.audit_ledger().is_ok());
         println!("Total blocks: {}", self.blockchain.chain.len());
-        println!("Total security tokens: {:.2}", 
-            self.blockchain.token_holders.values().map(|h| h.security_tokens).sum::<f64>());
-        println!("Total utility tokens: {:.2}", self.blockchain.utility_token.total_supply);
+        println!("Total security tokens: {}", 
+            format_units(self.blockchain.token_holders.values().map(|h| h.security_tokens).sum()));
+        println!("Total utility tokens: {}", format_units(self.blockchain.utility_token.total_supply));
         println!("Main owner: {}", self.blockchain.main_owner);
         
         println!("\nToken Holders:");
         for (address, holder) in &self.blockchain.token_holders {
-            println!("  {}: {:?} - Security: {:.2}, Utility: {:.2}", 
-                address, holder.role, holder.security_tokens, holder.utility_tokens);
+            println!("  {}: {:?} - Security: {}, Utility: {}", 
+                address, holder.role, format_units(holder.security_tokens), format_units(holder.utility_tokens));
         }
     }
 
-    fn mine_block(&mut self) {
-        println!("\n⛏️ Mining Block");
-        println!("==============");
+    fn produce_block(&mut self) {
+        println!("\n🧱 Producing Block");
+        println!("=================");
         
-        match self.blockchain.mine_block() {
-            Ok(()) => println!("✅ Block mined successfully!"),
+        match self.blockchain.produce_block() {
+            Ok(()) => println!("✅ Block produced successfully!"),
             Err(e) => println!("❌ Error: {}", e),
         }
     }
@@ -3171,7 +4056,7 @@ impl UI {
                 14 => {
                     println!("🌐 Starting API Server...");
                     let blockchain_arc = Arc::new(Mutex::new(self.blockchain.clone()));
-                    let api_server = SimpleServer::new(blockchain_arc, 3000);
+                    let api_server = ApiServer::new(blockchain_arc, 3000);
                     api_server.start();
                 },
                 0 => {
@@ -3200,10 +4085,477 @@ impl UI {
 fn main() {
     println!("🍔 Fast Food Truck Blockchain with Security Tokens & Voting 🍔\n");
     
-    // Initialize blockchain with main owner
-    let main_owner = "MainOwner_Alice".to_string();
-    let mut blockchain = Blockchain::new(main_owner.clone());
+    // Optional: verify a wallet through a full node as a light client when LIGHT_CLIENT=1
+    if env::var("LIGHT_CLIENT").map(|v| v == "1").unwrap_or(false) {
+        run_light_client();
+        return;
+    }
     
+    let tokenomics = load_tokenomics();
+    let data_dir = env::var("DATA_DIR").unwrap_or_else(|_| "node_data".to_string());
+    let data_dir = Path::new(&data_dir);
+
+    // Восстановление состояния из зашифрованного бэкапа (RESTORE_BACKUP=<cid манифеста>)
+    if let Ok(cid) = env::var("RESTORE_BACKUP") {
+        run_backup_restore(&cid, data_dir);
+        return;
+    }
+
+    // Кошелек владельца сети, которым подписываются запросы оператора
+    let main_owner = operator_wallet();
+
+    // Единый реестр после миграции: Blockchain и франшизная сеть проводят через него эмиссию и списания
+    let ledger = open_ledger(data_dir);
+
+    // Восстанавливаем состояние с диска; демо-данные создаются только при первом запуске
+    let mut blockchain = match storage::recover(data_dir, storage::DEFAULT_SNAPSHOT_INTERVAL, ledger.clone()) {
+        Ok(Some(mut blockchain)) => {
+            println!("💾 Restored blockchain state from {}", data_dir.display());
+            add_validator_keys(&mut blockchain);
+            if blockchain.main_owner != main_owner {
+                eprintln!("❌ Stored state belongs to operator {}, OPERATOR_PUBLIC_KEY gives {}", blockchain.main_owner, main_owner);
+                std::process::exit(1);
+            }
+            // Политики из конфигурации, которые еще не вступили в силу и не запланированы
+            for policy in tokenomics.blockchain.policies() {
+                if policy.effective_from_height > blockchain.chain.len() as u64 && !blockchain.tokenomics.policies().contains(policy) {
+                    if let Err(e) = blockchain.schedule_tokenomics(policy.clone()) {
+                        eprintln!("⚠️  Tokenomics policy from height {} not scheduled: {}", policy.effective_from_height, e);
+                    }
+                }
+            }
+            blockchain
+        }
+        Ok(None) => {
+            let mut genesis = Blockchain::new(main_owner.clone()).with_tokenomics(tokenomics.blockchain.clone());
+            add_validator_keys(&mut genesis);
+            let mut blockchain = storage::initialize(data_dir, storage::DEFAULT_SNAPSHOT_INTERVAL, genesis)
+                .unwrap_or_else(|e| {
+                    eprintln!("❌ Failed to initialize storage: {}", e);
+                    std::process::exit(1);
+                });
+            seed_demo_data(&mut blockchain, &main_owner);
+            blockchain
+        }
+        Err(e) => {
+            eprintln!("❌ Refusing to start, stored state is corrupted: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = blockchain.checkpoint() {
+        eprintln!("Error writing snapshot: {}", e);
+    }
+
+    // Optional: import both token models into one ledger when MIGRATE_LEDGER=1
+    if env::var("MIGRATE_LEDGER").map(|v| v == "1").unwrap_or(false) {
+        run_ledger_migration(&blockchain, data_dir);
+        return;
+    }
+
+    // Blockchain, разошедшийся с реестром, не запускается
+    if let Some(ledger) = &ledger {
+        if let Err(e) = blockchain.attach_ledger(Arc::clone(ledger)) {
+            eprintln!("❌ Refusing to start, blockchain state diverged from the unified ledger: {}", e);
+            std::process::exit(1);
+        }
+        println!("📒 Unified ledger attached from {}", data_dir.join("ledger.json").display());
+    }
+
+    // Optional: start API server only (no interactive UI) when API_ONLY=1
+    if env::var("API_ONLY").map(|v| v == "1").unwrap_or(false) {
+        println!("🌐 Starting API Server (API_ONLY mode) on port 3000...");
+        let blockchain_arc = Arc::new(Mutex::new(blockchain.clone()));
+        let api_server = ApiServer::new(blockchain_arc, 3000);
+        api_server.start();
+        return;
+    }
+
+    // Optional: start Franchise Network API when FRANCHISE_API=1
+    if env::var("FRANCHISE_API").map(|v| v == "1").unwrap_or(false) {
+        println!("🏪 Starting Franchise Network API on port 3001...");
+        
+        let pos_api_server = match &ledger {
+            // Балансы сети проведены в реестр: сеть берется из DATA_DIR/franchise_network.json
+            // и сохраняется после каждого изменения
+            Some(ledger) => {
+                let network_path = data_dir.join("franchise_network.json");
+                let mut network = match FranchiseNetwork::load(&network_path) {
+                    Ok(Some(network)) => network,
+                    Ok(None) => {
+                        eprintln!("❌ {} is missing, the franchise network cannot use the ledger", network_path.display());
+                        std::process::exit(1);
+                    }
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        std::process::exit(1);
+                    }
+                };
+                if let Err(e) = network.attach_ledger(Arc::clone(ledger)) {
+                    eprintln!("❌ Refusing to start, franchise network diverged from the ledger: {}", e);
+                    std::process::exit(1);
+                }
+                PosApiServer::new(Arc::new(Mutex::new(network)), 3001).with_state_file(network_path)
+            }
+            None => {
+                // Создаем франшизную сеть
+                let franchise_network = Arc::new(Mutex::new(demo_network_genesis(&tokenomics)));
+                
+                // Демонстрация работы сети
+                demo_franchise_network(&franchise_network);
+                PosApiServer::new(franchise_network, 3001)
+            }
+        };
+        
+        // Запускаем POS API сервер
+        pos_api_server.start();
+        return;
+    }
+
+    // Optional: start P2P Network when P2P_NETWORK=1
+    if env::var("P2P_NETWORK").map(|v| v == "1").unwrap_or(false) {
+        println!("🌐 Starting P2P Network...");
+        
+        // Франшизная сеть и цепь из DATA_DIR (после восстановления из бэкапа) или демо-сеть
+        let (franchise_network, franchise_chain) = load_franchise_network(data_dir, &tokenomics);
+        
+        // Создаем P2P узел
+        let node_id = env::var("NODE_ID").unwrap_or_else(|_| "1".to_string()).parse::<u64>().unwrap_or(1);
+        let port = env::var("P2P_PORT").unwrap_or_else(|_| "8080".to_string()).parse::<u16>().unwrap_or(8080);
+        let address = format!("127.0.0.1:{}", port).parse().unwrap();
+        let keypair = load_node_keypair(&franchise_network, node_id);
+        
+        let p2p_node = P2PNode::new(node_id, address, franchise_network, keypair, load_peer_book(data_dir));
+        restore_franchise_chain(&p2p_node, franchise_chain);
+        submit_franchise_governance(&p2p_node, &blockchain);
+        
+        println!("🚀 Starting P2P Node {} on {}", node_id, address);
+        p2p_node.start();
+        return;
+    }
+
+    // Optional: start Full Decentralized Network when FULL_DECENTRALIZED=1
+    if env::var("FULL_DECENTRALIZED").map(|v| v == "1").unwrap_or(false) {
+        println!("🌐 Starting Full Decentralized Network...");
+        
+        // Франшизная сеть и цепь из DATA_DIR (после восстановления из бэкапа) или демо-сеть
+        let (franchise_network, franchise_chain) = load_franchise_network(data_dir, &tokenomics);
+        
+        // Создаем IPFS хранилище; его содержимое отдает локальный шлюз веб-сервера
+        let web_port = env::var("WEB_PORT").unwrap_or_else(|_| "8090".to_string()).parse::<u16>().unwrap_or(8090);
+        let mut ipfs_storage = open_ipfs_storage(data_dir, web_port);
+        
+        if let Ok(factor) = env::var("REPLICATION_FACTOR") {
+            ipfs_storage.replication_factor = factor.parse().unwrap_or(ipfs_storage::DEFAULT_REPLICATION_FACTOR);
+        }
+        
+        // Демонстрация IPFS
+        demo_ipfs_storage(&mut ipfs_storage, &franchise_network);
+        
+        // Создаем P2P узел с IPFS
+        let node_id = env::var("NODE_ID").unwrap_or_else(|_| "1".to_string()).parse::<u64>().unwrap_or(1);
+        let port = env::var("P2P_PORT").unwrap_or_else(|_| "8080".to_string()).parse::<u16>().unwrap_or(8080);
+        let address = format!("127.0.0.1:{}", port).parse().unwrap();
+        let keypair = load_node_keypair(&franchise_network, node_id);
+        
+        let ipfs_storage = Arc::new(Mutex::new(ipfs_storage));
+        let p2p_node = P2PNode::new(node_id, address, franchise_network, keypair, load_peer_book(data_dir))
+            .with_storage(Arc::clone(&ipfs_storage));
+        restore_franchise_chain(&p2p_node, franchise_chain);
+        submit_franchise_governance(&p2p_node, &blockchain);
+        
+        // API сервер для веб-интерфейсов меняет тот же блокчейн, который попадает в бэкапы
+        let blockchain = Arc::new(Mutex::new(blockchain));
+        let api_server = ApiServer::new(Arc::clone(&blockchain), 3000);
+        thread::spawn(move || api_server.start());
+        
+        // Зашифрованные бэкапы живого состояния ноды по расписанию и по запросу оператора
+        match load_backup_key() {
+            Some(key) => start_backups(p2p_node.context(), Arc::clone(&ipfs_storage), key, blockchain, data_dir),
+            None => println!("🔐 Backups are disabled (set BACKUP_KEY to a 32-byte hex key)"),
+        }
+        
+        start_web_interfaces(web_port, ipfs_storage);
+        
+        println!("🚀 Starting Full Decentralized Node {} on {}", node_id, address);
+        p2p_node.start();
+        return;
+    }
+    
+    // Start UI
+    let mut ui = UI::new(blockchain);
+    ui.run();
+}
+
+fn open_ipfs_storage(data_dir: &Path, web_port: u16) -> IPFSStorage {
+    let gateway_url = format!("http://127.0.0.1:{}/ipfs/", web_port);
+    IPFSStorage::open(&data_dir.join("ipfs"), gateway_url).unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    })
+}
+
+// Ключ оператора для бэкапов из BACKUP_KEY (hex, 32 байта)
+fn load_backup_key() -> Option<BackupKey> {
+    let key_hex = env::var("BACKUP_KEY").ok()?;
+    Some(BackupKey::from_hex(&key_hex).unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }))
+}
+
+// Бэкап раз в BACKUP_INTERVAL_SECS (по умолчанию час, первый — при запуске) и по запросу оператора:
+// файл DATA_DIR/backup.request запускает внеочередной бэкап и удаляется.
+// Блокчейн общий с остальными потоками ноды: в бэкап попадает его состояние на момент бэкапа
+fn start_backups(context: NodeContext, ipfs_storage: Arc<Mutex<IPFSStorage>>, key: BackupKey, blockchain: Arc<Mutex<Blockchain>>, data_dir: &Path) {
+    let interval = env::var("BACKUP_INTERVAL_SECS").ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(BACKUP_INTERVAL_SECS);
+    let request_path = data_dir.join(BACKUP_REQUEST_FILE);
+    println!("🔐 Backups every {} s; touch {} to back up now", interval, request_path.display());
+    thread::spawn(move || {
+        let mut next_backup = clock::now_secs();
+        loop {
+            let requested = std::fs::remove_file(&request_path).is_ok();
+            if requested || clock::now_secs() >= next_backup {
+                let (network, chain) = P2PNode::backup_state(&context);
+                let blockchain = blockchain.lock().unwrap();
+                if let Err(e) = backup::create_backup(&mut ipfs_storage.lock().unwrap(), &key, &blockchain, &network, &chain) {
+                    eprintln!("❌ Backup failed: {}", e);
+                }
+                next_backup = clock::now_secs() + interval;
+            }
+            thread::sleep(std::time::Duration::from_secs(1));
+        }
+    });
+}
+
+// Франшизная сеть из DATA_DIR/franchise_network.json и ее цепь из DATA_DIR/franchise_chain.json
+// (их пишет восстановление из бэкапа); без файла сети — демо-сеть
+fn load_franchise_network(data_dir: &Path, tokenomics: &TokenomicsConfig) -> (Arc<Mutex<FranchiseNetwork>>, Vec<ConsensusBlock>) {
+    let network_path = data_dir.join("franchise_network.json");
+    let network = match FranchiseNetwork::load(&network_path) {
+        Ok(Some(network)) => network,
+        Ok(None) => {
+            let franchise_network = Arc::new(Mutex::new(demo_network_genesis(tokenomics)));
+            demo_franchise_network(&franchise_network);
+            return (franchise_network, Vec::new());
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    let chain_path = data_dir.join(FRANCHISE_CHAIN_FILE);
+    let chain: Vec<ConsensusBlock> = match std::fs::read(&chain_path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+            eprintln!("❌ Invalid {}: {}", chain_path.display(), e);
+            std::process::exit(1);
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            eprintln!("❌ Failed to read {}: {}", chain_path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    println!("💾 Loaded franchise network from {} ({} blocks)", network_path.display(), chain.len());
+    (Arc::new(Mutex::new(network)), chain)
+}
+
+fn restore_franchise_chain(p2p_node: &P2PNode, chain: Vec<ConsensusBlock>) {
+    if let Err(e) = p2p_node.restore_chain(chain) {
+        eprintln!("❌ Refusing to start, {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Нода основателя отправляет в цепь франшизной сети решения, принятые голосованием держателей
+fn submit_franchise_governance(p2p_node: &P2PNode, blockchain: &Blockchain) {
+    let context = p2p_node.context();
+    let is_owner = context.franchise_network.lock().unwrap().nodes.get(&context.node_id)
+        .is_some_and(|node| node.node_type == NodeType::OWNER);
+    if !is_owner {
+        return;
+    }
+    let consensus = ConsensusAlgorithm::new();
+    for transaction in blockchain.franchise_governance_transactions(context.node_id, &context.keypair) {
+        if let Err(e) = P2PNode::submit_transaction(&context, &consensus, transaction) {
+            eprintln!("❌ Governance decision was not submitted: {}", e);
+        }
+    }
+}
+
+// Восстановление из бэкапа в пустой DATA_DIR: бэкап берется из DATA_DIR/ipfs (скопированного
+// или полученного от пиров), Blockchain становится снимком хранилища, сеть — DATA_DIR/franchise_network.json,
+// ее цепь — DATA_DIR/franchise_chain.json. P2P режимы запускаются с них.
+fn run_backup_restore(cid: &str, data_dir: &Path) {
+    let Some(key) = load_backup_key() else {
+        eprintln!("❌ RESTORE_BACKUP requires BACKUP_KEY");
+        std::process::exit(1);
+    };
+    let ipfs_storage = open_ipfs_storage(data_dir, 0);
+    let restored = backup::restore_from_backup(&ipfs_storage, &key, cid).unwrap_or_else(|e| {
+        eprintln!("❌ Backup {} cannot be restored: {}", cid, e);
+        std::process::exit(1);
+    });
+
+    // initialize отказывается перезаписать существующее состояние, поэтому сеть пишется после него
+    let blockchain = storage::initialize(data_dir, storage::DEFAULT_SNAPSHOT_INTERVAL, restored.blockchain).unwrap_or_else(|e| {
+        eprintln!("❌ Failed to initialize storage: {}", e);
+        std::process::exit(1);
+    });
+    let chain_path = data_dir.join(FRANCHISE_CHAIN_FILE);
+    let chain_json = serde_json::to_vec_pretty(&restored.franchise_chain).expect("franchise chain serializes");
+    let written = std::fs::write(&chain_path, chain_json)
+        .map_err(|e| format!("Failed to write {}: {}", chain_path.display(), e))
+        .and_then(|_| restored.franchise_network.save(&data_dir.join("franchise_network.json")));
+    if let Err(e) = written {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+    println!("♻️  Restored backup {} from {}", cid, restored.manifest.created_at);
+    println!("   Blocks: {}, token holders: {}", blockchain.chain.len(), blockchain.token_holders.len());
+    println!("   Franchise nodes: {}, sales: {}, blocks: {}", restored.franchise_network.nodes.len(), restored.franchise_network.sales.len(), restored.franchise_chain.len());
+}
+
+// Адрес кошелька оператора из его публичного ключа OPERATOR_PUBLIC_KEY (hex Ed25519)
+fn operator_wallet() -> String {
+    let public_key = env::var("OPERATOR_PUBLIC_KEY").unwrap_or_else(|_| {
+        eprintln!("❌ OPERATOR_PUBLIC_KEY is required: operator requests are signed with this key");
+        std::process::exit(1);
+    });
+    crypto::wallet_address(&public_key).unwrap_or_else(|e| {
+        eprintln!("❌ Invalid OPERATOR_PUBLIC_KEY: {}", e);
+        std::process::exit(1);
+    })
+}
+
+// Ключи держателей из VALIDATOR_KEYS (hex seed через запятую): нода производит блоки в слотах их адресов
+fn add_validator_keys(blockchain: &mut Blockchain) {
+    let Ok(secrets) = env::var("VALIDATOR_KEYS") else {
+        println!("🧱 No VALIDATOR_KEYS, this node does not produce blocks");
+        return;
+    };
+    for secret in secrets.split(',').map(str::trim).filter(|secret| !secret.is_empty()) {
+        let keypair = Keypair::from_secret_hex(secret).unwrap_or_else(|e| {
+            eprintln!("❌ Invalid VALIDATOR_KEYS entry: {}", e);
+            std::process::exit(1);
+        });
+        println!("🧱 Producing blocks for {}", blockchain.add_validator_key(keypair));
+    }
+}
+
+// Токеномика из JSON файла TOKENOMICS (разделы blockchain и franchise_network), без него — генезис-политики
+fn load_tokenomics() -> TokenomicsConfig {
+    let Ok(path) = env::var("TOKENOMICS") else {
+        return TokenomicsConfig::default();
+    };
+    TokenomicsConfig::load(Path::new(&path)).unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    })
+}
+
+// Франшизная сеть демо-режимов с политикой токеномики из конфигурации
+fn demo_network_genesis(tokenomics: &TokenomicsConfig) -> FranchiseNetwork {
+    let mut network = FranchiseNetwork::new("master_owner_georgia".to_string());
+    network.tokenomics = tokenomics.franchise_network.clone();
+    network
+}
+
+// Легкий клиент: заголовки и доказательства от полной ноды LIGHT_PEER.
+// TRUSTED_KEYS — ключи нод "node_id:hex,..."; WALLET и CHECK_SALE_ID — что проверить.
+fn run_light_client() {
+    let peer = env::var("LIGHT_PEER").unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+    let Ok(peer) = peer.parse() else {
+        eprintln!("❌ Invalid LIGHT_PEER address: {}", peer);
+        std::process::exit(1);
+    };
+    let trusted_keys = light_client::parse_trusted_keys(&env::var("TRUSTED_KEYS").unwrap_or_default())
+        .unwrap_or_else(|e| {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        });
+
+    let mut client = LightClient::new(trusted_keys);
+    let wallet = env::var("WALLET").ok();
+    let sale_id = env::var("CHECK_SALE_ID").ok();
+    if let Some(address) = &wallet {
+        client.watch(state_tree::StateKey::Wallet(address.clone()));
+    }
+    if let Some(sale_id) = &sale_id {
+        client.watch(state_tree::StateKey::Check(sale_id.clone()));
+    }
+
+    println!("📱 Syncing headers from {}...", peer);
+    if let Err(e) = client.sync(peer) {
+        eprintln!("❌ Light client sync failed at height {}: {}", client.height(), e);
+        std::process::exit(1);
+    }
+    println!("✅ Verified {} headers", client.height());
+
+    if let Some(address) = &wallet {
+        match client.balance(address) {
+            Some((height, balance)) => println!("💰 {}: {} security tokens (verified at height {})", address, format_units(balance), height),
+            None => println!("⚠️  No verified balance for {}", address),
+        }
+    }
+    if let Some(sale_id) = &sale_id {
+        match client.entry(&state_tree::StateKey::Check(sale_id.clone())) {
+            Some((height, state_tree::StateEntry::Check { check_address, balance, .. })) =>
+                println!("🧾 Check for {}: {} holds {} tokens (verified at height {})", sale_id, check_address, format_units(*balance), height),
+            _ => println!("⚠️  No verified check for sale {}", sale_id),
+        }
+    }
+}
+
+// Миграция в единый реестр: security токены Blockchain и кошельки FranchiseNetwork.
+// Сеть берется из DATA_DIR/franchise_network.json и обязательна; реестр пишется в DATA_DIR/ledger.json
+// и с этого момента подключается при каждом запуске. Повторная миграция поверх реестра запрещена.
+fn run_ledger_migration(blockchain: &Blockchain, data_dir: &Path) {
+    let ledger_path = data_dir.join("ledger.json");
+    if ledger_path.exists() {
+        eprintln!("❌ {} already exists, the ledger is already migrated", ledger_path.display());
+        std::process::exit(1);
+    }
+    let network_path = data_dir.join("franchise_network.json");
+    let network = match FranchiseNetwork::load(&network_path) {
+        Ok(Some(network)) => network,
+        Ok(None) => {
+            eprintln!("❌ Ledger migration needs {}: the franchise network state is missing", network_path.display());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let ledger = Ledger::migrate(&[blockchain, &network]).unwrap_or_else(|e| {
+        eprintln!("❌ Ledger migration failed: {}", e);
+        std::process::exit(1);
+    });
+    println!("📒 Unified ledger: {} accounts, {} franchise nodes, supply {} (blockchain {} + franchise network {})",
+        ledger.accounts.len(), ledger.franchise_nodes.len(), format_units(ledger.total_supply()),
+        format_units(blockchain.total_supply()), format_units(network.total_supply()));
+
+    if let Err(e) = ledger.save(&ledger_path) {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    }
+    println!("💾 Ledger written to {}", ledger_path.display());
+}
+
+// Единый реестр из DATA_DIR/ledger.json и его журнала ledger.log, если миграция проведена
+fn open_ledger(data_dir: &Path) -> Option<SharedLedger> {
+    LedgerStore::open(&data_dir.join("ledger.json")).unwrap_or_else(|e| {
+        eprintln!("❌ {}", e);
+        std::process::exit(1);
+    })
+}
+
+// Демо-данные для первого запуска узла
+fn seed_demo_data(blockchain: &mut Blockchain, main_owner: &str) {
     // Добавляем примеры меню с полной информацией
     println!("Adding sample menu items...");
     
@@ -3230,7 +4582,7 @@ fn main() {
         8,
         10,
         burger_ingredients,
-        main_owner.clone()
+        main_owner.to_string()
     );
     
     let _ = blockchain.add_menu_item_with_details(
@@ -3241,7 +4593,7 @@ fn main() {
         9,
         15,
         pizza_ingredients,
-        main_owner.clone()
+        main_owner.to_string()
     );
     
     // Simulate some purchases to generate checks
@@ -3254,8 +4606,10 @@ fn main() {
     ];
     
     for (customer, truck, amount, food_items) in purchases {
-        let check = blockchain.process_purchase(customer, truck, amount, food_items);
-        println!("Generated check: {} for ${:.2}", check.check_id, check.amount);
+        match blockchain.process_purchase(customer, truck, amount, food_items) {
+            Ok(check) => println!("Generated check: {} for ${}", check.check_id, format_units(check.amount)),
+            Err(e) => println!("Failed to process purchase: {}", e),
+        }
     }
     
     // Создаем пример заказа
@@ -3266,100 +4620,69 @@ fn main() {
     ];
     
     match blockchain.create_order("Customer_John".to_string(), order_items, 30) {
-        Ok(order) => println!("Created order: {} for ${:.2}", order.id, order.total_amount),
+        Ok(order) => println!("Created order: {} for ${}", order.id, format_units(order.total_amount)),
         Err(e) => println!("Failed to create order: {}", e),
     }
     
-    // Mine some blocks
-    println!("\nMining blocks...");
-    for i in 0..2 {
-        match blockchain.mine_block() {
-            Ok(()) => println!("Block {} mined successfully!", i + 1),
-            Err(e) => println!("Failed to mine block {}: {}", i + 1, e),
-        }
+    // Produce a block; the next slot opens in BLOCK_TIME_SECS
+    println!("\nProducing blocks...");
+    match blockchain.produce_block() {
+        Ok(()) => println!("Block produced successfully!"),
+        Err(e) => println!("Failed to produce block: {}", e),
     }
     
     // Update roles
-    blockchain.update_roles();
-
-    // Optional: start API server only (no interactive UI) when API_ONLY=1
-    if env::var("API_ONLY").map(|v| v == "1").unwrap_or(false) {
-        println!("🌐 Starting API Server (API_ONLY mode) on port 3000...");
-        let blockchain_arc = Arc::new(Mutex::new(blockchain.clone()));
-        let api_server = SimpleServer::new(blockchain_arc, 3000);
-        api_server.start();
-        return;
+    if let Err(e) = blockchain.update_roles() {
+        println!("Failed to update roles: {}", e);
     }
+}
 
-    // Optional: start Franchise Network API when FRANCHISE_API=1
-    if env::var("FRANCHISE_API").map(|v| v == "1").unwrap_or(false) {
-        println!("🏪 Starting Franchise Network API on port 3001...");
-        
-        // Создаем франшизную сеть
-        let franchise_network = Arc::new(Mutex::new(FranchiseNetwork::new("master_owner_georgia".to_string())));
-        
-        // Демонстрация работы сети
-        demo_franchise_network(&franchise_network);
-        
-        // Запускаем POS API сервер
-        let pos_api_server = PosApiServer::new(franchise_network, 3001);
-        pos_api_server.start();
-        return;
+// Ключ P2P ноды: из NODE_KEY (hex seed) или новый; публичная часть регистрируется в сети вместе с PEER_KEYS
+fn load_node_keypair(franchise_network: &Arc<Mutex<FranchiseNetwork>>, node_id: u64) -> Keypair {
+    let keypair = match env::var("NODE_KEY") {
+        Ok(secret) => Keypair::from_secret_hex(&secret).expect("NODE_KEY must be a 32-byte hex seed"),
+        Err(_) => {
+            let keypair = Keypair::generate();
+            println!("🔑 Generated an ephemeral node key (set NODE_KEY to keep it across restarts)");
+            keypair
+        }
+    };
+
+    let mut network = franchise_network.lock().unwrap();
+    match network.register_node_key(node_id, keypair.public_key_hex()) {
+        Ok(()) => println!("🔑 Node {} public key: {}", node_id, keypair.public_key_hex()),
+        Err(e) => eprintln!("⚠️  Node {} key not registered: {}", node_id, e),
     }
 
-    // Optional: start P2P Network when P2P_NETWORK=1
-    if env::var("P2P_NETWORK").map(|v| v == "1").unwrap_or(false) {
-        println!("🌐 Starting P2P Network...");
-        
-        // Создаем франшизную сеть
-        let franchise_network = Arc::new(Mutex::new(FranchiseNetwork::new("master_owner_georgia".to_string())));
-        
-        // Демонстрация работы сети
-        demo_franchise_network(&franchise_network);
-        
-        // Создаем P2P узел
-        let node_id = env::var("NODE_ID").unwrap_or_else(|_| "1".to_string()).parse::<u64>().unwrap_or(1);
-        let port = env::var("P2P_PORT").unwrap_or_else(|_| "8080".to_string()).parse::<u16>().unwrap_or(8080);
-        let address = format!("127.0.0.1:{}", port).parse().unwrap();
-        
-        let p2p_node = P2PNode::new(node_id, address, franchise_network);
-        
-        println!("🚀 Starting P2P Node {} on {}", node_id, address);
-        p2p_node.start();
-        return;
+    // Ключи остальных нод (PEER_KEYS="node_id:public_key_hex,..."): пир принимается только с подписью своим ключом
+    let peer_keys = light_client::parse_trusted_keys(&env::var("PEER_KEYS").unwrap_or_default()).unwrap_or_else(|e| {
+        eprintln!("❌ Invalid PEER_KEYS: {}", e);
+        std::process::exit(1);
+    });
+    for (peer_id, public_key) in peer_keys {
+        if let Err(e) = network.register_node_key(peer_id, public_key) {
+            eprintln!("⚠️  Node {} key not registered: {}", peer_id, e);
+        }
     }
+    keypair
+}
 
-    // Optional: start Full Decentralized Network when FULL_DECENTRALIZED=1
-    if env::var("FULL_DECENTRALIZED").map(|v| v == "1").unwrap_or(false) {
-        println!("🌐 Starting Full Decentralized Network...");
-        
-        // Создаем франшизную сеть
-        let franchise_network = Arc::new(Mutex::new(FranchiseNetwork::new("master_owner_georgia".to_string())));
-        
-        // Демонстрация работы сети
-        demo_franchise_network(&franchise_network);
-        
-        // Создаем IPFS хранилище
-        let mut ipfs_storage = IPFSStorage::new("https://ipfs.io/ipfs/".to_string());
-        
-        // Демонстрация IPFS
-        demo_ipfs_storage(&mut ipfs_storage, &franchise_network);
-        
-        // Создаем P2P узел с IPFS
-        let node_id = env::var("NODE_ID").unwrap_or_else(|_| "1".to_string()).parse::<u64>().unwrap_or(1);
-        let port = env::var("P2P_PORT").unwrap_or_else(|_| "8080".to_string()).parse::<u16>().unwrap_or(8080);
-        let address = format!("127.0.0.1:{}", port).parse().unwrap();
-        
-        let p2p_node = P2PNode::new(node_id, address, franchise_network);
-        
-        println!("🚀 Starting Full Decentralized Node {} on {}", node_id, address);
-        p2p_node.start();
-        return;
+// Книга пиров из data_dir; seed-адреса из P2P_SEEDS (через запятую) добавляются к ней
+fn load_peer_book(data_dir: &Path) -> PeerBook {
+    let path = data_dir.join("peers.json");
+    let mut peer_book = PeerBook::load(path.clone()).unwrap_or_else(|e| {
+        eprintln!("⚠️  {}, starting with an empty peer book", e);
+        PeerBook::new_at(path)
+    });
+
+    let seeds = env::var("P2P_SEEDS").unwrap_or_else(|_| "127.0.0.1:8081,127.0.0.1:8082".to_string());
+    for seed in seeds.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match seed.parse() {
+            Ok(address) => { peer_book.add(address, clock::now_secs()); }
+            Err(_) => eprintln!("⚠️  Invalid seed address: {}", seed),
+        }
     }
-    
-    // Start UI
-    let mut ui = UI::new(blockchain);
-    ui.run();
+    peer_book
 }
 
 // Демонстрация работы франшизной сети
@@ -3481,7 +4804,15 @@ fn demo_franchise_network(franchise_network: &Arc<Mutex<FranchiseNetwork>>) {
 fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mutex<FranchiseNetwork>>) {
     println!("\n📦 === IPFS STORAGE DEMO ===");
     
-    // 1. Создаем пример меню
+    // 1. Создаем пример меню; изображения блюд сохраняются в хранилище, меню ссылается на их CID
+    let mut store_dish_image = |name: &str, filename: &str| {
+        let svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\"><text y=\"20\">{}</text></svg>", name);
+        ipfs_storage.store_image(svg.as_bytes(), filename)
+            .map_err(|e| println!("❌ Failed to store image: {}", e))
+            .ok()
+    };
+    let khinkali_image = store_dish_image("Хинкали", "khinkali.svg");
+    let khachapuri_image = store_dish_image("Хачапури", "khachapuri.svg");
     let menu_data = ipfs_storage::MenuData {
         items: vec![
             ipfs_storage::MenuItem {
@@ -3491,7 +4822,7 @@ fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mut
                 price: 1.5,
                 category: "Основные блюда".to_string(),
                 ingredients: vec!["мука".to_string(), "говядина".to_string(), "лук".to_string()],
-                image_hash: Some("QmKhinkaliImage".to_string()),
+                image_hash: khinkali_image,
                 nutritional_info: ipfs_storage::NutritionalInfo {
                     calories: 250,
                     protein: 15.0,
@@ -3507,7 +4838,7 @@ fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mut
                 price: 5.0,
                 category: "Основные блюда".to_string(),
                 ingredients: vec!["мука".to_string(), "сыр".to_string(), "яйцо".to_string()],
-                image_hash: Some("QmKhachapuriImage".to_string()),
+                image_hash: khachapuri_image,
                 nutritional_info: ipfs_storage::NutritionalInfo {
                     calories: 400,
                     protein: 20.0,
@@ -3522,7 +4853,16 @@ fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mut
         version: 1,
     };
     
-    // Сохраняем меню в IPFS
+    // Сохраняем меню в IPFS; предыдущие версии меню открепляются
+    let outdated_menus: Vec<(String, ipfs_storage::PinReason)> = ipfs_storage.pins()
+        .filter(|(_, reason)| matches!(reason, ipfs_storage::PinReason::MenuVersion { version } if *version < menu_data.version))
+        .map(|(hash, reason)| (hash.to_string(), reason.clone()))
+        .collect();
+    for (hash, reason) in outdated_menus {
+        if let Err(e) = ipfs_storage.unpin(&hash, &reason) {
+            println!("❌ Failed to unpin old menu: {}", e);
+        }
+    }
     match ipfs_storage.store_menu(&menu_data) {
         Ok(hash) => println!("✅ Menu stored in IPFS: {}", hash),
         Err(e) => println!("❌ Failed to store menu: {}", e),
@@ -3559,13 +4899,16 @@ fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mut
         Err(e) => println!("❌ Failed to create network report: {}", e),
     }
     
-    // 4. Синхронизация с IPFS сетью
+    // 4. Синхронизация с IPFS сетью и сборка мусора
     match ipfs_storage.sync_with_network() {
-        Ok(new_hashes) => {
-            println!("🔄 Synced with IPFS network, found {} new files", new_hashes.len());
+        Ok(missing) => {
+            println!("🔄 Synced with IPFS network, {} pinned files missing locally", missing.len());
         }
         Err(e) => println!("❌ Failed to sync with IPFS: {}", e),
     }
+    if let Err(e) = ipfs_storage.collect_garbage() {
+        println!("❌ Garbage collection failed: {}", e);
+    }
     
     // 5. Показываем статистику хранилища
     let stats = ipfs_storage.get_storage_stats();
@@ -3573,32 +4916,33 @@ fn demo_ipfs_storage(ipfs_storage: &mut IPFSStorage, franchise_network: &Arc<Mut
     println!("   Total files: {}", stats.total_files);
     println!("   Total size: {} bytes", stats.total_size);
     println!("   Pinned hashes: {}", stats.pinned_hashes);
+    println!("   Garbage files: {}", stats.garbage_files);
+    println!("   Reclaimed: {} bytes", stats.reclaimed_bytes);
     println!("   Gateway URL: {}", stats.gateway_url);
     
     drop(network);
-    
-    // Запускаем веб-сервер для обслуживания HTML интерфейсов
+}
+
+// Веб-сервер HTML интерфейсов и IPFS шлюза (GET /ipfs/{cid}) в отдельном потоке
+fn start_web_interfaces(port: u16, ipfs_storage: Arc<Mutex<IPFSStorage>>) {
     println!("\n🌐 === ЗАПУСК ВЕБ-СЕРВЕРА ===");
-    let web_server = WebServer::new(8080);
+    let web_server = WebServer::new(port).with_storage(ipfs_storage);
     
     // Запускаем веб-сервер в отдельном потоке
     thread::spawn(move || {
         web_server.start();
     });
     
-    println!("✅ Веб-сервер запущен на http://127.0.0.1:8080");
+    let base = format!("http://127.0.0.1:{}", port);
+    println!("✅ Веб-сервер запущен на {}", base);
     println!("📱 Доступные интерфейсы:");
-    println!("   • Главная страница: http://127.0.0.1:8080/");
-    println!("   • Владелец сети: http://127.0.0.1:8080/owner_dashboard.html");
-    println!("   • Владелец франшизы: http://127.0.0.1:8080/franchise_dashboard.html");
-    println!("   • Покупатель: http://127.0.0.1:8080/customer_wallet.html");
-    println!("   • Старый интерфейс владельца: http://127.0.0.1:8080/restaurant_owner.html");
-    println!("   • Старый интерфейс кошелька: http://127.0.0.1:8080/wallet_interface.html");
-    
-    // Оставляем программу запущенной
-    loop {
-        thread::sleep(std::time::Duration::from_secs(1));
-    }
+    println!("   • Главная страница: {}/", base);
+    println!("   • Владелец сети: {}/owner_dashboard.html", base);
+    println!("   • Владелец франшизы: {}/franchise_dashboard.html", base);
+    println!("   • Покупатель: {}/customer_wallet.html", base);
+    println!("   • Старый интерфейс владельца: {}/restaurant_owner.html", base);
+    println!("   • Старый интерфейс кошелька: {}/wallet_interface.html", base);
+    println!("   • Содержимое IPFS: {}/ipfs/<cid>", base);
 }
 
 #[cfg(test)]
@@ -3614,4 +4958,21 @@ mod tests {
     mod load_testing;
     mod new_token_distribution;
     mod unclaimed_tokens_distribution;
+    mod storage;
+    mod consensus;
+    mod chain_sync;
+    mod p2p;
+    mod peer_book;
+    mod network_simulator;
+    mod bft;
+    mod rewards;
+    mod executor;
+    mod merkle;
+    mod light_client;
+    mod ledger;
+    mod tokenomics;
+    mod governance;
+    mod ipfs_storage;
+    mod backup;
+    mod fixtures;
 }
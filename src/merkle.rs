@@ -0,0 +1,95 @@
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+
+// Дерево Меркла над сериализованными транзакциями блока.
+// Лист — SHA-256(0x00 || байты транзакции), узел — SHA-256(0x01 || левый || правый):
+// разные префиксы не дают выдать внутренний узел за лист.
+// Непарный узел уровня поднимается на следующий уровень без изменений (без дублирования,
+// поэтому два разных списка транзакций не дают один корень).
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+// Шаг доказательства: хеш соседа и его сторона
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub hash: String,
+    pub sibling_is_left: bool,
+}
+
+// Доказательство включения листа: путь от листа к корню
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf_index: usize,
+    pub steps: Vec<ProofStep>,
+}
+
+fn leaf_hash(leaf: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(leaf);
+    hasher.finalize().into()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+// Уровни дерева от листьев к корню
+fn levels(leaves: &[Vec<u8>]) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves.iter().map(|leaf| leaf_hash(leaf)).collect::<Vec<_>>()];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels.last().unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+// Корень дерева в hex; у пустого блока — хеш пустой строки
+pub fn root(leaves: &[Vec<u8>]) -> String {
+    if leaves.is_empty() {
+        return hex::encode(Sha256::digest(b""));
+    }
+    hex::encode(levels(leaves).last().unwrap()[0])
+}
+
+pub fn proof(leaves: &[Vec<u8>], leaf_index: usize) -> Option<MerkleProof> {
+    if leaf_index >= leaves.len() {
+        return None;
+    }
+
+    let mut steps = Vec::new();
+    let mut index = leaf_index;
+    for level in levels(leaves).iter().take_while(|level| level.len() > 1) {
+        let sibling = index ^ 1;
+        if sibling < level.len() {
+            steps.push(ProofStep { hash: hex::encode(level[sibling]), sibling_is_left: sibling < index });
+        }
+        index /= 2;
+    }
+    Some(MerkleProof { leaf_index, steps })
+}
+
+// Проверка доказательства без остальных транзакций блока
+pub fn verify(root: &str, leaf: &[u8], proof: &MerkleProof) -> bool {
+    let mut hash = leaf_hash(leaf);
+    for step in &proof.steps {
+        let sibling: [u8; 32] = match hex::decode(&step.hash).ok().and_then(|bytes| bytes.try_into().ok()) {
+            Some(sibling) => sibling,
+            None => return false,
+        };
+        hash = if step.sibling_is_left { node_hash(&sibling, &hash) } else { node_hash(&hash, &sibling) };
+    }
+    hex::encode(hash) == root
+}
@@ -0,0 +1,425 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use crate::chain_sync::BlockImport;
+use crate::clock;
+use crate::consensus::{Block, ConsensusAlgorithm, Transaction};
+use crate::crypto::Keypair;
+use crate::franchise_network::FranchiseNetwork;
+use crate::ipfs_storage::IPFSStorage;
+use crate::p2p_network::{
+    P2PMessage, P2PNode, PeerConnection, Transport, CONSENSUS_TICK, DIAL_INTERVAL, MAX_OUTBOUND_PEERS, PING_INTERVAL,
+    SYNC_INTERVAL,
+};
+use crate::peer_book::PeerBook;
+
+// Детерминированный симулятор P2P сети: N нод P2PNode в одном потоке,
+// виртуальные соединения вместо TCP и виртуальные часы (clock::freeze) вместо системных.
+// Ноды обрабатывают сообщения тем же кодом, что и в реальной сети; симулятор только
+// доставляет сообщения с задержкой, теряет их и разделяет сеть на группы.
+// Прогон с тем же seed повторяется в точности.
+
+pub const SIM_EPOCH_SECS: u64 = 1_700_000_000; // Начало виртуального времени
+const SIM_PORT: u16 = 8080;
+
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    pub seed: u64,
+    pub min_latency_ms: u64,
+    pub max_latency_ms: u64,
+    pub loss_rate: f64, // Доля потерянных сообщений, 0.0..1.0
+    pub consensus: bool, // Ноды сами финализируют блоки BFT раундами; иначе блоки публикует тест
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            min_latency_ms: 20,
+            max_latency_ms: 200,
+            loss_rate: 0.0,
+            consensus: false,
+        }
+    }
+}
+
+// Счетчики доставки сообщений
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimStats {
+    pub delivered: u64,
+    pub lost: u64,
+    pub partitioned: u64, // Отброшены из-за разделения сети
+}
+
+// Сообщение в пути
+#[derive(Debug)]
+struct Envelope {
+    from: u64,
+    to: u64,
+    link_closed: Arc<AtomicBool>,
+    message: P2PMessage,
+}
+
+// Одно направление виртуального соединения; флаг закрытия общий для обоих направлений
+#[derive(Debug)]
+struct VirtualLink {
+    from: u64,
+    to: u64,
+    closed: Arc<AtomicBool>,
+    outbox: Arc<Mutex<Vec<Envelope>>>,
+}
+
+impl Transport for VirtualLink {
+    fn send(&self, message: &P2PMessage) -> Result<(), String> {
+        self.outbox.lock().unwrap().push(Envelope {
+            from: self.from,
+            to: self.to,
+            link_closed: Arc::clone(&self.closed),
+            message: message.clone(),
+        });
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+pub struct Simulator {
+    config: SimConfig,
+    nodes: BTreeMap<u64, P2PNode>,
+    rng: fastrand::Rng,
+    now_ms: u64,
+    queue: BTreeMap<(u64, u64), Envelope>, // (время доставки, порядковый номер) -> сообщение
+    next_seq: u64,
+    last_delivery: HashMap<(u64, u64), u64>, // Соединение сохраняет порядок сообщений, как TCP
+    outbox: Arc<Mutex<Vec<Envelope>>>,
+    groups: HashMap<u64, usize>, // node_id -> группа при разделении; пусто — сеть целая
+    next_dial_ms: u64,
+    next_ping_ms: u64,
+    next_sync_ms: u64,
+    next_consensus_ms: u64,
+    stats: SimStats,
+}
+
+impl Simulator {
+    // Ноды с общим начальным состоянием франшизной сети; каждая знает только адрес первой ноды
+    pub fn new(config: SimConfig, network: &FranchiseNetwork, keypairs: Vec<(u64, Keypair)>) -> Self {
+        let seed_address = keypairs.first().map(|(node_id, _)| Self::address(*node_id));
+        let mut nodes = BTreeMap::new();
+        for (node_id, keypair) in keypairs {
+            let mut peer_book = PeerBook::new();
+            if let Some(seed) = seed_address.filter(|seed| *seed != Self::address(node_id)) {
+                peer_book.add(seed, SIM_EPOCH_SECS);
+            }
+            let node = P2PNode::new(node_id, Self::address(node_id), Arc::new(Mutex::new(network.clone())), keypair, peer_book);
+            nodes.insert(node_id, node);
+        }
+
+        Self {
+            rng: fastrand::Rng::with_seed(config.seed),
+            nodes,
+            now_ms: 0,
+            queue: BTreeMap::new(),
+            next_seq: 0,
+            last_delivery: HashMap::new(),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+            groups: HashMap::new(),
+            next_dial_ms: 0,
+            next_ping_ms: millis(PING_INTERVAL),
+            next_sync_ms: millis(SYNC_INTERVAL),
+            next_consensus_ms: if config.consensus { millis(CONSENSUS_TICK) } else { u64::MAX },
+            stats: SimStats::default(),
+            config,
+        }
+    }
+
+    // Виртуальный адрес ноды: у каждой свой IP, чтобы бан одной не задевал остальные
+    pub fn address(node_id: u64) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, (node_id / 256) as u8, (node_id % 256) as u8)), SIM_PORT)
+    }
+
+    pub fn node(&self, node_id: u64) -> &P2PNode {
+        &self.nodes[&node_id]
+    }
+
+    // Нода получает IPFS хранилище и начинает обмениваться содержимым
+    pub fn attach_storage(&mut self, node_id: u64, storage: IPFSStorage) {
+        let node = self.nodes.remove(&node_id).expect("unknown node");
+        self.nodes.insert(node_id, node.with_storage(Arc::new(Mutex::new(storage))));
+    }
+
+    pub fn now_secs(&self) -> u64 {
+        SIM_EPOCH_SECS + self.now_ms / 1000
+    }
+
+    pub fn stats(&self) -> &SimStats {
+        &self.stats
+    }
+
+    // Разделение сети: сообщения между разными группами не доходят, подключиться нельзя
+    pub fn partition(&mut self, groups: &[&[u64]]) {
+        self.groups.clear();
+        for (group, node_ids) in groups.iter().enumerate() {
+            for node_id in node_ids.iter() {
+                self.groups.insert(*node_id, group);
+            }
+        }
+    }
+
+    pub fn heal(&mut self) {
+        self.groups.clear();
+    }
+
+    fn reachable(&self, from: u64, to: u64) -> bool {
+        self.groups.get(&from) == self.groups.get(&to)
+    }
+
+    pub fn connected_peers(&self, node_id: u64) -> Vec<u64> {
+        let mut peers: Vec<u64> = self.nodes[&node_id].peers.lock().unwrap().values()
+            .filter(|p| p.is_connected)
+            .map(|p| p.node_id)
+            .collect();
+        peers.sort();
+        peers
+    }
+
+    // Вершина основной цепи каждой ноды
+    pub fn tips(&self) -> BTreeMap<u64, String> {
+        self.nodes.iter()
+            .map(|(node_id, node)| (*node_id, node.blockchain.lock().unwrap().tip_hash()))
+            .collect()
+    }
+
+    pub fn converged(&self) -> bool {
+        let tips = self.tips();
+        tips.values().all(|tip| Some(tip) == tips.values().next())
+    }
+
+    // Нода публикует блок, как после его создания: импорт и рассылка пирам
+    pub fn publish_block(&mut self, node_id: u64, block: Block) -> Result<BlockImport, String> {
+        let _clock = clock::freeze(self.now_secs());
+        let context = self.nodes[&node_id].context();
+        let outcome = P2PNode::publish_block(&context, &ConsensusAlgorithm::new(), block);
+        self.flush_outbox();
+        outcome
+    }
+
+    // Транзакция, отправленная в ноду (как через API): пул ожидающих транзакций и рассылка пирам
+    pub fn submit_transaction(&mut self, node_id: u64, transaction: Transaction) -> Result<(), String> {
+        let _clock = clock::freeze(self.now_secs());
+        let context = self.nodes[&node_id].context();
+        let result = P2PNode::submit_transaction(&context, &ConsensusAlgorithm::new(), transaction);
+        self.flush_outbox();
+        result
+    }
+
+    // Нода запрашивает содержимое у пиров (как через API)
+    pub fn request_content(&mut self, node_id: u64, cid: &str) -> Result<bool, String> {
+        let _clock = clock::freeze(self.now_secs());
+        let result = P2PNode::request_content(&self.nodes[&node_id].context(), cid);
+        self.flush_outbox();
+        result
+    }
+
+    // Прогон виртуального времени: доставка сообщений и периодические задачи нод
+    pub fn run_for(&mut self, duration_ms: u64) {
+        let end_ms = self.now_ms + duration_ms;
+        loop {
+            let next_message = self.queue.keys().next().map(|(at, _)| *at);
+            let next_timer = self.next_dial_ms.min(self.next_ping_ms).min(self.next_sync_ms).min(self.next_consensus_ms);
+            let next = next_message.map_or(next_timer, |at| at.min(next_timer));
+            if next > end_ms {
+                break;
+            }
+
+            self.now_ms = next;
+            let _clock = clock::freeze(self.now_secs());
+            if next_message == Some(next) {
+                let (_, envelope) = self.queue.pop_first().unwrap();
+                self.deliver(envelope);
+            } else {
+                self.fire_timers();
+            }
+            self.flush_outbox();
+            self.sweep_closed_connections();
+        }
+        self.now_ms = end_ms;
+    }
+
+    // Прогон, пока не выполнится условие; false — не выполнилось за max_ms
+    pub fn run_until(&mut self, max_ms: u64, condition: impl Fn(&Simulator) -> bool) -> bool {
+        let end_ms = self.now_ms + max_ms;
+        while self.now_ms < end_ms {
+            if condition(self) {
+                return true;
+            }
+            self.run_for(1000.min(end_ms - self.now_ms));
+        }
+        condition(self)
+    }
+
+    // Те же периодические задачи, что и потоки P2PNode: подключение, ping, синхронизация, таймеры консенсуса
+    fn fire_timers(&mut self) {
+        let node_ids: Vec<u64> = self.nodes.keys().copied().collect();
+        if self.now_ms >= self.next_dial_ms {
+            for node_id in &node_ids {
+                self.dial(*node_id);
+            }
+            self.next_dial_ms += millis(DIAL_INTERVAL);
+        }
+        if self.now_ms >= self.next_ping_ms {
+            for node in self.nodes.values() {
+                P2PNode::disconnect_stale_peers(&node.peers, self.now_secs());
+                P2PNode::broadcast_message(&node.peers, P2PMessage::Ping { node_id: node.node_id, timestamp: self.now_secs() });
+            }
+            self.next_ping_ms += millis(PING_INTERVAL);
+        }
+        if self.now_ms >= self.next_sync_ms {
+            for node in self.nodes.values() {
+                let from_height = node.blockchain.lock().unwrap().height();
+                P2PNode::broadcast_message(&node.peers, P2PMessage::SyncRequest { from_height });
+                P2PNode::sync_content(&node.context());
+            }
+            self.next_sync_ms += millis(SYNC_INTERVAL);
+        }
+        if self.now_ms >= self.next_consensus_ms {
+            let consensus = ConsensusAlgorithm::new();
+            for node in self.nodes.values() {
+                if let Err(e) = P2PNode::drive_consensus(&node.context(), &consensus, None) {
+                    println!("⛔ Node {} consensus step failed: {}", node.node_id, e);
+                }
+            }
+            self.next_consensus_ms += millis(CONSENSUS_TICK);
+        }
+    }
+
+    // Исходящие подключения ноды к адресам из ее книги пиров
+    fn dial(&mut self, node_id: u64) {
+        let context = self.nodes[&node_id].context();
+        let mut connected: Vec<SocketAddr> = context.peers.lock().unwrap().values()
+            .filter(|p| p.is_connected)
+            .map(|p| p.address)
+            .collect();
+        let free_slots = MAX_OUTBOUND_PEERS.saturating_sub(connected.len());
+        connected.push(Self::address(node_id));
+        let candidates = context.peer_book.lock().unwrap().dial_candidates(self.now_secs(), &connected, free_slots);
+
+        for address in candidates {
+            let target = self.nodes.values().map(|n| n.node_id).find(|id| Self::address(*id) == address);
+            match target {
+                Some(target) if self.reachable(node_id, target) => self.connect(node_id, target),
+                _ => context.peer_book.lock().unwrap().mark_failed(&address),
+            }
+        }
+    }
+
+    // Виртуальное соединение и обмен Hello (мгновенный, как и установка TCP соединения)
+    fn connect(&mut self, from: u64, to: u64) {
+        let dialer = self.nodes[&from].context();
+        let listener = self.nodes[&to].context();
+        if listener.peer_book.lock().unwrap().is_banned(Self::address(from).ip(), self.now_secs()) {
+            dialer.peer_book.lock().unwrap().mark_failed(&Self::address(to));
+            return;
+        }
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let outbound = Arc::new(self.link(from, to, &closed));
+        let inbound = Arc::new(self.link(to, from, &closed));
+        let dialer_hello = dialer.hello();
+        let listener_hello = listener.hello();
+
+        let dialer_signature = dialer.handshake_signature(&listener_hello);
+        let listener_signature = listener.handshake_signature(&dialer_hello);
+
+        let accepted = P2PNode::accept_peer(&listener, &listener_hello, &dialer_hello, &dialer_signature, Self::address(from).ip(), false, &inbound)
+            .and_then(|accepted| {
+                let dialer_accepted = P2PNode::accept_peer(&dialer, &dialer_hello, &listener_hello, &listener_signature, Self::address(to).ip(), true, &outbound)?;
+                Ok(accepted && dialer_accepted)
+            });
+        match accepted {
+            Ok(true) => {}
+            Ok(false) => outbound.close(),
+            Err(_) => {
+                outbound.close();
+                dialer.peer_book.lock().unwrap().remove(&Self::address(to));
+            }
+        }
+    }
+
+    fn link(&self, from: u64, to: u64, closed: &Arc<AtomicBool>) -> PeerConnection {
+        PeerConnection::with_transport(Box::new(VirtualLink {
+            from,
+            to,
+            closed: Arc::clone(closed),
+            outbox: Arc::clone(&self.outbox),
+        }))
+    }
+
+    fn deliver(&mut self, envelope: Envelope) {
+        if envelope.link_closed.load(Ordering::SeqCst) {
+            return;
+        }
+        // Сообщение в пути в момент разделения сети тоже теряется
+        if !self.reachable(envelope.from, envelope.to) {
+            self.stats.partitioned += 1;
+            return;
+        }
+
+        let context = self.nodes[&envelope.to].context();
+        let peer = context.peers.lock().unwrap().get(&envelope.from).cloned();
+        let (address, connection) = match peer.and_then(|p| p.connection.map(|c| (p.address, c))) {
+            Some(peer) => peer,
+            None => return,
+        };
+        self.stats.delivered += 1;
+        if P2PNode::process_message(&context, &connection, envelope.from, address, envelope.message).is_err() {
+            connection.close();
+        }
+    }
+
+    // Отправленные нодами сообщения ставятся в очередь с задержкой или теряются
+    fn flush_outbox(&mut self) {
+        let sent: Vec<Envelope> = self.outbox.lock().unwrap().drain(..).collect();
+        for envelope in sent {
+            if !self.reachable(envelope.from, envelope.to) {
+                self.stats.partitioned += 1;
+                continue;
+            }
+            if self.rng.f64() < self.config.loss_rate {
+                self.stats.lost += 1;
+                continue;
+            }
+
+            let latency = self.rng.u64(self.config.min_latency_ms..=self.config.max_latency_ms);
+            let direction = (envelope.from, envelope.to);
+            let deliver_at = (self.now_ms + latency).max(self.last_delivery.get(&direction).copied().unwrap_or(0));
+            self.last_delivery.insert(direction, deliver_at);
+            self.queue.insert((deliver_at, self.next_seq), envelope);
+            self.next_seq += 1;
+        }
+    }
+
+    // Закрытое соединение снимается с обеих сторон, как при EOF в TCP
+    fn sweep_closed_connections(&mut self) {
+        for node in self.nodes.values() {
+            let closed: Vec<(u64, Arc<PeerConnection>)> = node.peers.lock().unwrap().values()
+                .filter(|p| p.is_connected)
+                .filter_map(|p| p.connection.as_ref().filter(|c| c.is_closed()).map(|c| (p.node_id, Arc::clone(c))))
+                .collect();
+            for (peer_id, connection) in closed {
+                P2PNode::unregister_peer(&node.peers, peer_id, &connection);
+            }
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_millis() as u64
+}
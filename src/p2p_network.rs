@@ -1,19 +1,51 @@
 use std::collections::HashMap;
-use std::net::{TcpListener, TcpStream, SocketAddr};
-use std::io::{Read, Write, BufRead, BufReader};
+use std::net::{TcpListener, TcpStream, SocketAddr, IpAddr, Shutdown};
+use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
 use crate::franchise_network::FranchiseNetwork;
-use crate::consensus::{ConsensusAlgorithm, ConsensusResult, Block, Transaction};
+use crate::consensus::{ConsensusAlgorithm, ConsensusResult, Block, BlockHeader, Transaction};
+use crate::chain_sync::{BlockImport, ChainSync};
+use crate::bft::{Bft, BftEnv, BftMessage, BftOutput};
+use crate::executor::Executor;
+use crate::crypto::{self, Keypair};
+use crate::p2p_protocol::{self, FrameError, Hello};
+use crate::peer_book::{Misbehavior, PeerBook, BAN_DURATION_SECS, MAX_SHARED_PEERS};
+use crate::state_tree::{self, StateKey, StateProof};
+use crate::ipfs_storage::{IPFSStorage, MAX_WANTED_BLOCKS};
+use crate::clock;
+
+const SYNC_BACKTRACK: u64 = 64; // На сколько блоков назад запрашивать цепь при неизвестном родителе
+const MAX_SYNC_BLOCKS: usize = 500; // Блоков в одном SyncResponse, чтобы кадр не превышал лимит
+pub const MAX_SYNC_HEADERS: usize = 2000; // Заголовков в одном ответе легкому клиенту
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+const DIAL_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_OUTBOUND_PEERS: usize = 8;
+pub const PING_INTERVAL: Duration = Duration::from_secs(30);
+pub const DIAL_INTERVAL: Duration = Duration::from_secs(30); // Подключение к новым адресам из книги пиров
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(60);
+pub const PEER_TIMEOUT_SECS: u64 = 90; // Пир без ping/pong дольше этого считается отключившимся
+pub const CONSENSUS_TICK: Duration = Duration::from_secs(1); // Проверка таймаутов BFT раунда
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+const MAX_MESSAGES_PER_WINDOW: u32 = 200;
 
 // P2P сообщения
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum P2PMessage {
+    // Handshake, первое сообщение соединения, и подпись challenge пира ключом ноды
+    Hello { hello: Hello },
+    HelloAuth { signature: String },
+    
     // Обнаружение сети
     Ping { node_id: u64, timestamp: u64 },
     Pong { node_id: u64, timestamp: u64 },
+    GetPeers,
+    Peers { addresses: Vec<SocketAddr> },
     
     // Синхронизация данных
     SyncRequest { from_height: u64 },
@@ -26,12 +58,26 @@ pub enum P2PMessage {
     // Консенсус
     ConsensusRequest { block_height: u64 },
     ConsensusResponse { result: ConsensusResult },
+    Bft { message: BftMessage }, // Предложение блока или голос BFT раунда
     
     // Блоки
     NewBlock { block: Block },
     BlockRequest { block_height: u64 },
     BlockResponse { block: Block },
     
+    // Легкие клиенты: заголовки основной цепи и доказательства состояния.
+    // Доказательство строится по state_root вершины цепи (блок height).
+    GetHeaders { from_height: u64 },
+    Headers { headers: Vec<BlockHeader> },
+    GetStateProof { key: StateKey },
+    StateProof { height: u64, proof: Option<StateProof> },
+    
+    // Обмен содержимым IPFS хранилища по CID. На WantList пир отвечает блоками, которые у него есть;
+    // Have предлагает реплику, получатель запрашивает недостающее через WantList.
+    WantList { cids: Vec<String> },
+    Have { cids: Vec<String> },
+    ContentBlock { cid: String, data: String, content_type: Option<String> }, // data — hex
+    
     // Статус ноды
     NodeStatus { node_id: u64, status: NodeStatus },
     NetworkStats { stats: NetworkStats },
@@ -58,14 +104,50 @@ pub struct NetworkStats {
 pub struct P2PNode {
     pub node_id: u64,
     pub address: SocketAddr,
+    pub keypair: Arc<Keypair>, // Ключ ноды; публичная часть зарегистрирована в FranchiseNetwork
     pub peers: Arc<Mutex<HashMap<u64, PeerInfo>>>,
+    pub peer_book: Arc<Mutex<PeerBook>>,
     pub franchise_network: Arc<Mutex<FranchiseNetwork>>,
     pub consensus: Arc<Mutex<ConsensusAlgorithm>>,
-    pub blockchain: Arc<Mutex<Vec<Block>>>,
+    pub blockchain: Arc<Mutex<ChainSync>>, // Все ветки и основная цепь после fork choice
+    pub executor: Arc<Executor>, // Применяет блоки основной цепи к franchise_network
+    pub committed_state: Arc<Mutex<FranchiseNetwork>>, // Состояние до вершины цепи, его фиксирует state_root вершины
+    pub bft: Arc<Mutex<Bft>>, // Раунд финализации следующего блока
     pub pending_transactions: Arc<Mutex<Vec<Transaction>>>,
+    pub storage: Option<Arc<Mutex<IPFSStorage>>>, // Содержимое, которым нода обменивается с пирами
     pub is_running: Arc<Mutex<bool>>,
 }
 
+// Общее состояние ноды, нужное потокам соединений
+#[derive(Clone)]
+pub struct NodeContext {
+    pub node_id: u64,
+    pub listen_port: u16,
+    pub keypair: Arc<Keypair>,
+    pub peers: Arc<Mutex<HashMap<u64, PeerInfo>>>,
+    pub peer_book: Arc<Mutex<PeerBook>>,
+    pub franchise_network: Arc<Mutex<FranchiseNetwork>>,
+    pub blockchain: Arc<Mutex<ChainSync>>,
+    pub executor: Arc<Executor>,
+    pub committed_state: Arc<Mutex<FranchiseNetwork>>,
+    pub bft: Arc<Mutex<Bft>>,
+    pub pending_transactions: Arc<Mutex<Vec<Transaction>>>,
+    pub storage: Option<Arc<Mutex<IPFSStorage>>>,
+}
+
+impl NodeContext {
+    // Hello с текущим состоянием цепи
+    pub fn hello(&self) -> Hello {
+        let chain = self.blockchain.lock().unwrap();
+        Hello::new(self.node_id, self.listen_port, chain.height(), chain.genesis_hash())
+    }
+
+    // Подпись challenge из Hello пира для HelloAuth
+    pub fn handshake_signature(&self, peer_hello: &Hello) -> String {
+        self.keypair.sign(&p2p_protocol::handshake_bytes(&peer_hello.challenge, self.node_id, peer_hello.node_id))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
     pub node_id: u64,
@@ -73,22 +155,133 @@ pub struct PeerInfo {
     pub last_ping: u64,
     pub is_connected: bool,
     pub block_height: u64,
+    pub connection: Option<Arc<PeerConnection>>, // Открытое соединение, через него идут и ответы, и рассылки
+}
+
+// Способ доставки сообщений пиру: TCP в реальной сети, виртуальный канал в симуляторе
+pub trait Transport: Send + Sync + fmt::Debug {
+    fn send(&self, message: &P2PMessage) -> Result<(), String>;
+    fn close(&self);
+    fn is_closed(&self) -> bool;
+}
+
+// TCP соединение. Запись идет под мьютексом, чтобы кадры из разных потоков не перемешивались.
+#[derive(Debug)]
+struct TcpTransport {
+    stream: Mutex<TcpStream>,
+    closed: AtomicBool,
+}
+
+impl Transport for TcpTransport {
+    fn send(&self, message: &P2PMessage) -> Result<(), String> {
+        p2p_protocol::write_frame(&mut *self.stream.lock().unwrap(), message)
+    }
+
+    // Закрытие сокета; поток чтения этого соединения получит EOF и снимет пира
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _ = self.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+}
+
+// Постоянное двунаправленное соединение с пиром
+#[derive(Debug)]
+pub struct PeerConnection {
+    transport: Box<dyn Transport>,
+}
+
+impl PeerConnection {
+    pub fn new(stream: TcpStream) -> Self {
+        Self::with_transport(Box::new(TcpTransport { stream: Mutex::new(stream), closed: AtomicBool::new(false) }))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        Self { transport }
+    }
+
+    pub fn send(&self, message: &P2PMessage) -> Result<(), String> {
+        if self.transport.is_closed() {
+            return Err("Connection is closed".to_string());
+        }
+        self.transport.send(message)
+    }
+
+    pub fn close(&self) {
+        self.transport.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.transport.is_closed()
+    }
 }
 
 impl P2PNode {
-    pub fn new(node_id: u64, address: SocketAddr, franchise_network: Arc<Mutex<FranchiseNetwork>>) -> Self {
+    pub fn new(node_id: u64, address: SocketAddr, franchise_network: Arc<Mutex<FranchiseNetwork>>, keypair: Keypair, peer_book: PeerBook) -> Self {
+        let base = franchise_network.lock().unwrap().clone();
         Self {
             node_id,
             address,
+            keypair: Arc::new(keypair),
             peers: Arc::new(Mutex::new(HashMap::new())),
+            peer_book: Arc::new(Mutex::new(peer_book)),
             franchise_network,
             consensus: Arc::new(Mutex::new(ConsensusAlgorithm::new())),
-            blockchain: Arc::new(Mutex::new(Vec::new())),
+            blockchain: Arc::new(Mutex::new(ChainSync::new())),
+            executor: Arc::new(Executor::new(base.clone())),
+            committed_state: Arc::new(Mutex::new(base)),
+            bft: Arc::new(Mutex::new(Bft::new(node_id))),
             pending_transactions: Arc::new(Mutex::new(Vec::new())),
+            storage: None,
             is_running: Arc::new(Mutex::new(false)),
         }
     }
 
+    // Цепь из бэкапа до запуска ноды: пиров еще нет, блоки проходят обычный импорт и применяются к сети
+    pub fn restore_chain(&self, blocks: Vec<Block>) -> Result<(), String> {
+        let context = self.context();
+        let consensus = ConsensusAlgorithm::new();
+        for block in blocks {
+            let height = block.height;
+            Self::publish_block(&context, &consensus, block)
+                .map_err(|e| format!("Restored block {} rejected: {}", height, e))?;
+        }
+        Ok(())
+    }
+
+    // Состояние для бэкапа: сеть до первого блока и основная цепь, из которых нода восстанавливает текущее
+    pub fn backup_state(context: &NodeContext) -> (FranchiseNetwork, Vec<Block>) {
+        let chain = context.blockchain.lock().unwrap().main_chain().to_vec();
+        (context.executor.base().clone(), chain)
+    }
+
+    // Нода с IPFS хранилищем отдает содержимое пирам и реплицирует свое закрепленное
+    pub fn with_storage(mut self, storage: Arc<Mutex<IPFSStorage>>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
+    // Общее состояние для потоков соединений
+    pub fn context(&self) -> NodeContext {
+        NodeContext {
+            node_id: self.node_id,
+            listen_port: self.address.port(),
+            keypair: Arc::clone(&self.keypair),
+            peers: Arc::clone(&self.peers),
+            peer_book: Arc::clone(&self.peer_book),
+            franchise_network: Arc::clone(&self.franchise_network),
+            blockchain: Arc::clone(&self.blockchain),
+            executor: Arc::clone(&self.executor),
+            committed_state: Arc::clone(&self.committed_state),
+            bft: Arc::clone(&self.bft),
+            pending_transactions: Arc::clone(&self.pending_transactions),
+            storage: self.storage.clone(),
+        }
+    }
+
     // Запуск P2P узла
     pub fn start(&self) {
         println!("🌐 Starting P2P Node {} on {}", self.node_id, self.address);
@@ -107,25 +300,26 @@ impl P2PNode {
         // Запускаем синхронизацию
         let sync_handle = self.start_sync();
         
+        // Проверка живости пиров и сохранение книги пиров
+        let maintenance_handle = self.start_maintenance();
+        
         // Ждем завершения
         server_handle.join().unwrap();
         client_handle.join().unwrap();
         consensus_handle.join().unwrap();
         sync_handle.join().unwrap();
+        maintenance_handle.join().unwrap();
     }
 
     // Сервер для входящих соединений
     fn start_server(&self) -> thread::JoinHandle<()> {
-        let node_id = self.node_id;
-        let peers = Arc::clone(&self.peers);
-        let franchise_network = Arc::clone(&self.franchise_network);
-        let blockchain = Arc::clone(&self.blockchain);
-        let pending_transactions = Arc::clone(&self.pending_transactions);
+        let listen_address = SocketAddr::from(([0, 0, 0, 0], self.address.port()));
+        let context = self.context();
         let is_running = Arc::clone(&self.is_running);
         
         thread::spawn(move || {
-            let listener = TcpListener::bind("0.0.0.0:8080").expect("Failed to bind server");
-            println!("🔗 P2P Server listening on 0.0.0.0:8080");
+            let listener = TcpListener::bind(listen_address).expect("Failed to bind server");
+            println!("🔗 P2P Server listening on {}", listen_address);
             
             for stream in listener.incoming() {
                 if !*is_running.lock().unwrap() {
@@ -134,13 +328,11 @@ impl P2PNode {
                 
                 match stream {
                     Ok(stream) => {
-                        let peers = Arc::clone(&peers);
-                        let franchise_network = Arc::clone(&franchise_network);
-                        let blockchain = Arc::clone(&blockchain);
-                        let pending_transactions = Arc::clone(&pending_transactions);
-                        
+                        let context = context.clone();
                         thread::spawn(move || {
-                            Self::handle_incoming_connection(stream, node_id, peers, franchise_network, blockchain, pending_transactions);
+                            if let Err(e) = Self::handle_connection(stream, false, context) {
+                                eprintln!("Incoming connection closed: {}", e);
+                            }
                         });
                     }
                     Err(e) => {
@@ -151,249 +343,685 @@ impl P2PNode {
         })
     }
 
-    // Клиент для исходящих соединений
+    // Клиент для исходящих соединений: адреса берутся из книги пиров
     fn start_client(&self) -> thread::JoinHandle<()> {
-        let node_id = self.node_id;
-        let peers = Arc::clone(&self.peers);
+        let own_address = self.address;
+        let context = self.context();
         let is_running = Arc::clone(&self.is_running);
         
         thread::spawn(move || {
-            // Список известных пиров (в реальности это может быть из конфига или DNS)
-            let known_peers = vec![
-                "127.0.0.1:8081".parse::<SocketAddr>().unwrap(),
-                "127.0.0.1:8082".parse::<SocketAddr>().unwrap(),
-            ];
-            
             while *is_running.lock().unwrap() {
-                for peer_addr in &known_peers {
-                    if let Ok(stream) = TcpStream::connect(peer_addr) {
-                        let peers = Arc::clone(&peers);
-                        thread::spawn(move || {
-                            Self::handle_outgoing_connection(stream, node_id, peers);
-                        });
+                // Соединения постоянные: подключаемся только к тем, с кем соединения нет
+                let mut connected: Vec<SocketAddr> = context.peers.lock().unwrap().values()
+                    .filter(|p| p.is_connected)
+                    .map(|p| p.address)
+                    .collect();
+                let free_slots = MAX_OUTBOUND_PEERS.saturating_sub(connected.len());
+                connected.push(own_address);
+                let candidates = context.peer_book.lock().unwrap()
+                    .dial_candidates(clock::now_secs(), &connected, free_slots);
+                
+                for peer_addr in candidates {
+                    match TcpStream::connect_timeout(&peer_addr, DIAL_TIMEOUT) {
+                        Ok(stream) => {
+                            let context = context.clone();
+                            thread::spawn(move || {
+                                if let Err(e) = Self::handle_connection(stream, true, context) {
+                                    eprintln!("Connection to {} closed: {}", peer_addr, e);
+                                }
+                            });
+                        }
+                        Err(_) => context.peer_book.lock().unwrap().mark_failed(&peer_addr),
                     }
                 }
                 
-                thread::sleep(Duration::from_secs(30)); // Переподключение каждые 30 секунд
+                thread::sleep(DIAL_INTERVAL);
             }
         })
     }
 
-    // Консенсус
-    fn start_consensus(&self) -> thread::JoinHandle<()> {
-        let node_id = self.node_id;
+    // Проверка живости: ping всем пирам, отключение молчащих, сохранение книги пиров
+    fn start_maintenance(&self) -> thread::JoinHandle<()> {
         let peers = Arc::clone(&self.peers);
-        let franchise_network = Arc::clone(&self.franchise_network);
-        let consensus = Arc::clone(&self.consensus);
-        let blockchain = Arc::clone(&self.blockchain);
-        let pending_transactions = Arc::clone(&self.pending_transactions);
+        let peer_book = Arc::clone(&self.peer_book);
+        let node_id = self.node_id;
         let is_running = Arc::clone(&self.is_running);
         
         thread::spawn(move || {
             while *is_running.lock().unwrap() {
-                // Ждем накопления транзакций или таймаут
-                thread::sleep(Duration::from_secs(10));
-                
-                let pending_count = pending_transactions.lock().unwrap().len();
-                if pending_count == 0 {
-                    continue;
-                }
+                thread::sleep(PING_INTERVAL);
                 
-                // Выбираем валидаторов
-                let network = franchise_network.lock().unwrap();
-                let consensus_alg = consensus.lock().unwrap();
-                let current_height = blockchain.lock().unwrap().len() as u64;
+                Self::disconnect_stale_peers(&peers, clock::now_secs());
+                Self::broadcast_message(&peers, P2PMessage::Ping { node_id, timestamp: clock::now_secs() });
                 
-                let consensus_result = consensus_alg.select_validators(&network, current_height);
-                drop(network);
-                drop(consensus_alg);
+                if let Err(e) = peer_book.lock().unwrap().save() {
+                    eprintln!("Error saving peer book: {}", e);
+                }
+            }
+        })
+    }
+
+    // Закрытие соединений с пирами, от которых давно не было ping/pong
+    pub fn disconnect_stale_peers(peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>, now: u64) -> Vec<u64> {
+        let stale: Vec<(u64, Arc<PeerConnection>)> = peers.lock().unwrap().values()
+            .filter(|p| p.is_connected && now.saturating_sub(p.last_ping) > PEER_TIMEOUT_SECS)
+            .filter_map(|p| p.connection.as_ref().map(|c| (p.node_id, Arc::clone(c))))
+            .collect();
+        
+        for (peer_id, connection) in &stale {
+            println!("⏱️ Node {} is not responding, disconnecting", peer_id);
+            connection.close();
+        }
+        stale.into_iter().map(|(peer_id, _)| peer_id).collect()
+    }
+
+    // Консенсус: таймеры BFT раунда; предложения и голоса пиров обрабатываются в потоках соединений
+    fn start_consensus(&self) -> thread::JoinHandle<()> {
+        let context = self.context();
+        let consensus = Arc::clone(&self.consensus);
+        let is_running = Arc::clone(&self.is_running);
+        
+        thread::spawn(move || {
+            while *is_running.lock().unwrap() {
+                thread::sleep(CONSENSUS_TICK);
                 
-                // Проверяем, являемся ли мы валидатором
-                if consensus_result.selected_validators.contains(&node_id) {
-                    println!("🎯 Node {} selected as validator for block {}", node_id, current_height);
-                    
-                    // Создаем новый блок
-                    let mut transactions = pending_transactions.lock().unwrap();
-                    let block_transactions = transactions.drain(..).collect();
-                    drop(transactions);
-                    
-                    let blockchain_guard = blockchain.lock().unwrap();
-                    let previous_hash = blockchain_guard.last()
-                        .map(|b| b.hash.clone())
-                        .unwrap_or_else(|| "genesis".to_string());
-                    drop(blockchain_guard);
-                    
-                    let mut new_block = Block::new(current_height, previous_hash, block_transactions);
-                    
-                    // Подписываем блок
-                    new_block.add_signature(node_id, format!("signature_{}_{}", node_id, current_height));
-                    
-                    // Добавляем блок в блокчейн
-                    blockchain.lock().unwrap().push(new_block.clone());
-                    
-                    // Рассылаем блок другим узлам
-                    Self::broadcast_message(&peers, P2PMessage::NewBlock { block: new_block });
+                let consensus_alg = consensus.lock().unwrap().clone();
+                if let Err(e) = Self::drive_consensus(&context, &consensus_alg, None) {
+                    println!("⛔ Consensus step failed: {}", e);
                 }
             }
         })
     }
+    
+    // Шаг BFT раунда: по таймеру (message = None) или по сообщению пира.
+    // Err — сообщение пира невалидно.
+    pub fn drive_consensus(context: &NodeContext, consensus: &ConsensusAlgorithm, message: Option<BftMessage>) -> Result<(), String> {
+        let (outputs, evidence) = {
+            let network = context.franchise_network.lock().unwrap();
+            let chain = context.blockchain.lock().unwrap();
+            let committed = context.committed_state.lock().unwrap();
+            let pending = context.pending_transactions.lock().unwrap();
+            let env = BftEnv {
+                chain: &chain,
+                network: &network,
+                committed: &committed,
+                consensus,
+                pending: &pending,
+                keypair: &context.keypair,
+            };
+            let mut bft = context.bft.lock().unwrap();
+            let outputs = match message {
+                Some(message) => bft.handle(message, &env),
+                None => Ok(bft.tick(&env)),
+            };
+            (outputs, bft.take_evidence())
+        };
+
+        // Двойная подпись, замеченная в голосах, уходит в цепь доказательством
+        for evidence in evidence {
+            let transaction = evidence.into_transaction(context.node_id, &context.keypair);
+            if let Err(e) = Self::submit_transaction(context, consensus, transaction) {
+                println!("⚠️ Evidence not submitted: {}", e);
+            }
+        }
+        let outputs = outputs?;
+        
+        let mut finalized = false;
+        for output in outputs {
+            match output {
+                BftOutput::Broadcast(message) => Self::broadcast_message(&context.peers, P2PMessage::Bft { message }),
+                BftOutput::Finalized(block) => {
+                    let height = block.height;
+                    match Self::publish_block(context, consensus, block) {
+                        Ok(_) => finalized = true,
+                        Err(e) => println!("⛔ Finalized block {} not accepted: {}", height, e),
+                    }
+                }
+            }
+        }
+        // Следующая высота начинается сразу, не дожидаясь таймера
+        if finalized {
+            Self::drive_consensus(context, consensus, None)?;
+        }
+        Ok(())
+    }
+    
+    // Своя транзакция: проверка, пул ожидающих транзакций и рассылка пирам
+    pub fn submit_transaction(context: &NodeContext, consensus: &ConsensusAlgorithm, transaction: Transaction) -> Result<(), String> {
+        if !consensus.validate_transaction(&transaction, &context.franchise_network.lock().unwrap()) {
+            return Err(format!("Transaction {} has no valid node signature", transaction.id));
+        }
+        Self::add_pending(context, transaction.clone());
+        Self::broadcast_message(&context.peers, P2PMessage::NewTransaction { transaction });
+        Ok(())
+    }
+    
+    // Транзакция попадает в пул один раз и только если ее еще нет в основной цепи
+    fn add_pending(context: &NodeContext, transaction: Transaction) -> bool {
+        let chain = context.blockchain.lock().unwrap();
+        let mut pending = context.pending_transactions.lock().unwrap();
+        let known = pending.iter().any(|tx| tx.id == transaction.id)
+            || chain.main_chain().iter().any(|b| b.transactions.iter().any(|tx| tx.id == transaction.id));
+        if !known {
+            pending.push(transaction);
+        }
+        !known
+    }
+
+    // Собственный блок проходит ту же проверку, что и блоки от пиров, затем рассылается им
+    pub fn publish_block(context: &NodeContext, consensus: &ConsensusAlgorithm, block: Block) -> Result<BlockImport, String> {
+        let mut network = context.franchise_network.lock().unwrap();
+        let mut chain = context.blockchain.lock().unwrap();
+        let old_height = chain.height();
+        let outcome = chain.import(block.clone(), consensus, context.executor.base())?;
+        let mut committed = context.committed_state.lock().unwrap();
+        context.executor.apply_chain_update(&mut network, &mut committed, consensus, &chain, &outcome, old_height);
+        drop(committed);
+        let mut pending = context.pending_transactions.lock().unwrap();
+        ChainSync::reconcile_pending(&outcome, chain.main_chain(), &mut pending);
+        drop(pending);
+        drop(chain);
+        drop(network);
+        
+        Self::broadcast_message(&context.peers, P2PMessage::NewBlock { block });
+        Ok(outcome)
+    }
 
     // Синхронизация
     fn start_sync(&self) -> thread::JoinHandle<()> {
-        let node_id = self.node_id;
-        let peers = Arc::clone(&self.peers);
-        let blockchain = Arc::clone(&self.blockchain);
+        let context = self.context();
         let is_running = Arc::clone(&self.is_running);
         
         thread::spawn(move || {
             while *is_running.lock().unwrap() {
-                thread::sleep(Duration::from_secs(60)); // Синхронизация каждую минуту
+                thread::sleep(SYNC_INTERVAL);
                 
-                let current_height = blockchain.lock().unwrap().len() as u64;
+                let current_height = context.blockchain.lock().unwrap().height();
                 
                 // Запрашиваем синхронизацию у пиров
-                Self::broadcast_message(&peers, P2PMessage::SyncRequest { from_height: current_height });
+                Self::broadcast_message(&context.peers, P2PMessage::SyncRequest { from_height: current_height });
+                Self::sync_content(&context);
             }
         })
     }
 
-    // Обработка входящего соединения
-    fn handle_incoming_connection(
-        mut stream: TcpStream,
-        node_id: u64,
-        peers: Arc<Mutex<HashMap<u64, PeerInfo>>>,
-        franchise_network: Arc<Mutex<FranchiseNetwork>>,
-        blockchain: Arc<Mutex<Vec<Block>>>,
-        pending_transactions: Arc<Mutex<Vec<Transaction>>>,
-    ) {
-        let peer_addr = stream.peer_addr().unwrap();
-        println!("📡 Incoming connection from {}", peer_addr);
-        
-        let reader = BufReader::new(stream.try_clone().unwrap());
+    // Запрос содержимого у всех пиров; Ok(false) — оно уже есть локально
+    pub fn request_content(context: &NodeContext, cid: &str) -> Result<bool, String> {
+        let storage = context.storage.as_ref().ok_or("Node has no content storage")?;
+        if !storage.lock().unwrap().want(cid, None)? {
+            return Ok(false);
+        }
+        Self::broadcast_message(&context.peers, P2PMessage::WantList { cids: vec![cid.to_string()] });
+        Ok(true)
+    }
+
+    // Периодический обмен содержимым: повтор незакрытых запросов, восстановление недостающего
+    // закрепленного содержимого и предложение реплик replication_factor пирам
+    pub fn sync_content(context: &NodeContext) {
+        let Some(storage) = &context.storage else {
+            return;
+        };
+        let (missing, own, replication_factor) = {
+            let mut storage = storage.lock().unwrap();
+            (storage.sync_with_network().unwrap_or_default(), storage.replication_set(), storage.replication_factor)
+        };
+        for cid in missing {
+            if let Err(e) = Self::request_content(context, &cid) {
+                println!("⚠️ Failed to request {}: {}", cid, e);
+            }
+        }
+        let wanted = storage.lock().unwrap().wanted();
+        if !wanted.is_empty() {
+            Self::broadcast_message(&context.peers, P2PMessage::WantList { cids: wanted });
+        }
         
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if let Ok(message) = serde_json::from_str::<P2PMessage>(&line) {
-                    Self::handle_message(
-                        message,
-                        &mut stream,
-                        node_id,
-                        &peers,
-                        &franchise_network,
-                        &blockchain,
-                        &pending_transactions,
-                    );
+        let peer_ids = Self::replica_candidates(context, context.node_id);
+        let mut offers: HashMap<u64, Vec<String>> = HashMap::new();
+        for cid in own {
+            for peer_id in Self::replica_peers(&cid, &peer_ids, replication_factor) {
+                offers.entry(peer_id).or_default().push(cid.clone());
+            }
+        }
+        for (peer_id, cids) in offers {
+            let connection = context.peers.lock().unwrap().get(&peer_id).and_then(|p| p.connection.clone());
+            for chunk in cids.chunks(MAX_WANTED_BLOCKS) {
+                if let Some(connection) = &connection {
+                    let _ = connection.send(&P2PMessage::Have { cids: chunk.to_vec() });
                 }
             }
         }
     }
 
-    // Обработка исходящего соединения
-    fn handle_outgoing_connection(
-        mut stream: TcpStream,
-        node_id: u64,
-        peers: Arc<Mutex<HashMap<u64, PeerInfo>>>,
-    ) {
-        let peer_addr = stream.peer_addr().unwrap();
-        println!("🔗 Connected to peer {}", peer_addr);
+    // Кандидаты в хранители реплик ноды origin: активные ноды зафиксированного состояния сети, кроме нее.
+    // Состав берется из цепи, а не из соединений, поэтому отправитель и получатель Have выбирают одинаково;
+    // реплика для ноды без соединения ждет, пока та подключится.
+    fn replica_candidates(context: &NodeContext, origin: u64) -> Vec<u64> {
+        let committed = context.committed_state.lock().unwrap();
+        let mut peer_ids: Vec<u64> = committed.nodes.values()
+            .filter(|node| node.active && node.node_id != origin)
+            .map(|node| node.node_id)
+            .collect();
+        peer_ids.sort();
+        peer_ids
+    }
+
+    // Реплики из Have ноды origin, которые эта нода начинает собирать: только те, чьим хранителем ее
+    // выбирает replica_peers, и в пределах квоты реплик origin
+    pub fn accept_replicas(context: &NodeContext, storage: &Mutex<IPFSStorage>, origin: u64, cids: Vec<String>) -> Vec<String> {
+        let candidates = Self::replica_candidates(context, origin);
+        let mut storage = storage.lock().unwrap();
+        let replication_factor = storage.replication_factor;
+        cids.into_iter()
+            .filter(|cid| Self::replica_peers(cid, &candidates, replication_factor).contains(&context.node_id))
+            .filter(|cid| storage.want(cid, Some(origin)).unwrap_or_else(|e| {
+                println!("⚠️ Declined replica {} from node {}: {}", cid, origin, e);
+                false
+            }))
+            .collect()
+    }
+
+    // Пиры, которые хранят реплику cid: первые replication_factor по хешу (cid, node_id).
+    // При смене состава пиров большая часть реплик остается на прежних нодах.
+    fn replica_peers(cid: &str, peer_ids: &[u64], replication_factor: usize) -> Vec<u64> {
+        let mut ranked: Vec<(Vec<u8>, u64)> = peer_ids.iter()
+            .map(|peer_id| (Sha256::digest(format!("{}|{}", cid, peer_id).as_bytes()).to_vec(), *peer_id))
+            .collect();
+        ranked.sort();
+        ranked.into_iter().take(replication_factor).map(|(_, peer_id)| peer_id).collect()
+    }
+
+    // Обслуживание соединения с пиром (входящего или исходящего):
+    // обмен Hello, регистрация пира под его node_id и чтение кадров до разрыва
+    pub fn handle_connection(stream: TcpStream, outbound: bool, context: NodeContext) -> Result<(), String> {
+        let peer_addr = stream.peer_addr().map_err(|e| format!("Failed to get peer address: {}", e))?;
+        if context.peer_book.lock().unwrap().is_banned(peer_addr.ip(), clock::now_secs()) {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(format!("{} is banned", peer_addr.ip()));
+        }
+        println!("📡 {} connection with {}", if outbound { "Outgoing" } else { "Incoming" }, peer_addr);
         
-        // Отправляем ping
-        let ping = P2PMessage::Ping {
-            node_id,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        let mut reader = stream.try_clone().map_err(|e| format!("Failed to clone stream: {}", e))?;
+        let connection = Arc::new(PeerConnection::new(stream));
+        
+        // Обе стороны сразу отправляют свой Hello
+        let our_hello = context.hello();
+        connection.send(&P2PMessage::Hello { hello: our_hello.clone() })?;
+        
+        let _ = reader.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+        let peer_hello = match p2p_protocol::read_frame(&mut reader) {
+            Ok(Some(P2PMessage::Hello { hello })) => hello,
+            Ok(Some(_)) => Err(format!("{} sent a message before Hello", peer_addr))?,
+            Ok(None) => Err(format!("{} closed connection during handshake", peer_addr))?,
+            Err(e) => Err(e)?,
+        };
+        let accepted = Self::exchange_handshake_signatures(&context, &our_hello, &peer_hello, &connection, &mut reader)
+            .and_then(|peer_signature| Self::accept_peer(&context, &our_hello, &peer_hello, &peer_signature, peer_addr.ip(), outbound, &connection));
+        match accepted {
+            Ok(true) => {}
+            Ok(false) => {
+                connection.close();
+                return Ok(());
+            }
+            Err(e) => {
+                // Несовместимую ноду (или самих себя) повторно не набираем
+                if outbound {
+                    context.peer_book.lock().unwrap().remove(&peer_addr);
+                }
+                connection.close();
+                return Err(format!("Handshake with {} failed: {}", peer_addr, e));
+            }
+        }
+        let _ = reader.set_read_timeout(None);
+        
+        let peer_id = peer_hello.node_id;
+        let advertised = SocketAddr::new(peer_addr.ip(), peer_hello.listen_port);
+        let mut window_started = Instant::now();
+        let mut window_messages = 0;
+        let result = loop {
+            let message = match p2p_protocol::read_frame(&mut reader) {
+                Ok(Some(message)) => message,
+                Ok(None) => break Ok(()),
+                Err(FrameError::Malformed(e)) => {
+                    Self::report_misbehavior(&context, peer_id, advertised, Misbehavior::MalformedFrame);
+                    break Err(e);
+                }
+                Err(e) => break Err(e.into()),
+            };
+            
+            // Ограничение частоты сообщений
+            if window_started.elapsed() >= RATE_WINDOW {
+                window_started = Instant::now();
+                window_messages = 0;
+            }
+            window_messages += 1;
+            if window_messages == MAX_MESSAGES_PER_WINDOW + 1
+                && Self::report_misbehavior(&context, peer_id, advertised, Misbehavior::Spam) {
+                break Err(format!("Node {} banned for spam", peer_id));
+            }
+            if window_messages > MAX_MESSAGES_PER_WINDOW {
+                continue;
+            }
+            
+            if let Err(e) = Self::process_message(&context, &connection, peer_id, advertised, message) {
+                break Err(e);
+            }
         };
         
-        if let Ok(json) = serde_json::to_string(&ping) {
-            let _ = writeln!(stream, "{}", json);
+        Self::unregister_peer(&context.peers, peer_id, &connection);
+        connection.close();
+        println!("🔌 Node {} disconnected", peer_id);
+        result
+    }
+
+    // Обмен HelloAuth: нода доказывает владение ключом своего node_id; легкий клиент ключа не имеет.
+    // Несовместимому пиру подпись не отправляется. Возвращает подпись пира.
+    fn exchange_handshake_signatures(
+        context: &NodeContext,
+        our_hello: &Hello,
+        peer_hello: &Hello,
+        connection: &PeerConnection,
+        reader: &mut TcpStream,
+    ) -> Result<String, String> {
+        our_hello.check_peer(peer_hello)?;
+        if peer_hello.light_client {
+            return Ok(String::new());
+        }
+        connection.send(&P2PMessage::HelloAuth { signature: context.handshake_signature(peer_hello) })?;
+        match p2p_protocol::read_frame(reader)? {
+            Some(P2PMessage::HelloAuth { signature }) => Ok(signature),
+            Some(_) => Err("peer did not sign the handshake".to_string()),
+            None => Err("peer closed connection during handshake".to_string()),
         }
+    }
+
+    // Завершение handshake (Hello и HelloAuth уже получены): проверка пира, регистрация под его node_id
+    // и первые запросы. Ok(false) — соединение лишнее (с этим пиром уже есть другое).
+    pub fn accept_peer(
+        context: &NodeContext,
+        our_hello: &Hello,
+        peer_hello: &Hello,
+        peer_signature: &str,
+        peer_ip: IpAddr,
+        outbound: bool,
+        connection: &Arc<PeerConnection>,
+    ) -> Result<bool, String> {
+        our_hello.check_peer(peer_hello)?;
         
-        // Добавляем пира
+        // Легкий клиент только задает вопросы: в пиры и книгу пиров он не попадает
+        if peer_hello.light_client {
+            println!("📱 Light client connected from {}", peer_ip);
+            return Ok(true);
+        }
+        
+        // node_id в Hello ничего не доказывает: регистрируем пира только по подписи ключом этой ноды
+        let peer_id = peer_hello.node_id;
+        let public_key = context.franchise_network.lock().unwrap().node_public_key(peer_id).map(str::to_string)
+            .ok_or_else(|| format!("Node {} has no registered key", peer_id))?;
+        let message = p2p_protocol::handshake_bytes(&our_hello.challenge, peer_id, context.node_id);
+        if !crypto::verify(&public_key, &message, peer_signature) {
+            return Err(format!("Node {} failed to sign the handshake with its registered key", peer_id));
+        }
+        
+        // Адрес, по которому пир принимает соединения
+        let advertised = SocketAddr::new(peer_ip, peer_hello.listen_port);
+        if !Self::register_peer(&context.peers, context.node_id, peer_hello, advertised, outbound, connection) {
+            return Ok(false);
+        }
+        context.peer_book.lock().unwrap().mark_connected(advertised, peer_id, clock::now_secs());
+        println!("🤝 Connected to node {} at {} (height {})", peer_id, advertised, peer_hello.block_height);
+        
+        // Пир впереди — сразу запрашиваем недостающие блоки
+        if peer_hello.block_height > our_hello.block_height {
+            Self::request_sync(connection, our_hello.block_height);
+        }
+        let _ = connection.send(&P2PMessage::GetPeers);
+        Ok(true)
+    }
+
+    // Обработка сообщения пира со штрафом за нарушения; Err — пир забанен, соединение закрывается
+    pub fn process_message(
+        context: &NodeContext,
+        connection: &PeerConnection,
+        peer_id: u64,
+        peer_address: SocketAddr,
+        message: P2PMessage,
+    ) -> Result<(), String> {
+        match Self::handle_message(message, connection, peer_id, peer_address, context) {
+            Err(misbehavior) if Self::report_misbehavior(context, peer_id, peer_address, misbehavior) => {
+                Err(format!("Node {} banned for {:?}", peer_id, misbehavior))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Штраф пиру; true, если он забанен и соединение нужно закрыть
+    fn report_misbehavior(context: &NodeContext, peer_id: u64, address: SocketAddr, misbehavior: Misbehavior) -> bool {
+        let mut peer_book = context.peer_book.lock().unwrap();
+        let banned = peer_book.penalize(address.ip(), misbehavior, clock::now_secs());
+        println!("⚠️ Node {} misbehaved ({:?}), score {}", peer_id, misbehavior, peer_book.misbehavior_score(address.ip()));
+        if banned {
+            println!("🚫 Banned {} for {} seconds", address.ip(), BAN_DURATION_SECS);
+        }
+        banned
+    }
+
+    // Регистрация пира после handshake. Если между парой нод уже есть соединение,
+    // остается исходящее соединение ноды с меньшим id — обе стороны выбирают одно и то же.
+    fn register_peer(
+        peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>,
+        node_id: u64,
+        hello: &Hello,
+        address: SocketAddr,
+        outbound: bool,
+        connection: &Arc<PeerConnection>,
+    ) -> bool {
         let mut peers_guard = peers.lock().unwrap();
-        peers_guard.insert(node_id, PeerInfo {
-            node_id,
-            address: peer_addr,
-            last_ping: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        if let Some(existing) = peers_guard.get(&hello.node_id).and_then(|p| p.connection.as_ref()) {
+            let preferred = outbound == (node_id < hello.node_id);
+            if !preferred {
+                return false;
+            }
+            existing.close();
+        }
+        
+        peers_guard.insert(hello.node_id, PeerInfo {
+            node_id: hello.node_id,
+            address,
+            last_ping: clock::now_secs(),
             is_connected: true,
-            block_height: 0,
+            block_height: hello.block_height,
+            connection: Some(Arc::clone(connection)),
         });
+        true
     }
 
-    // Обработка сообщений
+    // Пир отключается, только если закрылось именно его текущее соединение
+    pub fn unregister_peer(peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>, peer_id: u64, connection: &Arc<PeerConnection>) {
+        let mut peers_guard = peers.lock().unwrap();
+        if let Some(peer) = peers_guard.get_mut(&peer_id) {
+            if peer.connection.as_ref().is_some_and(|c| Arc::ptr_eq(c, connection)) {
+                peer.is_connected = false;
+                peer.connection = None;
+            }
+        }
+    }
+
+    // Обработка сообщений; Err — нарушение протокола со стороны пира
     fn handle_message(
         message: P2PMessage,
-        stream: &mut TcpStream,
-        node_id: u64,
-        peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>,
-        franchise_network: &Arc<Mutex<FranchiseNetwork>>,
-        blockchain: &Arc<Mutex<Vec<Block>>>,
-        pending_transactions: &Arc<Mutex<Vec<Transaction>>>,
-    ) {
+        connection: &PeerConnection,
+        peer_id: u64,
+        peer_address: SocketAddr,
+        context: &NodeContext,
+    ) -> Result<(), Misbehavior> {
+        // Параметры консенсуса одинаковы на всех нодах (значения по умолчанию)
+        let consensus = ConsensusAlgorithm::new();
         match message {
-            P2PMessage::Ping { node_id: peer_id, timestamp } => {
-                println!("📡 Ping from node {}", peer_id);
+            P2PMessage::Ping { node_id: _, timestamp: _ } => {
+                Self::touch_peer(&context.peers, peer_id);
                 
                 let pong = P2PMessage::Pong {
-                    node_id,
-                    timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+                    node_id: context.node_id,
+                    timestamp: clock::now_secs(),
                 };
                 
-                if let Ok(json) = serde_json::to_string(&pong) {
-                    let _ = writeln!(stream, "{}", json);
-                }
+                let _ = connection.send(&pong);
             }
             
-            P2PMessage::Pong { node_id: peer_id, timestamp: _ } => {
-                println!("📡 Pong from node {}", peer_id);
-                
+            P2PMessage::Pong { node_id: _, timestamp: _ } => {
                 // Обновляем информацию о пире
-                let mut peers_guard = peers.lock().unwrap();
-                if let Some(peer) = peers_guard.get_mut(&peer_id) {
-                    peer.last_ping = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+                Self::touch_peer(&context.peers, peer_id);
+            }
+            
+            P2PMessage::GetPeers => {
+                let addresses = context.peer_book.lock().unwrap().shareable(clock::now_secs(), &peer_address);
+                let _ = connection.send(&P2PMessage::Peers { addresses });
+            }
+            
+            P2PMessage::Peers { addresses } => {
+                if addresses.len() > MAX_SHARED_PEERS {
+                    return Err(Misbehavior::Spam);
+                }
+                
+                let now = clock::now_secs();
+                let mut peer_book = context.peer_book.lock().unwrap();
+                let added = addresses.into_iter().filter(|address| peer_book.add(*address, now)).count();
+                if added > 0 {
+                    println!("📒 Learned {} new peer addresses from node {}", added, peer_id);
                 }
             }
             
             P2PMessage::SyncRequest { from_height } => {
                 println!("📡 Sync request from height {}", from_height);
                 
-                let blockchain_guard = blockchain.lock().unwrap();
-                let blocks_to_send: Vec<Block> = blockchain_guard
-                    .iter()
-                    .skip(from_height as usize)
-                    .cloned()
-                    .collect();
-                drop(blockchain_guard);
+                let mut blocks_to_send = context.blockchain.lock().unwrap().blocks_from(from_height);
+                blocks_to_send.truncate(MAX_SYNC_BLOCKS);
                 
                 let sync_response = P2PMessage::SyncResponse { blocks: blocks_to_send };
-                if let Ok(json) = serde_json::to_string(&sync_response) {
-                    let _ = writeln!(stream, "{}", json);
+                if let Err(e) = connection.send(&sync_response) {
+                    println!("⚠️ Failed to send sync response: {}", e);
                 }
             }
             
             P2PMessage::SyncResponse { blocks } => {
                 println!("📡 Received {} blocks in sync response", blocks.len());
                 
-                let mut blockchain_guard = blockchain.lock().unwrap();
+                let full_batch = blocks.len() == MAX_SYNC_BLOCKS;
+                let (missing_from, rejected) = Self::import_blocks(blocks, &consensus, context);
+                if let Some(from_height) = missing_from {
+                    Self::request_sync(connection, from_height);
+                } else if full_batch {
+                    // Ответ был обрезан по лимиту — догружаем следующую порцию
+                    Self::request_sync(connection, context.blockchain.lock().unwrap().height());
+                }
+                if rejected {
+                    return Err(Misbehavior::InvalidBlock);
+                }
+            }
+            
+            P2PMessage::GetHeaders { from_height } => {
+                let headers: Vec<BlockHeader> = context.blockchain.lock().unwrap().main_chain().iter()
+                    .skip(from_height as usize)
+                    .take(MAX_SYNC_HEADERS)
+                    .map(Block::header)
+                    .collect();
+                let _ = connection.send(&P2PMessage::Headers { headers });
+            }
+            
+            P2PMessage::GetStateProof { key } => {
+                let chain = context.blockchain.lock().unwrap();
+                let committed = context.committed_state.lock().unwrap();
+                let response = match chain.main_chain().last() {
+                    Some(tip) => P2PMessage::StateProof { height: tip.height, proof: state_tree::prove(&committed, &key) },
+                    None => P2PMessage::StateProof { height: 0, proof: None },
+                };
+                drop(committed);
+                drop(chain);
+                let _ = connection.send(&response);
+            }
+            
+            P2PMessage::WantList { cids } => {
+                if cids.len() > MAX_WANTED_BLOCKS {
+                    return Err(Misbehavior::Spam);
+                }
+                let Some(storage) = &context.storage else {
+                    return Ok(());
+                };
+                // Чего нет, о том молчим: пир запросит у других
+                let blocks: Vec<P2PMessage> = {
+                    let storage = storage.lock().unwrap();
+                    cids.into_iter()
+                        .filter_map(|cid| storage.provide(&cid).map(|(data, content_type)| {
+                            P2PMessage::ContentBlock { cid, data: hex::encode(data), content_type }
+                        }))
+                        .collect()
+                };
                 for block in blocks {
-                    // Проверяем, что блок еще не существует
-                    if blockchain_guard.len() <= block.height as usize {
-                        blockchain_guard.push(block);
-                    }
+                    let _ = connection.send(&block);
+                }
+            }
+            
+            P2PMessage::Have { cids } => {
+                if cids.len() > MAX_WANTED_BLOCKS {
+                    return Err(Misbehavior::Spam);
+                }
+                let Some(storage) = &context.storage else {
+                    return Ok(());
+                };
+                let wanted = Self::accept_replicas(context, storage, peer_id, cids);
+                if !wanted.is_empty() {
+                    let _ = connection.send(&P2PMessage::WantList { cids: wanted });
+                }
+            }
+            
+            P2PMessage::ContentBlock { cid, data, content_type } => {
+                let Some(storage) = &context.storage else {
+                    return Ok(());
+                };
+                let data = hex::decode(&data).map_err(|_| Misbehavior::InvalidContent)?;
+                let next = storage.lock().unwrap().receive_block(&cid, &data, content_type).map_err(|e| {
+                    println!("⛔ Rejected content block from node {}: {}", peer_id, e);
+                    Misbehavior::InvalidContent
+                })?;
+                for chunk in next.chunks(MAX_WANTED_BLOCKS) {
+                    let _ = connection.send(&P2PMessage::WantList { cids: chunk.to_vec() });
                 }
             }
             
             P2PMessage::NewTransaction { transaction } => {
                 println!("📡 New transaction: {}", transaction.id);
                 
-                let mut pending = pending_transactions.lock().unwrap();
-                pending.push(transaction);
+                // Транзакция должна быть подписана ключом зарегистрированной активной ноды
+                let network = context.franchise_network.lock().unwrap();
+                if !consensus.validate_transaction(&transaction, &network) {
+                    println!("⛔ Rejected transaction {} with invalid signature", transaction.id);
+                    return Err(Misbehavior::InvalidTransaction);
+                }
+                drop(network);
+                
+                // Новую транзакцию пересылаем дальше, чтобы она дошла до лидера раунда
+                if Self::add_pending(context, transaction.clone()) {
+                    Self::broadcast_message(&context.peers, P2PMessage::NewTransaction { transaction });
+                }
+            }
+            
+            P2PMessage::Bft { message } => {
+                if let Err(e) = Self::drive_consensus(context, &consensus, Some(message)) {
+                    println!("⛔ Rejected consensus message from node {}: {}", peer_id, e);
+                    return Err(Misbehavior::InvalidConsensusMessage);
+                }
             }
             
             P2PMessage::NewBlock { block } => {
                 println!("📡 New block: height {}", block.height);
                 
-                let mut blockchain_guard = blockchain.lock().unwrap();
-                if blockchain_guard.len() <= block.height as usize {
-                    blockchain_guard.push(block);
+                let (missing_from, rejected) = Self::import_blocks(vec![block], &consensus, context);
+                if let Some(from_height) = missing_from {
+                    Self::request_sync(connection, from_height);
+                }
+                if rejected {
+                    return Err(Misbehavior::InvalidBlock);
                 }
             }
             
@@ -401,18 +1029,70 @@ impl P2PNode {
                 println!("📡 Unhandled message type");
             }
         }
+        Ok(())
     }
 
-    // Рассылка сообщений всем пирам
-    fn broadcast_message(peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>, message: P2PMessage) {
-        let peers_guard = peers.lock().unwrap();
-        for peer in peers_guard.values() {
-            if peer.is_connected {
-                if let Ok(mut stream) = TcpStream::connect(peer.address) {
-                    if let Ok(json) = serde_json::to_string(&message) {
-                        let _ = writeln!(stream, "{}", json);
+    fn touch_peer(peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>, peer_id: u64) {
+        if let Some(peer) = peers.lock().unwrap().get_mut(&peer_id) {
+            peer.last_ping = clock::now_secs();
+        }
+    }
+
+    // Импорт блоков от пира с полной проверкой и fork choice.
+    // Возвращает высоту, с которой нужно догрузить цепь, если не хватает родителей,
+    // и был ли среди блоков невалидный.
+    fn import_blocks(blocks: Vec<Block>, consensus: &ConsensusAlgorithm, context: &NodeContext) -> (Option<u64>, bool) {
+        let mut network = context.franchise_network.lock().unwrap();
+        let mut chain = context.blockchain.lock().unwrap();
+        let mut missing_from: Option<u64> = None;
+        let mut rejected = false;
+        
+        for block in blocks {
+            let height = block.height;
+            let old_height = chain.height();
+            match chain.import(block, consensus, context.executor.base()) {
+                Ok(BlockImport::Orphan) => {
+                    missing_from = Some(missing_from.map_or(height, |h| h.min(height)));
+                }
+                Ok(outcome) => {
+                    if let BlockImport::Reorganized { reverted, applied } = &outcome {
+                        println!("🔀 Reorganized: {} blocks reverted, {} applied", reverted.len(), applied.len());
                     }
+                    let mut committed = context.committed_state.lock().unwrap();
+                    context.executor.apply_chain_update(&mut network, &mut committed, consensus, &chain, &outcome, old_height);
+                    let mut pending = context.pending_transactions.lock().unwrap();
+                    ChainSync::reconcile_pending(&outcome, chain.main_chain(), &mut pending);
                 }
+                Err(e) => {
+                    println!("⛔ Rejected block at height {}: {}", height, e);
+                    rejected = true;
+                }
+            }
+        }
+        
+        // Точка расхождения веток неизвестна: запрашиваем цепь пира с запасом назад
+        (missing_from.map(|height| height.saturating_sub(SYNC_BACKTRACK)), rejected)
+    }
+
+    fn request_sync(connection: &PeerConnection, from_height: u64) {
+        if let Err(e) = connection.send(&P2PMessage::SyncRequest { from_height }) {
+            println!("⚠️ Failed to request sync: {}", e);
+        }
+    }
+
+    // Рассылка сообщений всем пирам через открытые соединения
+    pub fn broadcast_message(peers: &Arc<Mutex<HashMap<u64, PeerInfo>>>, message: P2PMessage) {
+        let mut connections: Vec<(u64, Arc<PeerConnection>)> = peers.lock().unwrap().values()
+            .filter(|p| p.is_connected)
+            .filter_map(|p| p.connection.as_ref().map(|c| (p.node_id, Arc::clone(c))))
+            .collect();
+        // Порядок отправки не зависит от порядка обхода HashMap
+        connections.sort_by_key(|(peer_id, _)| *peer_id);
+        
+        for (peer_id, connection) in connections {
+            if let Err(e) = connection.send(&message) {
+                println!("⚠️ Failed to send to node {}: {}", peer_id, e);
+                connection.close();
             }
         }
     }
@@ -431,7 +1111,7 @@ impl P2PNode {
         NetworkStats {
             total_nodes: peers_guard.len() + 1, // +1 для себя
             active_nodes: peers_guard.values().filter(|p| p.is_connected).count() + 1,
-            total_blocks: blockchain_guard.len() as u64,
+            total_blocks: blockchain_guard.height(),
             network_hashrate: 0.0, // Упрощенная версия
         }
     }
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::io::{ErrorKind, Read, Write};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use crate::crypto;
+
+// Формат P2P кадра: [версия протокола: 1 байт][длина: u32 big-endian][JSON сообщения].
+// Соединение начинается с обмена Hello; ноды с разной версией или другим генезисом не соединяются.
+// Затем каждая нода подписывает challenge из Hello пира своим ключом (HelloAuth), и пир регистрирует ее
+// под node_id, только если подпись сходится с ключом этой ноды в FranchiseNetwork. Легкий клиент не подписывает.
+
+pub const PROTOCOL_VERSION: u8 = 4; // 2: merkle_root в заголовке блока; 3: валидаторы, подписи родителя и state_root; 4: HelloAuth
+const CHALLENGE_BYTES: usize = 32;
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024; // 16 МБ
+const HEADER_SIZE: usize = 5;
+
+// Ошибка чтения кадра: обрыв соединения или нарушение формата пиром
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    Closed(String),
+    Malformed(String),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Closed(reason) | FrameError::Malformed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<FrameError> for String {
+    fn from(error: FrameError) -> Self {
+        error.to_string()
+    }
+}
+
+// Первое сообщение в каждом соединении
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+    pub protocol_version: u8,
+    pub node_id: u64,
+    pub listen_port: u16, // Порт для входящих соединений; вместе с IP пира дает адрес для книги пиров
+    pub block_height: u64,
+    pub genesis_hash: Option<String>, // None, пока у ноды нет ни одного блока
+    // Легкий клиент (light_client): не участвует в консенсусе и не попадает в пиры,
+    // только запрашивает заголовки и доказательства состояния
+    #[serde(default)]
+    pub light_client: bool,
+    pub challenge: String, // Случайный hex, который пир подписывает в HelloAuth
+}
+
+impl Hello {
+    pub fn new(node_id: u64, listen_port: u16, block_height: u64, genesis_hash: Option<String>) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            node_id,
+            listen_port,
+            block_height,
+            genesis_hash,
+            light_client: false,
+            challenge: crypto::random_hex(CHALLENGE_BYTES),
+        }
+    }
+
+    // У легкого клиента нет id ноды и порта для входящих соединений
+    pub fn light(block_height: u64, genesis_hash: Option<String>) -> Self {
+        Self { light_client: true, ..Self::new(0, 0, block_height, genesis_hash) }
+    }
+
+    // Проверка Hello пира относительно нашего
+    pub fn check_peer(&self, peer: &Hello) -> Result<(), String> {
+        if peer.protocol_version != self.protocol_version {
+            return Err(format!("Unsupported protocol version {} (ours is {})", peer.protocol_version, self.protocol_version));
+        }
+        if peer.node_id == self.node_id {
+            return Err("Connection to self".to_string());
+        }
+        // Нода без блоков догонит пира синхронизацией
+        if let (Some(ours), Some(theirs)) = (&self.genesis_hash, &peer.genesis_hash) {
+            if ours != theirs {
+                return Err(format!("Genesis mismatch: peer has {}", theirs));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Байты HelloAuth: challenge проверяющей ноды и id обеих сторон, чтобы подпись не годилась для другого соединения
+pub fn handshake_bytes(challenge: &str, signer: u64, verifier: u64) -> Vec<u8> {
+    format!("p2p-hello|{}|{}|{}", challenge, signer, verifier).into_bytes()
+}
+
+// Запись одного кадра
+pub fn write_frame<W: Write, T: Serialize>(writer: &mut W, message: &T) -> Result<(), String> {
+    let payload = serde_json::to_vec(message)
+        .map_err(|e| format!("Failed to serialize message: {}", e))?;
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(format!("Frame of {} bytes exceeds limit of {}", payload.len(), MAX_FRAME_SIZE));
+    }
+
+    let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+    frame.push(PROTOCOL_VERSION);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&payload);
+    writer.write_all(&frame)
+        .and_then(|_| writer.flush())
+        .map_err(|e| format!("Failed to write frame: {}", e))
+}
+
+// Чтение одного кадра; Ok(None) — пир закрыл соединение между кадрами
+pub fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, FrameError> {
+    let mut header = [0u8; HEADER_SIZE];
+    let mut filled = 0;
+    while filled < HEADER_SIZE {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(FrameError::Closed("Connection closed inside frame header".to_string())),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(FrameError::Closed(format!("Failed to read frame header: {}", e))),
+        }
+    }
+
+    if header[0] != PROTOCOL_VERSION {
+        return Err(FrameError::Malformed(format!("Unsupported frame version {}", header[0])));
+    }
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(FrameError::Malformed(format!("Frame of {} bytes exceeds limit of {}", length, MAX_FRAME_SIZE)));
+    }
+
+    let mut payload = vec![0u8; length];
+    reader.read_exact(&mut payload)
+        .map_err(|e| FrameError::Closed(format!("Failed to read frame body: {}", e)))?;
+    serde_json::from_slice(&payload)
+        .map(Some)
+        .map_err(|e| FrameError::Malformed(format!("Malformed frame: {}", e)))
+}
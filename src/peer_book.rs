@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+
+// Книга пиров: известные адреса нод, очки за нарушения и временные баны.
+// Адреса приходят из seed-списка и обмена GetPeers/Peers; книга сохраняется на диск,
+// чтобы после перезапуска нода подключалась без ручного списка адресов.
+// Баны ставятся по IP: смена порта или node_id не снимает бан.
+
+pub const MAX_BOOK_SIZE: usize = 1000;
+pub const MAX_SHARED_PEERS: usize = 100; // Адресов в одном сообщении Peers
+pub const BAN_THRESHOLD: u32 = 100;
+pub const BAN_DURATION_SECS: u64 = 24 * 3600;
+const MAX_DIAL_FAILURES: u32 = 10; // После стольких неудачных подключений подряд адрес забывается
+const SHARE_WINDOW_SECS: u64 = 3 * 3600; // Делимся только адресами, с которыми недавно было соединение
+
+// Нарушения протокола и их штраф
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misbehavior {
+    InvalidBlock,
+    InvalidTransaction,
+    MalformedFrame,
+    Spam,
+    InvalidConsensusMessage, // Чужое предложение блока, неверная подпись голоса, двойной голос
+    InvalidContent,          // Блок содержимого не совпадает со своим CID
+}
+
+impl Misbehavior {
+    pub fn penalty(self) -> u32 {
+        match self {
+            Misbehavior::InvalidBlock => 25,
+            Misbehavior::InvalidTransaction => 10,
+            Misbehavior::MalformedFrame => BAN_THRESHOLD,
+            Misbehavior::Spam => 20,
+            Misbehavior::InvalidConsensusMessage => 25,
+            Misbehavior::InvalidContent => 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerRecord {
+    pub address: SocketAddr,
+    pub node_id: Option<u64>,
+    pub last_seen: u64, // 0 — соединения еще не было
+    pub failures: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BanRecord {
+    pub score: u32,
+    pub banned_until: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PeerBook {
+    peers: HashMap<SocketAddr, PeerRecord>,
+    bans: HashMap<IpAddr, BanRecord>,
+    #[serde(skip)]
+    path: Option<PathBuf>, // None — книга только в памяти
+}
+
+impl PeerBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Пустая книга, которая сохраняется в указанный файл
+    pub fn new_at(path: PathBuf) -> Self {
+        Self { path: Some(path), ..Self::default() }
+    }
+
+    // Загрузка книги с диска; отсутствующий файл — пустая книга
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::new_at(path));
+        }
+        let content = fs::read(&path)
+            .map_err(|e| format!("Failed to read peer book: {}", e))?;
+        let mut book = serde_json::from_slice::<PeerBook>(&content)
+            .map_err(|e| format!("Corrupted peer book: {}", e))?;
+        book.path = Some(path);
+        Ok(book)
+    }
+
+    // Атомарная запись (tmp + rename)
+    pub fn save(&self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let content = serde_json::to_vec_pretty(self)
+            .map_err(|e| format!("Failed to serialize peer book: {}", e))?;
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to write peer book: {}", e))
+    }
+
+    // Новый адрес (из seed-списка или от пира); false, если не добавлен
+    pub fn add(&mut self, address: SocketAddr, now: u64) -> bool {
+        if self.peers.contains_key(&address) || self.peers.len() >= MAX_BOOK_SIZE || self.is_banned(address.ip(), now) {
+            return false;
+        }
+        self.peers.insert(address, PeerRecord { address, node_id: None, last_seen: 0, failures: 0 });
+        true
+    }
+
+    pub fn remove(&mut self, address: &SocketAddr) {
+        self.peers.remove(address);
+    }
+
+    // Успешный handshake с нодой по этому адресу
+    pub fn mark_connected(&mut self, address: SocketAddr, node_id: u64, now: u64) {
+        let record = self.peers.entry(address)
+            .or_insert(PeerRecord { address, node_id: None, last_seen: 0, failures: 0 });
+        record.node_id = Some(node_id);
+        record.last_seen = now;
+        record.failures = 0;
+    }
+
+    // Неудачное подключение; адрес, к которому долго не подключиться, забывается
+    pub fn mark_failed(&mut self, address: &SocketAddr) {
+        if let Some(record) = self.peers.get_mut(address) {
+            record.failures += 1;
+            if record.failures >= MAX_DIAL_FAILURES {
+                self.peers.remove(address);
+            }
+        }
+    }
+
+    // Адреса для исходящих подключений: сначала недавно живые, затем с меньшим числом ошибок
+    pub fn dial_candidates(&self, now: u64, exclude: &[SocketAddr], limit: usize) -> Vec<SocketAddr> {
+        let mut candidates: Vec<&PeerRecord> = self.peers.values()
+            .filter(|p| !exclude.contains(&p.address) && !self.is_banned(p.address.ip(), now))
+            .collect();
+        candidates.sort_by(|a, b| b.last_seen.cmp(&a.last_seen)
+            .then(a.failures.cmp(&b.failures))
+            .then(a.address.cmp(&b.address)));
+        candidates.into_iter().take(limit).map(|p| p.address).collect()
+    }
+
+    // Адреса для ответа на GetPeers: только проверенные соединением
+    pub fn shareable(&self, now: u64, exclude: &SocketAddr) -> Vec<SocketAddr> {
+        let mut recent: Vec<&PeerRecord> = self.peers.values()
+            .filter(|p| p.last_seen > 0 && now.saturating_sub(p.last_seen) <= SHARE_WINDOW_SECS)
+            .filter(|p| p.address != *exclude && !self.is_banned(p.address.ip(), now))
+            .collect();
+        recent.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(a.address.cmp(&b.address)));
+        recent.into_iter().take(MAX_SHARED_PEERS).map(|p| p.address).collect()
+    }
+
+    pub fn is_banned(&self, ip: IpAddr, now: u64) -> bool {
+        self.bans.get(&ip).is_some_and(|ban| ban.banned_until > now)
+    }
+
+    pub fn misbehavior_score(&self, ip: IpAddr) -> u32 {
+        self.bans.get(&ip).map(|ban| ban.score).unwrap_or(0)
+    }
+
+    // Штраф за нарушение; при достижении порога IP банится, его адреса удаляются.
+    // Возвращает true, если пир забанен.
+    pub fn penalize(&mut self, ip: IpAddr, misbehavior: Misbehavior, now: u64) -> bool {
+        let ban = self.bans.entry(ip).or_default();
+        if ban.banned_until > 0 && ban.banned_until <= now {
+            // Бан истек — счет начинается заново
+            *ban = BanRecord::default();
+        }
+        ban.score += misbehavior.penalty();
+        if ban.score < BAN_THRESHOLD {
+            return ban.banned_until > now;
+        }
+
+        ban.banned_until = now + BAN_DURATION_SECS;
+        self.peers.retain(|address, _| address.ip() != ip);
+        true
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::{Arc, Mutex};
 use std::net::{TcpListener, TcpStream};
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::thread;
 use serde::{Serialize, Deserialize};
 use crate::franchise_network::{FranchiseNetwork, NodeType, SaleItem};
@@ -11,6 +12,8 @@ pub struct RegisterNodeRequest {
     pub owner_address: String,
     pub node_type: String, // "OWNER" or "FRANCHISE"
     pub city: String,
+    #[serde(default)]
+    pub public_key: Option<String>, // Ed25519 ключ ноды в hex для подписи транзакций
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,11 +42,18 @@ pub struct ApiResponse<T> {
 pub struct PosApiServer {
     franchise_network: Arc<Mutex<FranchiseNetwork>>,
     port: u16,
+    state_file: Option<Arc<PathBuf>>, // Куда сохранять сеть после каждого изменения
 }
 
 impl PosApiServer {
     pub fn new(franchise_network: Arc<Mutex<FranchiseNetwork>>, port: u16) -> Self {
-        PosApiServer { franchise_network, port }
+        PosApiServer { franchise_network, port, state_file: None }
+    }
+
+    // Сеть сохраняется после каждого изменяющего запроса: ее балансы проведены в единый реестр
+    pub fn with_state_file(mut self, path: PathBuf) -> Self {
+        self.state_file = Some(Arc::new(path));
+        self
     }
 
     pub fn start(&self) {
@@ -56,8 +66,9 @@ impl PosApiServer {
             match stream {
                 Ok(stream) => {
                     let franchise_network = Arc::clone(&self.franchise_network);
+                    let state_file = self.state_file.clone();
                     thread::spawn(move || {
-                        Self::handle_client(stream, franchise_network);
+                        Self::handle_client(stream, franchise_network, state_file);
                     });
                 }
                 Err(e) => {
@@ -67,13 +78,13 @@ impl PosApiServer {
         }
     }
 
-    fn handle_client(mut stream: TcpStream, franchise_network: Arc<Mutex<FranchiseNetwork>>) {
+    fn handle_client(mut stream: TcpStream, franchise_network: Arc<Mutex<FranchiseNetwork>>, state_file: Option<Arc<PathBuf>>) {
         let mut buffer = [0; 4096];
         
         match stream.read(&mut buffer) {
             Ok(size) => {
                 let request = String::from_utf8_lossy(&buffer[..size]);
-                let response = Self::process_request(&request, franchise_network);
+                let response = Self::process_request(&request, franchise_network, state_file);
                 
                 let http_response = format!(
                     "HTTP/1.1 200 OK\r\n\
@@ -98,7 +109,15 @@ impl PosApiServer {
         }
     }
 
-    fn process_request(request: &str, franchise_network: Arc<Mutex<FranchiseNetwork>>) -> String {
+    // Сохранение сети после изменения, если задан файл состояния
+    fn persist(network: &FranchiseNetwork, state_file: &Option<Arc<PathBuf>>) -> Result<(), String> {
+        match state_file {
+            Some(path) => network.save(path),
+            None => Ok(()),
+        }
+    }
+
+    fn process_request(request: &str, franchise_network: Arc<Mutex<FranchiseNetwork>>, state_file: Option<Arc<PathBuf>>) -> String {
         // Handle OPTIONS request for CORS
         if request.starts_with("OPTIONS") {
             return "{}".to_string();
@@ -132,8 +151,11 @@ impl PosApiServer {
                                     }
                                 };
                                 
-                                match network.register_node(req.owner_address, node_type, req.city) {
+                                match network.register_node_with_key(req.owner_address, node_type, req.city, req.public_key) {
                                     Ok(node_id) => {
+                                        if let Err(e) = Self::persist(&network, &state_file) {
+                                            return serde_json::json!({"success": false, "error": e}).to_string();
+                                        }
                                         return serde_json::json!({
                                             "success": true,
                                             "data": {"node_id": node_id}
@@ -160,6 +182,9 @@ impl PosApiServer {
                                     req.items
                                 ) {
                                     Ok(minting) => {
+                                        if let Err(e) = Self::persist(&network, &state_file) {
+                                            return serde_json::json!({"success": false, "error": e}).to_string();
+                                        }
                                         return serde_json::json!({
                                             "success": true,
                                             "data": minting
@@ -178,6 +203,9 @@ impl PosApiServer {
                             if let Ok(req) = serde_json::from_value::<WhitelistPosRequest>(val.clone()) {
                                 let mut network = franchise_network.lock().unwrap();
                                 network.whitelist_pos(req.pos_id);
+                                if let Err(e) = Self::persist(&network, &state_file) {
+                                    return serde_json::json!({"success": false, "error": e}).to_string();
+                                }
                                 return serde_json::json!({
                                     "success": true,
                                     "data": {"message": "POS system whitelisted"}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use crate::consensus::{block_signing_bytes, Block, BlockSignature, Transaction, TransactionType};
+use crate::crypto::{self, Keypair};
+use crate::franchise_network::{FranchiseNetwork, SCALE};
+
+// Награды и штрафы валидаторов.
+//   Награда — BLOCK_REWARD_UNITS за каждый финализированный блок, поровну между подписавшими его
+//   валидаторами (Block.validator_rewards); начисляется на кошелек владельца ноды.
+//   Двойная подпись — подписи валидатора под двумя разными блоками одной высоты и одного раунда
+//   (в позднем раунде честный валидатор может перейти на другой блок). Доказательство
+//   попадает в цепь транзакцией Evidence, и с кошелька владельца ноды сжигается SLASH_PERCENT баланса.
+//   Пропуск слота — выбранный валидатор не подписал блок. Каждый пропуск в последних
+//   MISSED_SLOT_WINDOW блоках снижает репутацию ноды на MISSED_SLOT_PENALTY.
+// Награды и штрафы применяются к сети вместе с остальными транзакциями блока (executor::apply_block).
+
+pub const BLOCK_REWARD_UNITS: u64 = SCALE; // 1 токен за блок
+pub const SLASH_PERCENT: u64 = 50;
+pub const MISSED_SLOT_PENALTY: f64 = 0.05;
+pub const MISSED_SLOT_WINDOW: u64 = 1000;
+
+// Доля награды каждого подписавшего; остаток от деления получают валидаторы с меньшими id
+pub fn block_rewards(signatures: &[BlockSignature]) -> HashMap<u64, u64> {
+    let mut signers: Vec<u64> = signatures.iter().map(|s| s.validator_id).collect();
+    signers.sort_unstable();
+    signers.dedup();
+
+    let mut rewards = HashMap::new();
+    if signers.is_empty() {
+        return rewards;
+    }
+    let share = BLOCK_REWARD_UNITS / signers.len() as u64;
+    let remainder = BLOCK_REWARD_UNITS % signers.len() as u64;
+    for (index, validator_id) in signers.into_iter().enumerate() {
+        rewards.insert(validator_id, share + u64::from((index as u64) < remainder));
+    }
+    rewards
+}
+
+// Доказательство двойной подписи: две подписи валидатора под разными блоками одной высоты и раунда
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EquivocationEvidence {
+    pub validator_id: u64,
+    pub height: u64,
+    pub round: u32,
+    pub first_hash: String,
+    pub first_signature: String,
+    pub second_hash: String,
+    pub second_signature: String,
+}
+
+impl EquivocationEvidence {
+    // Раунд берется из первой подписи; подпись второй проверяется в том же раунде
+    pub fn new(first: (&str, &BlockSignature), second: (&str, &BlockSignature), height: u64) -> Self {
+        Self {
+            validator_id: first.1.validator_id,
+            height,
+            round: first.1.round,
+            first_hash: first.0.to_string(),
+            first_signature: first.1.signature.clone(),
+            second_hash: second.0.to_string(),
+            second_signature: second.1.signature.clone(),
+        }
+    }
+
+    pub fn verify(&self, network: &FranchiseNetwork) -> Result<(), String> {
+        if self.first_hash == self.second_hash {
+            return Err(format!("Evidence against node {} names the same block twice", self.validator_id));
+        }
+        let public_key = network.node_public_key(self.validator_id)
+            .ok_or_else(|| format!("Node {} has no registered key", self.validator_id))?;
+        for (hash, signature) in [(&self.first_hash, &self.first_signature), (&self.second_hash, &self.second_signature)] {
+            if !crypto::verify(public_key, &block_signing_bytes(self.height, self.round, hash, self.validator_id), signature) {
+                return Err(format!("Invalid signature of node {} on block {} in evidence", self.validator_id, hash));
+            }
+        }
+        Ok(())
+    }
+
+    // Транзакция с доказательством от ноды reporter. Id зависит только от нарушения,
+    // поэтому доказательства разных нод об одной двойной подписи дедуплицируются в пуле.
+    pub fn into_transaction(self, reporter: u64, keypair: &Keypair) -> Transaction {
+        let id = format!("evidence_{}_{}", self.validator_id, self.height);
+        let data = serde_json::to_value(&self).expect("evidence fields are always serializable");
+        let mut transaction = Transaction::new(reporter, TransactionType::Evidence, data);
+        transaction.id = id;
+        transaction.sign(keypair);
+        transaction
+    }
+
+    pub fn from_transaction(transaction: &Transaction) -> Result<Self, String> {
+        if transaction.transaction_type != TransactionType::Evidence {
+            return Err(format!("Transaction {} is not evidence", transaction.id));
+        }
+        serde_json::from_value(transaction.data.clone())
+            .map_err(|e| format!("Malformed evidence in transaction {}: {}", transaction.id, e))
+    }
+}
+
+// Награды подписавшим блок и пропуски слотов выбранных валидаторов
+pub fn apply_block(network: &mut FranchiseNetwork, block: &Block, validators: &[u64]) {
+    let mut rewards: Vec<(&u64, &u64)> = block.validator_rewards.iter().collect();
+    rewards.sort();
+    for (validator_id, units) in rewards {
+        if let Err(e) = network.mint_to_node_owner(*validator_id, *units) {
+            println!("⚠️ Reward for validator {} at height {} not paid: {}", validator_id, block.height, e);
+        }
+    }
+
+    let missed: Vec<u64> = validators.iter()
+        .filter(|id| !block.signatures.iter().any(|s| s.validator_id == **id))
+        .copied()
+        .collect();
+    if !missed.is_empty() {
+        network.missed_slots.insert(block.height, missed);
+    }
+}
+
+// Штраф по транзакции Evidence из блока на высоте block_height; возвращает сожженные units
+pub fn apply_evidence(network: &mut FranchiseNetwork, transaction: &Transaction, block_height: u64) -> Result<u64, String> {
+    let evidence = EquivocationEvidence::from_transaction(transaction)?;
+    network.slash_node(evidence.validator_id, evidence.height, block_height, SLASH_PERCENT)
+}
@@ -0,0 +1,81 @@
+use serde::{Serialize, Deserialize};
+use crate::franchise_network::FranchiseNetwork;
+use crate::merkle::{self, MerkleProof};
+
+// Дерево состояния франшизной сети для легких клиентов.
+// Листья — записи состояния в каноническом порядке: ноды по id, затем кошельки по адресу, затем чеки по sale_id.
+// Корень попадает в заголовок блока (Block.state_root), поэтому подписи валидаторов под блоком
+// подтверждают и баланс отдельного кошелька: клиенту хватает записи и пути до корня.
+// Доказать отсутствие записи нельзя — ответ «не найдено» клиент проверить не может.
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum StateKey {
+    Node(u64),
+    Wallet(String),
+    Check(String), // sale_id продажи, по которой выдан чек
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StateEntry {
+    Node { node_id: u64, public_key: Option<String>, active: bool },
+    Wallet { address: String, balance: u64 },
+    Check { sale_id: String, check_address: String, node_id: u64, recorded_at: u64, balance: u64 },
+}
+
+impl StateEntry {
+    pub fn key(&self) -> StateKey {
+        match self {
+            StateEntry::Node { node_id, .. } => StateKey::Node(*node_id),
+            StateEntry::Wallet { address, .. } => StateKey::Wallet(address.clone()),
+            StateEntry::Check { sale_id, .. } => StateKey::Check(sale_id.clone()),
+        }
+    }
+
+    fn leaf(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("state entries are always serializable")
+    }
+}
+
+// Запись состояния с путем до корня
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateProof {
+    pub entry: StateEntry,
+    pub proof: MerkleProof,
+}
+
+impl StateProof {
+    pub fn verify(&self, state_root: &str) -> bool {
+        merkle::verify(state_root, &self.entry.leaf(), &self.proof)
+    }
+}
+
+// Все записи состояния в каноническом порядке (порядок вариантов StateKey, затем ключ)
+pub fn entries(network: &FranchiseNetwork) -> Vec<StateEntry> {
+    let nodes = network.nodes.values()
+        .map(|node| StateEntry::Node { node_id: node.node_id, public_key: node.public_key.clone(), active: node.active });
+    let wallets = network.wallets.values()
+        .map(|wallet| StateEntry::Wallet { address: wallet.address.clone(), balance: wallet.balance });
+    let checks = network.sales.iter()
+        .map(|sale| StateEntry::Check {
+            sale_id: sale.sale_id.clone(),
+            check_address: sale.check_address.clone(),
+            node_id: sale.node_id,
+            recorded_at: sale.timestamp,
+            balance: network.get_wallet_balance(&sale.check_address),
+        });
+    let mut entries: Vec<StateEntry> = nodes.chain(wallets).chain(checks).collect();
+    entries.sort_by_key(StateEntry::key);
+    entries
+}
+
+pub fn root(network: &FranchiseNetwork) -> String {
+    let leaves: Vec<Vec<u8>> = entries(network).iter().map(StateEntry::leaf).collect();
+    merkle::root(&leaves)
+}
+
+pub fn prove(network: &FranchiseNetwork, key: &StateKey) -> Option<StateProof> {
+    let entries = entries(network);
+    let index = entries.iter().position(|entry| entry.key() == *key)?;
+    let leaves: Vec<Vec<u8>> = entries.iter().map(StateEntry::leaf).collect();
+    Some(StateProof { entry: entries[index].clone(), proof: merkle::proof(&leaves, index)? })
+}
@@ -0,0 +1,456 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use crate::clock;
+use crate::{Block, Blockchain, Ingredient, MenuItem, Order, OrderItem, PersonalData};
+use crate::governance::{ProposalChange, VotingStrategy};
+use crate::ledger::{SharedLedger, TokenLedger};
+use crate::tokenomics::TokenomicsPolicy;
+
+// Персистентность состояния Blockchain:
+// каждая изменяющая операция сначала дописывается в журнал (WAL) и синхронизируется на диск,
+// периодически пишется полный снимок состояния, после чего журнал обрезается.
+// Восстановление = последний снимок + воспроизведение журнала поверх него.
+
+const WAL_FILE: &str = "wal.log";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.json.tmp";
+const SNAPSHOT_VERSION: u32 = 2; // 2: балансы токенов в целых subunits
+pub const DEFAULT_SNAPSHOT_INTERVAL: u64 = 100; // Снимок каждые 100 операций
+
+// Операции, изменяющие состояние блокчейна
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WalOperation {
+    ProcessPurchase { customer: String, food_truck: String, amount: f64, food_items: Vec<String>, activation_code: String },
+    ActivateAccount { check_id: String, activation_code: String, personal_data: PersonalData },
+    SuggestMenuItem { name: String, description: String, price: f64, suggested_by: String },
+    AddMenuItemWithDetails {
+        name: String,
+        description: String,
+        price: f64,
+        availability: u32,
+        priority_rank: u32,
+        cooking_time_minutes: u32,
+        ingredients: Vec<Ingredient>,
+        suggested_by: String,
+    },
+    MakeMenuItemAvailableForVoting {
+        menu_item_id: String,
+        #[serde(default)]
+        strategy: VotingStrategy,
+    },
+    UpsertMenuItem { item: MenuItem },
+    RemoveMenuItem { menu_item_id: String },
+    CreateOrder { customer_wallet: String, items: Vec<OrderItem>, delivery_time_minutes: u32 },
+    InsertOrder { order: Order },
+    ConfirmOrder { order_id: String },
+    CancelOrder { order_id: String, reason: String },
+    CreatePurchaseContract { customer: String, amount: f64 },
+    CreateVotingContract { voter: String, menu_item_id: String },
+    ExecuteVotingContract { contract_id: String, voter: String, vote_for: bool },
+    VoteOnMenuItem { voter: String, menu_item_id: String, vote_for: bool },
+    // Подписанный лидером блок целиком: воспроизведение не требует его ключа
+    AppendBlock { block: Block },
+    UpdateRoles,
+    RegisterUserWithPhone { phone_number: String, wallet_address: String, verification_code: String },
+    VerifyPhoneNumber { phone_number: String, verification_code: String },
+    TransferBalanceFromCheck { check_id: String, to_phone_number: String },
+    UseWalletNonce { wallet: String, nonce: u64 },
+    AddFranchiseNode { node_id: String, franchise_owner: String },
+    EmitTokensForInvestors { amount: f64, investor_address: String },
+    DistributeUnclaimedTokensAnnually,
+    ScheduleTokenomics { policy: TokenomicsPolicy },
+    SubmitProposal {
+        proposer: String,
+        description: String,
+        change: ProposalChange,
+        #[serde(default)]
+        strategy: VotingStrategy,
+    },
+    VoteOnProposal { voter: String, proposal_id: String, vote_for: bool },
+    FinalizeProposal { proposal_id: String },
+    DelegateVotingPower { wallet: String, delegate: Option<String> },
+}
+
+// Запись журнала: операция + время и seed, с которыми она выполнялась,
+// чтобы воспроизведение дало те же чеки и метки времени.
+// Seed хранится открытым, поэтому секреты (коды активации и подтверждения)
+// от него не зависят: они берутся из ОС и журналируются в самой операции
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalRecord {
+    pub seq: u64,
+    pub timestamp: u64,
+    pub seed: u64,
+    pub operation: WalOperation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+    last_seq: u64,
+    created_at: u64,
+    size: usize,
+    checksum: String,
+}
+
+// Журнал операций узла
+#[derive(Debug)]
+pub struct Journal {
+    data_dir: PathBuf,
+    wal: File,
+    next_seq: u64,
+    snapshot_seq: u64,
+    snapshot_interval: u64,
+}
+
+impl Journal {
+    fn open(data_dir: &Path, next_seq: u64, snapshot_seq: u64, snapshot_interval: u64) -> Result<Self, String> {
+        let wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(data_dir.join(WAL_FILE))
+            .map_err(|e| format!("Failed to open WAL: {}", e))?;
+
+        Ok(Self {
+            data_dir: data_dir.to_path_buf(),
+            wal,
+            next_seq,
+            snapshot_seq,
+            snapshot_interval,
+        })
+    }
+
+    // Дописывает операцию в журнал и дожидается записи на диск
+    pub fn append(&mut self, timestamp: u64, seed: u64, operation: WalOperation) -> Result<u64, String> {
+        let record = WalRecord {
+            seq: self.next_seq,
+            timestamp,
+            seed,
+            operation,
+        };
+        let json = serde_json::to_string(&record)
+            .map_err(|e| format!("Failed to serialize WAL record: {}", e))?;
+        let line = format!("{} {}\n", checksum(json.as_bytes()), json);
+
+        self.wal.write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to append to WAL: {}", e))?;
+        self.wal.sync_data()
+            .map_err(|e| format!("Failed to sync WAL: {}", e))?;
+
+        self.next_seq += 1;
+        Ok(record.seq)
+    }
+
+    // Количество операций после последнего снимка
+    pub fn records_since_snapshot(&self) -> u64 {
+        self.next_seq - 1 - self.snapshot_seq
+    }
+
+    // Атомарная запись снимка (tmp + rename) и обрезка журнала
+    fn write_snapshot(&mut self, state: &Blockchain) -> Result<(), String> {
+        let body = serde_json::to_vec(state)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        let last_seq = self.next_seq - 1;
+        let header = SnapshotHeader {
+            version: SNAPSHOT_VERSION,
+            last_seq,
+            created_at: clock::now_secs(),
+            size: body.len(),
+            checksum: checksum(&body),
+        };
+        let header_json = serde_json::to_string(&header)
+            .map_err(|e| format!("Failed to serialize snapshot header: {}", e))?;
+
+        let tmp_path = self.data_dir.join(SNAPSHOT_TMP_FILE);
+        let mut tmp = File::create(&tmp_path)
+            .map_err(|e| format!("Failed to create snapshot: {}", e))?;
+        tmp.write_all(header_json.as_bytes())
+            .and_then(|_| tmp.write_all(b"\n"))
+            .and_then(|_| tmp.write_all(&body))
+            .and_then(|_| tmp.sync_all())
+            .map_err(|e| format!("Failed to write snapshot: {}", e))?;
+        drop(tmp);
+
+        fs::rename(&tmp_path, self.data_dir.join(SNAPSHOT_FILE))
+            .map_err(|e| format!("Failed to install snapshot: {}", e))?;
+        sync_dir(&self.data_dir)?;
+
+        // Все записи до last_seq уже в снимке. Если упадем до обрезки,
+        // при восстановлении они будут пропущены по номеру.
+        self.wal.set_len(0)
+            .and_then(|_| self.wal.sync_all())
+            .map_err(|e| format!("Failed to truncate WAL: {}", e))?;
+
+        self.snapshot_seq = last_seq;
+        Ok(())
+    }
+}
+
+// Guard операции: держит замороженное время и номер операции в едином реестре до конца операции
+pub struct OperationGuard {
+    _clock: Option<clock::FrozenClock>,
+    ledger: Option<(SharedLedger, &'static str)>,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if let Some((ledger, namespace)) = &self.ledger {
+            ledger.lock().unwrap().end_operation(namespace);
+        }
+    }
+}
+
+// Создание нового хранилища для свежего блокчейна (записывает начальный снимок)
+pub fn initialize(data_dir: &Path, snapshot_interval: u64, mut blockchain: Blockchain) -> Result<Blockchain, String> {
+    fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Failed to create data dir: {}", e))?;
+    if data_dir.join(SNAPSHOT_FILE).exists() {
+        return Err(format!("Storage in {} is already initialized", data_dir.display()));
+    }
+
+    let mut journal = Journal::open(data_dir, 1, 0, snapshot_interval)?;
+    journal.write_snapshot(&blockchain)?;
+    blockchain.journal = Some(Arc::new(Mutex::new(journal)));
+    Ok(blockchain)
+}
+
+// Восстановление состояния: снимок + журнал.
+// Единый реестр, если он есть, подключается до воспроизведения: проводки операций, которые не попали
+// в него до сбоя, дописываются. Сверку с восстановленным состоянием делает attach_ledger.
+// Ok(None) — хранилище пустое, Err — данные повреждены и узел не должен стартовать.
+pub fn recover(data_dir: &Path, snapshot_interval: u64, ledger: Option<SharedLedger>) -> Result<Option<Blockchain>, String> {
+    let (header, mut blockchain) = match read_snapshot(data_dir)? {
+        Some(snapshot) => snapshot,
+        None => {
+            let wal_path = data_dir.join(WAL_FILE);
+            if fs::metadata(&wal_path).map(|m| m.len() > 0).unwrap_or(false) {
+                return Err("WAL found without a snapshot".to_string());
+            }
+            return Ok(None);
+        }
+    };
+
+    blockchain.ledger = ledger;
+    let mut last_seq = header.last_seq;
+    for record in read_wal(data_dir)? {
+        if record.seq <= header.last_seq {
+            continue; // Уже учтено в снимке
+        }
+        if record.seq != last_seq + 1 {
+            return Err(format!("WAL gap: expected record {}, found {}", last_seq + 1, record.seq));
+        }
+        blockchain.replay(&record);
+        last_seq = record.seq;
+    }
+
+    let journal = Journal::open(data_dir, last_seq + 1, header.last_seq, snapshot_interval)?;
+    blockchain.journal = Some(Arc::new(Mutex::new(journal)));
+    Ok(Some(blockchain))
+}
+
+fn read_snapshot(data_dir: &Path) -> Result<Option<(SnapshotHeader, Blockchain)>, String> {
+    let path = data_dir.join(SNAPSHOT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut bytes = Vec::new();
+    File::open(&path)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| format!("Failed to read snapshot: {}", e))?;
+
+    let newline = bytes.iter().position(|b| *b == b'\n')
+        .ok_or("Snapshot header is missing")?;
+    let header: SnapshotHeader = serde_json::from_slice(&bytes[..newline])
+        .map_err(|e| format!("Invalid snapshot header: {}", e))?;
+    if header.version != SNAPSHOT_VERSION {
+        return Err(format!("Unsupported snapshot version {}", header.version));
+    }
+
+    let body = &bytes[newline + 1..];
+    if body.len() != header.size || checksum(body) != header.checksum {
+        return Err("Snapshot checksum mismatch".to_string());
+    }
+
+    let blockchain = serde_json::from_slice(body)
+        .map_err(|e| format!("Invalid snapshot body: {}", e))?;
+    Ok(Some((header, blockchain)))
+}
+
+fn read_wal(data_dir: &Path) -> Result<Vec<WalRecord>, String> {
+    let path = data_dir.join(WAL_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .map_err(|e| format!("Failed to read WAL: {}", e))?;
+
+    // Последняя строка без перевода строки — оборванная запись
+    if !content.is_empty() && !content.ends_with('\n') {
+        return Err("WAL has a torn record at the tail".to_string());
+    }
+
+    let mut records = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let (sum, json) = line.split_once(' ')
+            .ok_or(format!("WAL record {} is malformed", index + 1))?;
+        if checksum(json.as_bytes()) != sum {
+            return Err(format!("WAL record {} is corrupted", index + 1));
+        }
+        let record: WalRecord = serde_json::from_str(json)
+            .map_err(|e| format!("WAL record {} is unreadable: {}", index + 1, e))?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+pub fn checksum(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hex::encode(&hasher.finalize()[..8])
+}
+
+pub fn sync_dir(dir: &Path) -> Result<(), String> {
+    File::open(dir)
+        .and_then(|d| d.sync_all())
+        .map_err(|e| format!("Failed to sync data dir: {}", e))
+}
+
+// Журналирование на стороне блокчейна
+impl Blockchain {
+    // Начало изменяющей операции: запись в WAL, фиксация времени и seed.
+    // Вложенные вызовы (и воспроизведение журнала) ничего не пишут.
+    pub fn begin_operation(&self, operation: WalOperation) -> Result<OperationGuard, String> {
+        if clock::is_frozen() {
+            return Ok(OperationGuard { _clock: None, ledger: None });
+        }
+
+        let timestamp = clock::now_secs();
+        let seed = fastrand::u64(..);
+        let mut ledger = None;
+        if let Some(journal) = &self.journal {
+            let seq = journal.lock().unwrap().append(timestamp, seed, operation)?;
+            ledger = self.begin_ledger_operation(seq);
+        }
+
+        fastrand::seed(seed);
+        Ok(OperationGuard { _clock: Some(clock::freeze(timestamp)), ledger })
+    }
+
+    // Проводки в едином реестре помечаются номером операции в WAL
+    fn begin_ledger_operation(&self, seq: u64) -> Option<(SharedLedger, &'static str)> {
+        let ledger = self.ledger.as_ref()?;
+        ledger.lock().unwrap().begin_operation(self.namespace(), seq);
+        Some((Arc::clone(ledger), self.namespace()))
+    }
+
+    // Принудительный снимок состояния
+    pub fn checkpoint(&mut self) -> Result<(), String> {
+        let journal = match &self.journal {
+            Some(journal) => Arc::clone(journal),
+            None => return Ok(()),
+        };
+        let mut journal = journal.lock().unwrap();
+        journal.write_snapshot(self)
+    }
+
+    // Снимок, если с предыдущего накопилось достаточно операций
+    pub fn checkpoint_if_due(&mut self) -> Result<bool, String> {
+        let due = match &self.journal {
+            Some(journal) => {
+                let journal = journal.lock().unwrap();
+                journal.records_since_snapshot() >= journal.snapshot_interval
+            }
+            None => false,
+        };
+        if due {
+            self.checkpoint()?;
+        }
+        Ok(due)
+    }
+
+    // Повторное выполнение записи журнала с исходными временем и seed
+    fn replay(&mut self, record: &WalRecord) {
+        let _clock = clock::freeze(record.timestamp);
+        let _ledger = OperationGuard { _clock: None, ledger: self.begin_ledger_operation(record.seq) };
+        fastrand::seed(record.seed);
+
+        // Результат не важен: операция, завершившаяся ошибкой при записи,
+        // так же завершится ошибкой и при воспроизведении
+        let operation = record.operation.clone();
+        let _ = match operation {
+            WalOperation::ProcessPurchase { customer, food_truck, amount, food_items, activation_code } => {
+                self.process_purchase_with_code(customer, food_truck, amount, food_items, activation_code).map(|_| ())
+            }
+            WalOperation::ActivateAccount { check_id, activation_code, personal_data } => {
+                self.activate_account(&check_id, &activation_code, personal_data)
+            }
+            WalOperation::SuggestMenuItem { name, description, price, suggested_by } => {
+                self.suggest_menu_item(name, description, price, suggested_by)
+            }
+            WalOperation::AddMenuItemWithDetails { name, description, price, availability, priority_rank, cooking_time_minutes, ingredients, suggested_by } => {
+                self.add_menu_item_with_details(name, description, price, availability, priority_rank, cooking_time_minutes, ingredients, suggested_by)
+            }
+            WalOperation::MakeMenuItemAvailableForVoting { menu_item_id, strategy } => {
+                self.make_menu_item_available_for_voting(menu_item_id, strategy)
+            }
+            WalOperation::UpsertMenuItem { item } => self.upsert_menu_item(item),
+            WalOperation::RemoveMenuItem { menu_item_id } => self.remove_menu_item(menu_item_id),
+            WalOperation::CreateOrder { customer_wallet, items, delivery_time_minutes } => {
+                self.create_order(customer_wallet, items, delivery_time_minutes).map(|_| ())
+            }
+            WalOperation::InsertOrder { order } => self.insert_order(order),
+            WalOperation::ConfirmOrder { order_id } => self.confirm_order(order_id),
+            WalOperation::CancelOrder { order_id, reason } => self.cancel_order(order_id, reason),
+            WalOperation::CreatePurchaseContract { customer, amount } => {
+                self.create_purchase_contract(customer, amount).map(|_| ())
+            }
+            WalOperation::CreateVotingContract { voter, menu_item_id } => {
+                self.create_voting_contract(voter, menu_item_id).map(|_| ())
+            }
+            WalOperation::ExecuteVotingContract { contract_id, voter, vote_for } => {
+                self.execute_voting_contract(contract_id, voter, vote_for)
+            }
+            WalOperation::VoteOnMenuItem { voter, menu_item_id, vote_for } => {
+                self.vote_on_menu_item(voter, menu_item_id, vote_for)
+            }
+            WalOperation::AppendBlock { block } => self.append_block(block),
+            WalOperation::UpdateRoles => self.update_roles(),
+            WalOperation::RegisterUserWithPhone { phone_number, wallet_address, verification_code } => {
+                self.register_user_with_code(phone_number, wallet_address, verification_code).map(|_| ())
+            }
+            WalOperation::VerifyPhoneNumber { phone_number, verification_code } => {
+                self.verify_phone_number(phone_number, verification_code)
+            }
+            WalOperation::TransferBalanceFromCheck { check_id, to_phone_number } => {
+                self.transfer_balance_from_check(check_id, to_phone_number).map(|_| ())
+            }
+            WalOperation::UseWalletNonce { wallet, nonce } => self.use_wallet_nonce(wallet, nonce),
+            WalOperation::AddFranchiseNode { node_id, franchise_owner } => {
+                self.add_franchise_node(node_id, franchise_owner)
+            }
+            WalOperation::EmitTokensForInvestors { amount, investor_address } => {
+                self.emit_tokens_for_investors(amount, investor_address)
+            }
+            WalOperation::DistributeUnclaimedTokensAnnually => {
+                self.distribute_unclaimed_tokens_annually().map(|_| ())
+            }
+            WalOperation::ScheduleTokenomics { policy } => self.schedule_tokenomics(policy),
+            WalOperation::SubmitProposal { proposer, description, change, strategy } => {
+                self.submit_proposal(proposer, description, change, strategy).map(|_| ())
+            }
+            WalOperation::VoteOnProposal { voter, proposal_id, vote_for } => {
+                self.vote_on_proposal(voter, proposal_id, vote_for)
+            }
+            WalOperation::FinalizeProposal { proposal_id } => self.finalize_proposal(proposal_id).map(|_| ()),
+            WalOperation::DelegateVotingPower { wallet, delegate } => self.delegate_voting_power(wallet, delegate),
+        };
+    }
+}
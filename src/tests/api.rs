@@ -20,3 +20,170 @@ fn api_request_response_serde_roundtrip() {
 }
 
 
+fn signed(keypair: &crypto::Keypair, nonce: u64, request: &ApiRequest) -> ApiRequest {
+    let payload = serde_json::to_string(request).unwrap();
+    let signature = keypair.sign(&signed_request_bytes(nonce, &payload));
+    ApiRequest::Signed { payload, public_key: keypair.public_key_hex(), nonce, signature }
+}
+
+fn blockchain_with_menu() -> Arc<Mutex<Blockchain>> {
+    let mut bc = Blockchain::new("Owner".to_string());
+    bc.add_menu_item_with_details("Burger".to_string(), "Beef".to_string(), 12.5, 10, 1, 10, vec![], "Owner".to_string()).unwrap();
+    Arc::new(Mutex::new(bc))
+}
+
+fn order_request(wallet: &str, bc: &Arc<Mutex<Blockchain>>) -> ApiRequest {
+    let menu_item_id = bc.lock().unwrap().menu_items[0].id.clone();
+    ApiRequest::CreateOrder {
+        customer_wallet: wallet.to_string(),
+        items: vec![OrderItem { menu_item_id, quantity: 1 }],
+        delivery_time_minutes: 30,
+    }
+}
+
+#[test]
+fn wallet_operations_require_signature_of_that_wallet() {
+    let bc = blockchain_with_menu();
+    let alice = crypto::Keypair::generate();
+    let mallory = crypto::Keypair::generate();
+    let alice_wallet = crypto::wallet_address(&alice.public_key_hex()).unwrap();
+
+    // Без подписи нельзя действовать от имени кошелька
+    match ApiServer::process_request(order_request(&alice_wallet, &bc), Arc::clone(&bc)) {
+        ApiResponse::Error { message } => assert!(message.contains("must be signed")),
+        other => panic!("unsigned order accepted: {:?}", other),
+    }
+
+    // Чужой ключ не подходит для кошелька Alice
+    match ApiServer::process_request(signed(&mallory, 1, &order_request(&alice_wallet, &bc)), Arc::clone(&bc)) {
+        ApiResponse::Error { message } => assert!(message.contains("is signed by")),
+        other => panic!("forged order accepted: {:?}", other),
+    }
+    assert!(bc.lock().unwrap().orders.is_empty());
+
+    let order_id = match ApiServer::process_request(signed(&alice, 1, &order_request(&alice_wallet, &bc)), Arc::clone(&bc)) {
+        ApiResponse::OrderCreated { order } => order.id,
+        other => panic!("signed order rejected: {:?}", other),
+    };
+
+    // Mallory не может отменить заказ Alice, подписав запрос своим ключом
+    let cancel = ApiRequest::CancelOrder { order_id: order_id.clone(), reason: "grief".to_string(), customer_wallet: alice_wallet.clone() };
+    assert!(matches!(ApiServer::process_request(signed(&mallory, 1, &cancel), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&alice, 2, &cancel), Arc::clone(&bc)), ApiResponse::OrderCancelled { success: true }));
+}
+
+#[test]
+fn signed_requests_cannot_be_replayed_or_tampered() {
+    let bc = blockchain_with_menu();
+    let alice = crypto::Keypair::generate();
+    let alice_wallet = crypto::wallet_address(&alice.public_key_hex()).unwrap();
+
+    let request = signed(&alice, 5, &order_request(&alice_wallet, &bc));
+    assert!(matches!(ApiServer::process_request(request.clone(), Arc::clone(&bc)), ApiResponse::OrderCreated { .. }));
+
+    // Повтор того же запроса и меньший nonce отклоняются
+    assert!(matches!(ApiServer::process_request(request, Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&alice, 4, &order_request(&alice_wallet, &bc)), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert_eq!(bc.lock().unwrap().orders.len(), 1);
+
+    // Подмена содержимого после подписи
+    let tampered = match signed(&alice, 6, &order_request(&alice_wallet, &bc)) {
+        ApiRequest::Signed { payload, public_key, nonce, signature } => ApiRequest::Signed {
+            payload: payload.replace("\"delivery_time_minutes\":30", "\"delivery_time_minutes\":1"),
+            public_key,
+            nonce,
+            signature,
+        },
+        _ => unreachable!(),
+    };
+    match ApiServer::process_request(tampered, Arc::clone(&bc)) {
+        ApiResponse::Error { message } => assert_eq!(message, "Invalid request signature"),
+        other => panic!("tampered request accepted: {:?}", other),
+    }
+
+    // Nonce 6 не был израсходован отклоненным запросом
+    assert!(matches!(ApiServer::process_request(signed(&alice, 6, &order_request(&alice_wallet, &bc)), Arc::clone(&bc)), ApiResponse::OrderCreated { .. }));
+    assert_eq!(bc.lock().unwrap().wallet_nonces[&alice_wallet], 6);
+}
+
+#[test]
+fn check_transfer_is_signed_by_wallet_bound_to_phone() {
+    let bc = blockchain_with_menu();
+    let alice = crypto::Keypair::generate();
+    let mallory = crypto::Keypair::generate();
+    let alice_wallet = crypto::wallet_address(&alice.public_key_hex()).unwrap();
+
+    let register = ApiRequest::RegisterUserWithPhone { phone_number: "+995555".to_string(), wallet_address: alice_wallet.clone() };
+    let code = match ApiServer::process_request(signed(&alice, 1, &register), Arc::clone(&bc)) {
+        ApiResponse::UserRegistered { verification_code } => verification_code,
+        other => panic!("registration rejected: {:?}", other),
+    };
+    bc.lock().unwrap().verify_phone_number("+995555".to_string(), code).unwrap();
+
+    // Другие покупатели, чтобы получатель не превысил лимит владения
+    for i in 0..5 {
+        bc.lock().unwrap().process_purchase(format!("Other{}", i), "Truck1".to_string(), 10.0, vec!["Tea".to_string()]).unwrap();
+    }
+    let check = bc.lock().unwrap()
+        .process_purchase("Customer".to_string(), "Truck1".to_string(), 10.0, vec!["Burger".to_string()]).unwrap();
+    let transfer = ApiRequest::TransferBalanceFromCheck {
+        check_id: check.check_id.clone(),
+        activation_code: check.activation_code.clone(),
+        to_phone_number: "+995555".to_string(),
+    };
+
+    // Без кода активации с чека и на незарегистрированный телефон перевод не принимается даже с подписью
+    let guessed = ApiRequest::TransferBalanceFromCheck {
+        check_id: check.check_id.clone(),
+        activation_code: "000000".to_string(),
+        to_phone_number: "+995555".to_string(),
+    };
+    match ApiServer::process_request(signed(&alice, 2, &guessed), Arc::clone(&bc)) {
+        ApiResponse::Error { message } => assert_eq!(message, "Invalid activation code"),
+        other => panic!("transfer without activation code accepted: {:?}", other),
+    }
+    let unregistered = ApiRequest::TransferBalanceFromCheck {
+        check_id: check.check_id.clone(),
+        activation_code: check.activation_code.clone(),
+        to_phone_number: "+995000".to_string(),
+    };
+    assert!(matches!(ApiServer::process_request(unregistered, Arc::clone(&bc)), ApiResponse::Error { .. }));
+
+    assert!(matches!(ApiServer::process_request(transfer.clone(), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&mallory, 1, &transfer), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&alice, 2, &transfer), Arc::clone(&bc)), ApiResponse::BalanceTransferred { .. }));
+}
+
+#[test]
+fn operator_requests_require_the_operator_wallet() {
+    let operator = crypto::Keypair::generate();
+    let mallory = crypto::Keypair::generate();
+    let operator_wallet = crypto::wallet_address(&operator.public_key_hex()).unwrap();
+    let bc = Arc::new(Mutex::new(Blockchain::new(operator_wallet.clone())));
+    let add_node = ApiRequest::AddFranchiseNode { node_id: "Truck_Franchise".to_string(), franchise_owner: "Bob".to_string() };
+    let emit = ApiRequest::EmitTokensForInvestors { amount: 100.0, investor_address: "Investor".to_string() };
+
+    for request in [&add_node, &emit, &ApiRequest::ConfirmOrder { order_id: "order".to_string() }] {
+        match ApiServer::process_request(request.clone(), Arc::clone(&bc)) {
+            ApiResponse::Error { message } => assert!(message.contains("operator wallet")),
+            other => panic!("unsigned operator request accepted: {:?}", other),
+        }
+    }
+    match ApiServer::process_request(signed(&mallory, 1, &add_node), Arc::clone(&bc)) {
+        ApiResponse::Error { message } => assert!(message.contains("Operator request is signed by")),
+        other => panic!("operator request from another wallet accepted: {:?}", other),
+    }
+    assert!(bc.lock().unwrap().franchise_nodes.is_empty());
+
+    assert!(matches!(ApiServer::process_request(signed(&operator, 1, &add_node), Arc::clone(&bc)), ApiResponse::FranchiseNodeAdded { success: true }));
+    assert!(matches!(ApiServer::process_request(signed(&operator, 2, &emit), Arc::clone(&bc)), ApiResponse::TokensEmitted { success: true }));
+
+    // Предложение позиции меню — от имени предложившего кошелька
+    let suggest = ApiRequest::AddMenuItem {
+        name: "Khachapuri".to_string(), description: "Cheese".to_string(), price: 9.0, availability: 5,
+        priority_rank: 1, cooking_time_minutes: 15, ingredients: vec![], suggested_by: operator_wallet,
+    };
+    assert!(matches!(ApiServer::process_request(suggest.clone(), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&mallory, 2, &suggest), Arc::clone(&bc)), ApiResponse::Error { .. }));
+    assert!(matches!(ApiServer::process_request(signed(&operator, 3, &suggest), Arc::clone(&bc)), ApiResponse::MenuItemAdded { success: true }));
+}
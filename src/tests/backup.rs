@@ -0,0 +1,133 @@
+use crate::*;
+use crate::backup::{self, BackupKey};
+use crate::franchise_network::{FranchiseNetwork, NodeType};
+use crate::ipfs_storage::PinReason;
+use crate::tests::fixtures::{add_validators, holder};
+use std::fs;
+use std::path::PathBuf;
+
+const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+fn temp_backup_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("backup_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn node_state() -> (Blockchain, FranchiseNetwork) {
+    // Лидерами слота могут стать только держатели с ключами на ноде, доля фонда ниже min_stake
+    let mut bc = Blockchain::new(holder("Alice"));
+    bc.min_stake = 50 * SCALE;
+    add_validators(&mut bc, &["Alice", "Customer1"]);
+    bc.process_purchase(holder("Customer1"), "Truck1".to_string(), 400.0, vec!["Burger".to_string()]).unwrap();
+    bc.produce_block().unwrap();
+    let mut network = FranchiseNetwork::new("master".to_string());
+    network.register_node("Bob".to_string(), NodeType::FRANCHISE, "Tbilisi".to_string()).unwrap();
+    (bc, network)
+}
+
+fn chain_hashes(bc: &Blockchain) -> Vec<String> {
+    bc.chain.iter().map(|block| block.hash.clone()).collect()
+}
+
+#[test]
+fn backup_restores_node_state() {
+    let dir = temp_backup_dir("roundtrip");
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let key = BackupKey::from_hex(KEY).unwrap();
+    let (bc, network) = node_state();
+
+    let cid = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    assert_eq!(backup::latest_backup(&storage), Some(cid.clone()));
+
+    // Восстановление после "перезапуска" только с диска
+    drop(storage);
+    let storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let restored = backup::restore_from_backup(&storage, &key, &cid).unwrap();
+    assert_eq!(chain_hashes(&restored.blockchain), chain_hashes(&bc));
+    assert_eq!(restored.blockchain.token_holders.len(), bc.token_holders.len());
+    assert_eq!(restored.franchise_network.nodes.len(), 1);
+    assert_eq!(restored.franchise_network.nodes[&1].owner_address, "Bob");
+    assert!(restored.manifest.previous.is_none());
+}
+
+#[test]
+fn incremental_backup_reuses_unchanged_parts() {
+    let dir = temp_backup_dir("incremental");
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let key = BackupKey::from_hex(KEY).unwrap();
+    let (bc, mut network) = node_state();
+
+    let first = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    let first_manifest = backup::read_manifest(&storage, &key, &first).unwrap();
+    // Без изменений новый бэкап не пишет ни одной части
+    let second = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    let second_manifest = backup::read_manifest(&storage, &key, &second).unwrap();
+    assert_eq!(second_manifest.previous, Some(first.clone()));
+    assert_eq!(second_manifest.new_parts(Some(&first_manifest)), 0);
+
+    network.register_node("Carol".to_string(), NodeType::FRANCHISE, "Batumi".to_string()).unwrap();
+    let third = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    let third_manifest = backup::read_manifest(&storage, &key, &third).unwrap();
+    // Изменились только nodes, wallets и next_node_id
+    assert_eq!(third_manifest.new_parts(Some(&second_manifest)), 3);
+    assert_eq!(backup::restore_from_backup(&storage, &key, &third).unwrap().franchise_network.nodes.len(), 2);
+    assert_eq!(backup::restore_from_backup(&storage, &key, &first).unwrap().franchise_network.nodes.len(), 1);
+}
+
+#[test]
+fn backup_requires_the_operator_key() {
+    let dir = temp_backup_dir("wrong_key");
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let (bc, network) = node_state();
+    let cid = backup::create_backup(&mut storage, &BackupKey::from_hex(KEY).unwrap(), &bc, &network, &[]).unwrap();
+
+    let other = BackupKey::from_hex(&"ff".repeat(32)).unwrap();
+    assert!(backup::restore_from_backup(&storage, &other, &cid).err().unwrap().contains("wrong key"));
+    assert!(BackupKey::from_hex("abcd").is_err());
+    // Содержимое в хранилище зашифровано
+    let owner = holder("Alice");
+    assert!(!storage.retrieve_bytes(&cid).unwrap().windows(owner.len()).any(|w| w == owner.as_bytes()));
+}
+
+#[test]
+fn tampered_or_missing_parts_are_rejected() {
+    let dir = temp_backup_dir("tampered");
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let key = BackupKey::from_hex(KEY).unwrap();
+    let (bc, network) = node_state();
+    let cid = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    let manifest = backup::read_manifest(&storage, &key, &cid).unwrap();
+    let parts: Vec<String> = manifest.components.iter().flat_map(|c| &c.parts).map(|part| part.cid.clone()).collect();
+
+    let tampered = dir.join("blocks").join(&parts[0]);
+    let mut bytes = fs::read(&tampered).unwrap();
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    fs::write(&tampered, bytes).unwrap();
+    assert!(backup::restore_from_backup(&storage, &key, &cid).err().unwrap().contains("integrity"));
+
+    fs::remove_file(&tampered).unwrap();
+    assert!(backup::restore_from_backup(&storage, &key, &cid).err().unwrap().contains("not in the local store"));
+}
+
+#[test]
+fn garbage_collection_keeps_backup_parts() {
+    let dir = temp_backup_dir("gc");
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let key = BackupKey::from_hex(KEY).unwrap();
+    let (bc, mut network) = node_state();
+    let first = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+    network.register_node("Carol".to_string(), NodeType::FRANCHISE, "Batumi".to_string()).unwrap();
+    let second = backup::create_backup(&mut storage, &key, &bc, &network, &[]).unwrap();
+
+    assert_eq!(storage.collect_garbage().unwrap(), 0);
+    assert!(backup::restore_from_backup(&storage, &key, &first).is_ok());
+    assert!(backup::restore_from_backup(&storage, &key, &second).is_ok());
+
+    // После снятия закрепления старый бэкап собирается, новый остается целым
+    storage.unpin(&first, &PinReason::Backup { created_at: backup::read_manifest(&storage, &key, &first).unwrap().created_at }).unwrap();
+    assert!(storage.collect_garbage().unwrap() > 0);
+    assert!(backup::restore_from_backup(&storage, &key, &first).is_err());
+    assert!(backup::restore_from_backup(&storage, &key, &second).is_ok());
+}
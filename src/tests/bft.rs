@@ -0,0 +1,201 @@
+use crate::bft::{Bft, BftEnv, BftMessage, BftOutput, Proposal, Vote, VoteKind};
+use crate::chain_sync::ChainSync;
+use crate::clock;
+use crate::consensus::{Block, BlockSignature, ConsensusAlgorithm, Transaction, TransactionType};
+use crate::franchise_network::FranchiseNetwork;
+use crate::network_simulator::{SimConfig, Simulator, SIM_EPOCH_SECS};
+use crate::rewards::EquivocationEvidence;
+use crate::state_tree;
+use crate::tests::fixtures::{franchise_validators, node_key};
+
+// Четыре валидатора в разных городах, кворум — 3 подписи
+fn franchise_network() -> (FranchiseNetwork, Vec<u64>) {
+    let (network, nodes) = franchise_validators(&["Tbilisi", "Batumi", "Kutaisi", "Rustavi"]);
+    (network, nodes.into_iter().map(|(node_id, _)| node_id).collect())
+}
+
+fn sale(node_id: u64, tag: &str) -> Transaction {
+    let mut tx = Transaction::new(node_id, TransactionType::Sale, serde_json::json!({"tag": tag}));
+    tx.id = format!("tx_{}_{}", node_id, tag);
+    tx.sign(&node_key(node_id));
+    tx
+}
+
+fn simulator() -> (Simulator, Vec<u64>) {
+    let (network, node_ids) = franchise_network();
+    let keypairs = node_ids.iter().map(|id| (*id, node_key(*id))).collect();
+    let config = SimConfig { consensus: true, ..SimConfig::default() };
+    let mut sim = Simulator::new(config, &network, keypairs);
+    assert!(sim.run_until(120_000, |sim| node_ids.iter().all(|id| sim.connected_peers(*id).len() == 3)));
+    (sim, node_ids)
+}
+
+fn chain(sim: &Simulator, node_id: u64) -> Vec<Block> {
+    sim.node(node_id).blockchain.lock().unwrap().main_chain().to_vec()
+}
+
+fn signers(block: &Block) -> Vec<u64> {
+    block.signatures.iter().map(|s| s.validator_id).collect()
+}
+
+#[test]
+fn validators_finalize_block_with_quorum_signatures() {
+    let (mut sim, ids) = simulator();
+    let tx = sale(ids[3], "first");
+    sim.submit_transaction(ids[3], tx.clone()).unwrap();
+
+    assert!(sim.run_until(30_000, |sim| ids.iter().all(|id| chain(sim, *id).len() == 1)));
+    assert!(sim.converged());
+    let block = &chain(&sim, ids[0])[0];
+    assert_eq!(block.transactions, vec![tx]);
+    // Лидер высоты 0 — валидатор с наименьшим id, блок подписан кворумом
+    assert_eq!(sim.node(ids[0]).bft.lock().unwrap().validators(), &ids[..]);
+    assert!(signers(block).len() >= 3);
+    assert!(ids.iter().all(|id| sim.node(*id).pending_transactions.lock().unwrap().is_empty()));
+
+    // Следующая высота — следующий лидер
+    sim.submit_transaction(ids[0], sale(ids[0], "second")).unwrap();
+    assert!(sim.run_until(30_000, |sim| ids.iter().all(|id| chain(sim, *id).len() == 2)));
+    assert!(sim.converged());
+    assert_eq!(sim.node(ids[2]).bft.lock().unwrap().leader(0), Some(ids[2]));
+
+    // Копии первого блока у реплик заменены подписями от лидера второго, состояние совпадает
+    let parent_signatures = chain(&sim, ids[0])[1].parent_signatures.clone();
+    assert!(ids.iter().all(|id| chain(&sim, *id)[0].signatures == parent_signatures));
+    let roots: Vec<String> = ids.iter().map(|id| state_tree::root(&sim.node(*id).committed_state.lock().unwrap())).collect();
+    assert!(roots.iter().all(|root| *root == chain(&sim, ids[0])[1].state_root));
+}
+
+#[test]
+fn next_leader_takes_over_when_leader_is_cut_off() {
+    let (mut sim, ids) = simulator();
+    let leader = ids[0];
+    sim.partition(&[&[leader], &ids[1..]]);
+    sim.submit_transaction(ids[1], sale(ids[1], "rotated")).unwrap();
+
+    assert!(sim.run_until(60_000, |sim| ids[1..].iter().all(|id| chain(sim, *id).len() == 1)));
+    let block = &chain(&sim, ids[1])[0];
+    assert_eq!(signers(block), ids[1..].to_vec());
+    // Новая высота начинается с раунда 0
+    assert_eq!(sim.node(ids[1]).bft.lock().unwrap().round(), 0);
+    assert!(chain(&sim, leader).is_empty());
+
+    // Отрезанный лидер догоняет цепь после восстановления связи
+    sim.heal();
+    assert!(sim.run_until(300_000, |sim| sim.converged()));
+    assert_eq!(chain(&sim, leader).len(), 1);
+}
+
+#[test]
+fn no_block_is_finalized_without_quorum() {
+    let (mut sim, ids) = simulator();
+    sim.partition(&[&ids[..2], &ids[2..]]);
+    sim.submit_transaction(ids[0], sale(ids[0], "split")).unwrap();
+    sim.submit_transaction(ids[2], sale(ids[2], "split")).unwrap();
+
+    sim.run_for(120_000);
+    assert!(ids.iter().all(|id| chain(&sim, *id).is_empty()));
+
+    sim.heal();
+    assert!(sim.run_until(300_000, |sim| ids.iter().all(|id| chain(sim, *id).len() == 1) && sim.converged()));
+    assert!(signers(&chain(&sim, ids[0])[0]).len() >= 3);
+}
+
+// Движок одной ноды без сети: проверка чужих сообщений
+fn with_engine(test: impl FnOnce(&mut Bft, &BftEnv, &[u64])) {
+    let _clock = clock::freeze(SIM_EPOCH_SECS);
+    let (network, ids) = franchise_network();
+    let chain = ChainSync::new();
+    let consensus = ConsensusAlgorithm::new();
+    let keypair = node_key(ids[1]);
+    let pending = vec![sale(ids[1], "pending")];
+    let env = BftEnv { chain: &chain, network: &network, committed: &network, consensus: &consensus, pending: &pending, keypair: &keypair };
+    let mut bft = Bft::new(ids[1]);
+    assert!(bft.tick(&env).is_empty());
+    test(&mut bft, &env, &ids);
+}
+
+#[test]
+fn proposal_from_non_leader_is_rejected() {
+    with_engine(|bft, env, ids| {
+        let block = Block::new(0, "genesis".to_string(), vec![sale(ids[2], "x")])
+            .with_header_commitments(bft.validators().to_vec(), Vec::new(), state_tree::root(env.network));
+        let forged = Proposal::new(block.clone(), 0, ids[2], &node_key(ids[2]));
+        assert!(bft.handle(BftMessage::Proposal(Box::new(forged)), env).is_err());
+
+        // Тот же блок от лидера принимается, пересылается дальше и получает наш Prevote
+        let proposal = Proposal::new(block.clone(), 0, ids[0], &node_key(ids[0]));
+        let outputs = bft.handle(BftMessage::Proposal(Box::new(proposal.clone())), env).unwrap();
+        assert_eq!(outputs[0], BftOutput::Broadcast(BftMessage::Proposal(Box::new(proposal))));
+        assert!(matches!(&outputs[1], BftOutput::Broadcast(BftMessage::Vote(v))
+            if v.kind == VoteKind::Prevote && v.block_hash == block.hash && v.validator_id == ids[1]));
+    });
+}
+
+#[test]
+fn proposal_committing_to_another_state_is_rejected() {
+    with_engine(|bft, env, ids| {
+        let mut richer = env.network.clone();
+        richer.mint_to_node_owner(ids[0], 1_000).unwrap();
+        let block = Block::new(0, "genesis".to_string(), vec![sale(ids[2], "x")])
+            .with_header_commitments(bft.validators().to_vec(), Vec::new(), state_tree::root(&richer));
+        let proposal = Proposal::new(block, 0, ids[0], &node_key(ids[0]));
+        assert!(bft.handle(BftMessage::Proposal(Box::new(proposal)), env).is_err());
+
+        let block = Block::new(0, "genesis".to_string(), vec![sale(ids[2], "x")])
+            .with_header_commitments(ids[..3].to_vec(), Vec::new(), state_tree::root(env.network));
+        let proposal = Proposal::new(block, 0, ids[0], &node_key(ids[0]));
+        assert!(bft.handle(BftMessage::Proposal(Box::new(proposal)), env).is_err());
+    });
+}
+
+#[test]
+fn conflicting_and_forged_votes_are_rejected() {
+    with_engine(|bft, env, ids| {
+        let vote = Vote::new(VoteKind::Prevote, 0, 0, "hash_a".to_string(), ids[2], &node_key(ids[2]));
+        assert_eq!(bft.handle(BftMessage::Vote(vote.clone()), env).unwrap().len(), 1);
+        // Повтор не пересылается
+        assert!(bft.handle(BftMessage::Vote(vote), env).unwrap().is_empty());
+
+        let double = Vote::new(VoteKind::Prevote, 0, 0, "hash_b".to_string(), ids[2], &node_key(ids[2]));
+        assert!(bft.handle(BftMessage::Vote(double), env).is_err());
+
+        let forged = Vote::new(VoteKind::Prevote, 0, 0, "hash_a".to_string(), ids[3], &node_key(ids[2]));
+        assert!(bft.handle(BftMessage::Vote(forged), env).is_err());
+    });
+}
+
+#[test]
+fn conflicting_commits_become_equivocation_evidence() {
+    with_engine(|bft, env, ids| {
+        let commit = Vote::new(VoteKind::Commit, 0, 0, "hash_a".to_string(), ids[2], &node_key(ids[2]));
+        bft.handle(BftMessage::Vote(commit), env).unwrap();
+        assert!(bft.take_evidence().is_empty());
+
+        let double = Vote::new(VoteKind::Commit, 0, 0, "hash_b".to_string(), ids[2], &node_key(ids[2]));
+        assert!(bft.handle(BftMessage::Vote(double), env).is_err());
+        let evidence = bft.take_evidence();
+        assert_eq!(evidence.len(), 1);
+        assert_eq!((evidence[0].validator_id, evidence[0].height), (ids[2], 0));
+        assert!(evidence[0].verify(env.network).is_ok());
+        assert!(bft.take_evidence().is_empty());
+    });
+}
+
+#[test]
+fn relock_in_later_round_is_not_slashable() {
+    with_engine(|bft, env, ids| {
+        let first = Vote::new(VoteKind::Commit, 0, 0, "hash_a".to_string(), ids[2], &node_key(ids[2]));
+        let second = Vote::new(VoteKind::Commit, 0, 1, "hash_b".to_string(), ids[2], &node_key(ids[2]));
+        bft.handle(BftMessage::Vote(first.clone()), env).unwrap();
+        bft.handle(BftMessage::Vote(second.clone()), env).unwrap();
+        assert!(bft.take_evidence().is_empty());
+
+        // Подписи разных раундов не складываются в доказательство ни для одного из раундов
+        let signature = |vote: &Vote| BlockSignature { validator_id: vote.validator_id, round: vote.round, signature: vote.signature.clone(), timestamp: vote.timestamp };
+        let mut evidence = EquivocationEvidence::new((&first.block_hash, &signature(&first)), (&second.block_hash, &signature(&second)), 0);
+        assert!(evidence.verify(env.network).is_err());
+        evidence.round = 1;
+        assert!(evidence.verify(env.network).is_err());
+    });
+}
@@ -1,14 +1,16 @@
 use crate::*;
+use crate::tests::fixtures::{add_validators, holder, holder_key};
 
 #[test]
 fn transaction_creation_generates_check() {
     let tx = Transaction::new(
         "A".to_string(),
         "B".to_string(),
-        1.23,
+        123,
         vec!["X".to_string()],
-        1.0,
-        0.1,
+        100,
+        10,
+        crypto::random_code(),
     );
     assert!(tx.check.is_some());
     assert!(!tx.transaction_id.is_empty());
@@ -16,26 +18,23 @@ fn transaction_creation_generates_check() {
 
 #[test]
 fn blockchain_genesis_and_basic_flows() {
-    let owner = "Alice".to_string();
-    let mut bc = Blockchain::new(owner.clone());
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Customer", "Customer2"]);
     assert_eq!(bc.chain.len(), 1); // genesis
     assert!(bc.is_chain_valid());
 
     // Process a purchase to give owner security tokens and queue tx
     let check = bc.process_purchase(
-        "Customer".to_string(),
+        holder("Customer"),
         "Truck".to_string(),
         50.0,
         vec!["Meal".to_string()],
-    );
+    ).unwrap();
     assert!(!check.check_id.is_empty());
     assert!(!bc.pending_transactions.is_empty());
 
-    // Make mining fast for tests
-    bc.difficulty = 1;
-
     // Ensure validator has enough stake (owner gets 20 from purchase)
-    bc.mine_block().expect("mining should succeed with stake >= min_stake");
+    bc.produce_block().expect("block production should succeed with stake >= min_stake");
     assert!(bc.chain.len() >= 2);
     assert!(bc.is_chain_valid());
 }
@@ -74,7 +73,7 @@ fn balance_transfer_from_check() {
         "Truck".to_string(),
         0.1,
         vec!["Burger".to_string(), "Fries".to_string()],
-    );
+    ).unwrap();
     
     // Register and verify a user
     let phone = "+1234567890".to_string();
@@ -93,8 +92,8 @@ fn balance_transfer_from_check() {
     
     // Check that tokens were transferred
     let to_holder = bc.token_holders.get(&wallet).unwrap();
-    assert_eq!(to_holder.security_tokens, 10.0);
-    assert_eq!(to_holder.utility_tokens, 1.0);
+    assert_eq!(to_holder.security_tokens, 10);
+    assert_eq!(to_holder.utility_tokens, 1);
     
     // Check that original check is marked as claimed
     let owner_holder = bc.token_holders.get(&owner).unwrap();
@@ -118,7 +117,7 @@ fn balance_transfer_security_limits() {
             "Truck".to_string(),
             20.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     // Register and verify a user
@@ -135,7 +134,7 @@ fn balance_transfer_security_limits() {
         "Truck".to_string(),
         200.0, // This should exceed the 10% limit
         vec!["BigMeal".to_string()],
-    );
+    ).unwrap();
     
     let result = bc.transfer_balance_from_check(check.check_id, phone);
     assert!(result.is_err());
@@ -149,23 +148,23 @@ fn network_security_report() {
     
     // Create some token holders with different amounts
     let mut holder1 = TokenHolder::new("holder1".to_string(), false);
-    holder1.add_security_tokens(30.0);
+    holder1.add_security_tokens(3000);
     bc.token_holders.insert("holder1".to_string(), holder1);
     
     let mut holder2 = TokenHolder::new("holder2".to_string(), false);
-    holder2.add_security_tokens(20.0);
+    holder2.add_security_tokens(2000);
     bc.token_holders.insert("holder2".to_string(), holder2);
     
     // Generate report
     let report = bc.check_network_security();
     
-    assert_eq!(report.total_security_tokens, 50.0);
+    assert_eq!(report.total_security_tokens, 5000);
     assert_eq!(report.max_owner_percentage, 48.0);
     assert!(report.is_secure); // No one exceeds 48%
     
     // Add a holder with too many tokens
     let mut holder3 = TokenHolder::new("holder3".to_string(), false);
-    holder3.add_security_tokens(200.0); // This will be > 49% of total (80%)
+    holder3.add_security_tokens(20_000); // This will be > 49% of total (80%)
     bc.token_holders.insert("holder3".to_string(), holder3);
     
     let report_risky = bc.check_network_security();
@@ -185,7 +184,7 @@ fn balance_transfer_history() {
             "Truck".to_string(),
             0.1 + i as f64 * 0.01,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
         
         let phone = format!("+123456789{}", i);
         let wallet = format!("0xwallet{}", i);
@@ -204,3 +203,191 @@ fn balance_transfer_history() {
 }
 
 
+
+#[test]
+fn purchase_splits_reconcile_to_the_tetri() {
+    let owner = "Alice".to_string();
+    let mut bc = Blockchain::new(owner.clone());
+    bc.add_franchise_node("Truck_Franchise".to_string(), "Bob".to_string()).unwrap();
+    let charity = bc.charity_fund.fund_id.clone();
+
+    // 0.07 GEL на своей ноде: 48% / 3% / 49% дают 3 + 0 + 3 тетри, 1 тетри пыли уходит фонду
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 0.07, vec!["Tea".to_string()]).unwrap();
+    assert_eq!(bc.token_holders[&owner].security_tokens, 3);
+    assert_eq!(bc.token_holders[&charity].security_tokens, 1);
+    assert_eq!(bc.token_holders["Customer1"].security_tokens, 3);
+    assert_eq!(bc.charity_fund.total_donations, 1);
+
+    // Франшизная нода, сумма с остатками на каждой доле
+    bc.process_purchase("Customer2".to_string(), "Truck_Franchise".to_string(), 33.33, vec!["Soup".to_string()]).unwrap();
+    assert_eq!(bc.token_holders[&owner].security_tokens, 3 + 833);
+    assert_eq!(bc.token_holders["Bob"].security_tokens, 799);
+    assert_eq!(bc.token_holders[&charity].security_tokens, 1 + 102);
+    assert_eq!(bc.token_holders["Customer2"].security_tokens, 1599);
+
+    for i in 0..50 {
+        bc.process_purchase(format!("Customer{}", i), "Truck1".to_string(), 0.01 * i as f64 + 1.11, vec!["Meal".to_string()]).unwrap();
+    }
+    bc.emit_tokens_for_investors(12.37, "Whale".to_string()).unwrap();
+
+    let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    assert_eq!(total, bc.security_supply);
+    bc.audit_ledger().expect("minted supply must equal the sum of balances");
+}
+
+#[test]
+fn blocks_are_produced_by_stake_weighted_slot_leaders() {
+    let start = clock::now_secs() / BLOCK_TIME_SECS * BLOCK_TIME_SECS + BLOCK_TIME_SECS;
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Customer", "Customer2"]);
+    bc.process_purchase(holder("Customer"), "Truck".to_string(), 50.0, vec!["Meal".to_string()]).unwrap();
+    bc.process_purchase(holder("Customer2"), "Truck".to_string(), 80.0, vec!["Tea".to_string()]).unwrap();
+
+    // Лидер зависит только от хеша родителя и слота
+    let prev_hash = bc.chain[0].hash.clone();
+    let leader = bc.slot_leader(&prev_hash, 7).unwrap();
+    assert_eq!(bc.slot_leader(&prev_hash, 7), Some(leader.clone()));
+    assert!(bc.token_holders[&leader].security_tokens >= bc.min_stake);
+
+    let expected_leader = bc.slot_leader(&prev_hash, start / BLOCK_TIME_SECS).unwrap();
+    {
+        let _clock = clock::freeze(start);
+        bc.produce_block().unwrap();
+    }
+    let block = bc.chain.last().unwrap();
+    assert_eq!((block.slot, block.nonce, block.timestamp), (Some(start / BLOCK_TIME_SECS), 0, start));
+    assert_eq!(block.validator, expected_leader);
+    assert!(bc.is_chain_valid());
+
+    // Второй блок в том же слоте не производится
+    bc.process_purchase(holder("Customer"), "Truck".to_string(), 10.0, vec!["Meal".to_string()]).unwrap();
+    {
+        let _clock = clock::freeze(start + BLOCK_TIME_SECS - 1);
+        assert!(bc.produce_block().unwrap_err().contains("already has a block"));
+    }
+    {
+        let _clock = clock::freeze(start + BLOCK_TIME_SECS);
+        bc.produce_block().unwrap();
+    }
+    assert!(bc.is_chain_valid());
+
+    // Метка, не совпадающая со слотом, и блок в слоте родителя ломают цепь
+    let mut forged = bc.clone();
+    let last = forged.chain.last_mut().unwrap();
+    last.slot = Some(start / BLOCK_TIME_SECS);
+    last.hash = last.calculate_hash();
+    assert!(!forged.is_chain_valid());
+    let mut forged = bc.clone();
+    let last = forged.chain.last_mut().unwrap();
+    last.timestamp = start;
+    last.slot = Some(start / BLOCK_TIME_SECS);
+    last.hash = last.calculate_hash();
+    assert!(!forged.is_chain_valid());
+}
+
+#[test]
+fn blocks_from_non_leaders_are_rejected() {
+    let start = clock::now_secs() / BLOCK_TIME_SECS * BLOCK_TIME_SECS + BLOCK_TIME_SECS;
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Customer", "Customer2"]);
+    bc.process_purchase(holder("Customer"), "Truck".to_string(), 50.0, vec!["Meal".to_string()]).unwrap();
+    bc.process_purchase(holder("Customer2"), "Truck".to_string(), 80.0, vec!["Tea".to_string()]).unwrap();
+    {
+        let _clock = clock::freeze(start);
+        bc.produce_block().unwrap();
+    }
+    assert!(bc.is_chain_valid());
+
+    // Другой держатель с достаточной долей подписывает блок вместо лидера слота
+    let leader = bc.chain[1].validator.clone();
+    let (other, stake) = bc.slot_stakes[&1].stakes.iter()
+        .find(|(address, _)| *address != leader)
+        .cloned()
+        .expect("another eligible holder");
+    let mut forged = bc.clone();
+    let block = &mut forged.chain[1];
+    block.validator = other;
+    block.stake_used = stake;
+    block.hash = block.calculate_hash();
+    assert!(!forged.is_chain_valid());
+
+    // Лидер с заявленной долей, которой у него не было, тоже не принимается
+    let mut forged = bc.clone();
+    let block = &mut forged.chain[1];
+    block.stake_used += 1;
+    block.hash = block.calculate_hash();
+    assert!(!forged.is_chain_valid());
+
+    // Единственный кандидат с долей ниже min_stake не становится лидером
+    let mut forged = bc.clone();
+    let recorded = forged.slot_stakes.get_mut(&1).unwrap();
+    recorded.stakes = vec![(leader.clone(), recorded.min_stake - 1)];
+    let block = &mut forged.chain[1];
+    block.stake_used = bc.min_stake - 1;
+    block.hash = block.calculate_hash();
+    assert!(!forged.is_chain_valid());
+
+    // Локально подмененные доли не сходятся с корнем в хеше блока
+    let mut forged = bc.clone();
+    forged.slot_stakes.get_mut(&1).unwrap().stakes.retain(|(address, _)| *address == leader);
+    assert!(!forged.is_chain_valid());
+
+    // Блок, подписанный не ключом лидера, и блок без подписи не принимаются
+    let mut forged = bc.clone();
+    let other = ["Alice", "Customer", "Customer2"].into_iter().find(|name| holder(name) != leader).unwrap();
+    forged.chain[1].sign(&holder_key(other));
+    assert!(!forged.is_chain_valid());
+    let mut forged = bc.clone();
+    forged.chain[1].signature = String::new();
+    assert!(!forged.is_chain_valid());
+}
+
+#[test]
+fn appended_blocks_need_the_slot_leader_signature() {
+    let start = clock::now_secs() / BLOCK_TIME_SECS * BLOCK_TIME_SECS + BLOCK_TIME_SECS;
+    let mut producer = Blockchain::new(holder("Alice"));
+    add_validators(&mut producer, &["Alice", "Customer"]);
+    producer.process_purchase(holder("Customer"), "Truck".to_string(), 50.0, vec!["Meal".to_string()]).unwrap();
+    let mut follower = producer.clone();
+    follower.validator_keys.clear();
+
+    // Нода без ключа лидера блок не производит, но принимает подписанный им блок
+    let _clock = clock::freeze(start);
+    assert!(follower.produce_block().unwrap_err().contains("no validator key"));
+    producer.produce_block().unwrap();
+    let block = producer.chain[1].clone();
+
+    let mut unsigned = block.clone();
+    unsigned.signature = String::new();
+    assert!(follower.append_block(unsigned).unwrap_err().contains("not signed by the slot leader"));
+    let mut padded = block.clone();
+    padded.transactions.insert(0, padded.transactions[0].clone());
+    padded.update_merkle_root();
+    assert!(follower.append_block(padded).is_err());
+
+    follower.append_block(block).unwrap();
+    assert_eq!(follower.chain.last().unwrap().hash, producer.chain.last().unwrap().hash);
+    assert_eq!(follower.token_holders[&producer.chain[1].validator].security_tokens, producer.token_holders[&producer.chain[1].validator].security_tokens);
+    assert!(follower.pending_transactions.is_empty());
+    assert!(follower.is_chain_valid());
+}
+
+#[test]
+fn historic_pow_blocks_still_validate() {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.process_purchase("Customer".to_string(), "Truck".to_string(), 50.0, vec!["Meal".to_string()]).unwrap();
+
+    // Блок, добытый перебором nonce до PoS: без слота, хеш с ведущими нулями
+    let transactions = bc.pending_transactions.drain(..).collect();
+    let mut block = Block::new(1, transactions, bc.chain[0].hash.clone(), "Alice".to_string(), 0);
+    block.hash = block.calculate_hash();
+    while !block.hash.starts_with('0') {
+        block.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    bc.chain.push(block);
+    assert!(bc.is_chain_valid());
+
+    bc.chain[1].nonce += 1;
+    assert!(!bc.is_chain_valid());
+}
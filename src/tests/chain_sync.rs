@@ -0,0 +1,190 @@
+use crate::chain_sync::{BlockImport, ChainSync};
+use crate::consensus::{Block, ConsensusAlgorithm, Transaction, TransactionType};
+use crate::crypto::Keypair;
+use crate::franchise_network::{FranchiseNetwork, NodeType};
+use crate::tests::fixtures::{franchise_validators, signed_block};
+
+// Три ноды с ключами и одинаковыми продажами — все проходят порог валидатора
+fn validator_network() -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    franchise_validators(&["Tbilisi", "Batumi", "Kutaisi"])
+}
+
+fn sale(node: &(u64, Keypair), label: &str) -> Transaction {
+    let mut tx = Transaction::new(node.0, TransactionType::Sale, serde_json::json!({"sale": label}));
+    tx.sign(&node.1);
+    tx
+}
+
+#[test]
+fn valid_blocks_extend_chain_and_duplicates_are_ignored() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+
+    let b0 = signed_block(&nodes, 0, "genesis", vec![sale(&nodes[0], "a")]);
+    let b1 = signed_block(&nodes, 1, &b0.hash, vec![sale(&nodes[1], "b")]);
+
+    assert_eq!(chain.import(b0.clone(), &consensus, &network).unwrap(), BlockImport::Extended);
+    assert_eq!(chain.import(b1.clone(), &consensus, &network).unwrap(), BlockImport::Extended);
+    assert_eq!(chain.import(b1.clone(), &consensus, &network).unwrap(), BlockImport::AlreadyKnown);
+    assert_eq!(chain.height(), 2);
+    assert_eq!(chain.tip_hash(), b1.hash);
+    assert_eq!(chain.blocks_from(1).len(), 1);
+}
+
+#[test]
+fn invalid_blocks_are_rejected() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+    let b0 = signed_block(&nodes, 0, "genesis", vec![]);
+    chain.import(b0.clone(), &consensus, &network).unwrap();
+
+    // Неверная высота относительно родителя
+    let wrong_height = signed_block(&nodes, 5, &b0.hash, vec![]);
+    assert!(chain.import(wrong_height, &consensus, &network).is_err());
+
+    // Подписи только одного валидатора — нет кворума
+    let mut single = Block::new(1, b0.hash.clone(), vec![]);
+    single.sign(nodes[0].0, &nodes[0].1);
+    assert!(chain.import(single, &consensus, &network).is_err());
+
+    // Подмененная транзакция: хеш не сходится
+    let mut tampered = signed_block(&nodes, 1, &b0.hash, vec![sale(&nodes[0], "x")]);
+    tampered.transactions[0].data = serde_json::json!({"sale": "y"});
+    assert!(chain.import(tampered, &consensus, &network).is_err());
+
+    // Блок на генезисе должен иметь высоту 0
+    let fake_genesis = signed_block(&nodes, 3, "genesis", vec![]);
+    assert!(chain.import(fake_genesis, &consensus, &network).is_err());
+
+    assert_eq!(chain.height(), 1);
+}
+
+#[test]
+fn parent_signatures_must_be_a_quorum_of_parent_validators() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+    let validators: Vec<u64> = nodes.iter().map(|(node_id, _)| *node_id).collect();
+    let mut b0 = Block::new(0, "genesis".to_string(), vec![]).with_header_commitments(validators.clone(), Vec::new(), String::new());
+    for (node_id, keypair) in &nodes {
+        b0.sign(*node_id, keypair);
+    }
+    chain.import(b0.clone(), &consensus, &network).unwrap();
+
+    // Кворума родителя нет — блок отклоняется, копия родителя не меняется
+    let child = |parent_signatures| {
+        let mut block = Block::new(1, b0.hash.clone(), vec![]).with_header_commitments(validators.clone(), parent_signatures, String::new());
+        for (node_id, keypair) in &nodes {
+            block.sign(*node_id, keypair);
+        }
+        block
+    };
+    assert!(chain.import(child(b0.signatures[..2].to_vec()), &consensus, &network).is_err());
+    assert_eq!(chain.main_chain()[0].signatures, b0.signatures);
+
+    // Те же подписи с метками времени лидера заменяют нашу копию
+    let mut leader_copy = b0.signatures.clone();
+    leader_copy.iter_mut().for_each(|signature| signature.timestamp += 1);
+    assert_eq!(chain.import(child(leader_copy.clone()), &consensus, &network).unwrap(), BlockImport::Extended);
+    assert_eq!(chain.main_chain()[0].signatures, leader_copy);
+}
+
+#[test]
+fn block_signed_by_unselected_node_is_rejected() {
+    let (mut network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+
+    // Нода без продаж не проходит порог и не выбирается валидатором
+    let outsider_id = network.register_node("owner_gori".to_string(), NodeType::FRANCHISE, "Gori".to_string()).unwrap();
+    let outsider = Keypair::generate();
+    network.register_node_key(outsider_id, outsider.public_key_hex()).unwrap();
+    let seed = chain.selection_seed(0).unwrap();
+    assert!(!consensus.select_validators(&network, 0, &seed).selected_validators.contains(&outsider_id));
+
+    let mut block = signed_block(&nodes, 0, "genesis", vec![]);
+    block.sign(outsider_id, &outsider);
+    assert!(chain.import(block, &consensus, &network).is_err());
+    assert_eq!(chain.height(), 0);
+}
+
+#[test]
+fn orphans_are_connected_when_parent_arrives() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+
+    let b0 = signed_block(&nodes, 0, "genesis", vec![]);
+    let b1 = signed_block(&nodes, 1, &b0.hash, vec![]);
+    let b2 = signed_block(&nodes, 2, &b1.hash, vec![]);
+
+    assert_eq!(chain.import(b2.clone(), &consensus, &network).unwrap(), BlockImport::Orphan);
+    assert_eq!(chain.import(b1, &consensus, &network).unwrap(), BlockImport::Orphan);
+    assert_eq!(chain.height(), 0);
+
+    assert_eq!(chain.import(b0, &consensus, &network).unwrap(), BlockImport::Extended);
+    assert_eq!(chain.height(), 3);
+    assert_eq!(chain.tip_hash(), b2.hash);
+}
+
+#[test]
+fn heavier_branch_wins_and_reverted_transactions_return_to_pool() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+
+    let b0 = signed_block(&nodes, 0, "genesis", vec![]);
+    let only_on_a = sale(&nodes[0], "only_a");
+    let shared = sale(&nodes[1], "shared");
+    let x = signed_block(&nodes, 1, &b0.hash, vec![only_on_a, shared.clone()]);
+    let y = signed_block(&nodes, 1, &b0.hash, vec![shared.clone()]);
+    // При равном весе побеждает меньший хеш: a1 — вершина, c1 — боковая ветка
+    let (a1, c1) = if x.hash < y.hash { (x, y) } else { (y, x) };
+    chain.import(b0.clone(), &consensus, &network).unwrap();
+    chain.import(a1.clone(), &consensus, &network).unwrap();
+
+    assert_eq!(chain.import(c1.clone(), &consensus, &network).unwrap(), BlockImport::SideBranch);
+    assert_eq!(chain.tip_hash(), a1.hash);
+
+    // Продление боковой ветки делает ее тяжелее
+    let c2 = signed_block(&nodes, 2, &c1.hash, vec![sale(&nodes[2], "c2")]);
+    let outcome = chain.import(c2.clone(), &consensus, &network).unwrap();
+    match &outcome {
+        BlockImport::Reorganized { reverted, applied } => {
+            assert_eq!(reverted.iter().map(|b| b.hash.clone()).collect::<Vec<_>>(), vec![a1.hash.clone()]);
+            assert_eq!(applied.iter().map(|b| b.hash.clone()).collect::<Vec<_>>(), vec![c1.hash.clone(), c2.hash.clone()]);
+        }
+        other => panic!("expected reorganization, got {:?}", other),
+    }
+    assert_eq!(chain.main_chain().iter().map(|b| b.hash.clone()).collect::<Vec<_>>(), vec![b0.hash, c1.hash.clone(), c2.hash]);
+
+    // Транзакции отмененного блока, которых нет в новой ветке, возвращаются в пул
+    let mut pending = vec![];
+    ChainSync::reconcile_pending(&outcome, chain.main_chain(), &mut pending);
+    let returned: Vec<String> = pending.iter().map(|tx| tx.id.clone()).collect();
+    let expected: Vec<String> = a1.transactions.iter()
+        .filter(|tx| !c1.transactions.iter().any(|c| c.id == tx.id))
+        .map(|tx| tx.id.clone())
+        .collect();
+    assert_eq!(returned, expected);
+    assert!(!returned.contains(&shared.id));
+}
+
+#[test]
+fn reconcile_removes_included_transactions_from_pool() {
+    let (network, nodes) = validator_network();
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+
+    let included = sale(&nodes[0], "included");
+    let waiting = sale(&nodes[1], "waiting");
+    let mut pending = vec![included.clone(), waiting.clone()];
+
+    let b0 = signed_block(&nodes, 0, "genesis", vec![included]);
+    let outcome = chain.import(b0, &consensus, &network).unwrap();
+    ChainSync::reconcile_pending(&outcome, chain.main_chain(), &mut pending);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, waiting.id);
+}
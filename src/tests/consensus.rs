@@ -0,0 +1,145 @@
+use crate::consensus::{Block, ConsensusAlgorithm, SelectionSeed, Transaction, TransactionType};
+use crate::crypto::Keypair;
+use crate::franchise_network::{FranchiseNetwork, NodeType, SaleItem};
+use crate::tests::fixtures::franchise_validators;
+
+// Сеть с тремя нодами, у каждой зарегистрирован свой ключ
+fn network_with_keys() -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    franchise_validators(&["Tbilisi", "Batumi", "Kutaisi"])
+}
+
+fn signed_sale(node_id: u64, keypair: &Keypair) -> Transaction {
+    let mut tx = Transaction::new(node_id, TransactionType::Sale, serde_json::json!({"price_gel": 25.5, "pos_id": "POS_1"}));
+    tx.sign(keypair);
+    tx
+}
+
+#[test]
+fn signed_transaction_from_registered_node_is_accepted() {
+    let (network, nodes) = network_with_keys();
+    let consensus = ConsensusAlgorithm::new();
+    let (node_id, keypair) = &nodes[0];
+
+    let tx = signed_sale(*node_id, keypair);
+    assert!(consensus.validate_transaction(&tx, &network));
+
+    // Любое изменение подписанных полей ломает подпись
+    let mut tampered = tx.clone();
+    tampered.data = serde_json::json!({"price_gel": 2550.0, "pos_id": "POS_1"});
+    assert!(!consensus.validate_transaction(&tampered, &network));
+
+    let mut empty = tx;
+    empty.signature = String::new();
+    assert!(!consensus.validate_transaction(&empty, &network));
+}
+
+#[test]
+fn forged_sale_from_unknown_or_unkeyed_node_is_rejected() {
+    let (mut network, nodes) = network_with_keys();
+    let consensus = ConsensusAlgorithm::new();
+    let attacker = Keypair::generate();
+
+    // Нода не из реестра
+    assert!(!consensus.validate_transaction(&signed_sale(99, &attacker), &network));
+
+    // Существующая нода, но подпись чужим ключом
+    assert!(!consensus.validate_transaction(&signed_sale(nodes[1].0, &attacker), &network));
+
+    // Нода без зарегистрированного ключа не может подписывать
+    let unkeyed = network.register_node("owner_gori".to_string(), NodeType::FRANCHISE, "Gori".to_string()).unwrap();
+    assert!(!consensus.validate_transaction(&signed_sale(unkeyed, &attacker), &network));
+
+    // Неактивная нода отклоняется даже с корректной подписью
+    let (node_id, keypair) = &nodes[2];
+    network.nodes.get_mut(node_id).unwrap().active = false;
+    assert!(!consensus.validate_transaction(&signed_sale(*node_id, keypair), &network));
+}
+
+#[test]
+fn node_key_registry_rejects_invalid_and_shared_keys() {
+    let (mut network, nodes) = network_with_keys();
+
+    assert!(network.register_node_key(nodes[0].0, "not-hex".to_string()).is_err());
+    assert!(network.register_node_key(nodes[0].0, "abcd".to_string()).is_err());
+    assert!(network.register_node_key(42, Keypair::generate().public_key_hex()).is_err());
+    assert!(network.register_node_key(nodes[0].0, nodes[1].1.public_key_hex()).is_err());
+
+    // Нода с чужим или некорректным ключом не регистрируется вовсе
+    let registered = network.nodes.len();
+    assert!(network.register_node_with_key("owner_gori".to_string(), NodeType::FRANCHISE, "Gori".to_string(),
+                                           Some(nodes[1].1.public_key_hex())).is_err());
+    assert!(network.register_node_with_key("owner_gori".to_string(), NodeType::FRANCHISE, "Gori".to_string(),
+                                           Some("abcd".to_string())).is_err());
+    assert_eq!(network.nodes.len(), registered);
+
+    // Ротация ключа своей ноды разрешена
+    let rotated = Keypair::generate();
+    network.register_node_key(nodes[0].0, rotated.public_key_hex()).unwrap();
+    assert_eq!(network.node_public_key(nodes[0].0), Some(rotated.public_key_hex().as_str()));
+}
+
+#[test]
+fn block_requires_two_thirds_of_genuine_validator_signatures() {
+    let (network, nodes) = network_with_keys();
+    let consensus = ConsensusAlgorithm::new();
+    let validators: Vec<u64> = nodes.iter().map(|(id, _)| *id).collect();
+
+    let transactions = vec![signed_sale(nodes[0].0, &nodes[0].1)];
+    let mut block = Block::new(1, "genesis".to_string(), transactions);
+
+    // Две подписи из трех — меньше 2/3 + 1
+    block.sign(nodes[0].0, &nodes[0].1);
+    block.sign(nodes[1].0, &nodes[1].1);
+    assert!(!consensus.validate_block(&block, &validators, &network));
+
+    // Повтор подписи того же валидатора не засчитывается
+    let mut duplicated = block.clone();
+    duplicated.sign(nodes[1].0, &nodes[1].1);
+    assert!(!consensus.validate_block(&duplicated, &validators, &network));
+
+    block.sign(nodes[2].0, &nodes[2].1);
+    assert!(consensus.validate_block(&block, &validators, &network));
+
+    // Подпись от имени валидатора, сделанная чужим ключом
+    let mut forged = block.clone();
+    forged.signatures[2].signature = Keypair::generate().sign(&forged.signing_bytes(0, nodes[2].0));
+    assert!(!consensus.validate_block(&forged, &validators, &network));
+
+    // Подмена транзакции меняет хеш блока
+    let mut swapped = block.clone();
+    swapped.transactions[0].data = serde_json::json!({"price_gel": 1.0});
+    assert!(!consensus.validate_block(&swapped, &validators, &network));
+}
+
+#[test]
+fn validator_selection_is_pure_function_of_chain_state() {
+    let (mut network, nodes) = network_with_keys();
+    network.whitelist_pos("POS_1".to_string());
+    for (node_id, _) in &nodes {
+        let items = vec![SaleItem { item_id: "khachapuri".to_string(), quantity: 1, price: 40.0 }];
+        network.record_sale(*node_id, format!("sale_{}", node_id), 40.0, "buyer".to_string(), "POS_1".to_string(), items).unwrap();
+    }
+    let consensus = ConsensusAlgorithm::new();
+    let mut parent = Block::new(0, "genesis".to_string(), vec![]);
+    parent.timestamp = 1_700_000_000;
+    parent.hash = parent.calculate_hash();
+    let seed = SelectionSeed::from_parent(&parent);
+
+    let result = consensus.select_validators(&network, 1, &seed);
+    assert_eq!(result.consensus_timestamp, parent.timestamp);
+    assert_eq!(result.selected_validators.len(), nodes.len());
+
+    // Та же сеть с другим порядком обхода HashMap дает тот же выбор
+    let mut reordered = network.clone();
+    reordered.nodes = network.nodes.clone().into_iter().collect::<Vec<_>>().into_iter().rev().collect();
+    let recomputed = consensus.select_validators(&reordered, 1, &seed);
+    assert_eq!(recomputed.selected_validators, result.selected_validators);
+    assert!(consensus.verify_result(&result, &reordered).is_ok());
+
+    // Подмененный набор валидаторов не проходит проверку
+    let mut forged = result.clone();
+    forged.selected_validators.swap(0, 1);
+    assert!(consensus.verify_result(&forged, &network).is_err());
+    forged.selected_validators = vec![nodes[0].0];
+    assert!(consensus.verify_result(&forged, &network).is_err());
+}
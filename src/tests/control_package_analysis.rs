@@ -20,7 +20,7 @@ fn test_owner_control_package_accumulation() {
     let mut total_purchases = 0.0;
     let mut owner_initial_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
     
-    println!("Начальные токены владельца: {:.2}", owner_initial_tokens);
+    println!("Начальные токены владельца: {}", format_units(owner_initial_tokens));
     
     // Создаем 100 покупок
     for i in 0..100 {
@@ -30,17 +30,17 @@ fn test_owner_control_package_accumulation() {
             "Truck".to_string(),
             purchase_amount,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
         total_purchases += purchase_amount;
     }
     
     let owner_final_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let owner_percentage = (owner_final_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let owner_percentage = percentage_of(owner_final_tokens, total_tokens);
     
     println!("Общая сумма покупок: {:.2}", total_purchases);
-    println!("Финальные токены владельца: {:.2}", owner_final_tokens);
-    println!("Общее количество токенов в сети: {:.2}", total_tokens);
+    println!("Финальные токены владельца: {}", format_units(owner_final_tokens));
+    println!("Общее количество токенов в сети: {}", format_units(total_tokens));
     println!("Процент владения владельцем: {:.2}%", owner_percentage);
     
     // Проверяем, превышает ли владелец лимит
@@ -70,12 +70,12 @@ fn test_token_redistribution_mechanisms() {
             "Truck".to_string(),
             20.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     let initial_owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let initial_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let initial_percentage = (initial_owner_tokens / initial_total) * 100.0;
+    let initial_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let initial_percentage = percentage_of(initial_owner_tokens, initial_total);
     
     println!("Начальный процент владельца: {:.2}%", initial_percentage);
     
@@ -89,7 +89,7 @@ fn test_token_redistribution_mechanisms() {
             "Truck".to_string(),
             15.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
         
         // Регистрируем нового пользователя
         let phone = format!("+123456789{}", i);
@@ -112,8 +112,8 @@ fn test_token_redistribution_mechanisms() {
     }
     
     let final_owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let final_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let final_percentage = (final_owner_tokens / final_total) * 100.0;
+    let final_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let final_percentage = percentage_of(final_owner_tokens, final_total);
     
     println!("Перераспределено токенов: {:.2}", redistributed_tokens);
     println!("Финальный процент владельца: {:.2}%", final_percentage);
@@ -134,13 +134,13 @@ fn test_coordinated_group_attack_on_control() {
             "Truck".to_string(),
             25.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     let initial_owner_percentage = {
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-        (owner_tokens / total) * 100.0
+        let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        percentage_of(owner_tokens, total)
     };
     
     println!("Начальный процент владельца: {:.2}%", initial_owner_percentage);
@@ -158,28 +158,28 @@ fn test_coordinated_group_attack_on_control() {
                 "Truck".to_string(),
                 purchase_amount,
                 vec!["Meal".to_string()],
-            );
+            ).unwrap();
             group_total_tokens += purchase_amount;
         }
         
         // Создаем отдельного держателя токенов для группы
         let mut group_member = TokenHolder::new(format!("group_member{}", i), false);
-        group_member.add_security_tokens(100.0); // Каждый участник группы получает токены
+        group_member.add_security_tokens(100 * SCALE); // Каждый участник группы получает токены
         bc.token_holders.insert(format!("group_member{}", i), group_member);
     }
     
     // Анализируем распределение власти
-    let final_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let final_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let owner_percentage = (owner_tokens / final_total) * 100.0;
+    let owner_percentage = percentage_of(owner_tokens, final_total);
     
     // Считаем общую долю группы
-    let group_tokens: f64 = (0..group_size)
+    let group_tokens: u64 = (0..group_size)
         .map(|i| bc.token_holders.get(&format!("group_member{}", i)).unwrap().security_tokens)
         .sum();
-    let group_percentage = (group_tokens / final_total) * 100.0;
+    let group_percentage = percentage_of(group_tokens, final_total);
     
-    println!("Токены группы: {:.2}", group_tokens);
+    println!("Токены группы: {}", format_units(group_tokens));
     println!("Процент группы: {:.2}%", group_percentage);
     println!("Финальный процент владельца: {:.2}%", owner_percentage);
     
@@ -231,13 +231,13 @@ fn test_load_testing_token_concentration() {
                 "Truck".to_string(),
                 purchase_amount,
                 vec!["Meal".to_string()],
-            );
+            ).unwrap();
             total_purchases += purchase_amount;
         }
         
         let owner_tokens = bc_scenario.token_holders.get(&owner).unwrap().security_tokens;
-        let total_tokens: f64 = bc_scenario.token_holders.values().map(|h| h.security_tokens).sum();
-        let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+        let total_tokens: u64 = bc_scenario.token_holders.values().map(|h| h.security_tokens).sum();
+        let owner_percentage = percentage_of(owner_tokens, total_tokens);
         
         let report = bc_scenario.check_network_security();
         let is_secure = report.is_secure;
@@ -245,7 +245,7 @@ fn test_load_testing_token_concentration() {
         
         println!("  Покупок: {}", num_purchases);
         println!("  Общая сумма: {:.2}", total_purchases);
-        println!("  Токены владельца: {:.2}", owner_tokens);
+        println!("  Токены владельца: {}", format_units(owner_tokens));
         println!("  Процент владельца: {:.2}%", owner_percentage);
         println!("  Безопасность: {}", if is_secure { "✅ Безопасно" } else { "⚠️ Риски" });
         println!("  Количество рисков: {}", risks_count);
@@ -253,7 +253,7 @@ fn test_load_testing_token_concentration() {
         // Анализируем распределение токенов
         let mut distribution = HashMap::new();
         for holder in bc_scenario.token_holders.values() {
-            let percentage = (holder.security_tokens / total_tokens) * 100.0;
+            let percentage = percentage_of(holder.security_tokens, total_tokens);
             let range = match percentage {
                 p if p >= 10.0 => "10%+",
                 p if p >= 5.0 => "5-10%",
@@ -284,25 +284,26 @@ fn test_decentralization_mechanisms() {
             "Truck".to_string(),
             30.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     let initial_owner_percentage = {
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-        (owner_tokens / total) * 100.0
+        let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        percentage_of(owner_tokens, total)
     };
     
     println!("Начальная концентрация владельца: {:.2}%", initial_owner_percentage);
     
     // Механизм 1: Автоматическое перераспределение части токенов
-    let redistribution_percentage = 0.1; // 10% от токенов владельца
+    let redistribution_percentage = 10; // 10% от токенов владельца
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let tokens_to_redistribute = owner_tokens * redistribution_percentage;
+    let num_new_holders = 20;
+    // Переносим только то, что делится между держателями без остатка
+    let tokens_per_holder = owner_tokens * redistribution_percentage / 100 / num_new_holders;
+    let tokens_to_redistribute = tokens_per_holder * num_new_holders;
     
     // Создаем новых держателей токенов
-    let num_new_holders = 20;
-    let tokens_per_holder = tokens_to_redistribute / num_new_holders as f64;
     
     for i in 0..num_new_holders {
         let mut new_holder = TokenHolder::new(format!("decentralized_holder{}", i), false);
@@ -317,19 +318,19 @@ fn test_decentralization_mechanisms() {
     
     let final_owner_percentage = {
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-        (owner_tokens / total) * 100.0
+        let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        percentage_of(owner_tokens, total)
     };
     
-    println!("Перераспределено токенов: {:.2}", tokens_to_redistribute);
+    println!("Перераспределено токенов: {}", format_units(tokens_to_redistribute));
     println!("Создано новых держателей: {}", num_new_holders);
     println!("Финальная концентрация владельца: {:.2}%", final_owner_percentage);
     println!("Снижение концентрации: {:.2}%", initial_owner_percentage - final_owner_percentage);
     
     // Механизм 2: Стимулирование децентрализации через бонусы
-    let decentralization_bonus = 0.05; // 5% бонус за децентрализацию
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let bonus_tokens = total_tokens * decentralization_bonus;
+    let decentralization_bonus = 5; // 5% бонус за децентрализацию
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let bonus_tokens = total_tokens * decentralization_bonus / 100;
     
     // Распределяем бонус между всеми держателями (кроме владельца)
     let non_owner_addresses: Vec<String> = bc.token_holders.iter()
@@ -337,7 +338,7 @@ fn test_decentralization_mechanisms() {
         .map(|(addr, _)| addr.clone())
         .collect();
     
-    let bonus_per_holder = bonus_tokens / non_owner_addresses.len() as f64;
+    let bonus_per_holder = bonus_tokens / non_owner_addresses.len() as u64;
     
     for addr in non_owner_addresses {
         if let Some(holder_mut) = bc.token_holders.get_mut(&addr) {
@@ -347,11 +348,11 @@ fn test_decentralization_mechanisms() {
     
     let final_owner_percentage_with_bonus = {
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-        (owner_tokens / total) * 100.0
+        let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        percentage_of(owner_tokens, total)
     };
     
-    println!("Бонус за децентрализацию: {:.2}", bonus_tokens);
+    println!("Бонус за децентрализацию: {}", format_units(bonus_tokens));
     println!("Финальная концентрация с бонусом: {:.2}%", final_owner_percentage_with_bonus);
     
     // Проверяем безопасность
@@ -370,20 +371,20 @@ fn test_what_if_51_percent_always_goes_to_owner() {
     // Модифицируем логику process_purchase для симуляции
     // В реальной системе это было бы опасно!
     
-    let mut total_purchases = 0.0;
-    let mut owner_accumulation = 0.0;
+    let mut total_purchases = 0;
+    let mut owner_accumulation = 0;
     
     // Симулируем 100 покупок, где владелец получает 51% от каждой
     for i in 0..100 {
-        let purchase_amount = 20.0;
+        let purchase_amount = 20 * SCALE;
         total_purchases += purchase_amount;
         
         // Владелец получает 51% от каждой покупки
-        let owner_share = purchase_amount * 0.51;
+        let owner_share = purchase_amount * 51 / 100;
         owner_accumulation += owner_share;
         
         // Остальные 49% распределяются между другими участниками
-        let remaining = purchase_amount * 0.49;
+        let remaining = purchase_amount - owner_share;
         
         // Создаем других держателей токенов
         let mut other_holder = TokenHolder::new(format!("other_holder{}", i), false);
@@ -397,12 +398,12 @@ fn test_what_if_51_percent_always_goes_to_owner() {
     }
     
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let owner_percentage = percentage_of(owner_tokens, total_tokens);
     
-    println!("Общая сумма покупок: {:.2}", total_purchases);
-    println!("Накоплено владельцем: {:.2}", owner_accumulation);
-    println!("Общее количество токенов: {:.2}", total_tokens);
+    println!("Общая сумма покупок: {}", format_units(total_purchases));
+    println!("Накоплено владельцем: {}", format_units(owner_accumulation));
+    println!("Общее количество токенов: {}", format_units(total_tokens));
     println!("Процент владения владельцем: {:.2}%", owner_percentage);
     
     // Анализируем последствия
@@ -420,16 +421,16 @@ fn test_what_if_51_percent_always_goes_to_owner() {
     println!("\n🔧 ВОЗМОЖНЫЕ РЕШЕНИЯ:");
     
     // Решение 1: Принудительное перераспределение
-    let redistribution_needed = owner_tokens - (total_tokens * 0.49); // До 49%
-    println!("1. Принудительное перераспределение: нужно перераспределить {:.2} токенов", redistribution_needed);
+    let redistribution_needed = owner_tokens - total_tokens * 49 / 100; // До 49%
+    println!("1. Принудительное перераспределение: нужно перераспределить {} токенов", format_units(redistribution_needed));
     
     // Решение 2: Создание новых токенов для других участников
-    let new_tokens_needed = (owner_tokens / 0.49) - total_tokens;
-    println!("2. Создание новых токенов: нужно создать {:.2} новых токенов", new_tokens_needed);
+    let new_tokens_needed = owner_tokens * 100 / 49 - total_tokens;
+    println!("2. Создание новых токенов: нужно создать {} новых токенов", format_units(new_tokens_needed));
     
     // Решение 3: Сжигание части токенов владельца
-    let burn_needed = owner_tokens - (total_tokens * 0.49);
-    println!("3. Сжигание токенов владельца: нужно сжечь {:.2} токенов", burn_needed);
+    let burn_needed = owner_tokens - total_tokens * 49 / 100;
+    println!("3. Сжигание токенов владельца: нужно сжечь {} токенов", format_units(burn_needed));
     
     // Решение 4: Изменение правил консенсуса
     println!("4. Изменение консенсуса: переход на Proof-of-Stake с ограничениями");
@@ -459,13 +460,13 @@ fn test_group_coordination_to_take_control() {
             "Truck".to_string(),
             25.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     let initial_owner_percentage = {
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-        (owner_tokens / total) * 100.0
+        let total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        percentage_of(owner_tokens, total)
     };
     
     println!("Начальный процент владельца: {:.2}%", initial_owner_percentage);
@@ -484,7 +485,7 @@ fn test_group_coordination_to_take_control() {
                 "Truck".to_string(),
                 purchase_amount,
                 vec!["Meal".to_string()],
-            );
+            ).unwrap();
             group_total_tokens += purchase_amount;
         }
     }
@@ -494,7 +495,7 @@ fn test_group_coordination_to_take_control() {
     for i in 0..group_size {
         for j in 0..10 { // Каждый участник создает 10 кошельков
             let mut wallet = TokenHolder::new(format!("group{}_wallet{}", i, j), false);
-            wallet.add_security_tokens(50.0); // Каждый кошелек получает токены
+            wallet.add_security_tokens(50 * SCALE); // Каждый кошелек получает токены
             bc.token_holders.insert(format!("group{}_wallet{}", i, j), wallet);
         }
     }
@@ -509,7 +510,7 @@ fn test_group_coordination_to_take_control() {
                 "Truck".to_string(),
                 20.0,
                 vec!["Meal".to_string()],
-            );
+            ).unwrap();
             
             // Регистрируем пользователя группы
             let phone = format!("+123456789{}{}", i, j);
@@ -528,21 +529,21 @@ fn test_group_coordination_to_take_control() {
     }
     
     // Анализируем результат
-    let final_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let final_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let owner_percentage = (owner_tokens / final_total) * 100.0;
+    let owner_percentage = percentage_of(owner_tokens, final_total);
     
     // Считаем общую долю группы
-    let group_tokens: f64 = bc.token_holders.iter()
+    let group_tokens: u64 = bc.token_holders.iter()
         .filter(|(addr, _)| addr.starts_with("group"))
         .map(|(_, holder)| holder.security_tokens)
         .sum();
-    let group_percentage = (group_tokens / final_total) * 100.0;
+    let group_percentage = percentage_of(group_tokens, final_total);
     
     println!("\n📊 РЕЗУЛЬТАТЫ КООРДИНИРОВАННОЙ АТАКИ:");
-    println!("Токены группы: {:.2}", group_tokens);
+    println!("Токены группы: {}", format_units(group_tokens));
     println!("Процент группы: {:.2}%", group_percentage);
-    println!("Токены владельца: {:.2}", owner_tokens);
+    println!("Токены владельца: {}", format_units(owner_tokens));
     println!("Процент владельца: {:.2}%", owner_percentage);
     
     if group_percentage > owner_percentage {
@@ -571,7 +572,7 @@ fn test_group_coordination_to_take_control() {
     // Анализируем распределение власти
     let mut power_distribution = HashMap::new();
     for (addr, holder) in &bc.token_holders {
-        let percentage = (holder.security_tokens / final_total) * 100.0;
+        let percentage = percentage_of(holder.security_tokens, final_total);
         let category = if addr == &owner {
             "Владелец"
         } else if addr.starts_with("group") {
@@ -3,11 +3,36 @@ use crate::*;
 #[test]
 fn utility_token_issuing_increases_supply_and_power() {
     let mut token = UtilityToken::new("VOTE".to_string());
-    let power1 = token.issue_voting_tokens(10.0);
-    let power2 = token.issue_voting_tokens(5.5);
-    assert_eq!(power1, 10.0);
-    assert_eq!(power2, 5.5);
-    assert!((token.total_supply - 15.5).abs() < f64::EPSILON);
+    let power1 = token.issue_voting_tokens(1000);
+    let power2 = token.issue_voting_tokens(550);
+    assert_eq!(power1, 1000);
+    assert_eq!(power2, 550);
+    assert_eq!(token.total_supply, 1550);
+}
+
+#[test]
+fn split_units_assigns_dust_to_designated_recipient() {
+    // 0.07 GEL: 48% / 3% / 49% не делятся нацело
+    let parts = split_units(7, &[48, 3, 49], 1);
+    assert_eq!(parts, vec![3, 1, 3]);
+    assert_eq!(parts.iter().sum::<u64>(), 7);
+
+    let parts = split_units(3333, &[25, 24, 3, 48], 2);
+    assert_eq!(parts, vec![833, 799, 102, 1599]);
+    assert_eq!(parts.iter().sum::<u64>(), 3333);
+
+    assert_eq!(split_units(100, &[0, 0], 0), vec![0, 0]);
+    assert_eq!(to_units(0.1), 10);
+    assert_eq!(to_units(19.999), 2000);
+    assert_eq!(format_units(1205), "12.05");
+}
+
+#[test]
+fn exceeds_percentage_is_exact_at_the_limit() {
+    assert!(!exceeds_percentage(48, 100, 48.0));
+    assert!(exceeds_percentage(4801, 10_000, 48.0));
+    assert!(!exceeds_percentage(0, 0, 48.0));
+    assert!(exceeds_percentage(1, 0, 48.0));
 }
 
 #[test]
@@ -22,10 +47,10 @@ fn user_role_from_percentage_mapping() {
 #[test]
 fn check_creation_sets_expected_fields() {
     let items = vec!["Burger".to_string(), "Fries".to_string()];
-    let check = Check::new(12.34, items.clone());
+    let check = Check::new(1234, items.clone(), crypto::random_code());
     assert!(!check.check_id.is_empty());
     assert!(check.qr_code.starts_with("QR_CODE_"));
-    assert_eq!(check.amount, 12.34);
+    assert_eq!(check.amount, 1234);
     assert_eq!(check.food_items, items);
     assert!(!check.is_activated);
     assert!(check.blockchain_account.starts_with("0x"));
@@ -54,7 +79,7 @@ fn blockchain_account_activation_and_listing() {
 #[test]
 fn token_holder_add_check_and_activate_account_flow() {
     let mut holder = TokenHolder::new("holder1".to_string(), false);
-    let check = Check::new(5.0, vec!["Item".to_string()]);
+    let check = Check::new(500, vec!["Item".to_string()], crypto::random_code());
     let check_id = check.check_id.clone();
     let activation_code = check.activation_code.clone();
     let blockchain_account = check.blockchain_account.clone();
@@ -82,9 +107,9 @@ fn token_holder_add_check_and_activate_account_flow() {
 fn check_with_phone_number_creation() {
     let items = vec!["Burger".to_string(), "Fries".to_string()];
     let phone = "+1234567890".to_string();
-    let check = Check::new_with_phone(15.50, items.clone(), phone.clone());
+    let check = Check::new_with_phone(1550, items.clone(), phone.clone());
     
-    assert_eq!(check.amount, 15.50);
+    assert_eq!(check.amount, 1550);
     assert_eq!(check.food_items, items);
     assert_eq!(check.phone_number, Some(phone));
     assert!(!check.is_claimed);
@@ -94,7 +119,7 @@ fn check_with_phone_number_creation() {
 fn authorized_user_creation_and_verification() {
     let phone = "+1234567890".to_string();
     let wallet = "0xwallet123".to_string();
-    let mut user = AuthorizedUser::new(phone.clone(), wallet.clone());
+    let mut user = AuthorizedUser::new(phone.clone(), wallet.clone(), crypto::random_code());
     
     assert_eq!(user.phone_number, phone);
     assert_eq!(user.wallet_address, wallet);
@@ -129,23 +154,23 @@ fn balance_transfer_record_creation() {
         from_wallet: "0xfrom".to_string(),
         to_wallet: "0xto".to_string(),
         to_phone: "+1234567890".to_string(),
-        security_tokens_transferred: 10.0,
-        utility_tokens_transferred: 1.0,
+        security_tokens_transferred: 1000,
+        utility_tokens_transferred: 100,
         timestamp: 1234567890,
         status: TransferStatus::Completed,
     };
     
     assert_eq!(record.transfer_id, "TRANSFER_123");
-    assert_eq!(record.security_tokens_transferred, 10.0);
-    assert_eq!(record.utility_tokens_transferred, 1.0);
+    assert_eq!(record.security_tokens_transferred, 1000);
+    assert_eq!(record.utility_tokens_transferred, 100);
     assert!(matches!(record.status, TransferStatus::Completed));
 }
 
 #[test]
 fn network_security_report_creation() {
     let report = NetworkSecurityReport {
-        total_security_tokens: 1000.0,
-        total_utility_tokens: 100.0,
+        total_security_tokens: 100_000,
+        total_utility_tokens: 10_000,
         max_owner_percentage: 49.0,
         security_risks: vec![
             OwnershipRisk {
@@ -158,7 +183,7 @@ fn network_security_report_creation() {
         is_secure: false,
     };
     
-    assert_eq!(report.total_security_tokens, 1000.0);
+    assert_eq!(report.total_security_tokens, 100_000);
     assert_eq!(report.security_risks.len(), 1);
     assert!(!report.is_secure);
 }
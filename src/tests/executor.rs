@@ -0,0 +1,198 @@
+use crate::chain_sync::{BlockImport, ChainSync};
+use crate::clock;
+use crate::consensus::{Block, ConsensusAlgorithm, ReputationWeights, SelectionSeed, Transaction, TransactionType};
+use crate::crypto::Keypair;
+use crate::executor::{self, Executor, GovernancePayload, NodeRegistrationPayload, SalePayload, TokenTransferPayload};
+use crate::franchise_network::{FranchiseNetwork, NodeType, SaleItem, FRANCHISE_OWNER_SHARE};
+use crate::network_simulator::SIM_EPOCH_SECS;
+use crate::tests::fixtures::{signed_block, validator_network};
+
+// Собственная точка основателя и две франшизы; у всех ключи и продажи, все проходят в валидаторы
+fn network() -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    validator_network(&[("Tbilisi", NodeType::OWNER), ("Batumi", NodeType::FRANCHISE), ("Kutaisi", NodeType::FRANCHISE)])
+}
+
+fn transaction(node: &(u64, Keypair), transaction_type: TransactionType, id: &str, data: serde_json::Value) -> Transaction {
+    let mut tx = Transaction::new(node.0, transaction_type, data);
+    tx.id = id.to_string();
+    tx.sign(&node.1);
+    tx
+}
+
+fn sale(node: &(u64, Keypair), sale_id: &str) -> Transaction {
+    sale_in(node, sale_id, sale_id)
+}
+
+fn sale_in(node: &(u64, Keypair), tx_id: &str, sale_id: &str) -> Transaction {
+    let payload = SalePayload {
+        sale_id: sale_id.to_string(),
+        price_gel: 25.5,
+        buyer_meta: "buyer".to_string(),
+        pos_id: "POS_1".to_string(),
+        items: vec![SaleItem { item_id: "khachapuri".to_string(), quantity: 1, price: 25.5 }],
+    };
+    transaction(node, TransactionType::Sale, tx_id, serde_json::to_value(payload).unwrap())
+}
+
+fn balance(network: &FranchiseNetwork, node_id: u64) -> u64 {
+    network.get_wallet_balance(&network.nodes[&node_id].owner_address)
+}
+
+#[test]
+fn block_transactions_change_franchise_network() {
+    let (mut network, nodes) = network();
+    let consensus = ConsensusAlgorithm::new();
+    let chain = ChainSync::new();
+    let (franchise, owner) = (&nodes[1], &nodes[0]);
+    let franchise_balance = balance(&network, franchise.0);
+    let new_key = Keypair::generate();
+
+    let registration = NodeRegistrationPayload {
+        owner_address: "owner_Gori".to_string(),
+        node_type: NodeType::FRANCHISE,
+        city: "Gori".to_string(),
+        public_key: Some(new_key.public_key_hex()),
+    };
+    let transactions = vec![
+        sale(franchise, "chain_sale"),
+        sale_in(franchise, "chain_sale_dup", "chain_sale"),
+        sale(franchise, "chain_sale_copy"),
+        transaction(owner, TransactionType::NodeRegistration, "register_gori", serde_json::to_value(&registration).unwrap()),
+        // Франшиза не может регистрировать ноды и менять параметры сети
+        transaction(franchise, TransactionType::NodeRegistration, "register_rogue", serde_json::to_value(&registration).unwrap()),
+        transaction(owner, TransactionType::Governance, "whitelist_pos_2",
+            serde_json::to_value(GovernancePayload::WhitelistPos { pos_id: "POS_2".to_string() }).unwrap()),
+        transaction(franchise, TransactionType::Governance, "disable_owner",
+            serde_json::to_value(GovernancePayload::SetNodeActive { node_id: owner.0, active: false }).unwrap()),
+        transaction(franchise, TransactionType::TokenTransfer, "pay_supplier",
+            serde_json::to_value(TokenTransferPayload { to: "supplier".to_string(), units: 10 }).unwrap()),
+        transaction(franchise, TransactionType::TokenTransfer, "overdraft",
+            serde_json::to_value(TokenTransferPayload { to: "supplier".to_string(), units: 1_000_000 }).unwrap()),
+        transaction(franchise, TransactionType::Sale, "not_a_sale", serde_json::json!({"tag": "x"})),
+    ];
+    let block = Block::new(0, "genesis".to_string(), transactions);
+    let receipts = executor::apply_block(&mut network, &consensus, &chain, &block);
+    let failed: Vec<&str> = receipts.iter().filter(|r| r.error.is_some()).map(|r| r.tx_id.as_str()).collect();
+    assert_eq!(failed, vec!["chain_sale_dup", "register_rogue", "disable_owner", "overdraft", "not_a_sale"]);
+
+    // Продажи записаны с меткой времени блока
+    let recorded: Vec<_> = network.sales.iter().filter(|s| s.sale_id.starts_with("chain_sale")).collect();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded.iter().all(|s| s.timestamp == block.timestamp));
+    assert_eq!(balance(&network, franchise.0), franchise_balance + 2 * FRANCHISE_OWNER_SHARE - 10);
+    assert_eq!(network.get_wallet_balance("supplier"), 10);
+
+    let gori = network.nodes.values().find(|n| n.city == "Gori").unwrap();
+    assert_eq!(gori.public_key, Some(new_key.public_key_hex()));
+    assert_eq!(gori.registered_at, block.timestamp);
+    assert_eq!(network.whitelisted_pos.get("POS_2"), Some(&true));
+    assert!(network.nodes[&owner.0].active);
+}
+
+#[test]
+fn replicas_derive_identical_state_and_follow_reorgs() {
+    let (base, nodes) = network();
+    let consensus = ConsensusAlgorithm::new();
+    let executor = Executor::new(base.clone());
+    let mut network = base.clone();
+    let mut committed = base.clone();
+    let mut chain = ChainSync::new();
+    let mut import = |block: Block, network: &mut FranchiseNetwork, committed: &mut FranchiseNetwork| {
+        let old_height = chain.height();
+        let outcome = chain.import(block, &consensus, executor.base()).unwrap();
+        executor.apply_chain_update(network, committed, &consensus, &chain, &outcome, old_height);
+        (outcome, chain.clone())
+    };
+
+    // Ветка A: одна продажа; ветка B длиннее и с другой продажей
+    let block_at = |timestamp: u64, height: u64, previous_hash: &str, transactions: Vec<Transaction>| {
+        let _clock = clock::freeze(timestamp);
+        signed_block(&nodes, height, previous_hash, transactions)
+    };
+    let a0 = block_at(SIM_EPOCH_SECS, 0, "genesis", vec![sale(&nodes[1], "sale_a")]);
+    let b0 = block_at(SIM_EPOCH_SECS + 1, 0, "genesis", vec![sale(&nodes[2], "sale_b")]);
+    let b1 = block_at(SIM_EPOCH_SECS + 2, 1, &b0.hash, vec![sale(&nodes[1], "sale_b1")]);
+
+    assert_eq!(import(a0, &mut network, &mut committed).0, BlockImport::Extended);
+    assert!(network.sales.iter().any(|s| s.sale_id == "sale_a"));
+    // Равная по весу ветка B может победить уже на b0 (по хешу), b1 делает ее тяжелее в любом случае
+    import(b0, &mut network, &mut committed);
+    let (_, chain) = import(b1, &mut network, &mut committed);
+    assert_eq!(chain.height(), 2);
+    // Состояние до вершины — то, что фиксирует state_root вершины
+    assert!(committed.sales.iter().any(|s| s.sale_id == "sale_b"));
+    assert!(!committed.sales.iter().any(|s| s.sale_id == "sale_b1"));
+
+    // Продажа из отмененной ветки исчезла, состояние совпадает с пересчетом с нуля
+    assert!(!network.sales.iter().any(|s| s.sale_id == "sale_a"));
+    assert!(network.sales.iter().any(|s| s.sale_id == "sale_b1"));
+    let (replica, _) = Executor::new(base).replay(&consensus, &chain);
+    assert_eq!(replica.wallets.len(), network.wallets.len());
+    for (address, wallet) in &network.wallets {
+        assert_eq!(replica.get_wallet_balance(address), wallet.balance);
+    }
+    assert_eq!(replica.total_supply, network.total_supply);
+    assert_eq!(replica.sales.len(), network.sales.len());
+}
+
+#[test]
+fn side_branch_blocks_are_validated_against_their_parent_state() {
+    let (base, nodes) = network();
+    let consensus = ConsensusAlgorithm::new();
+    let executor = Executor::new(base.clone());
+    let mut chain = ChainSync::new();
+    let block_at = |timestamp: u64, signers: &[(u64, Keypair)], height: u64, previous_hash: &str, transactions: Vec<Transaction>| {
+        let _clock = clock::freeze(timestamp);
+        signed_block(signers, height, previous_hash, transactions)
+    };
+
+    // Основная цепь отключает Kutaisi: со следующего блока он не валидатор
+    let deactivate = GovernancePayload::SetNodeActive { node_id: nodes[2].0, active: false };
+    let m0 = block_at(SIM_EPOCH_SECS, &nodes, 0, "genesis",
+                      vec![transaction(&nodes[0], TransactionType::Governance, "deactivate", serde_json::to_value(deactivate).unwrap())]);
+    chain.import(m0.clone(), &consensus, executor.base()).unwrap();
+    assert!(chain.import(block_at(SIM_EPOCH_SECS + 1, &nodes, 1, &m0.hash, vec![]), &consensus, executor.base()).is_err());
+    let m1 = block_at(SIM_EPOCH_SECS + 1, &nodes[..2], 1, &m0.hash, vec![]);
+    assert_eq!(chain.import(m1.clone(), &consensus, executor.base()).unwrap(), BlockImport::Extended);
+
+    // На боковой ветке Kutaisi активен, и ее блоки подписаны прежним набором валидаторов
+    let s0 = block_at(SIM_EPOCH_SECS + 2, &nodes, 0, "genesis", vec![sale(&nodes[1], "side_sale")]);
+    let s1 = block_at(SIM_EPOCH_SECS + 3, &nodes, 1, &s0.hash, vec![]);
+    assert_eq!(chain.import(s0.clone(), &consensus, executor.base()).unwrap(), BlockImport::SideBranch);
+    assert!(matches!(chain.import(s1.clone(), &consensus, executor.base()).unwrap(), BlockImport::Reorganized { .. }));
+    assert_eq!(chain.tip_hash(), s1.hash);
+
+    // Повторная проверка тех же блоков новой нодой дает тот же результат
+    let mut replica = ChainSync::new();
+    for block in [m0, m1, s0, s1.clone()] {
+        replica.import(block, &consensus, executor.base()).unwrap();
+    }
+    assert_eq!(replica.tip_hash(), s1.hash);
+}
+
+#[test]
+fn governance_sets_reputation_weights_used_by_validator_selection() {
+    let (mut network, nodes) = network();
+    let consensus = ConsensusAlgorithm::new();
+    let seed = SelectionSeed::genesis();
+    let governance = |id: &str, node: &(u64, Keypair), weights: ReputationWeights| {
+        transaction(node, TransactionType::Governance, id, serde_json::to_value(GovernancePayload::SetReputationWeights { weights }).unwrap())
+    };
+
+    // Только собственная нода основателя меняет веса, и только на корректные
+    let geographic_only = ReputationWeights { sales_weight: 0.0, reputation_weight: 0.0, geographic_weight: 1.0, stake_weight: 0.0 };
+    let broken = ReputationWeights { geographic_weight: 0.5, ..geographic_only.clone() };
+    assert!(executor::execute_transaction(&mut network, &governance("from_franchise", &nodes[1], geographic_only.clone()), 0).is_err());
+    assert!(executor::execute_transaction(&mut network, &governance("broken", &nodes[0], broken), 0).unwrap_err().contains("sum to"));
+    assert_eq!(network.reputation_weights, None);
+
+    executor::execute_transaction(&mut network, &governance("weights", &nodes[0], geographic_only.clone()), 0).unwrap();
+    assert_eq!(network.reputation_weights, Some(geographic_only));
+
+    // Выбор валидаторов читает веса из состояния сети, а не из параметров алгоритма
+    let result = consensus.select_validators(&network, 1, &seed);
+    assert!(!result.validator_scores.is_empty());
+    for score in &result.validator_scores {
+        assert_eq!(score.total_score, score.geographic_score);
+    }
+}
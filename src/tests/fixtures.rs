@@ -0,0 +1,58 @@
+use crate::*;
+use crate::consensus::{Block, Transaction};
+
+// Ключ держателя из его имени: адрес кошелька одинаков при каждом запуске теста
+pub fn holder_key(name: &str) -> Keypair {
+    Keypair::from_secret_hex(&hex::encode(Sha256::digest(name.as_bytes()))).unwrap()
+}
+
+// Адрес кошелька держателя, выведенный из его ключа
+pub fn holder(name: &str) -> String {
+    crypto::wallet_address(&holder_key(name).public_key_hex()).unwrap()
+}
+
+// Нода подписывает блоки в слотах этих держателей
+pub fn add_validators(bc: &mut Blockchain, names: &[&str]) {
+    for name in names {
+        bc.add_validator_key(holder_key(name));
+    }
+}
+
+// Детерминированный ключ ноды франшизной сети: тест и симулятор получают одинаковые пары
+pub fn node_key(node_id: u64) -> Keypair {
+    Keypair::from_secret_hex(&format!("{:064x}", node_id + 1000)).unwrap()
+}
+
+// Франшизная сеть с нодами в этих городах: у каждой ключ node_key и три продажи,
+// поэтому все проходят порог валидатора
+pub fn validator_network(nodes: &[(&str, NodeType)]) -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    let mut network = FranchiseNetwork::new("master".to_string());
+    network.whitelist_pos("POS_1".to_string());
+    let mut validators = Vec::new();
+    for (city, node_type) in nodes {
+        let node_id = network.register_node(format!("owner_{}", city), node_type.clone(), city.to_string()).unwrap();
+        let keypair = node_key(node_id);
+        network.register_node_key(node_id, keypair.public_key_hex()).unwrap();
+        for i in 0..3 {
+            let items = vec![SaleItem { item_id: format!("item_{}", i), quantity: 1, price: 40.0 }];
+            network.record_sale(node_id, format!("sale_{}_{}", node_id, i), 40.0, "buyer".to_string(), "POS_1".to_string(), items).unwrap();
+        }
+        validators.push((node_id, keypair));
+    }
+    (network, validators)
+}
+
+// Сеть только из франшиз, по одной в каждом городе
+pub fn franchise_validators(cities: &[&str]) -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    let nodes: Vec<(&str, NodeType)> = cities.iter().map(|city| (*city, NodeType::FRANCHISE)).collect();
+    validator_network(&nodes)
+}
+
+// Блок, подписанный всеми валидаторами
+pub fn signed_block(nodes: &[(u64, Keypair)], height: u64, previous_hash: &str, transactions: Vec<Transaction>) -> Block {
+    let mut block = Block::new(height, previous_hash.to_string(), transactions);
+    for (node_id, keypair) in nodes {
+        block.sign(*node_id, keypair);
+    }
+    block
+}
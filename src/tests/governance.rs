@@ -0,0 +1,310 @@
+use crate::*;
+use crate::tests::fixtures::{add_validators, holder, validator_network};
+
+// Alice (владелец) — 48% utility токенов, Customer — 49%, фонд — 3%
+fn governed_blockchain() -> Blockchain {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    bc
+}
+
+fn voting_ends(bc: &Blockchain, proposal_id: &str) -> u64 {
+    bc.proposals.iter().find(|p| p.id == proposal_id).unwrap().voting_ends
+}
+
+#[test]
+fn passed_proposals_change_chain_parameters() {
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+
+    let (reward_id, stake_id) = {
+        let _clock = clock::freeze(start);
+        let reward_id = bc.submit_proposal("Alice".to_string(), "Raise reward".to_string(), ProposalChange::BlockReward { units: 7 * SCALE }, VotingStrategy::Linear).unwrap();
+        let _clock = clock::freeze(start + 1);
+        let stake_id = bc.submit_proposal("Customer".to_string(), "Lower stake".to_string(), ProposalChange::MinStake { units: 2 * SCALE }, VotingStrategy::Linear).unwrap();
+        (reward_id, stake_id)
+    };
+
+    {
+        let _clock = clock::freeze(start + 60);
+        bc.vote_on_proposal("Alice".to_string(), reward_id.clone(), true).unwrap();
+        // Один кошелек — один бюллетень
+        assert!(bc.vote_on_proposal("Alice".to_string(), reward_id.clone(), true).unwrap_err().contains("already voted"));
+        bc.vote_on_proposal("Customer".to_string(), stake_id.clone(), true).unwrap();
+        assert!(bc.finalize_proposal(reward_id.clone()).unwrap_err().contains("open until"));
+        assert_eq!(bc.block_reward, 5 * SCALE);
+    }
+
+    let _clock = clock::freeze(voting_ends(&bc, &stake_id) + 1);
+    assert!(bc.vote_on_proposal("Customer".to_string(), reward_id.clone(), false).is_err());
+    assert_eq!(bc.finalize_proposal(reward_id.clone()).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.finalize_proposal(stake_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.block_reward, 7 * SCALE);
+    assert_eq!(bc.min_stake, 2 * SCALE);
+    assert!(bc.finalize_proposal(reward_id).unwrap_err().contains("already finalized"));
+}
+
+#[test]
+fn proposals_without_quorum_or_majority_are_rejected() {
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+    let charity = bc.charity_fund.fund_id.clone();
+
+    let _clock = clock::freeze(start);
+    assert!(bc.submit_proposal("Nobody".to_string(), "Lower stake".to_string(), ProposalChange::MinStake { units: 0 }, VotingStrategy::Linear).is_err());
+    assert!(bc.submit_proposal("Alice".to_string(), "Broken".to_string(), ProposalChange::MaxOwnerPercentage { percentage: 150.0 }, VotingStrategy::Linear).is_err());
+
+    // Проголосовал только фонд: 3% меньше кворума
+    let quorum_id = bc.submit_proposal("Alice".to_string(), "Lower stake".to_string(), ProposalChange::MinStake { units: SCALE }, VotingStrategy::Linear).unwrap();
+    bc.vote_on_proposal(charity, quorum_id.clone(), true).unwrap();
+    // Кворум есть, но "против" больше
+    let _clock = clock::freeze(start + 1);
+    let majority_id = bc.submit_proposal("Alice".to_string(), "Owner cap".to_string(), ProposalChange::MaxOwnerPercentage { percentage: 60.0 }, VotingStrategy::Linear).unwrap();
+    bc.vote_on_proposal("Alice".to_string(), majority_id.clone(), true).unwrap();
+    bc.vote_on_proposal("Customer".to_string(), majority_id.clone(), true).unwrap();
+    // До конца окна голос можно изменить
+    bc.vote_on_proposal("Customer".to_string(), majority_id.clone(), false).unwrap();
+
+    let _clock = clock::freeze(voting_ends(&bc, &majority_id) + 1);
+    assert_eq!(bc.finalize_proposal(quorum_id).unwrap(), ProposalStatus::Rejected);
+    assert_eq!(bc.finalize_proposal(majority_id).unwrap(), ProposalStatus::Rejected);
+    assert_eq!(bc.min_stake, 10 * SCALE);
+    assert_eq!(bc.max_owner_percentage, 48.0);
+}
+
+#[test]
+fn royalty_and_franchise_admission_proposals() {
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+    bc.add_franchise_node("Truck_Franchise".to_string(), "Bob".to_string()).unwrap();
+    let height = bc.chain.len() as u64;
+
+    let _clock = clock::freeze(start);
+    let royalty_id = bc.submit_proposal("Alice".to_string(), "Lower royalty".to_string(), ProposalChange::RoyaltyShare { percentage: 10 }, VotingStrategy::Linear).unwrap();
+    let admit_id = bc.submit_proposal("Alice".to_string(), "Admit Carol".to_string(), ProposalChange::AdmitFranchise {
+        node_id: "Truck_Carol".to_string(),
+        franchise_owner: "Carol".to_string(),
+    }, VotingStrategy::Linear).unwrap();
+    let _clock = clock::freeze(start + 1);
+    let duplicate_id = bc.submit_proposal("Alice".to_string(), "Admit Bob again".to_string(), ProposalChange::AdmitFranchise {
+        node_id: "Truck_Franchise".to_string(),
+        franchise_owner: "Dave".to_string(),
+    }, VotingStrategy::Linear).unwrap();
+    for id in [&royalty_id, &admit_id, &duplicate_id] {
+        bc.vote_on_proposal("Alice".to_string(), id.clone(), true).unwrap();
+    }
+
+    let _clock = clock::freeze(voting_ends(&bc, &duplicate_id) + 1);
+    assert_eq!(bc.finalize_proposal(royalty_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.finalize_proposal(admit_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.token_holders["Carol"].franchise_nodes, vec!["Truck_Carol".to_string()]);
+    // Нода уже есть: предложение принято, но не исполнено
+    assert!(matches!(bc.finalize_proposal(duplicate_id).unwrap(), ProposalStatus::Failed { .. }));
+    assert_eq!(bc.franchise_nodes["Truck_Franchise"], "Bob");
+
+    // Новая ставка роялти — со следующего блока, уже принятые продажи ее не видят
+    assert_eq!(bc.tokenomics.policy_at(height).franchise_node.network_owner, 25);
+    let policy = bc.tokenomics.policy_at(height + 1);
+    assert_eq!((policy.franchise_node.network_owner, policy.franchise_node.node_owner), (10, 39));
+}
+
+#[test]
+fn owner_cap_and_royalty_proposals_both_take_effect() {
+    let start = clock::now_secs();
+    // Блок в конце производит нода с ключами держателей
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Customer"]);
+    bc.process_purchase(holder("Customer"), "Truck1".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    let height = bc.chain.len() as u64;
+
+    let _clock = clock::freeze(start);
+    let cap_id = bc.submit_proposal(holder("Alice"), "Owner cap".to_string(), ProposalChange::MaxOwnerPercentage { percentage: 60.0 }, VotingStrategy::Linear).unwrap();
+    let royalty_id = bc.submit_proposal(holder("Alice"), "Lower royalty".to_string(), ProposalChange::RoyaltyShare { percentage: 10 }, VotingStrategy::Linear).unwrap();
+    for id in [&cap_id, &royalty_id] {
+        bc.vote_on_proposal(holder("Alice"), id.clone(), true).unwrap();
+        bc.vote_on_proposal(holder("Customer"), id.clone(), true).unwrap();
+    }
+
+    // Лимит принят первым; следующая политика роялти его не отменяет
+    let _clock = clock::freeze(voting_ends(&bc, &royalty_id) + 1);
+    assert_eq!(bc.finalize_proposal(cap_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.finalize_proposal(royalty_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!(bc.max_owner_percentage, 48.0);
+    let policy = bc.tokenomics.policy_at(height + 1);
+    assert_eq!(policy.limits.max_owner_percentage, 60.0);
+    assert_eq!(policy.franchise_node.network_owner, 10);
+
+    // Оба изменения действуют с блока, следующего за финализацией
+    bc.produce_block().unwrap();
+    assert_eq!(bc.max_owner_percentage, 60.0);
+    assert_eq!(bc.tokenomics.policy_at(bc.chain.len() as u64).franchise_node.network_owner, 10);
+}
+
+#[test]
+fn quadratic_menu_voting_limits_a_single_large_holder() {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.token_holders.get_mut("Alice").unwrap().add_utility_tokens(900);
+    let customers = ["C1", "C2", "C3", "C4"];
+    for customer in customers {
+        let mut holder = TokenHolder::new(customer.to_string(), false);
+        holder.add_utility_tokens(100);
+        bc.token_holders.insert(customer.to_string(), holder);
+    }
+    for (name, strategy) in [("Linear dish", VotingStrategy::Linear), ("Quadratic dish", VotingStrategy::Quadratic)] {
+        let item = MenuItem::new(name.to_string(), "".to_string(), 10.0, "Alice".to_string(), 7);
+        let menu_item_id = item.id.clone();
+        bc.menu_items.push(item);
+        bc.make_menu_item_available_for_voting(menu_item_id.clone(), strategy).unwrap();
+        bc.vote_on_menu_item("Alice".to_string(), menu_item_id.clone(), true).unwrap();
+        for customer in customers {
+            bc.vote_on_menu_item(customer.to_string(), menu_item_id.clone(), false).unwrap();
+        }
+    }
+
+    // Линейно 900 против 400, квадратично 30 против 4 * 10
+    assert_eq!((bc.menu_items[0].votes_for, bc.menu_items[0].votes_against), (900, 400));
+    assert_eq!((bc.menu_items[1].votes_for, bc.menu_items[1].votes_against), (30, 40));
+    assert!(bc.get_voting_history().iter().any(|record| record.strategy == VotingStrategy::Quadratic && record.vote_weight == 10));
+}
+
+#[test]
+fn conviction_rewards_votes_held_through_the_window() {
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+
+    let _clock = clock::freeze(start);
+    let proposal_id = bc.submit_proposal("Alice".to_string(), "Lower stake".to_string(), ProposalChange::MinStake { units: SCALE }, VotingStrategy::Conviction).unwrap();
+    bc.vote_on_proposal("Alice".to_string(), proposal_id.clone(), true).unwrap();
+    let ends = voting_ends(&bc, &proposal_id);
+
+    // Customer голосует в последние сутки и получает множитель 1, голос Alice стоял все 7 дней
+    let _clock = clock::freeze(ends - 3600);
+    bc.vote_on_proposal("Customer".to_string(), proposal_id.clone(), false).unwrap();
+    let alice = bc.proposals[0].ballot_box.ballots["Alice"].weight;
+    let customer = bc.proposals[0].ballot_box.ballots["Customer"].weight;
+    assert!(customer > alice);
+
+    let _clock = clock::freeze(ends + 1);
+    assert_eq!(bc.finalize_proposal(proposal_id).unwrap(), ProposalStatus::Executed);
+    assert_eq!((bc.proposals[0].votes_for, bc.proposals[0].votes_against), (alice * 8, customer));
+    assert_eq!(bc.min_stake, SCALE);
+}
+
+#[test]
+fn conviction_votes_lock_utility_tokens_until_the_window_closes() {
+    let start = clock::now_secs();
+    let _clock = clock::freeze(start);
+    let mut bc = governed_blockchain();
+    // Чек лежит на кошельке владельца сети, перенос списывает с него
+    let check = bc.process_purchase("Guest".to_string(), "Truck1".to_string(), 1.0, vec!["Tea".to_string()]).unwrap();
+    let code = bc.register_user_with_phone("+995500".to_string(), "0xguest".to_string()).unwrap();
+    bc.verify_phone_number("+995500".to_string(), code).unwrap();
+
+    let proposal_id = bc.submit_proposal("Alice".to_string(), "Lower stake".to_string(), ProposalChange::MinStake { units: SCALE }, VotingStrategy::Conviction).unwrap();
+    bc.vote_on_proposal("Alice".to_string(), proposal_id.clone(), true).unwrap();
+    let alice = &bc.token_holders["Alice"];
+    assert_eq!(alice.locked_utility_tokens(start), alice.utility_tokens);
+
+    // Пока голос копит множитель, токены не уходят с кошелька
+    let ends = voting_ends(&bc, &proposal_id);
+    let _clock = clock::freeze(ends - 1);
+    let err = bc.transfer_balance_from_check(check.check_id.clone(), "+995500".to_string()).unwrap_err();
+    assert!(err.contains("locked by conviction votes"));
+
+    let _clock = clock::freeze(ends + 1);
+    bc.transfer_balance_from_check(check.check_id, "+995500".to_string()).unwrap();
+    assert!(bc.token_holders["0xguest"].utility_tokens > 0);
+}
+
+// Квадратичное голосование считает кошельки, а не людей: баланс, разделенный на несколько кошельков,
+// весит больше. Защита от этого — авторизация держателей по телефону, а не формула голосования.
+#[test]
+fn quadratic_weight_grows_when_a_balance_is_split_across_wallets() {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.token_holders.get_mut("Alice").unwrap().add_utility_tokens(400);
+    for wallet in ["Split1", "Split2", "Split3", "Split4"] {
+        let mut holder = TokenHolder::new(wallet.to_string(), false);
+        holder.add_utility_tokens(100);
+        bc.token_holders.insert(wallet.to_string(), holder);
+    }
+    let item = MenuItem::new("Quadratic dish".to_string(), "".to_string(), 10.0, "Alice".to_string(), 7);
+    let menu_item_id = item.id.clone();
+    bc.menu_items.push(item);
+    bc.make_menu_item_available_for_voting(menu_item_id.clone(), VotingStrategy::Quadratic).unwrap();
+    bc.vote_on_menu_item("Alice".to_string(), menu_item_id.clone(), true).unwrap();
+    for wallet in ["Split1", "Split2", "Split3", "Split4"] {
+        bc.vote_on_menu_item(wallet.to_string(), menu_item_id.clone(), false).unwrap();
+    }
+
+    // Те же 400 токенов: одним кошельком — 20, четырьмя — 4 * 10
+    assert_eq!((bc.menu_items[0].votes_for, bc.menu_items[0].votes_against), (20, 40));
+}
+
+#[test]
+fn delegated_power_votes_through_the_final_delegate() {
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+    let charity = bc.charity_fund.fund_id.clone();
+
+    bc.delegate_voting_power(charity.clone(), Some("Customer".to_string())).unwrap();
+    bc.delegate_voting_power("Customer".to_string(), Some("Alice".to_string())).unwrap();
+    assert!(bc.delegate_voting_power("Alice".to_string(), Some(charity.clone())).unwrap_err().contains("cycle"));
+    bc.delegate_voting_power("Customer".to_string(), None).unwrap();
+
+    let _clock = clock::freeze(start);
+    let proposal_id = bc.submit_proposal("Alice".to_string(), "Owner cap".to_string(), ProposalChange::MaxOwnerPercentage { percentage: 60.0 }, VotingStrategy::Delegated).unwrap();
+    let snapshot = bc.proposals[0].ballot_box.snapshot.clone();
+    assert!(!snapshot.contains_key(&charity));
+    assert_eq!(snapshot["Customer"], bc.token_holders["Customer"].utility_tokens + bc.token_holders[&charity].utility_tokens);
+
+    // Делегат голосует за фонд; сам фонд в этом голосовании голоса не имеет
+    assert!(bc.vote_on_proposal(charity, proposal_id.clone(), true).is_err());
+    bc.vote_on_proposal("Customer".to_string(), proposal_id.clone(), false).unwrap();
+    bc.vote_on_proposal("Alice".to_string(), proposal_id.clone(), true).unwrap();
+
+    let tally = bc.voting_tallies().into_iter().find(|tally| tally.subject_id == proposal_id).unwrap();
+    assert_eq!(tally.strategy, VotingStrategy::Delegated);
+    assert_eq!((tally.votes_against, tally.voters, tally.is_final), (snapshot["Customer"], 2, false));
+
+    let _clock = clock::freeze(voting_ends(&bc, &proposal_id) + 1);
+    assert_eq!(bc.finalize_proposal(proposal_id).unwrap(), ProposalStatus::Rejected);
+}
+
+#[test]
+fn passed_reputation_weights_reach_validator_selection() {
+    use crate::consensus::{ReputationWeights, SelectionSeed};
+
+    let start = clock::now_secs();
+    let mut bc = governed_blockchain();
+    let geographic_only = ReputationWeights { sales_weight: 0.0, reputation_weight: 0.0, geographic_weight: 1.0, stake_weight: 0.0 };
+    let broken = ReputationWeights { geographic_weight: 0.5, ..geographic_only.clone() };
+
+    let _clock = clock::freeze(start);
+    assert!(bc.submit_proposal("Alice".to_string(), "Broken".to_string(), ProposalChange::ReputationWeights { weights: broken }, VotingStrategy::Linear).unwrap_err().contains("sum to"));
+    let weights_id = bc.submit_proposal("Alice".to_string(), "Geography first".to_string(), ProposalChange::ReputationWeights { weights: geographic_only.clone() }, VotingStrategy::Linear).unwrap();
+    bc.vote_on_proposal("Alice".to_string(), weights_id.clone(), true).unwrap();
+    bc.vote_on_proposal("Customer".to_string(), weights_id.clone(), true).unwrap();
+
+    let _clock = clock::freeze(voting_ends(&bc, &weights_id) + 1);
+    assert_eq!(bc.finalize_proposal(weights_id.clone()).unwrap(), ProposalStatus::Executed);
+
+    // Собственная точка основателя и франшиза с ключами и продажами
+    let (mut network, nodes) = validator_network(&[("Tbilisi", NodeType::OWNER), ("Batumi", NodeType::FRANCHISE)]);
+
+    // Решение держателей уходит в цепь сети транзакцией основателя с id предложения
+    let transactions = bc.franchise_governance_transactions(nodes[0].0, &nodes[0].1);
+    assert_eq!(transactions.len(), 1);
+    assert_eq!(transactions[0].id, format!("governance_{}", weights_id));
+    assert!(ConsensusAlgorithm::new().validate_transaction(&transactions[0], &network));
+    // Франшиза не может подписать решение за основателя
+    let forged = bc.franchise_governance_transactions(nodes[1].0, &nodes[1].1);
+    assert!(executor::execute_transaction(&mut network, &forged[0], 1).is_err());
+
+    executor::execute_transaction(&mut network, &transactions[0], 1).unwrap();
+    assert_eq!(network.reputation_weights, Some(geographic_only));
+    let result = ConsensusAlgorithm::new().select_validators(&network, 1, &SelectionSeed::genesis());
+    assert!(!result.validator_scores.is_empty());
+    for score in &result.validator_scores {
+        assert_eq!(score.total_score, score.geographic_score);
+    }
+}
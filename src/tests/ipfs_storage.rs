@@ -0,0 +1,201 @@
+use crate::*;
+use crate::blob_store::{Cid, CHUNK_SIZE};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn temp_ipfs_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ipfs_storage_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn open(dir: &Path) -> IPFSStorage {
+    IPFSStorage::open(dir, "https://ipfs.io/ipfs/".to_string()).unwrap()
+}
+
+#[test]
+fn content_is_addressed_by_cidv1() {
+    // Тот же CID выдает `ipfs add --cid-version 1 --raw-leaves`
+    let cid = Cid::of(blob_store::RAW_CODEC, b"hello world");
+    assert_eq!(cid.to_string(), "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e");
+    assert_eq!(cid.to_string().parse::<Cid>().unwrap(), cid);
+    assert!("QmNotACid".parse::<Cid>().is_err());
+
+    let dir = temp_ipfs_dir("cid");
+    let mut storage = open(&dir);
+    assert_eq!(storage.store_data("hello world", "text/plain").unwrap(), cid.to_string());
+    assert_eq!(storage.retrieve_data(&cid.to_string()).unwrap(), "hello world");
+}
+
+#[test]
+fn large_content_is_chunked_and_reassembled() {
+    let dir = temp_ipfs_dir("chunks");
+    let mut storage = open(&dir);
+    let image: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+
+    let hash = storage.store_image(&image, "khinkali.PNG").unwrap();
+    assert_eq!(storage.retrieve_image(&hash).unwrap(), image);
+    assert_eq!(storage.index[&hash].content_type, "image/png");
+    assert_eq!(storage.index[&hash].size, image.len());
+    // Три куска и корень со ссылками
+    assert_eq!(fs::read_dir(dir.join("blocks")).unwrap().count(), 4);
+}
+
+#[test]
+fn missing_or_corrupted_content_is_an_error() {
+    let dir = temp_ipfs_dir("corrupt");
+    let mut storage = open(&dir);
+    let hash = storage.store_data("{\"items\":[]}", "application/json").unwrap();
+
+    let absent = Cid::of(blob_store::RAW_CODEC, b"never stored").to_string();
+    assert!(storage.retrieve_data(&absent).unwrap_err().contains("not in the local store"));
+
+    fs::write(dir.join("blocks").join(&hash), b"{\"items\":[\"forged\"]}").unwrap();
+    assert!(storage.retrieve_data(&hash).unwrap_err().contains("integrity"));
+}
+
+#[test]
+fn chunk_roots_with_forged_sizes_are_rejected_before_allocation() {
+    let dir = temp_ipfs_dir("forged_root");
+    let store = blob_store::BlobStore::open(&dir).unwrap();
+    let chunk = store.put(b"chunk").unwrap().to_string();
+
+    // Размер корня больше суммы ссылок, ссылка больше куска
+    let huge = format!("{{\"size\":{},\"links\":[{{\"cid\":\"{}\",\"size\":5}}]}}", u64::MAX, chunk);
+    let root = store.put_block(blob_store::DAG_JSON_CODEC, huge.as_bytes()).unwrap();
+    assert!(store.get(&root).unwrap_err().contains("root claims"));
+    let oversized = format!("{{\"size\":{},\"links\":[{{\"cid\":\"{}\",\"size\":{}}}]}}", u64::MAX, chunk, u64::MAX);
+    let root = store.put_block(blob_store::DAG_JSON_CODEC, oversized.as_bytes()).unwrap();
+    assert!(store.get(&root).unwrap_err().contains("chunks are at most"));
+}
+
+#[test]
+fn stored_content_survives_reopen() {
+    let dir = temp_ipfs_dir("reopen");
+    let hash = open(&dir).store_data("menu v1", "application/json").unwrap();
+
+    let storage = open(&dir);
+    assert_eq!(storage.retrieve_data(&hash).unwrap(), "menu v1");
+    let stats = storage.get_storage_stats();
+    assert_eq!((stats.total_files, stats.total_size), (1, 7));
+}
+
+fn menu(version: u32, image_hash: Option<String>) -> ipfs_storage::MenuData {
+    ipfs_storage::MenuData {
+        items: vec![ipfs_storage::MenuItem {
+            id: "khinkali_001".to_string(),
+            name: "Хинкали".to_string(),
+            description: "".to_string(),
+            price: 1.5,
+            category: "Основные блюда".to_string(),
+            ingredients: vec![],
+            image_hash,
+            nutritional_info: ipfs_storage::NutritionalInfo { calories: 250, protein: 15.0, carbs: 30.0, fat: 8.0, fiber: 2.0 },
+        }],
+        categories: vec!["Основные блюда".to_string()],
+        last_updated: 0,
+        version,
+    }
+}
+
+#[test]
+fn garbage_collection_keeps_pinned_content_and_its_references() {
+    let dir = temp_ipfs_dir("gc");
+    let mut storage = open(&dir);
+    let image = storage.store_image(&vec![7u8; CHUNK_SIZE + 1], "khinkali.jpg").unwrap();
+    let scratch = storage.store_data("draft", "text/plain").unwrap();
+    let menu_v1 = storage.store_menu(&menu(1, Some(image.clone()))).unwrap();
+    let menu_v2 = storage.store_menu(&menu(2, Some(image.clone()))).unwrap();
+    let report = ipfs_storage::SalesReport {
+        node_id: 1, period_start: 0, period_end: 86400, total_sales: 0, total_revenue: 0.0,
+        top_items: vec![], daily_breakdown: vec![],
+    };
+    let report_hash = storage.store_sales_report(&report).unwrap();
+    // Тот же отчет еще раз — тот же объект, без второго закрепления
+    assert_eq!(storage.store_sales_report(&report).unwrap(), report_hash);
+    assert_eq!(storage.pins().count(), 3);
+    assert_eq!(storage.get_storage_stats().garbage_files, 1);
+
+    // Изображение не закреплено само, но на него ссылаются версии меню
+    let draft_size = storage.index[&scratch].size as u64;
+    assert_eq!(storage.collect_garbage().unwrap(), draft_size);
+    assert!(storage.retrieve_data(&scratch).is_err());
+    assert!(storage.retrieve_image(&image).is_ok());
+
+    let v1 = ipfs_storage::PinReason::MenuVersion { version: 1 };
+    storage.unpin(&menu_v1, &v1).unwrap();
+    assert!(storage.unpin(&menu_v1, &v1).unwrap_err().contains("not pinned"));
+    storage.collect_garbage().unwrap();
+    assert!(storage.retrieve_menu(&menu_v1).is_err());
+    assert!(storage.retrieve_image(&image).is_ok());
+
+    // Без меню изображение удаляется вместе со всеми кусками
+    storage.unpin(&menu_v2, &ipfs_storage::PinReason::MenuVersion { version: 2 }).unwrap();
+    let reclaimed = storage.collect_garbage().unwrap();
+    assert!(reclaimed > (CHUNK_SIZE + 1) as u64);
+    assert!(storage.retrieve_image(&image).is_err());
+    assert_eq!(storage.retrieve_sales_report(&report_hash).unwrap().node_id, 1);
+
+    let stats = storage.get_storage_stats();
+    assert_eq!((stats.total_files, stats.pinned_hashes, stats.garbage_files), (1, 1, 0));
+    assert_eq!(fs::read_dir(dir.join("blocks")).unwrap().count(), 1);
+    assert!(stats.reclaimed_bytes >= reclaimed + draft_size);
+    assert!(storage.sync_with_network().unwrap().is_empty());
+}
+
+#[test]
+fn blocks_from_peers_are_verified_before_caching() {
+    let source_dir = temp_ipfs_dir("exchange_source");
+    let mut source = open(&source_dir);
+    let image: Vec<u8> = (0..CHUNK_SIZE + 10).map(|i| (i % 7) as u8).collect();
+    let hash = source.store_image(&image, "khachapuri.webp").unwrap();
+
+    let mut cache = open(&temp_ipfs_dir("exchange_cache"));
+    let (root, content_type) = source.provide(&hash).unwrap();
+    // Незапрошенный блок не сохраняется
+    assert!(cache.receive_block(&hash, &root, content_type.clone()).unwrap().is_empty());
+    assert!(cache.want(&hash, None).unwrap());
+
+    let chunks = cache.receive_block(&hash, &root, content_type).unwrap();
+    assert_eq!(chunks.len(), 2);
+    assert!(cache.receive_block(&chunks[0], b"forged chunk", None).unwrap_err().contains("integrity"));
+    for chunk in &chunks {
+        let (data, _) = source.provide(chunk).unwrap();
+        assert!(cache.receive_block(chunk, &data, None).unwrap().is_empty());
+    }
+    assert!(cache.wanted().is_empty());
+    assert_eq!(cache.retrieve_image(&hash).unwrap(), image);
+    assert_eq!(cache.index[&hash].content_type, "image/webp");
+    assert!(!cache.want(&hash, None).unwrap());
+}
+
+#[test]
+fn garbage_collection_keeps_fetches_in_progress_and_skips_missing_roots() {
+    let mut source = open(&temp_ipfs_dir("gc_fetch_source"));
+    let image: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| (i % 5) as u8).collect();
+    let hash = source.store_image(&image, "mtsvadi.webp").unwrap();
+
+    // Корень и первый кусок уже получены, остальное в пути
+    let dir = temp_ipfs_dir("gc_fetch");
+    let mut cache = open(&dir);
+    let draft = cache.store_data("draft", "text/plain").unwrap();
+    assert!(cache.want(&hash, None).unwrap());
+    let (root, content_type) = source.provide(&hash).unwrap();
+    let chunks = cache.receive_block(&hash, &root, content_type).unwrap();
+    assert!(cache.receive_block(&chunks[0], &source.provide(&chunks[0]).unwrap().0, None).unwrap().is_empty());
+    cache.collect_garbage().unwrap();
+    assert!(cache.retrieve_data(&draft).is_err());
+    for chunk in &chunks[1..] {
+        cache.receive_block(chunk, &source.provide(chunk).unwrap().0, None).unwrap();
+    }
+    assert_eq!(cache.retrieve_image(&hash).unwrap(), image);
+
+    // Закрепленный объект без корня на диске не останавливает сборку
+    let menu_hash = cache.store_menu(&menu(1, None)).unwrap();
+    let scratch = cache.store_data("scratch", "text/plain").unwrap();
+    fs::remove_file(dir.join("blocks").join(&menu_hash)).unwrap();
+    cache.collect_garbage().unwrap();
+    assert!(cache.retrieve_data(&scratch).is_err());
+    assert!(cache.index.contains_key(&menu_hash));
+    assert_eq!(cache.sync_with_network().unwrap(), vec![menu_hash]);
+}
@@ -0,0 +1,147 @@
+use crate::*;
+use crate::franchise_network::{FRANCHISE_OWNER_SHARE, FRANCHISE_ROYALTY_SHARE};
+use std::fs;
+
+// Основатель "Alice" владеет и сетью Blockchain, и франшизной сетью
+fn sources() -> (Blockchain, FranchiseNetwork) {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.add_franchise_node("Truck_Franchise".to_string(), "Bob".to_string()).unwrap();
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 40.0, vec!["Burger".to_string()]).unwrap();
+    bc.process_purchase("Customer".to_string(), "Truck_Franchise".to_string(), 12.5, vec!["Pizza".to_string()]).unwrap();
+
+    let mut network = FranchiseNetwork::new("Alice".to_string());
+    network.whitelist_pos("POS_1".to_string());
+    let node_id = network.register_node("Bob".to_string(), NodeType::FRANCHISE, "Batumi".to_string()).unwrap();
+    let items = vec![SaleItem { item_id: "khachapuri".to_string(), quantity: 1, price: 25.5 }];
+    network.record_sale(node_id, "sale_1".to_string(), 25.5, "buyer".to_string(), "POS_1".to_string(), items).unwrap();
+    (bc, network)
+}
+
+#[test]
+fn migration_merges_wallets_supply_and_franchise_nodes() {
+    let (bc, network) = sources();
+    network.audit().unwrap();
+    bc.audit().unwrap();
+
+    let ledger = Ledger::migrate(&[&bc, &network]).unwrap();
+    assert_eq!(ledger.total_supply(), bc.security_supply + network.total_supply);
+    assert_eq!(ledger.balance("Alice"), bc.token_holders["Alice"].security_tokens + network.get_wallet_balance("Alice"));
+    assert_eq!(ledger.balance("Bob"), bc.token_holders["Bob"].security_tokens + FRANCHISE_OWNER_SHARE);
+    assert_eq!(network.get_wallet_balance("Alice"), SCALE + FRANCHISE_ROYALTY_SHARE);
+    assert_eq!(ledger.franchise_nodes.get("blockchain:Truck_Franchise"), Some(&"Bob".to_string()));
+    assert_eq!(ledger.franchise_nodes.get("franchise_network:1"), Some(&"Bob".to_string()));
+    ledger.check_consistency(&[&bc, &network]).unwrap();
+}
+
+#[test]
+fn consistency_check_rejects_diverged_supplies() {
+    let (mut bc, network) = sources();
+    let ledger = Ledger::migrate(&[&bc, &network]).unwrap();
+
+    // Перекос внутри реестра и расхождение с исходной моделью
+    let mut forged = ledger.clone();
+    *forged.accounts.get_mut("blockchain:Customer").unwrap() += 1;
+    assert!(forged.check_consistency(&[&bc, &network]).unwrap_err().contains("ledger mismatch"));
+    let mut forged = ledger.clone();
+    forged.credit("blockchain", "Mallory", 1);
+    assert!(forged.check_consistency(&[&bc, &network]).unwrap_err().contains("Supply mismatch"));
+    let mut forged = ledger.clone();
+    forged.debit("blockchain", "Customer", 1).unwrap();
+    forged.credit("blockchain", "Mallory", 1);
+    assert!(forged.check_consistency(&[&bc, &network]).unwrap_err().contains("Customer"));
+    assert!(ledger.check_consistency(&[&bc]).unwrap_err().contains("franchise_network"));
+
+    // Модель, чьи балансы не сходятся с эмиссией, не мигрирует
+    bc.security_supply += 1;
+    assert!(Ledger::migrate(&[&bc, &network]).is_err());
+}
+
+#[test]
+fn franchise_node_keys_are_namespaced_by_model() {
+    let (mut bc, network) = sources();
+    // Нода "1" в Blockchain и нода 1 франшизной сети — разные ноды
+    bc.add_franchise_node("1".to_string(), "Carol".to_string()).unwrap();
+    let ledger = Ledger::migrate(&[&bc, &network]).unwrap();
+    assert_eq!(ledger.franchise_nodes.get("blockchain:1"), Some(&"Carol".to_string()));
+    assert_eq!(ledger.franchise_nodes.get("franchise_network:1"), Some(&"Bob".to_string()));
+}
+
+#[test]
+fn both_models_mint_and_burn_through_the_ledger() {
+    let (mut bc, mut network) = sources();
+    let dir = std::env::temp_dir().join(format!("ledger_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ledger.json");
+
+    Ledger::migrate(&[&bc, &network]).unwrap().save(&path).unwrap();
+    let ledger = LedgerStore::open(&path).unwrap().unwrap();
+    bc.attach_ledger(Arc::clone(&ledger)).unwrap();
+    network.attach_ledger(Arc::clone(&ledger)).unwrap();
+
+    let owner = network.nodes[&1].owner_address.clone();
+    network.mint_to_node_owner(1, 500).unwrap();
+    assert_eq!(network.burn_from_node_owner(1, 10_000).unwrap(), 500 + FRANCHISE_OWNER_SHARE);
+    network.transfer("Alice", "new_wallet", 10).unwrap();
+    assert!(network.transfer("new_wallet", "Alice", 11).is_err());
+    assert_eq!(network.get_wallet_balance(&owner), 0);
+    network.audit().unwrap();
+
+    bc.mint("Investor", 700).unwrap();
+    assert_eq!(bc.burn("Investor", 200).unwrap(), 200);
+    bc.transfer("Investor", "Customer", 500).unwrap();
+    assert_eq!(bc.balance("Investor"), 0);
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 7.0, vec!["Tea".to_string()]).unwrap();
+    bc.audit_ledger().unwrap();
+
+    // Реестр видел каждое проведение обеих моделей и сохранен на диск
+    let store = ledger.lock().unwrap();
+    store.ledger().check_consistency(&[&bc, &network]).unwrap();
+    assert_eq!(store.ledger().balance("new_wallet"), 10);
+    let reopened = LedgerStore::open(&path).unwrap().unwrap();
+    assert_eq!(reopened.lock().unwrap().ledger(), store.ledger());
+    drop(store);
+
+    // Модель, изменившаяся в обход реестра, не подключается
+    let mut diverged = bc.clone();
+    diverged.ledger = None;
+    diverged.mint("Mallory", 1).unwrap();
+    assert!(diverged.attach_ledger(Arc::clone(&ledger)).unwrap_err().contains("Supply mismatch"));
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn wal_replay_completes_an_operation_torn_between_postings() {
+    let dir = std::env::temp_dir().join(format!("ledger_wal_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let path = dir.join("ledger.json");
+    let mut bc = storage::initialize(&dir, 1000, Blockchain::new("Alice".to_string())).unwrap();
+    Ledger::migrate(&[&bc]).unwrap().save(&path).unwrap();
+    let ledger = LedgerStore::open(&path).unwrap().unwrap();
+    bc.attach_ledger(Arc::clone(&ledger)).unwrap();
+
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 40.0, vec!["Burger".to_string()]).unwrap();
+    let before_last = ledger.lock().unwrap().ledger().seq;
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 12.5, vec!["Pizza".to_string()]).unwrap();
+    let expected = ledger.lock().unwrap().ledger().clone();
+    assert!(expected.seq >= before_last + 2, "the purchase should post several mints");
+    drop(bc);
+    drop(ledger);
+
+    // Сбой после первого начисления последней покупки: в журнале реестра только ее первая проводка
+    let log = fs::read_to_string(dir.join("ledger.log")).unwrap();
+    let kept: Vec<&str> = log.lines().take(before_last as usize + 1).collect();
+    fs::write(dir.join("ledger.log"), format!("{}\n", kept.join("\n"))).unwrap();
+    let ledger = LedgerStore::open(&path).unwrap().unwrap();
+    assert_eq!(ledger.lock().unwrap().ledger().seq, before_last + 1);
+    let mut partial = Blockchain::new("Alice".to_string());
+    partial.process_purchase("Customer".to_string(), "Truck1".to_string(), 40.0, vec!["Burger".to_string()]).unwrap();
+    assert!(ledger.lock().unwrap().ledger().verify_source(&partial).is_err());
+
+    // Воспроизведение WAL не повторяет записанные проводки и дописывает недостающие
+    let mut restored = storage::recover(&dir, 1000, Some(Arc::clone(&ledger))).unwrap().unwrap();
+    restored.attach_ledger(Arc::clone(&ledger)).unwrap();
+    assert_eq!(ledger.lock().unwrap().ledger(), &expected);
+    assert_eq!(LedgerStore::open(&path).unwrap().unwrap().lock().unwrap().ledger(), &expected);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,223 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::bft::Bft;
+use crate::chain_sync::ChainSync;
+use crate::clock;
+use crate::consensus::{block_signing_bytes, Block, BlockSignature, ConsensusAlgorithm, Transaction, TransactionType};
+use crate::crypto::Keypair;
+use crate::executor::{Executor, SalePayload};
+use crate::franchise_network::{FranchiseNetwork, NodeType, SaleItem};
+use crate::light_client::{self, LightClient};
+use crate::network_simulator::SIM_EPOCH_SECS;
+use crate::p2p_network::{NodeContext, P2PMessage, P2PNode};
+use crate::peer_book::PeerBook;
+use crate::state_tree::{self, StateEntry, StateKey};
+use crate::tests::fixtures::validator_network;
+
+// Четыре ноды с ключами и продажами: все проходят в валидаторы, кворум — три подписи
+fn network() -> (FranchiseNetwork, Vec<(u64, Keypair)>) {
+    validator_network(&[("Tbilisi", NodeType::OWNER), ("Batumi", NodeType::FRANCHISE), ("Kutaisi", NodeType::FRANCHISE), ("Gori", NodeType::FRANCHISE)])
+}
+
+fn trusted_keys(nodes: &[(u64, Keypair)]) -> BTreeMap<u64, String> {
+    nodes.iter().map(|(node_id, keypair)| (*node_id, keypair.public_key_hex())).collect()
+}
+
+fn sale(node: &(u64, Keypair), sale_id: &str) -> Transaction {
+    let payload = SalePayload {
+        sale_id: sale_id.to_string(),
+        price_gel: 25.5,
+        buyer_meta: "buyer".to_string(),
+        pos_id: "POS_1".to_string(),
+        items: vec![SaleItem { item_id: "khachapuri".to_string(), quantity: 1, price: 25.5 }],
+    };
+    let mut tx = Transaction::new(node.0, TransactionType::Sale, serde_json::to_value(payload).unwrap());
+    tx.id = format!("tx_{}", sale_id);
+    tx.sign(&node.1);
+    tx
+}
+
+// Полная нода с цепью из count блоков, каждый со своей продажей и подписями всех нод
+fn full_node(count: u64) -> (NodeContext, Vec<(u64, Keypair)>) {
+    let (base, nodes) = network();
+    let consensus = ConsensusAlgorithm::new();
+    let executor = Executor::new(base.clone());
+    let mut network = base.clone();
+    let mut committed = base.clone();
+    let mut chain = ChainSync::new();
+    let validators: Vec<u64> = nodes.iter().map(|(node_id, _)| *node_id).collect();
+
+    for height in 0..count {
+        let _clock = clock::freeze(SIM_EPOCH_SECS + height);
+        let mut block = Block::new(height, chain.tip_hash(), vec![sale(&nodes[1], &format!("light_sale_{}", height))])
+            .with_header_commitments(validators.clone(), Vec::new(), state_tree::root(&network));
+        for (node_id, keypair) in &nodes {
+            block.sign(*node_id, keypair);
+        }
+        let outcome = chain.import(block, &consensus, executor.base()).unwrap();
+        executor.apply_chain_update(&mut network, &mut committed, &consensus, &chain, &outcome, height);
+    }
+
+    let context = NodeContext {
+        node_id: nodes[0].0,
+        listen_port: 9001,
+        keypair: Arc::new(Keypair::generate()),
+        peers: Arc::new(Mutex::new(HashMap::new())),
+        peer_book: Arc::new(Mutex::new(PeerBook::new())),
+        franchise_network: Arc::new(Mutex::new(network)),
+        blockchain: Arc::new(Mutex::new(chain)),
+        executor: Arc::new(executor),
+        committed_state: Arc::new(Mutex::new(committed)),
+        bft: Arc::new(Mutex::new(Bft::new(nodes[0].0))),
+        pending_transactions: Arc::new(Mutex::new(Vec::new())),
+        storage: None,
+    };
+    (context, nodes)
+}
+
+fn headers(context: &NodeContext) -> Vec<crate::consensus::BlockHeader> {
+    context.blockchain.lock().unwrap().main_chain().iter().map(Block::header).collect()
+}
+
+#[test]
+fn headers_need_a_quorum_of_known_validator_signatures() {
+    let (context, nodes) = full_node(3);
+    let headers = headers(&context);
+
+    let mut client = LightClient::new(trusted_keys(&nodes));
+    for header in headers.clone() {
+        client.accept_header(header).unwrap();
+    }
+    assert_eq!(client.height(), 3);
+
+    let mut client = LightClient::new(trusted_keys(&nodes));
+    client.accept_header(headers[0].clone()).unwrap();
+
+    // Заголовок не с той высоты или не от того родителя
+    assert!(client.accept_header(headers[2].clone()).is_err());
+
+    // Подмена корня состояния ломает хеш, а пересчитанный хеш — подписи
+    let mut forged = headers[1].clone();
+    forged.state_root = state_tree::root(&FranchiseNetwork::new("other".to_string()));
+    assert!(client.accept_header(forged.clone()).is_err());
+    forged.hash = forged.calculate_hash();
+    assert!(client.accept_header(forged).unwrap_err().contains("verifiable signatures"));
+
+    // Двух подписей из четырех мало
+    let mut under_signed = headers[1].clone();
+    under_signed.signatures.truncate(2);
+    assert!(client.accept_header(under_signed).is_err());
+
+    // Подписи нод с неизвестными ключами не считаются
+    let mut stranger = LightClient::new(trusted_keys(&nodes[..1]));
+    assert!(stranger.accept_header(headers[0].clone()).is_err());
+
+    client.accept_header(headers[1].clone()).unwrap();
+    assert_eq!(client.tip().unwrap().hash, headers[1].hash);
+}
+
+#[test]
+fn quorum_comes_from_the_verified_validator_set() {
+    let (context, nodes) = full_node(2);
+    let headers = headers(&context);
+    let mut client = LightClient::new(trusted_keys(&nodes));
+    client.accept_header(headers[0].clone()).unwrap();
+
+    // Заголовок с одним валидатором, подписанный им самим, не набирает кворум проверенного набора
+    let mut forged = headers[1].clone();
+    forged.validators = vec![nodes[0].0];
+    forged.hash = forged.calculate_hash();
+    forged.signatures = vec![BlockSignature {
+        validator_id: nodes[0].0,
+        round: 0,
+        signature: nodes[0].1.sign(&block_signing_bytes(forged.height, 0, &forged.hash, nodes[0].0)),
+        timestamp: forged.timestamp,
+    }];
+    assert!(client.accept_header(forged.clone()).unwrap_err().contains("more than a third"));
+
+    // Даже при допустимой смене набора нужны подписи 2/3 + 1 прежних валидаторов
+    forged.validators = nodes[..3].iter().map(|(node_id, _)| *node_id).collect();
+    forged.hash = forged.calculate_hash();
+    forged.signatures = vec![BlockSignature {
+        validator_id: nodes[0].0,
+        round: 0,
+        signature: nodes[0].1.sign(&block_signing_bytes(forged.height, 0, &forged.hash, nodes[0].0)),
+        timestamp: forged.timestamp,
+    }];
+    assert!(client.accept_header(forged).unwrap_err().contains("verifiable signatures"));
+
+    client.accept_header(headers[1].clone()).unwrap();
+}
+
+#[test]
+fn wallet_balance_and_check_are_verified_against_state_root() {
+    let (context, nodes) = full_node(2);
+    let mut client = LightClient::new(trusted_keys(&nodes));
+    for header in headers(&context) {
+        client.accept_header(header).unwrap();
+    }
+
+    let committed = context.committed_state.lock().unwrap().clone();
+    let address = committed.nodes[&nodes[1].0].owner_address.clone();
+    let proof = state_tree::prove(&committed, &StateKey::Wallet(address.clone())).unwrap();
+    client.accept_proof(1, proof.clone()).unwrap();
+    assert_eq!(client.balance(&address), Some((1, committed.get_wallet_balance(&address))));
+
+    // Продажа из блока 0 уже в состоянии, на котором построен блок 1
+    let check = StateKey::Check("light_sale_0".to_string());
+    client.accept_proof(1, state_tree::prove(&committed, &check).unwrap()).unwrap();
+    assert!(matches!(client.entry(&check), Some((1, StateEntry::Check { node_id, .. })) if *node_id == nodes[1].0));
+    assert!(state_tree::prove(&committed, &StateKey::Check("light_sale_1".to_string())).is_none());
+
+    // Завышенный баланс и доказательство против чужого заголовка не проходят
+    let mut forged = proof.clone();
+    forged.entry = StateEntry::Wallet { address: address.clone(), balance: 1_000_000_000 };
+    assert!(client.accept_proof(1, forged).is_err());
+    assert!(client.accept_proof(0, proof.clone()).is_err());
+    assert!(client.accept_proof(2, proof).is_err());
+}
+
+#[test]
+fn light_client_syncs_from_full_node_without_becoming_a_peer() {
+    let (context, nodes) = full_node(3);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_context = context.clone();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        P2PNode::handle_connection(stream, false, server_context)
+    });
+
+    // Ключ четвертой ноды клиент узнает из состояния, подписей трех остальных хватает для кворума
+    let mut keys = trusted_keys(&nodes);
+    keys.remove(&nodes[3].0);
+    let committed = context.committed_state.lock().unwrap().clone();
+    let address_1 = committed.nodes[&nodes[1].0].owner_address.clone();
+    let mut client = LightClient::new(keys);
+    client.watch(StateKey::Wallet(address_1.clone()));
+    client.watch(StateKey::Check("light_sale_1".to_string()));
+
+    client.sync(address).unwrap();
+    assert_eq!(client.height(), 3);
+    assert_eq!(client.balance(&address_1), Some((2, committed.get_wallet_balance(&address_1))));
+    assert!(client.entry(&StateKey::Check("light_sale_1".to_string())).is_some());
+    assert!(matches!(client.entry(&StateKey::Node(nodes[3].0)), Some((2, StateEntry::Node { active: true, .. }))));
+
+    // Ответ на запрос отсутствующей записи не дает проверенной записи
+    assert!(client.handle(P2PMessage::StateProof { height: 2, proof: None }).unwrap().is_empty());
+
+    assert!(handle.join().unwrap().is_ok());
+    assert!(context.peers.lock().unwrap().is_empty());
+}
+
+#[test]
+fn trusted_keys_are_parsed_from_env_format() {
+    let keypair = Keypair::generate();
+    let keys = light_client::parse_trusted_keys(&format!("7:{}, ", keypair.public_key_hex())).unwrap();
+    assert_eq!(keys.get(&7), Some(&keypair.public_key_hex()));
+    assert!(light_client::parse_trusted_keys("7").is_err());
+    assert!(light_client::parse_trusted_keys("x:abcd").is_err());
+    assert!(light_client::parse_trusted_keys("7:nothex").is_err());
+}
@@ -38,7 +38,7 @@ fn test_high_volume_purchase_performance() {
                 "Truck".to_string(),
                 purchase_amount,
                 vec!["Meal".to_string()],
-            );
+            ).unwrap();
         }
         
         let purchase_time = start_time.elapsed();
@@ -49,14 +49,14 @@ fn test_high_volume_purchase_performance() {
         let security_time = security_start.elapsed();
         
         // Анализируем результаты
-        let total_tokens: f64 = bc_test.token_holders.values().map(|h| h.security_tokens).sum();
+        let total_tokens: u64 = bc_test.token_holders.values().map(|h| h.security_tokens).sum();
         let owner_tokens = bc_test.token_holders.get(&owner).unwrap().security_tokens;
-        let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+        let owner_percentage = percentage_of(owner_tokens, total_tokens);
         
         println!("  Время выполнения покупок: {:?}", purchase_time);
         println!("  Время проверки безопасности: {:?}", security_time);
         println!("  Покупок в секунду: {:.2}", num_purchases as f64 / purchase_time.as_secs_f64());
-        println!("  Токены владельца: {:.2}", owner_tokens);
+        println!("  Токены владельца: {}", format_units(owner_tokens));
         println!("  Процент владельца: {:.2}%", owner_percentage);
         println!("  Безопасность: {}", if report.is_secure { "✅" } else { "⚠️" });
         println!("  Рисков: {}", report.security_risks.len());
@@ -84,7 +84,7 @@ fn test_concurrent_balance_transfers() {
             "Truck".to_string(),
             15.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
         checks.push(check);
     }
     
@@ -148,7 +148,7 @@ fn test_memory_usage_under_load() {
         // Создаем множество держателей токенов
         for i in 0..size {
             let mut holder = TokenHolder::new(format!("holder{}", i), false);
-            holder.add_security_tokens(10.0 + (i as f64 % 100.0));
+            holder.add_security_tokens((10 + i as u64 % 100) * SCALE);
             bc_test.token_holders.insert(format!("holder{}", i), holder);
         }
         
@@ -156,7 +156,7 @@ fn test_memory_usage_under_load() {
         for i in 0..size {
             let phone = format!("+123456789{}", i);
             let wallet = format!("0xwallet{}", i);
-            let user = AuthorizedUser::new(phone.clone(), wallet.clone());
+            let user = AuthorizedUser::new(phone.clone(), wallet.clone(), crypto::random_code());
             bc_test.authorized_users.insert(phone, user);
         }
         
@@ -168,8 +168,8 @@ fn test_memory_usage_under_load() {
                 from_wallet: format!("0xfrom{}", i),
                 to_wallet: format!("0xto{}", i),
                 to_phone: format!("+123456789{}", i),
-                security_tokens_transferred: 10.0,
-                utility_tokens_transferred: 1.0,
+                security_tokens_transferred: 10 * SCALE,
+                utility_tokens_transferred: SCALE,
                 timestamp: 1234567890 + i as u64,
                 status: TransferStatus::Completed,
             };
@@ -213,9 +213,9 @@ fn test_stress_testing_security_checks() {
     for i in 0..100 {
         let mut holder = TokenHolder::new(format!("risky_holder{}", i), false);
         let tokens = match i % 10 {
-            0 => 1000.0, // Высокий риск
-            1..=3 => 500.0, // Средний риск
-            _ => 50.0, // Низкий риск
+            0 => 1000 * SCALE, // Высокий риск
+            1..=3 => 500 * SCALE, // Средний риск
+            _ => 50 * SCALE, // Низкий риск
         };
         holder.add_security_tokens(tokens);
         bc_stress.token_holders.insert(format!("risky_holder{}", i), holder);
@@ -224,7 +224,7 @@ fn test_stress_testing_security_checks() {
     // Создаем "китов" с большим количеством токенов
     for i in 0..10 {
         let mut whale = TokenHolder::new(format!("whale{}", i), false);
-        whale.add_security_tokens(2000.0 + (i as f64 * 100.0));
+        whale.add_security_tokens((2000 + i as u64 * 100) * SCALE);
         bc_stress.token_holders.insert(format!("whale{}", i), whale);
     }
     
@@ -235,7 +235,7 @@ fn test_stress_testing_security_checks() {
     for _ in 0..iterations {
         let report = bc_stress.check_network_security();
         // Проверяем, что отчет генерируется корректно
-        assert!(report.total_security_tokens > 0.0);
+        assert!(report.total_security_tokens > 0);
     }
     
     let total_time = start_time.elapsed();
@@ -250,7 +250,7 @@ fn test_stress_testing_security_checks() {
     let final_report = bc_stress.check_network_security();
     println!("  Финальная безопасность: {}", if final_report.is_secure { "✅" } else { "⚠️" });
     println!("  Количество рисков: {}", final_report.security_risks.len());
-    println!("  Общие токены: {:.2}", final_report.total_security_tokens);
+    println!("  Общие токены: {}", format_units(final_report.total_security_tokens));
     
     // Проверяем, что система остается стабильной
     assert!(bc_stress.is_chain_valid());
@@ -281,7 +281,7 @@ fn test_scalability_limits() {
         
         for i in 0..size {
             let mut holder = TokenHolder::new(format!("scale_holder{}", i), false);
-            holder.add_security_tokens(10.0 + (i as f64 % 50.0));
+            holder.add_security_tokens((10 + i as u64 % 50) * SCALE);
             bc_scale.token_holders.insert(format!("scale_holder{}", i), holder);
         }
         
@@ -302,7 +302,7 @@ fn test_scalability_limits() {
         
         // Операция 3: Обновление ролей
         let roles_start = Instant::now();
-        bc_scale.update_roles();
+        bc_scale.update_roles().unwrap();
         let roles_time = roles_start.elapsed();
         
         let ops_time = ops_start.elapsed();
@@ -349,22 +349,22 @@ fn test_network_growth_simulation() {
         
         for i in 0..users_to_add {
             let mut holder = TokenHolder::new(format!("growth_user{}", i), false);
-            holder.add_security_tokens(10.0 + (i as f64 % 100.0));
+            holder.add_security_tokens((10 + i as u64 % 100) * SCALE);
             bc.token_holders.insert(format!("growth_user{}", i), holder);
         }
         
         let growth_time = start_time.elapsed();
         
         // Анализируем состояние сети
-        let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+        let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
         let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-        let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+        let owner_percentage = percentage_of(owner_tokens, total_tokens);
         
         let report = bc.check_network_security();
         
         println!("  Пользователей: {}", bc.token_holders.len());
         println!("  Время роста: {:?}", growth_time);
-        println!("  Общие токены: {:.2}", total_tokens);
+        println!("  Общие токены: {}", format_units(total_tokens));
         println!("  Процент владельца: {:.2}%", owner_percentage);
         println!("  Безопасность: {}", if report.is_secure { "✅" } else { "⚠️" });
         println!("  Рисков: {}", report.security_risks.len());
@@ -372,7 +372,7 @@ fn test_network_growth_simulation() {
         // Анализируем распределение власти
         let mut power_distribution = HashMap::new();
         for holder in bc.token_holders.values() {
-            let percentage = (holder.security_tokens / total_tokens) * 100.0;
+            let percentage = percentage_of(holder.security_tokens, total_tokens);
             let category = match percentage {
                 p if p >= 10.0 => "10%+",
                 p if p >= 5.0 => "5-10%",
@@ -403,13 +403,13 @@ fn test_extreme_scenarios() {
     // Сценарий 1: Один "кит" с 90% токенов
     println!("\n--- Сценарий 1: Кит с 90% токенов ---");
     let mut whale = TokenHolder::new("mega_whale".to_string(), false);
-    whale.add_security_tokens(9000.0);
+    whale.add_security_tokens(9000 * SCALE);
     bc.token_holders.insert("mega_whale".to_string(), whale);
     
     // Добавляем мелких держателей
     for i in 0..1000 {
         let mut holder = TokenHolder::new(format!("small_holder{}", i), false);
-        holder.add_security_tokens(1.0);
+        holder.add_security_tokens(SCALE);
         bc.token_holders.insert(format!("small_holder{}", i), holder);
     }
     
@@ -423,7 +423,7 @@ fn test_extreme_scenarios() {
     
     for i in 0..1000 {
         let mut holder = TokenHolder::new(format!("even_holder{}", i), false);
-        holder.add_security_tokens(10.0);
+        holder.add_security_tokens(10 * SCALE);
         bc_even.token_holders.insert(format!("even_holder{}", i), holder);
     }
     
@@ -436,17 +436,17 @@ fn test_extreme_scenarios() {
     let mut bc_duopoly = Blockchain::new(owner.clone());
     
     let mut player1 = TokenHolder::new("player1".to_string(), false);
-    player1.add_security_tokens(4000.0);
+    player1.add_security_tokens(4000 * SCALE);
     bc_duopoly.token_holders.insert("player1".to_string(), player1);
     
     let mut player2 = TokenHolder::new("player2".to_string(), false);
-    player2.add_security_tokens(4000.0);
+    player2.add_security_tokens(4000 * SCALE);
     bc_duopoly.token_holders.insert("player2".to_string(), player2);
     
     // Добавляем мелких игроков
     for i in 0..100 {
         let mut holder = TokenHolder::new(format!("small_player{}", i), false);
-        holder.add_security_tokens(20.0);
+        holder.add_security_tokens(20 * SCALE);
         bc_duopoly.token_holders.insert(format!("small_player{}", i), holder);
     }
     
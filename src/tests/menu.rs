@@ -19,10 +19,65 @@ fn menu_item_details_and_voting_flow() {
     );
     assert_eq!(item.total_calories, 100.0);
     assert!(matches!(item.status, MenuItemStatus::Proposed));
-    item.start_voting();
+    item.start_voting(BTreeMap::from([("Sugg".to_string(), 250)]), VotingStrategy::Linear);
     assert!(matches!(item.status, MenuItemStatus::Voting));
-    item.vote(2.5, true).expect("vote allowed while voting active");
-    assert_eq!(item.votes_for, 2.5);
+    item.vote("Sugg", true).expect("vote allowed while voting active");
+    assert_eq!(item.votes_for, 250);
+    assert!(item.vote("Other", true).is_err());
+}
+
+#[test]
+fn one_ballot_per_wallet_with_snapshot_weights() {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    let item = MenuItem::new("Khinkali".to_string(), "Dumplings".to_string(), 12.0, "Alice".to_string(), 7);
+    let menu_item_id = item.id.clone();
+    bc.menu_items.push(item);
+    bc.make_menu_item_available_for_voting(menu_item_id.clone(), VotingStrategy::Linear).unwrap();
+    let customer_power = bc.token_holders["Customer"].utility_tokens;
+
+    // Токены, полученные после открытия голосования, не голосуют второй раз
+    bc.token_holders.get_mut("Customer").unwrap().utility_tokens -= customer_power;
+    bc.token_holders.get_mut("Alice").unwrap().utility_tokens += customer_power;
+    bc.token_holders.insert("Late".to_string(), TokenHolder::new("Late".to_string(), false));
+    bc.token_holders.get_mut("Late").unwrap().add_utility_tokens(customer_power);
+    assert!(bc.vote_on_menu_item("Late".to_string(), menu_item_id.clone(), true).is_err());
+
+    bc.vote_on_menu_item("Customer".to_string(), menu_item_id.clone(), true).unwrap();
+    assert!(bc.vote_on_menu_item("Customer".to_string(), menu_item_id.clone(), true).unwrap_err().contains("already voted"));
+    bc.vote_on_menu_item("Alice".to_string(), menu_item_id.clone(), true).unwrap();
+    let alice_power = bc.menu_items[0].ballot_box.snapshot["Alice"];
+    assert_eq!(bc.menu_items[0].votes_for, customer_power + alice_power);
+
+    // Смена голоса переносит вес, а не добавляет его
+    bc.vote_on_menu_item("Customer".to_string(), menu_item_id.clone(), false).unwrap();
+    assert_eq!((bc.menu_items[0].votes_for, bc.menu_items[0].votes_against), (alice_power, customer_power));
+
+    let history = bc.get_voting_history();
+    assert_eq!(history.len(), 3);
+    assert!(history.iter().all(|record| record.menu_item_id == menu_item_id && record.menu_item_name == "Khinkali"));
+}
+
+#[test]
+fn voting_contract_casts_a_ballot_on_its_menu_item() {
+    let mut bc = Blockchain::new("Alice".to_string());
+    bc.process_purchase("Customer".to_string(), "Truck1".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    let item = MenuItem::new("Lobio".to_string(), "Beans".to_string(), 8.0, "Alice".to_string(), 7);
+    let menu_item_id = item.id.clone();
+    bc.menu_items.push(item);
+    assert!(bc.create_voting_contract("Customer".to_string(), "missing".to_string()).is_err());
+    bc.make_menu_item_available_for_voting(menu_item_id.clone(), VotingStrategy::Linear).unwrap();
+
+    let contract_id = bc.create_voting_contract("Customer".to_string(), menu_item_id.clone()).unwrap();
+    bc.execute_voting_contract(contract_id.clone(), "Customer".to_string(), true).unwrap();
+    assert!(bc.execute_voting_contract(contract_id, "Customer".to_string(), true).is_err());
+
+    let record = &bc.get_voting_history()[0];
+    assert_eq!(record.menu_item_id, menu_item_id);
+    assert_eq!(record.vote_weight, bc.token_holders["Customer"].utility_tokens);
+    assert_eq!(bc.menu_items[0].votes_for, record.vote_weight);
+    let contract = bc.smart_contracts.iter().find(|c| c.subject.as_ref() == Some(&menu_item_id)).unwrap();
+    assert_eq!(contract.execution_history.len(), 1);
 }
 
 
@@ -0,0 +1,118 @@
+use crate::*;
+use crate::consensus::{Block as ConsensusBlock, Transaction as ConsensusTransaction, TransactionType};
+use crate::merkle;
+use crate::tests::fixtures::{add_validators, holder, holder_key};
+
+fn leaves(count: usize) -> Vec<Vec<u8>> {
+    (0..count).map(|i| format!("tx_{}", i).into_bytes()).collect()
+}
+
+fn produced_blockchain() -> Blockchain {
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Customer", "Customer2"]);
+    bc.process_purchase(holder("Customer"), "Truck".to_string(), 50.0, vec!["Meal".to_string()]).unwrap();
+    bc.process_purchase(holder("Customer2"), "Truck".to_string(), 20.0, vec!["Tea".to_string()]).unwrap();
+    bc.produce_block().unwrap();
+    bc
+}
+
+#[test]
+fn every_leaf_has_a_proof_to_the_root() {
+    for count in 1..=9 {
+        let leaves = leaves(count);
+        let root = merkle::root(&leaves);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = merkle::proof(&leaves, index).unwrap();
+            assert!(merkle::verify(&root, leaf, &proof), "leaf {} of {}", index, count);
+            assert!(!merkle::verify(&root, b"forged", &proof));
+        }
+        assert!(merkle::proof(&leaves, count).is_none());
+    }
+
+    // Поднятый без дублирования непарный лист не дает совпадения с дублированным списком
+    let mut duplicated = leaves(3);
+    duplicated.push(duplicated[2].clone());
+    assert_ne!(merkle::root(&leaves(3)), merkle::root(&duplicated));
+}
+
+#[test]
+fn mined_block_proves_inclusion_against_its_header() {
+    let bc = produced_blockchain();
+    let block = bc.chain.last().unwrap();
+    assert!(!block.merkle_root.is_empty());
+    assert!(bc.is_chain_valid());
+
+    let tx_id = block.transactions[0].transaction_id.clone();
+    let response = ApiServer::process_request(ApiRequest::GetInclusionProof { tx_id: tx_id.clone() }, Arc::new(Mutex::new(bc.clone())));
+    let proof = match response {
+        ApiResponse::InclusionProof { proof } => proof,
+        other => panic!("no proof: {:?}", other),
+    };
+
+    // Кошелек проверяет только заголовок, лист и путь
+    assert_eq!(proof.header.hash, proof.header.calculate_hash());
+    assert!(merkle::verify(&proof.header.merkle_root, proof.leaf.as_bytes(), &proof.proof));
+    let leaf: TransactionLeaf = serde_json::from_str(&proof.leaf).unwrap();
+    assert_eq!(leaf.transaction_id, tx_id);
+
+    // Секреты чека в доказательство не попадают
+    let check = block.transactions[0].check.as_ref().unwrap();
+    assert!(leaf.check_hash.is_some());
+    assert!(!proof.leaf.contains(&check.activation_code));
+
+    match ApiServer::process_request(ApiRequest::GetInclusionProof { tx_id: "missing".to_string() }, Arc::new(Mutex::new(bc))) {
+        ApiResponse::Error { message } => assert!(message.contains("not in the chain")),
+        other => panic!("proof for unknown transaction: {:?}", other),
+    }
+}
+
+#[test]
+fn changed_transaction_breaks_the_chain() {
+    let mut bc = produced_blockchain();
+    bc.chain.last_mut().unwrap().transactions[0].amount += 1;
+    assert!(!bc.is_chain_valid());
+
+    // Подмена чека видна через check_hash, хотя id транзакции не меняется
+    let mut bc = produced_blockchain();
+    bc.chain.last_mut().unwrap().transactions[0].check.as_mut().unwrap().activation_code = "000000".to_string();
+    assert!(!bc.is_chain_valid());
+}
+
+#[test]
+fn legacy_blocks_without_root_stay_valid() {
+    let mut bc = produced_blockchain();
+    let block = bc.chain.last_mut().unwrap();
+    block.merkle_root = String::new();
+    block.hash = block.calculate_hash();
+    let leader = ["Alice", "Customer", "Customer2"].into_iter().find(|name| holder(name) == block.validator).unwrap();
+    block.sign(&holder_key(leader));
+    let tx_id = block.transactions[0].transaction_id.clone();
+
+    // Блок, сохраненный до появления merkle_root, читается и проверяется по старому хешу
+    let mut stored = serde_json::to_value(&*block).unwrap();
+    stored.as_object_mut().unwrap().remove("merkle_root");
+    let restored: Block = serde_json::from_value(stored).unwrap();
+    assert_eq!(restored.hash, restored.calculate_hash());
+    assert!(bc.is_chain_valid());
+
+    let error = bc.inclusion_proof(&tx_id).unwrap_err();
+    assert!(error.contains("no Merkle root"));
+}
+
+#[test]
+fn consensus_block_hash_commits_to_transaction_contents() {
+    let transactions = vec![
+        ConsensusTransaction::new(1, TransactionType::Sale, serde_json::json!({"sale_id": "a"})),
+        ConsensusTransaction::new(2, TransactionType::Sale, serde_json::json!({"sale_id": "b"})),
+    ];
+    let block = ConsensusBlock::new(1, "parent".to_string(), transactions);
+    assert_eq!(block.merkle_root, block.calculate_merkle_root());
+
+    let mut tampered = block.clone();
+    tampered.transactions[1].data = serde_json::json!({"sale_id": "c"});
+    assert_ne!(tampered.calculate_merkle_root(), block.merkle_root);
+
+    // Подогнать хеш под новые транзакции можно только сменой корня, а корень входит в хеш
+    tampered.merkle_root = tampered.calculate_merkle_root();
+    assert_ne!(tampered.calculate_hash(), block.hash);
+}
@@ -23,14 +23,14 @@ fn test_mobile_customer_wallet_api() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     bc.process_purchase(
         customer.clone(),
         "Truck2".to_string(),
         50.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -73,7 +73,7 @@ fn test_mobile_check_activation() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     println!("Создан чек: {}", check.check_id);
     println!("Сумма чека: {:.2}", check.amount);
@@ -131,21 +131,21 @@ fn test_mobile_transaction_history() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let check2 = bc.process_purchase(
         customer.clone(),
         "Truck2".to_string(),
         75.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let check3 = bc.process_purchase(
         customer.clone(),
         "Truck1".to_string(),
         50.0,
         vec!["Salad".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -204,14 +204,14 @@ fn test_mobile_owner_dashboard() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     bc.process_purchase(
         "Customer2".to_string(),
         "Truck2".to_string(),
         75.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     // Добавляем франшизную ноду
     let franchise_owner = "FranchiseOwner1".to_string();
@@ -223,7 +223,7 @@ fn test_mobile_owner_dashboard() {
         franchise_node.clone(),
         50.0,
         vec!["Salad".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -287,14 +287,14 @@ fn test_mobile_franchise_dashboard() {
         franchise_node.clone(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     bc.process_purchase(
         "Customer2".to_string(),
         franchise_node.clone(),
         75.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -346,7 +346,7 @@ fn test_mobile_security_monitoring() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -401,7 +401,7 @@ fn test_mobile_data_consistency() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     // Активируем чек
     let personal_data = PersonalData {
@@ -0,0 +1,220 @@
+use crate::clock;
+use crate::consensus::{Block, Transaction, TransactionType};
+use crate::franchise_network::FranchiseNetwork;
+use crate::ipfs_storage::{self, IPFSStorage};
+use crate::network_simulator::{SimConfig, Simulator};
+use crate::p2p_network::P2PNode;
+use crate::peer_book::PeerBook;
+use crate::state_tree;
+use crate::tests::fixtures::{franchise_validators, node_key};
+use std::sync::{Arc, Mutex};
+
+// Четыре ноды в разных городах с ключами и продажами — все становятся валидаторами
+fn franchise_network() -> (FranchiseNetwork, Vec<u64>) {
+    let (network, nodes) = franchise_validators(&["Tbilisi", "Batumi", "Kutaisi", "Rustavi"]);
+    (network, nodes.into_iter().map(|(node_id, _)| node_id).collect())
+}
+
+fn simulator(config: SimConfig) -> (Simulator, Vec<u64>) {
+    let (network, node_ids) = franchise_network();
+    let keypairs = node_ids.iter().map(|id| (*id, node_key(*id))).collect();
+    (Simulator::new(config, &network, keypairs), node_ids)
+}
+
+// Блок с подписями всех валидаторов и одной продажей от node_id, созданный в виртуальное время
+fn block(sim: &Simulator, node_ids: &[u64], height: u64, previous_hash: &str, seller: u64) -> Block {
+    let _clock = clock::freeze(sim.now_secs());
+    let mut tx = Transaction::new(seller, TransactionType::Sale, serde_json::json!({"height": height, "prev": previous_hash}));
+    tx.sign(&node_key(seller));
+    let mut block = Block::new(height, previous_hash.to_string(), vec![tx]);
+    for node_id in node_ids {
+        block.sign(*node_id, &node_key(*node_id));
+    }
+    block
+}
+
+fn tip(sim: &Simulator, node_id: u64) -> String {
+    sim.tips()[&node_id].clone()
+}
+
+#[test]
+fn nodes_find_each_other_through_one_seed() {
+    let (mut sim, node_ids) = simulator(SimConfig::default());
+
+    let meshed = sim.run_until(120_000, |sim| node_ids.iter().all(|id| sim.connected_peers(*id).len() == node_ids.len() - 1));
+    assert!(meshed, "peers: {:?}", node_ids.iter().map(|id| sim.connected_peers(*id)).collect::<Vec<_>>());
+}
+
+#[test]
+fn partitioned_network_converges_on_heavier_branch_after_healing() {
+    let (mut sim, ids) = simulator(SimConfig::default());
+    assert!(sim.run_until(120_000, |sim| ids.iter().all(|id| sim.connected_peers(*id).len() == 3)));
+
+    let genesis = block(&sim, &ids, 0, "genesis", ids[0]);
+    sim.publish_block(ids[0], genesis.clone()).unwrap();
+    sim.run_for(5_000);
+    assert!(sim.converged());
+    assert_eq!(tip(&sim, ids[3]), genesis.hash);
+
+    // Две половины сети продолжают цепь независимо
+    sim.partition(&[&ids[..2], &ids[2..]]);
+    let a1 = block(&sim, &ids, 1, &genesis.hash, ids[0]);
+    sim.publish_block(ids[0], a1.clone()).unwrap();
+    sim.run_for(10_000);
+    let c1 = block(&sim, &ids, 1, &genesis.hash, ids[2]);
+    sim.publish_block(ids[2], c1.clone()).unwrap();
+    sim.run_for(10_000);
+    let c2 = block(&sim, &ids, 2, &c1.hash, ids[3]);
+    sim.publish_block(ids[3], c2.clone()).unwrap();
+    sim.run_for(180_000);
+
+    assert_eq!(tip(&sim, ids[1]), a1.hash);
+    assert_eq!(tip(&sim, ids[2]), c2.hash);
+    // Молчащие пиры из другой половины отключены по таймауту
+    assert_eq!(sim.connected_peers(ids[0]), vec![ids[1]]);
+
+    sim.heal();
+    assert!(sim.run_until(300_000, |sim| sim.converged()));
+    assert_eq!(tip(&sim, ids[0]), c2.hash);
+    assert_eq!(sim.connected_peers(ids[0]).len(), 3);
+
+    // Продажа из отмененного блока a1 вернулась в пул ожидающих транзакций
+    let pending = sim.node(ids[1]).pending_transactions.lock().unwrap().clone();
+    assert_eq!(pending.iter().map(|tx| tx.id.clone()).collect::<Vec<_>>(), vec![a1.transactions[0].id.clone()]);
+}
+
+// Сценарий с потерями и случайными задержками; возвращает итоговые вершины и статистику
+fn lossy_run(seed: u64) -> (Simulator, Vec<u64>, String) {
+    let config = SimConfig { seed, min_latency_ms: 50, max_latency_ms: 2_000, loss_rate: 0.2, ..SimConfig::default() };
+    let (mut sim, ids) = simulator(config);
+    sim.run_for(120_000);
+
+    let mut previous = "genesis".to_string();
+    for height in 0..4 {
+        let producer = ids[height as usize % ids.len()];
+        let next = block(&sim, &ids, height, &previous, producer);
+        sim.publish_block(producer, next.clone()).unwrap();
+        previous = next.hash;
+        sim.run_for(15_000);
+    }
+    (sim, ids, previous)
+}
+
+#[test]
+fn lossy_network_converges_and_runs_are_reproducible() {
+    let (mut sim, _, last) = lossy_run(7);
+    assert!(sim.run_until(600_000, |sim| sim.converged()));
+    assert!(sim.tips().values().all(|tip| *tip == last));
+    assert!(sim.stats().lost > 0);
+
+    // Тот же seed — тот же прогон
+    let (first, _, _) = lossy_run(11);
+    let (second, _, _) = lossy_run(11);
+    assert_eq!(first.stats(), second.stats());
+    assert_eq!(first.tips(), second.tips());
+}
+
+fn node_storage(sim: &Simulator, node_id: u64) -> std::sync::MutexGuard<'_, IPFSStorage> {
+    sim.node(node_id).storage.as_ref().unwrap().lock().unwrap()
+}
+
+#[test]
+fn pinned_content_is_replicated_and_fetched_by_cid() {
+    let (mut sim, node_ids) = simulator(SimConfig::default());
+    for node_id in &node_ids {
+        let dir = std::env::temp_dir().join(format!("sim_ipfs_{}_{}", node_id, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        sim.attach_storage(*node_id, IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap());
+    }
+    let origin = node_ids[0];
+    let (image, menu) = {
+        let _clock = clock::freeze(sim.now_secs());
+        let mut storage = node_storage(&sim, origin);
+        storage.replication_factor = 1;
+        let image = storage.store_image(&vec![3u8; crate::blob_store::CHUNK_SIZE * 2], "khinkali.png").unwrap();
+        let menu = storage.store_menu(&ipfs_storage::MenuData {
+            items: vec![], categories: vec![], last_updated: 0, version: 1,
+        }).unwrap();
+        storage.index.get_mut(&menu).unwrap().references.push(image.clone());
+        (image, menu)
+    };
+    let holders = |sim: &Simulator, cid: &str| -> Vec<u64> {
+        node_ids[1..].iter().copied().filter(|id| node_storage(sim, *id).retrieve_bytes(cid).is_ok()).collect()
+    };
+
+    // Каждый CID уходит ровно одному пиру и закрепляется там как реплика
+    let replicated = sim.run_until(300_000, |sim| holders(sim, &image).len() == 1 && holders(sim, &menu).len() == 1);
+    assert!(replicated);
+    sim.run_for(120_000);
+    let replica = holders(&sim, &image)[0];
+    assert_eq!(holders(&sim, &image), vec![replica]);
+    assert_eq!(node_storage(&sim, replica).index[&image].pins, vec![ipfs_storage::PinReason::Replica { node_id: origin }]);
+    assert_eq!(node_storage(&sim, replica).index[&image].content_type, "image/png");
+
+    // Нода без реплики запрашивает меню у пиров и кеширует его без закрепления
+    let reader = *node_ids[1..].iter().find(|id| !holders(&sim, &menu).contains(id)).unwrap();
+    assert!(sim.request_content(reader, &menu).unwrap());
+    assert!(sim.run_until(10_000, |sim| node_storage(sim, reader).retrieve_bytes(&menu).is_ok()));
+    assert!(node_storage(&sim, reader).index[&menu].pins.is_empty());
+    assert!(!sim.request_content(reader, &menu).unwrap());
+}
+
+#[test]
+fn replica_offers_are_accepted_only_by_designated_holders_within_quota() {
+    let (mut sim, node_ids) = simulator(SimConfig::default());
+    for node_id in &node_ids {
+        let dir = std::env::temp_dir().join(format!("sim_quota_{}_{}", node_id, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+        storage.replication_factor = 1;
+        sim.attach_storage(*node_id, storage);
+    }
+    let origin = node_ids[0];
+    let offer = |sim: &Simulator, node_id: u64, cids: Vec<String>| -> Vec<String> {
+        let node = sim.node(node_id);
+        P2PNode::accept_replicas(&node.context(), node.storage.as_ref().unwrap(), origin, cids)
+    };
+
+    // Каждый CID берет ровно одна нода, выбранная replica_peers; сама origin свое не берет
+    let cid = |i: usize| crate::blob_store::Cid::of(crate::blob_store::RAW_CODEC, format!("replica {}", i).as_bytes()).to_string();
+    let acceptors: Vec<u64> = node_ids.iter().copied().filter(|id| !offer(&sim, *id, vec![cid(0)]).is_empty()).collect();
+    assert_eq!(acceptors.len(), 1);
+    assert_ne!(acceptors[0], origin);
+
+    // Квота: сверх MAX_REPLICAS_PER_PEER объектов одного пира реплики не собираются
+    let holder = acceptors[0];
+    let flood: Vec<String> = (1..20 * ipfs_storage::MAX_REPLICAS_PER_PEER).map(cid).collect();
+    let accepted: usize = flood.chunks(ipfs_storage::MAX_WANTED_BLOCKS)
+        .map(|chunk| offer(&sim, holder, chunk.to_vec()).len())
+        .sum();
+    assert_eq!(accepted + 1, ipfs_storage::MAX_REPLICAS_PER_PEER);
+}
+
+#[test]
+fn backup_of_a_live_node_restores_its_chain_and_state() {
+    let (mut sim, ids) = simulator(SimConfig::default());
+    let mut previous = "genesis".to_string();
+    for height in 0..3 {
+        let next = block(&sim, &ids, height, &previous, ids[height as usize % ids.len()]);
+        sim.publish_block(ids[0], next.clone()).unwrap();
+        previous = next.hash;
+    }
+    let live = sim.node(ids[0]);
+    let (base, chain) = P2PNode::backup_state(&live.context());
+    assert_eq!(chain.len(), 3);
+
+    let dir = std::env::temp_dir().join(format!("sim_backup_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let mut storage = IPFSStorage::open(&dir, "https://ipfs.io/ipfs/".to_string()).unwrap();
+    let key = crate::backup::BackupKey::from_hex(&"07".repeat(32)).unwrap();
+    let blockchain = crate::Blockchain::new("Alice".to_string());
+    let cid = crate::backup::create_backup(&mut storage, &key, &blockchain, &base, &chain).unwrap();
+
+    // Новая нода с сетью и цепью из бэкапа приходит к тому же состоянию
+    let restored = crate::backup::restore_from_backup(&storage, &key, &cid).unwrap();
+    let node = P2PNode::new(ids[0], Simulator::address(ids[0]), Arc::new(Mutex::new(restored.franchise_network)), node_key(ids[0]), PeerBook::new());
+    node.restore_chain(restored.franchise_chain).unwrap();
+    assert_eq!(node.context().blockchain.lock().unwrap().tip_hash(), previous);
+    assert_eq!(state_tree::root(&node.franchise_network.lock().unwrap()), state_tree::root(&live.franchise_network.lock().unwrap()));
+    assert_eq!(state_tree::root(&node.committed_state.lock().unwrap()), state_tree::root(&live.committed_state.lock().unwrap()));
+}
@@ -23,7 +23,7 @@ fn test_new_token_distribution_owner_node() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     // Проверяем распределение
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
@@ -31,24 +31,24 @@ fn test_new_token_distribution_owner_node() {
     let customer_tokens = bc.token_holders.get("Customer1").unwrap().security_tokens;
     
     println!("Покупка на 100 токенов на ноде владельца сети:");
-    println!("  Владелец сети: {:.2} токенов (ожидается 48.0)", owner_tokens);
-    println!("  Благотворительный фонд: {:.2} токенов (ожидается 3.0)", charity_tokens);
-    println!("  Покупатель: {:.2} токенов (ожидается 49.0)", customer_tokens);
+    println!("  Владелец сети: {} токенов (ожидается 48.00)", format_units(owner_tokens));
+    println!("  Благотворительный фонд: {} токенов (ожидается 3.00)", format_units(charity_tokens));
+    println!("  Покупатель: {} токенов (ожидается 49.00)", format_units(customer_tokens));
     
-    // Проверяем точность распределения
-    assert!((owner_tokens - 48.0).abs() < 0.01, "Владелец сети должен получить 48 токенов");
-    assert!((charity_tokens - 3.0).abs() < 0.01, "Благотворительный фонд должен получить 3 токена");
-    assert!((customer_tokens - 49.0).abs() < 0.01, "Покупатель должен получить 49 токенов");
+    // Проверяем точность распределения (в subunits)
+    assert_eq!(owner_tokens, 4800, "Владелец сети должен получить 48 токенов");
+    assert_eq!(charity_tokens, 300, "Благотворительный фонд должен получить 3 токена");
+    assert_eq!(customer_tokens, 4900, "Покупатель должен получить 49 токенов");
     
     // Проверяем, что владелец не превышает лимит
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let owner_percentage = percentage_of(owner_tokens, total_tokens);
     
     println!("  Процент владельца: {:.2}% (лимит: 48%)", owner_percentage);
     assert!(owner_percentage <= 48.0, "Владелец не должен превышать 48%");
     
     // Проверяем благотворительный фонд
-    let charity_percentage = (charity_tokens / total_tokens) * 100.0;
+    let charity_percentage = percentage_of(charity_tokens, total_tokens);
     println!("  Процент благотворительного фонда: {:.2}% (ожидается 3%)", charity_percentage);
     assert!((charity_percentage - 3.0).abs() < 0.01, "Благотворительный фонд должен получать 3%");
     
@@ -73,7 +73,7 @@ fn test_new_token_distribution_franchise_node() {
         franchise_node.clone(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     // Проверяем распределение
     let main_owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
@@ -82,21 +82,21 @@ fn test_new_token_distribution_franchise_node() {
     let customer_tokens = bc.token_holders.get("Customer1").unwrap().security_tokens;
     
     println!("Покупка на 100 токенов на франшизной ноде:");
-    println!("  Владелец сети: {:.2} токенов (ожидается 25.0)", main_owner_tokens);
-    println!("  Владелец франшизы: {:.2} токенов (ожидается 24.0)", franchise_owner_tokens);
-    println!("  Благотворительный фонд: {:.2} токенов (ожидается 3.0)", charity_tokens);
-    println!("  Покупатель: {:.2} токенов (ожидается 48.0)", customer_tokens);
+    println!("  Владелец сети: {} токенов (ожидается 25.00)", format_units(main_owner_tokens));
+    println!("  Владелец франшизы: {} токенов (ожидается 24.00)", format_units(franchise_owner_tokens));
+    println!("  Благотворительный фонд: {} токенов (ожидается 3.00)", format_units(charity_tokens));
+    println!("  Покупатель: {} токенов (ожидается 48.00)", format_units(customer_tokens));
     
-    // Проверяем точность распределения
-    assert!((main_owner_tokens - 25.0).abs() < 0.01, "Владелец сети должен получить 25 токенов");
-    assert!((franchise_owner_tokens - 24.0).abs() < 0.01, "Владелец франшизы должен получить 24 токена");
-    assert!((charity_tokens - 3.0).abs() < 0.01, "Благотворительный фонд должен получить 3 токена");
-    assert!((customer_tokens - 48.0).abs() < 0.01, "Покупатель должен получить 48 токенов");
+    // Проверяем точность распределения (в subunits)
+    assert_eq!(main_owner_tokens, 2500, "Владелец сети должен получить 25 токенов");
+    assert_eq!(franchise_owner_tokens, 2400, "Владелец франшизы должен получить 24 токена");
+    assert_eq!(charity_tokens, 300, "Благотворительный фонд должен получить 3 токена");
+    assert_eq!(customer_tokens, 4800, "Покупатель должен получить 48 токенов");
     
     // Проверяем, что владелец сети не превышает лимит
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let main_owner_percentage = (main_owner_tokens / total_tokens) * 100.0;
-    let franchise_owner_percentage = (franchise_owner_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let main_owner_percentage = percentage_of(main_owner_tokens, total_tokens);
+    let franchise_owner_percentage = percentage_of(franchise_owner_tokens, total_tokens);
     
     println!("  Процент владельца сети: {:.2}% (лимит: 48%)", main_owner_percentage);
     println!("  Процент владельца франшизы: {:.2}% (лимит: 24%)", franchise_owner_percentage);
@@ -120,20 +120,20 @@ fn test_owner_cannot_exceed_48_percent() {
             "Truck1".to_string(),
             10.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let owner_percentage = percentage_of(owner_tokens, total_tokens);
     
     println!("После 100 покупок по 10 токенов:");
-    println!("  Токены владельца: {:.2}", owner_tokens);
-    println!("  Общие токены: {:.2}", total_tokens);
+    println!("  Токены владельца: {}", format_units(owner_tokens));
+    println!("  Общие токены: {}", format_units(total_tokens));
     println!("  Процент владельца: {:.2}%", owner_percentage);
     
     // Владелец должен получать ровно 48% от каждой покупки
-    assert!((owner_percentage - 48.0).abs() < 0.01, "Владелец должен получать ровно 48%");
+    assert_eq!(owner_tokens * 100, total_tokens * 48, "Владелец должен получать ровно 48%");
     
     // Проверяем алерты
     let alerts = bc.get_monitoring_alerts(Some(10));
@@ -153,7 +153,7 @@ fn test_charity_fund_always_gets_3_percent() {
     println!("\n💝 === ТЕСТ: БЛАГОТВОРИТЕЛЬНЫЙ ФОНД ВСЕГДА ПОЛУЧАЕТ 3% ===");
     
     let purchase_amounts = vec![50.0, 100.0, 200.0, 500.0, 1000.0];
-    let mut total_purchases = 0.0;
+    let mut total_purchases: f64 = 0.0;
     
     for (i, amount) in purchase_amounts.iter().enumerate() {
         bc.process_purchase(
@@ -161,21 +161,21 @@ fn test_charity_fund_always_gets_3_percent() {
             "Truck1".to_string(),
             *amount,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
         total_purchases += amount;
     }
     
     let charity_tokens = bc.token_holders.get(&bc.charity_fund.fund_id).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let charity_percentage = (charity_tokens / total_tokens) * 100.0;
-    let expected_charity = total_purchases * 0.03;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let charity_percentage = percentage_of(charity_tokens, total_tokens);
+    let expected_charity = to_units(total_purchases) * 3 / 100;
     
     println!("Общая сумма покупок: {:.2}", total_purchases);
-    println!("Токены благотворительного фонда: {:.2}", charity_tokens);
-    println!("Ожидаемые токены фонда: {:.2}", expected_charity);
+    println!("Токены благотворительного фонда: {}", format_units(charity_tokens));
+    println!("Ожидаемые токены фонда: {}", format_units(expected_charity));
     println!("Процент фонда: {:.2}%", charity_percentage);
     
-    assert!((charity_tokens - expected_charity).abs() < 0.01, "Благотворительный фонд должен получать 3% от каждой покупки");
+    assert_eq!(charity_tokens, expected_charity, "Благотворительный фонд должен получать 3% от каждой покупки");
     assert!((charity_percentage - 3.0).abs() < 0.01, "Процент благотворительного фонда должен быть 3%");
     
     // Проверяем информацию о фонде
@@ -183,8 +183,8 @@ fn test_charity_fund_always_gets_3_percent() {
     println!("Информация о фонде:");
     println!("  ID: {}", fund_info.fund_id);
     println!("  Название: {}", fund_info.fund_name);
-    println!("  Общие пожертвования: {:.2}", fund_info.total_donations);
-    assert!((fund_info.total_donations - expected_charity).abs() < 0.01, "Общие пожертвования должны соответствовать полученным токенам");
+    println!("  Общие пожертвования: {}", format_units(fund_info.total_donations));
+    assert_eq!(fund_info.total_donations, expected_charity, "Общие пожертвования должны соответствовать полученным токенам");
 }
 
 #[test]
@@ -210,20 +210,20 @@ fn test_franchise_owner_limits() {
             node_id.clone(),
             20.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
     let franchise_tokens = bc.token_holders.get(&franchise_owner).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let franchise_percentage = (franchise_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let franchise_percentage = percentage_of(franchise_tokens, total_tokens);
     
     println!("После 50 покупок по 20 токенов на франшизной ноде:");
-    println!("  Токены владельца франшизы: {:.2} (ожидается: {:.2})", franchise_tokens, 50.0 * 20.0 * 0.24);
+    // Владелец франшизы должен получать 24% от покупок на своей ноде
+    let expected_franchise_tokens = 50 * 20 * SCALE * 24 / 100; // 50 покупок * 20 токенов * 24%
+    println!("  Токены владельца франшизы: {} (ожидается: {})", format_units(franchise_tokens), format_units(expected_franchise_tokens));
     println!("  Процент владельца франшизы: {:.2}% (лимит: 24%)", franchise_percentage);
     
-    // Владелец франшизы должен получать 24% от покупок на своей ноде
-    let expected_franchise_tokens = 50.0 * 20.0 * 0.24; // 50 покупок * 20 токенов * 24%
-    assert!((franchise_tokens - expected_franchise_tokens).abs() < 0.01, "Владелец франшизы должен получать 24% от покупок на своей ноде");
+    assert_eq!(franchise_tokens, expected_franchise_tokens, "Владелец франшизы должен получать 24% от покупок на своей ноде");
     
     // Проверяем алерты - должны быть алерты о превышении лимита
     let alerts = bc.get_monitoring_alerts(Some(10));
@@ -258,20 +258,20 @@ fn test_customer_limits() {
             "Truck1".to_string(),
             10.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
     let customer_tokens = bc.token_holders.get(&big_customer).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-    let customer_percentage = (customer_tokens / total_tokens) * 100.0;
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let customer_percentage = percentage_of(customer_tokens, total_tokens);
     
     println!("После 100 покупок по 10 токенов одним покупателем:");
-    println!("  Токены покупателя: {:.2}", customer_tokens);
+    println!("  Токены покупателя: {}", format_units(customer_tokens));
     println!("  Процент покупателя: {:.2}% (лимит: 49%)", customer_percentage);
     
     // Покупатель должен получать 49% от каждой покупки
-    let expected_customer_tokens = 100.0 * 10.0 * 0.49; // 100 покупок * 10 токенов * 49%
-    assert!((customer_tokens - expected_customer_tokens).abs() < 0.01, "Покупатель должен получать 49% от каждой покупки");
+    let expected_customer_tokens = 100 * 10 * SCALE * 49 / 100; // 100 покупок * 10 токенов * 49%
+    assert_eq!(customer_tokens, expected_customer_tokens, "Покупатель должен получать 49% от каждой покупки");
     
     // Проверяем, что не превышает лимит
     assert!(customer_percentage <= 49.0, "Покупатель не должен превышать 49%");
@@ -300,15 +300,15 @@ fn test_token_emission_for_investors() {
             "Truck1".to_string(),
             100.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
     let initial_owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-    let initial_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let initial_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     
     println!("До эмиссии:");
-    println!("  Токены владельца: {:.2}", initial_owner_tokens);
-    println!("  Общие токены: {:.2}", initial_total);
+    println!("  Токены владельца: {}", format_units(initial_owner_tokens));
+    println!("  Общие токены: {}", format_units(initial_total));
     
     // Эмитируем токены для инвестора (новая логика: 48% владелец, 3% фонд, 49% инвестор)
     let investor = "WhaleInvestor".to_string();
@@ -320,25 +320,27 @@ fn test_token_emission_for_investors() {
     let final_owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
     let final_investor_tokens = bc.token_holders.get(&investor).unwrap().security_tokens;
     let final_charity_tokens = bc.token_holders.get(&bc.charity_fund.fund_id).unwrap().security_tokens;
-    let final_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let final_total: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     
-    let final_owner_percentage = (final_owner_tokens / final_total) * 100.0;
+    let final_owner_percentage = percentage_of(final_owner_tokens, final_total);
     
     println!("После эмиссии {} токенов для инвестора:", emission_amount);
-    println!("  Токены владельца: {:.2} (добавлено: {:.2})", final_owner_tokens, final_owner_tokens - initial_owner_tokens);
-    println!("  Токены инвестора: {:.2} (ожидается: {:.2})", final_investor_tokens, emission_amount * 0.49);
-    println!("  Токены фонда: {:.2} (добавлено: {:.2})", final_charity_tokens, emission_amount * 0.03);
-    println!("  Общие токены: {:.2}", final_total);
+    let emission_units = to_units(emission_amount);
+    println!("  Токены владельца: {} (добавлено: {})", format_units(final_owner_tokens), format_units(final_owner_tokens - initial_owner_tokens));
+    println!("  Токены инвестора: {} (ожидается: {})", format_units(final_investor_tokens), format_units(emission_units * 49 / 100));
+    println!("  Токены фонда: {} (добавлено: {})", format_units(final_charity_tokens), format_units(emission_units * 3 / 100));
+    println!("  Общие токены: {}", format_units(final_total));
     println!("  Процент владельца: {:.2}% (лимит: 48%)", final_owner_percentage);
     
     // Проверяем точность распределения эмиссии
     let owner_emission = final_owner_tokens - initial_owner_tokens;
-    let initial_charity_tokens = bc.charity_fund.total_donations - (emission_amount * 0.03);
+    let initial_charity_tokens = bc.charity_fund.total_donations - emission_units * 3 / 100;
     let charity_emission = final_charity_tokens - initial_charity_tokens;
     
-    assert!((owner_emission - (emission_amount * 0.48)).abs() < 0.01, "Владелец должен получить 48% от эмиссии");
-    assert!((final_investor_tokens - (emission_amount * 0.49)).abs() < 0.01, "Инвестор должен получить 49% от эмиссии");
-    assert!((charity_emission - (emission_amount * 0.03)).abs() < 0.01, "Фонд должен получить 3% от эмиссии");
+    assert_eq!(owner_emission, emission_units * 48 / 100, "Владелец должен получить 48% от эмиссии");
+    assert_eq!(final_investor_tokens, emission_units * 49 / 100, "Инвестор должен получить 49% от эмиссии");
+    assert_eq!(charity_emission, emission_units * 3 / 100, "Фонд должен получить 3% от эмиссии");
+    assert_eq!(final_total, initial_total + emission_units);
     
     // Проверяем, что владелец не превысил лимит
     assert!(final_owner_percentage <= 48.0, "Владелец не должен превышать 48% после эмиссии");
@@ -367,7 +369,7 @@ fn test_monitoring_system() {
             "Truck1".to_string(),
             50.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
     // Получаем алерты
@@ -437,7 +439,7 @@ fn test_load_testing_7_nodes() {
                 node_id.to_string(),
                 purchase_amount,
                 vec!["Burger".to_string()],
-            );
+            ).unwrap();
         }
     }
     
@@ -449,14 +451,14 @@ fn test_load_testing_7_nodes() {
     // Анализируем распределение токенов
     let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
     let charity_tokens = bc.token_holders.get(&bc.charity_fund.fund_id).unwrap().security_tokens;
-    let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     
-    let owner_percentage = (owner_tokens / total_tokens) * 100.0;
-    let charity_percentage = (charity_tokens / total_tokens) * 100.0;
+    let owner_percentage = percentage_of(owner_tokens, total_tokens);
+    let charity_percentage = percentage_of(charity_tokens, total_tokens);
     
     println!("Распределение токенов:");
-    println!("  Владелец: {:.2} токенов ({:.2}%)", owner_tokens, owner_percentage);
-    println!("  Благотворительный фонд: {:.2} токенов ({:.2}%)", charity_tokens, charity_percentage);
+    println!("  Владелец: {} токенов ({:.2}%)", format_units(owner_tokens), owner_percentage);
+    println!("  Благотворительный фонд: {} токенов ({:.2}%)", format_units(charity_tokens), charity_percentage);
     
     // Проверяем, что владелец получает примерно 48% (может быть меньше из-за франшизных нод)
     assert!(owner_percentage <= 48.0, "Владелец не должен превышать 48%");
@@ -473,8 +475,8 @@ fn test_load_testing_7_nodes() {
     println!("Франшизных владельцев: {}", franchise_owners.len());
     
     for franchise_holder in &franchise_owners {
-        let franchise_percentage = (franchise_holder.security_tokens / total_tokens) * 100.0;
-        println!("  {}: {:.2} токенов ({:.2}%)", franchise_holder.address, franchise_holder.security_tokens, franchise_percentage);
+        let franchise_percentage = percentage_of(franchise_holder.security_tokens, total_tokens);
+        println!("  {}: {} токенов ({:.2}%)", franchise_holder.address, format_units(franchise_holder.security_tokens), franchise_percentage);
         
         // Каждый франшизный владелец не должен превышать 48%
         assert!(franchise_percentage <= 48.0, "Франшизный владелец не должен превышать 48%");
@@ -10,9 +10,9 @@ fn order_create_confirm_cancel() {
     assert!(order.id.starts_with("ORDER_"));
     assert!(matches!(order.status, OrderStatus::Pending));
 
-    order.confirm(3.0);
+    order.confirm(300);
     assert!(matches!(order.status, OrderStatus::Confirmed));
-    assert_eq!(order.tokens_issued, 3.0);
+    assert_eq!(order.tokens_issued, 300);
     assert!(order.confirmed_timestamp.is_some());
 
     // Cancel a new order
@@ -49,15 +49,16 @@ fn blockchain_create_and_confirm_order_updates_balances_and_availability() {
             30,
         )
         .expect("order should be created");
-    assert_eq!(order.total_amount, 20.0);
+    assert_eq!(order.total_amount, 2000);
 
     // Confirm order
     bc.confirm_order(order.id.clone()).expect("confirm should succeed");
 
     // Tokens issued to customer
     let holder = bc.token_holders.get("Customer1").expect("customer holder exists");
-    assert_eq!(holder.security_tokens, 20.0);
-    assert_eq!(holder.utility_tokens, 2.0);
+    assert_eq!(holder.security_tokens, 2000);
+    assert_eq!(holder.utility_tokens, 200);
+    bc.audit_ledger().expect("confirmed order must reconcile");
 
     // Availability reduced
     let item = bc.menu_items.iter().find(|m| m.id == menu_id).unwrap();
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use crate::bft::Bft;
+use crate::chain_sync::ChainSync;
+use crate::crypto::{self, Keypair};
+use crate::executor::Executor;
+use crate::franchise_network::{FranchiseNetwork, NodeType};
+use crate::p2p_network::{NodeContext, P2PMessage, P2PNode, PEER_TIMEOUT_SECS};
+use crate::p2p_protocol::{self, Hello, MAX_FRAME_SIZE, PROTOCOL_VERSION};
+use crate::peer_book::PeerBook;
+use crate::tests::fixtures::node_key;
+
+// Нода 1; в ее сети зарегистрированы ключи нод 1 и 2
+fn node_one() -> NodeContext {
+    let mut network = FranchiseNetwork::new("master".to_string());
+    for node_id in [1, 2] {
+        let registered = network.register_node(format!("owner_{}", node_id), NodeType::FRANCHISE, "Tbilisi".to_string()).unwrap();
+        network.register_node_key(registered, node_key(node_id).public_key_hex()).unwrap();
+    }
+    NodeContext {
+        node_id: 1,
+        listen_port: 9001,
+        keypair: Arc::new(node_key(1)),
+        peers: Arc::new(Mutex::new(HashMap::new())),
+        peer_book: Arc::new(Mutex::new(PeerBook::new())),
+        franchise_network: Arc::new(Mutex::new(network)),
+        blockchain: Arc::new(Mutex::new(ChainSync::new())),
+        executor: Arc::new(Executor::new(FranchiseNetwork::new("master".to_string()))),
+        committed_state: Arc::new(Mutex::new(FranchiseNetwork::new("master".to_string()))),
+        bft: Arc::new(Mutex::new(Bft::new(1))),
+        pending_transactions: Arc::new(Mutex::new(Vec::new())),
+        storage: None,
+    }
+}
+
+// Нода обслуживает входящее соединение; тест играет роль пира
+fn serve(context: &NodeContext) -> (TcpStream, thread::JoinHandle<Result<(), String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+
+    let context = context.clone();
+    let handle = thread::spawn(move || P2PNode::handle_connection(server, false, context));
+    (client, handle)
+}
+
+// Handshake от имени ноды с ключом keypair: Hello, ответные Hello и HelloAuth, своя подпись challenge
+fn handshake_as(client: &mut TcpStream, node_id: u64, keypair: &Keypair) -> Hello {
+    let ours = Hello::new(node_id, 9000 + node_id as u16, 0, None);
+    p2p_protocol::write_frame(client, &P2PMessage::Hello { hello: ours.clone() }).unwrap();
+    let hello = match p2p_protocol::read_frame(client).unwrap() {
+        Some(P2PMessage::Hello { hello }) => hello,
+        other => panic!("expected Hello, got {:?}", other),
+    };
+    match p2p_protocol::read_frame(client).unwrap() {
+        Some(P2PMessage::HelloAuth { signature }) => {
+            let message = p2p_protocol::handshake_bytes(&ours.challenge, hello.node_id, node_id);
+            assert!(crypto::verify(&node_key(hello.node_id).public_key_hex(), &message, &signature));
+        }
+        other => panic!("expected HelloAuth, got {:?}", other),
+    }
+    let signature = keypair.sign(&p2p_protocol::handshake_bytes(&hello.challenge, node_id, hello.node_id));
+    p2p_protocol::write_frame(client, &P2PMessage::HelloAuth { signature }).unwrap();
+    hello
+}
+
+// Handshake от имени ноды 2; первый GetPeers ноды 1 читается
+fn handshake_as_node_two(client: &mut TcpStream) -> Hello {
+    let hello = handshake_as(client, 2, &node_key(2));
+    assert!(matches!(p2p_protocol::read_frame(client).unwrap(), Some(P2PMessage::GetPeers)));
+    hello
+}
+
+fn wait_until(condition: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("condition not reached");
+}
+
+#[test]
+fn frames_round_trip_and_stop_cleanly_at_eof() {
+    let mut buffer = Vec::new();
+    p2p_protocol::write_frame(&mut buffer, &P2PMessage::SyncRequest { from_height: 7 }).unwrap();
+    let hello = Hello::new(3, 9003, 10, None);
+    p2p_protocol::write_frame(&mut buffer, &P2PMessage::Hello { hello: hello.clone() }).unwrap();
+    assert_eq!(buffer[0], PROTOCOL_VERSION);
+
+    let mut reader = Cursor::new(buffer);
+    assert!(matches!(p2p_protocol::read_frame(&mut reader).unwrap(), Some(P2PMessage::SyncRequest { from_height: 7 })));
+    match p2p_protocol::read_frame(&mut reader).unwrap() {
+        Some(P2PMessage::Hello { hello: decoded }) => assert_eq!(decoded, hello),
+        other => panic!("unexpected frame {:?}", other),
+    }
+    assert!(p2p_protocol::read_frame::<_, P2PMessage>(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn oversized_truncated_and_foreign_version_frames_are_rejected() {
+    let mut oversized = vec![PROTOCOL_VERSION];
+    oversized.extend_from_slice(&((MAX_FRAME_SIZE + 1) as u32).to_be_bytes());
+    assert!(p2p_protocol::read_frame::<_, P2PMessage>(&mut Cursor::new(oversized)).is_err());
+
+    let mut frame = Vec::new();
+    p2p_protocol::write_frame(&mut frame, &P2PMessage::SyncRequest { from_height: 1 }).unwrap();
+    let truncated = frame[..frame.len() - 2].to_vec();
+    assert!(p2p_protocol::read_frame::<_, P2PMessage>(&mut Cursor::new(truncated)).is_err());
+
+    let mut foreign = frame.clone();
+    foreign[0] = PROTOCOL_VERSION + 1;
+    assert!(p2p_protocol::read_frame::<_, P2PMessage>(&mut Cursor::new(foreign)).is_err());
+
+    // Старый формат (JSON через перевод строки) не принимается
+    let legacy = b"{\"SyncRequest\":{\"from_height\":1}}\n".to_vec();
+    assert!(p2p_protocol::read_frame::<_, P2PMessage>(&mut Cursor::new(legacy)).is_err());
+}
+
+#[test]
+fn hello_rejects_other_version_genesis_and_self() {
+    let ours = Hello::new(1, 9001, 5, Some("aaa".to_string()));
+
+    assert!(ours.check_peer(&Hello::new(2, 9002, 9, Some("aaa".to_string()))).is_ok());
+    // Пустая нода может подключиться и догнать цепь
+    assert!(ours.check_peer(&Hello::new(2, 9002, 0, None)).is_ok());
+
+    assert!(ours.check_peer(&Hello::new(2, 9002, 5, Some("bbb".to_string()))).is_err());
+    assert!(ours.check_peer(&Hello::new(1, 9001, 5, Some("aaa".to_string()))).is_err());
+    let mut future = Hello::new(2, 9002, 5, Some("aaa".to_string()));
+    future.protocol_version = PROTOCOL_VERSION + 1;
+    assert!(ours.check_peer(&future).is_err());
+}
+
+#[test]
+fn handshake_registers_peer_under_its_own_id_until_disconnect() {
+    let context = node_one();
+    let peers = Arc::clone(&context.peers);
+    let (mut client, handle) = serve(&context);
+
+    assert_eq!(handshake_as_node_two(&mut client).node_id, 1);
+    wait_until(|| peers.lock().unwrap().get(&2).is_some_and(|p| p.is_connected));
+    assert!(!peers.lock().unwrap().contains_key(&1));
+    // В книгу пиров попадает адрес для входящих соединений, а не временный порт
+    assert_eq!(peers.lock().unwrap()[&2].address, "127.0.0.1:9002".parse().unwrap());
+
+    // Ответ приходит по тому же соединению
+    p2p_protocol::write_frame(&mut client, &P2PMessage::Ping { node_id: 2, timestamp: 0 }).unwrap();
+    assert!(matches!(p2p_protocol::read_frame(&mut client).unwrap(), Some(P2PMessage::Pong { node_id: 1, .. })));
+
+    drop(client);
+    assert!(handle.join().unwrap().is_ok());
+    let peer = peers.lock().unwrap()[&2].clone();
+    assert!(!peer.is_connected);
+    assert!(peer.connection.is_none());
+}
+
+#[test]
+fn handshake_with_incompatible_peer_is_refused() {
+    let context = node_one();
+    let peers = Arc::clone(&context.peers);
+    let (mut client, handle) = serve(&context);
+
+    let mut hello = Hello::new(2, 9002, 0, None);
+    hello.protocol_version = PROTOCOL_VERSION + 1;
+    p2p_protocol::write_frame(&mut client, &P2PMessage::Hello { hello }).unwrap();
+
+    assert!(handle.join().unwrap().is_err());
+    assert!(peers.lock().unwrap().is_empty());
+
+    // Сообщение до Hello тоже закрывает соединение
+    let (mut client, handle) = serve(&context);
+    p2p_protocol::write_frame(&mut client, &P2PMessage::SyncRequest { from_height: 0 }).unwrap();
+    assert!(handle.join().unwrap().is_err());
+    assert!(peers.lock().unwrap().is_empty());
+}
+
+#[test]
+fn peers_are_exchanged_and_learned() {
+    let context = node_one();
+    let known: std::net::SocketAddr = "10.0.0.5:8080".parse().unwrap();
+    context.peer_book.lock().unwrap().mark_connected(known, 5, crate::clock::now_secs());
+    let (mut client, handle) = serve(&context);
+    handshake_as_node_two(&mut client);
+
+    // Нода делится проверенными адресами, но не адресом самого запрашивающего
+    p2p_protocol::write_frame(&mut client, &P2PMessage::GetPeers).unwrap();
+    match p2p_protocol::read_frame(&mut client).unwrap() {
+        Some(P2PMessage::Peers { addresses }) => assert_eq!(addresses, vec![known]),
+        other => panic!("expected Peers, got {:?}", other),
+    }
+
+    // Адреса от пира попадают в книгу и становятся кандидатами для подключения
+    let learned: std::net::SocketAddr = "10.0.0.6:8080".parse().unwrap();
+    p2p_protocol::write_frame(&mut client, &P2PMessage::Peers { addresses: vec![learned] }).unwrap();
+    wait_until(|| context.peer_book.lock().unwrap().dial_candidates(0, &[], 10).contains(&learned));
+
+    drop(client);
+    handle.join().unwrap().unwrap();
+}
+
+#[test]
+fn malformed_frame_bans_peer_ip() {
+    let context = node_one();
+    let (mut client, handle) = serve(&context);
+    handshake_as_node_two(&mut client);
+
+    use std::io::Write;
+    client.write_all(&[PROTOCOL_VERSION, 0, 0, 0, 3, b'x', b'y', b'z']).unwrap();
+    assert!(handle.join().unwrap().is_err());
+    assert!(!context.peers.lock().unwrap()[&2].is_connected);
+
+    let localhost = "127.0.0.1".parse().unwrap();
+    assert!(context.peer_book.lock().unwrap().is_banned(localhost, crate::clock::now_secs()));
+
+    // Новое соединение с того же IP закрывается до handshake
+    let (_client, handle) = serve(&context);
+    assert!(handle.join().unwrap().is_err());
+}
+
+#[test]
+fn silent_peers_are_disconnected() {
+    let context = node_one();
+    let (mut client, handle) = serve(&context);
+    handshake_as_node_two(&mut client);
+    wait_until(|| context.peers.lock().unwrap().get(&2).is_some_and(|p| p.is_connected));
+
+    let now = crate::clock::now_secs();
+    assert!(P2PNode::disconnect_stale_peers(&context.peers, now).is_empty());
+    assert_eq!(P2PNode::disconnect_stale_peers(&context.peers, now + PEER_TIMEOUT_SECS + 1), vec![2]);
+
+    assert!(handle.join().unwrap().is_ok());
+    assert!(!context.peers.lock().unwrap()[&2].is_connected);
+}
+
+#[test]
+fn node_id_is_registered_only_with_a_signature_of_its_key() {
+    let context = node_one();
+    let peers = Arc::clone(&context.peers);
+    let (mut client, handle) = serve(&context);
+    handshake_as_node_two(&mut client);
+    wait_until(|| peers.lock().unwrap().get(&2).is_some_and(|p| p.is_connected));
+
+    // Чужой ключ под id ноды 2 не вытесняет ее соединение
+    let (mut impostor, impostor_handle) = serve(&context);
+    handshake_as(&mut impostor, 2, &Keypair::generate());
+    assert!(impostor_handle.join().unwrap().unwrap_err().contains("registered key"));
+    assert!(peers.lock().unwrap()[&2].is_connected);
+
+    // Нода без зарегистрированного ключа не становится пиром
+    let (mut stranger, stranger_handle) = serve(&context);
+    handshake_as(&mut stranger, 7, &node_key(7));
+    assert!(stranger_handle.join().unwrap().unwrap_err().contains("no registered key"));
+    assert!(!peers.lock().unwrap().contains_key(&7));
+
+    drop(client);
+    handle.join().unwrap().unwrap();
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::net::SocketAddr;
+use crate::peer_book::{Misbehavior, PeerBook, BAN_DURATION_SECS, MAX_SHARED_PEERS};
+
+fn addr(s: &str) -> SocketAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn dial_candidates_prefer_recently_seen_and_skip_excluded() {
+    let mut book = PeerBook::new();
+    assert!(book.add(addr("10.0.0.1:8080"), 100));
+    assert!(book.add(addr("10.0.0.2:8080"), 100));
+    assert!(book.add(addr("10.0.0.3:8080"), 100));
+    assert!(!book.add(addr("10.0.0.1:8080"), 100));
+    book.mark_connected(addr("10.0.0.3:8080"), 3, 200);
+    book.mark_failed(&addr("10.0.0.1:8080"));
+
+    assert_eq!(book.dial_candidates(300, &[], 10), vec![addr("10.0.0.3:8080"), addr("10.0.0.2:8080"), addr("10.0.0.1:8080")]);
+    assert_eq!(book.dial_candidates(300, &[addr("10.0.0.3:8080")], 1), vec![addr("10.0.0.2:8080")]);
+
+    // Делимся только адресами, с которыми было соединение
+    assert_eq!(book.shareable(300, &addr("10.0.0.9:8080")), vec![addr("10.0.0.3:8080")]);
+    assert!(book.shareable(300, &addr("10.0.0.3:8080")).is_empty());
+}
+
+#[test]
+fn unreachable_addresses_are_forgotten() {
+    let mut book = PeerBook::new();
+    book.add(addr("10.0.0.1:8080"), 0);
+    for _ in 0..10 {
+        book.mark_failed(&addr("10.0.0.1:8080"));
+    }
+    assert!(book.dial_candidates(0, &[], 10).is_empty());
+}
+
+#[test]
+fn misbehavior_accumulates_into_temporary_ban() {
+    let mut book = PeerBook::new();
+    let bad = addr("10.0.0.66:8080");
+    book.add(bad, 0);
+    book.add(addr("10.0.0.66:9090"), 0);
+
+    assert!(!book.penalize(bad.ip(), Misbehavior::InvalidBlock, 10));
+    assert!(!book.penalize(bad.ip(), Misbehavior::InvalidBlock, 10));
+    assert!(!book.penalize(bad.ip(), Misbehavior::InvalidBlock, 10));
+    assert!(book.penalize(bad.ip(), Misbehavior::InvalidBlock, 10));
+    assert!(book.is_banned(bad.ip(), 10));
+
+    // Все адреса забаненного IP удалены и не добавляются снова
+    assert!(book.dial_candidates(10, &[], 10).is_empty());
+    assert!(!book.add(addr("10.0.0.66:7070"), 11));
+
+    // Бан временный, после него счет начинается с нуля
+    let after = 10 + BAN_DURATION_SECS;
+    assert!(!book.is_banned(bad.ip(), after));
+    assert!(!book.penalize(bad.ip(), Misbehavior::Spam, after));
+    assert_eq!(book.misbehavior_score(bad.ip()), 20);
+    assert!(book.add(bad, after));
+
+    // Испорченный кадр — бан сразу
+    assert!(book.penalize(addr("10.0.0.77:8080").ip(), Misbehavior::MalformedFrame, 0));
+}
+
+#[test]
+fn peer_book_survives_restart() {
+    let dir = std::env::temp_dir().join(format!("peer_book_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("peers.json");
+
+    let mut book = PeerBook::load(path.clone()).unwrap();
+    book.mark_connected(addr("10.0.0.1:8080"), 1, 50);
+    book.penalize(addr("10.0.0.2:8080").ip(), Misbehavior::MalformedFrame, 50);
+    book.save().unwrap();
+
+    let restored = PeerBook::load(path.clone()).unwrap();
+    assert_eq!(restored.dial_candidates(60, &[], 10), vec![addr("10.0.0.1:8080")]);
+    assert!(restored.is_banned(addr("10.0.0.2:8080").ip(), 60));
+
+    fs::write(&path, b"{not json").unwrap();
+    assert!(PeerBook::load(path).is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn shared_addresses_are_capped() {
+    let mut book = PeerBook::new();
+    for i in 0..(MAX_SHARED_PEERS + 20) {
+        book.mark_connected(addr(&format!("10.1.{}.{}:8080", i / 200, i % 200 + 1)), i as u64, 100);
+    }
+    assert_eq!(book.shareable(100, &addr("10.9.9.9:8080")).len(), MAX_SHARED_PEERS);
+}
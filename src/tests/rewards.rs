@@ -0,0 +1,119 @@
+use crate::chain_sync::{BlockImport, ChainSync};
+use crate::consensus::{Block, BlockSignature, ConsensusAlgorithm};
+use crate::executor::Executor;
+use crate::franchise_network::FranchiseNetwork;
+use crate::rewards::{self, EquivocationEvidence, BLOCK_REWARD_UNITS};
+use crate::tests::fixtures::franchise_validators;
+
+fn owner_balance(network: &FranchiseNetwork, node_id: u64) -> u64 {
+    network.get_wallet_balance(&network.nodes[&node_id].owner_address)
+}
+
+fn signature(block: &Block, validator_id: u64) -> BlockSignature {
+    block.signatures.iter().find(|s| s.validator_id == validator_id).unwrap().clone()
+}
+
+#[test]
+fn block_reward_is_split_among_signers() {
+    let (network, nodes) = franchise_validators(&["Tbilisi", "Batumi", "Kutaisi"]);
+    let consensus = ConsensusAlgorithm::new();
+    let validators: Vec<u64> = nodes.iter().map(|(id, _)| *id).collect();
+
+    let mut block = Block::new(0, "genesis".to_string(), vec![]);
+    for (node_id, keypair) in &nodes {
+        block.sign(*node_id, keypair);
+    }
+    // 100 units на троих: остаток достается валидатору с меньшим id
+    assert_eq!(block.validator_rewards[&validators[0]], 34);
+    assert_eq!(block.validator_rewards[&validators[1]], 33);
+    assert_eq!(block.validator_rewards.values().sum::<u64>(), BLOCK_REWARD_UNITS);
+    assert!(consensus.validate_block(&block, &validators, &network));
+
+    // Награда, не совпадающая с подписями, делает блок невалидным
+    let mut greedy = block.clone();
+    greedy.validator_rewards.insert(validators[0], BLOCK_REWARD_UNITS);
+    assert!(!consensus.validate_block(&greedy, &validators, &network));
+}
+
+#[test]
+fn rewards_and_missed_slots_follow_main_chain() {
+    // Четыре валидатора: кворум 2/3+1 — три подписи
+    let (mut network, nodes) = franchise_validators(&["Tbilisi", "Batumi", "Kutaisi", "Rustavi"]);
+    let consensus = ConsensusAlgorithm::new();
+    let mut chain = ChainSync::new();
+    let supply = network.total_supply;
+    let absent = nodes[3].0;
+    let balance = owner_balance(&network, absent);
+    let reputation = |network: &FranchiseNetwork, chain: &ChainSync| {
+        consensus.select_validators(network, 1, &chain.selection_seed(1).unwrap())
+            .validator_scores.iter().find(|s| s.node_id == absent).unwrap().reputation_score
+    };
+
+    let mut block = Block::new(0, "genesis".to_string(), vec![]);
+    for (node_id, keypair) in &nodes[..3] {
+        block.sign(*node_id, keypair);
+    }
+    let executor = Executor::new(network.clone());
+    let outcome = chain.import(block, &consensus, &network).unwrap();
+    assert_eq!(outcome, BlockImport::Extended);
+    let reputation_before = reputation(&network, &chain);
+    let mut committed = network.clone();
+    executor.apply_chain_update(&mut network, &mut committed, &consensus, &chain, &outcome, 0);
+
+    assert_eq!(network.total_supply, supply + BLOCK_REWARD_UNITS);
+    assert_eq!(owner_balance(&network, nodes[0].0) - owner_balance(&network, nodes[1].0), 1);
+    assert_eq!(owner_balance(&network, absent), balance);
+    // Не подписавший валидатор теряет репутацию
+    assert_eq!(network.missed_slots[&0], vec![absent]);
+    assert!(reputation(&network, &chain) < reputation_before);
+
+    // Пересчет с начального состояния по цепи дает то же самое
+    let (replayed, _) = executor.replay(&consensus, &chain);
+    assert_eq!(replayed.total_supply, network.total_supply);
+    assert_eq!(replayed.missed_slots, network.missed_slots);
+}
+
+#[test]
+fn double_signing_is_slashed_once() {
+    let (mut network, nodes) = franchise_validators(&["Tbilisi", "Batumi", "Kutaisi"]);
+    let consensus = ConsensusAlgorithm::new();
+    let (cheater, cheater_key) = &nodes[1];
+    network.mint_to_node_owner(*cheater, 1_000).unwrap();
+    let balance = owner_balance(&network, *cheater);
+    let supply = network.total_supply;
+
+    let mut first = Block::new(4, "parent".to_string(), vec![]);
+    let mut second = Block::new(4, "parent".to_string(), vec![]);
+    second.timestamp += 1;
+    second.hash = second.calculate_hash();
+    first.sign(*cheater, cheater_key);
+    second.sign(*cheater, cheater_key);
+
+    let evidence = EquivocationEvidence::new(
+        (&first.hash, &signature(&first, *cheater)),
+        (&second.hash, &signature(&second, *cheater)),
+        4,
+    );
+    assert!(evidence.verify(&network).is_ok());
+    let transaction = evidence.clone().into_transaction(nodes[0].0, &nodes[0].1);
+    assert!(consensus.validate_transaction(&transaction, &network));
+
+    // Поддельные доказательства: один и тот же блок или чужая подпись
+    let mut same_block = evidence.clone();
+    same_block.second_hash = same_block.first_hash.clone();
+    same_block.second_signature = same_block.first_signature.clone();
+    assert!(!consensus.validate_transaction(&same_block.into_transaction(nodes[0].0, &nodes[0].1), &network));
+    let mut framed = evidence.clone();
+    framed.validator_id = nodes[2].0;
+    assert!(!consensus.validate_transaction(&framed.into_transaction(nodes[0].0, &nodes[0].1), &network));
+
+    let slashed = balance * rewards::SLASH_PERCENT / 100;
+    assert_eq!(rewards::apply_evidence(&mut network, &transaction, 7), Ok(slashed));
+    assert_eq!(owner_balance(&network, *cheater), balance - slashed);
+    assert_eq!(network.total_supply, supply - slashed);
+
+    // Повторное доказательство того же нарушения не штрафует еще раз
+    assert!(rewards::apply_evidence(&mut network, &transaction, 8).is_err());
+    assert_eq!(network.slashings.len(), 1);
+    assert_eq!(owner_balance(&network, *cheater), balance - slashed);
+}
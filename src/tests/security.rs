@@ -9,10 +9,10 @@ fn test_51_percent_attack_prevention() {
     bc.max_owner_percentage = 48.0;
     
     // Create multiple token holders
-    let mut total_tokens = 0.0;
+    let mut total_tokens = 0;
     for i in 0..10 {
         let mut holder = TokenHolder::new(format!("holder{}", i), false);
-        let tokens = 10.0 + i as f64;
+        let tokens = (10 + i as u64) * SCALE;
         holder.add_security_tokens(tokens);
         total_tokens += tokens;
         bc.token_holders.insert(format!("holder{}", i), holder);
@@ -20,12 +20,12 @@ fn test_51_percent_attack_prevention() {
     
     // Try to create a holder with 51% of tokens
     let mut attacker = TokenHolder::new("attacker".to_string(), false);
-    let attack_tokens = (total_tokens * 0.51) + 1.0; // Just over 51%
+    let attack_tokens = total_tokens * 51 / 100 + SCALE; // Just over 51%
     attacker.add_security_tokens(attack_tokens);
     bc.token_holders.insert("attacker".to_string(), attacker);
     
     // Обновляем общее количество токенов
-    let new_total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let new_total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     
     // Check security report
     let report = bc.check_network_security();
@@ -37,7 +37,7 @@ fn test_51_percent_attack_prevention() {
         .find(|risk| risk.wallet == "attacker")
         .expect("Attacker should be flagged as risk");
     
-    let attacker_percentage = (attack_tokens / new_total_tokens) * 100.0;
+    let attacker_percentage = percentage_of(attack_tokens, new_total_tokens);
     assert!(attacker_risk.percentage > 49.0); // Должен превышать лимит для обычных пользователей
 }
 
@@ -47,10 +47,10 @@ fn test_utility_token_concentration_prevention() {
     let mut bc = Blockchain::new(owner.clone());
     
     // Issue utility tokens to multiple holders
-    let mut total_utility = 0.0;
+    let mut total_utility = 0;
     for i in 0..5 {
         let mut holder = TokenHolder::new(format!("holder{}", i), false);
-        let utility_tokens = 20.0;
+        let utility_tokens = 20 * SCALE;
         holder.add_utility_tokens(utility_tokens);
         total_utility += utility_tokens;
         bc.token_holders.insert(format!("holder{}", i), holder);
@@ -58,7 +58,7 @@ fn test_utility_token_concentration_prevention() {
     
     // Try to concentrate utility tokens in one holder
     let mut whale = TokenHolder::new("whale".to_string(), false);
-    let whale_tokens = total_utility * 0.6; // 60% of utility tokens
+    let whale_tokens = total_utility * 60 / 100; // 60% of utility tokens
     whale.add_utility_tokens(whale_tokens);
     bc.token_holders.insert("whale".to_string(), whale);
     
@@ -85,11 +85,11 @@ fn test_balance_transfer_prevents_51_percent() {
     
     // Create initial token distribution
     let mut holder1 = TokenHolder::new("holder1".to_string(), false);
-    holder1.add_security_tokens(100.0);
+    holder1.add_security_tokens(100 * SCALE);
     bc.token_holders.insert("holder1".to_string(), holder1);
     
     let mut holder2 = TokenHolder::new("holder2".to_string(), false);
-    holder2.add_security_tokens(50.0);
+    holder2.add_security_tokens(50 * SCALE);
     bc.token_holders.insert("holder2".to_string(), holder2);
     
     // Register and verify a user
@@ -106,7 +106,7 @@ fn test_balance_transfer_prevents_51_percent() {
         "Truck".to_string(),
         200.0, // This would give the user > 30% of total tokens
         vec!["BigMeal".to_string()],
-    );
+    ).unwrap();
     
     // Attempt transfer should fail
     let result = bc.transfer_balance_from_check(check.check_id, phone);
@@ -131,12 +131,12 @@ fn test_main_owner_control_mechanism() {
             "Truck".to_string(),
             10.0,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
     }
     
     // Check that main owner has accumulated tokens
     let updated_owner = bc.token_holders.get(&owner).unwrap();
-    assert!(updated_owner.security_tokens > 0.0);
+    assert!(updated_owner.security_tokens > 0);
     
     // But main owner is still subject to security checks
     let report = bc.check_network_security();
@@ -157,10 +157,10 @@ fn test_multiple_attack_vectors() {
     let mut bc = Blockchain::new(owner.clone());
     
     // Test 1: Sybil attack prevention (multiple wallets controlled by one entity)
-    let mut total_controlled = 0.0;
+    let mut total_controlled = 0;
     for i in 0..20 {
         let mut holder = TokenHolder::new(format!("sybil{}", i), false);
-        let tokens = 5.0; // Each wallet has small amount
+        let tokens = 5 * SCALE; // Each wallet has small amount
         holder.add_security_tokens(tokens);
         total_controlled += tokens;
         bc.token_holders.insert(format!("sybil{}", i), holder);
@@ -168,21 +168,21 @@ fn test_multiple_attack_vectors() {
     
     // Even with many small wallets, if total exceeds limit, it should be detected
     let report = bc.check_network_security();
-    if total_controlled > (bc.max_owner_percentage / 100.0) * 1000.0 {
+    if exceeds_percentage(total_controlled, 1000 * SCALE, bc.max_owner_percentage) {
         // In a real system, we'd need additional logic to detect coordinated attacks
         // For now, we just verify the security system is working
-        assert!(report.total_security_tokens > 0.0);
+        assert!(report.total_security_tokens > 0);
     }
     
     // Test 2: Rapid token accumulation
     let mut rapid_accumulator = TokenHolder::new("rapid".to_string(), false);
     for _i in 0..10 {
-        rapid_accumulator.add_security_tokens(10.0);
+        rapid_accumulator.add_security_tokens(10 * SCALE);
     }
     bc.token_holders.insert("rapid".to_string(), rapid_accumulator);
     
     let report2 = bc.check_network_security();
-    assert!(report2.total_security_tokens > 0.0);
+    assert!(report2.total_security_tokens > 0);
 }
 
 #[test]
@@ -192,19 +192,19 @@ fn test_network_security_monitoring() {
     
     // Create a scenario with multiple risk factors
     let mut risky_holder = TokenHolder::new("risky".to_string(), false);
-    risky_holder.add_security_tokens(100.0);
-    risky_holder.add_utility_tokens(50.0);
+    risky_holder.add_security_tokens(100 * SCALE);
+    risky_holder.add_utility_tokens(50 * SCALE);
     bc.token_holders.insert("risky".to_string(), risky_holder);
     
     // Issue utility tokens to the system
-    bc.utility_token.issue_voting_tokens(50.0);
+    bc.utility_token.issue_voting_tokens(50 * SCALE);
     
     // Generate security report
     let report = bc.check_network_security();
     
     // Verify report contains all necessary information
-    assert!(report.total_security_tokens > 0.0);
-    assert!(report.total_utility_tokens > 0.0);
+    assert!(report.total_security_tokens > 0);
+    assert!(report.total_utility_tokens > 0);
     assert_eq!(report.max_owner_percentage, 48.0);
     
     // Check if risks are properly identified
@@ -232,7 +232,7 @@ fn test_balance_transfer_audit_trail() {
             "Truck".to_string(),
             0.1,
             vec!["Meal".to_string()],
-        );
+        ).unwrap();
         
         let phone = format!("+123456789{}", i);
         let wallet = format!("0xwallet{}", i);
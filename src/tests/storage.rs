@@ -0,0 +1,149 @@
+use crate::*;
+use crate::tests::fixtures::{add_validators, holder};
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+
+fn temp_data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("blockchain_storage_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn security_balances(bc: &Blockchain) -> Vec<(String, u64, u64)> {
+    let mut balances: Vec<(String, u64, u64)> = bc.token_holders.values()
+        .map(|h| (h.address.clone(), h.security_tokens, h.utility_tokens))
+        .collect();
+    balances.sort_by(|a, b| a.0.cmp(&b.0));
+    balances
+}
+
+#[test]
+fn recovery_replays_wal_with_identical_checks_and_balances() {
+    let dir = temp_data_dir("replay");
+    // Лидерами слота могут стать только держатели с ключами на ноде, доля фонда ниже min_stake
+    let mut genesis = Blockchain::new(holder("Alice"));
+    genesis.min_stake = 50 * SCALE;
+    add_validators(&mut genesis, &["Alice", "Bob", "Customer1", "Customer2", "Phone"]);
+    let mut bc = storage::initialize(&dir, 1000, genesis).unwrap();
+
+    bc.add_franchise_node("Truck_Franchise".to_string(), holder("Bob")).unwrap();
+    let check1 = bc.process_purchase(holder("Customer1"), "Truck1".to_string(), 400.0, vec!["Burger".to_string()]).unwrap();
+    let check2 = bc.process_purchase(holder("Customer2"), "Truck_Franchise".to_string(), 500.0, vec!["Pizza".to_string()]).unwrap();
+    let code = bc.register_user_with_phone("+995555".to_string(), holder("Phone")).unwrap();
+    bc.verify_phone_number("+995555".to_string(), code).unwrap();
+    bc.transfer_balance_from_check(check2.check_id.clone(), "+995555".to_string()).ok();
+    bc.produce_block().unwrap();
+
+    let expected_balances = security_balances(&bc);
+    let expected_chain: Vec<String> = bc.chain.iter().map(|b| b.hash.clone()).collect();
+    drop(bc);
+
+    // "Перезапуск": все состояние берется только с диска
+    let restored = storage::recover(&dir, 1000, None).unwrap().expect("state should be restored");
+    assert_eq!(security_balances(&restored), expected_balances);
+    assert_eq!(restored.chain.iter().map(|b| b.hash.clone()).collect::<Vec<_>>(), expected_chain);
+
+    let restored_check = restored.token_holders[&holder("Alice")].checks.iter()
+        .find(|c| c.check_id == check1.check_id)
+        .expect("check id must survive restart");
+    assert_eq!(restored_check.activation_code, check1.activation_code);
+    assert!(restored.authorized_users["+995555"].is_verified);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn snapshot_truncates_wal_and_recovery_continues_from_it() {
+    let dir = temp_data_dir("snapshot");
+    let mut bc = storage::initialize(&dir, 2, Blockchain::new("Alice".to_string())).unwrap();
+
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 10.0, vec!["Tea".to_string()]).unwrap();
+    assert!(!bc.checkpoint_if_due().unwrap());
+    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 20.0, vec!["Coffee".to_string()]).unwrap();
+    assert!(bc.checkpoint_if_due().unwrap());
+    assert_eq!(fs::metadata(dir.join("wal.log")).unwrap().len(), 0);
+
+    // Операция после снимка попадает только в журнал
+    bc.process_purchase("Customer3".to_string(), "Truck1".to_string(), 30.0, vec!["Cake".to_string()]).unwrap();
+    assert!(fs::metadata(dir.join("wal.log")).unwrap().len() > 0);
+
+    let expected_balances = security_balances(&bc);
+    drop(bc);
+
+    let mut restored = storage::recover(&dir, 2, None).unwrap().unwrap();
+    assert_eq!(security_balances(&restored), expected_balances);
+    assert_eq!(restored.pending_transactions.len(), 3);
+
+    // Нумерация продолжается после восстановления
+    restored.process_purchase("Customer4".to_string(), "Truck1".to_string(), 5.0, vec!["Water".to_string()]).unwrap();
+    let expected_balances = security_balances(&restored);
+    drop(restored);
+    let restored = storage::recover(&dir, 2, None).unwrap().unwrap();
+    assert_eq!(security_balances(&restored), expected_balances);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn secret_codes_are_journaled_and_independent_of_the_wal_seed() {
+    let dir = temp_data_dir("codes");
+    let mut bc = storage::initialize(&dir, 1000, Blockchain::new("Alice".to_string())).unwrap();
+    let check = bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 10.0, vec!["Tea".to_string()]).unwrap();
+    let code = bc.register_user_with_phone("+995777".to_string(), "0xphone".to_string()).unwrap();
+    drop(bc);
+
+    // Seed в журнале открыт: обнуляем его, коды все равно восстанавливаются из операций
+    let content = fs::read_to_string(dir.join("wal.log")).unwrap();
+    let mut rewritten = String::new();
+    for line in content.lines() {
+        let (_, json) = line.split_once(' ').unwrap();
+        let mut record: storage::WalRecord = serde_json::from_str(json).unwrap();
+        record.seed = 0;
+        let json = serde_json::to_string(&record).unwrap();
+        rewritten.push_str(&format!("{} {}\n", storage::checksum(json.as_bytes()), json));
+    }
+    fs::write(dir.join("wal.log"), rewritten).unwrap();
+
+    let restored = storage::recover(&dir, 1000, None).unwrap().expect("state should be restored");
+    let restored_check = restored.token_holders["Alice"].checks.iter()
+        .find(|c| c.check_id == check.check_id)
+        .expect("check must be replayed");
+    assert_eq!(restored_check.activation_code, check.activation_code);
+    assert_eq!(restored.authorized_users["+995777"].verification_code, code);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn corrupted_wal_tail_prevents_start() {
+    let dir = temp_data_dir("corrupted");
+    let mut bc = storage::initialize(&dir, 1000, Blockchain::new("Alice".to_string())).unwrap();
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 10.0, vec!["Tea".to_string()]).unwrap();
+    drop(bc);
+
+    // Оборванная запись: питание пропало посреди записи
+    let mut wal = OpenOptions::new().append(true).open(dir.join("wal.log")).unwrap();
+    wal.write_all(b"0123abcd {\"seq\":2,\"timest").unwrap();
+    drop(wal);
+
+    assert!(storage::recover(&dir, 1000, None).is_err());
+
+    // Запись с неверной контрольной суммой тоже отклоняется
+    let content = fs::read_to_string(dir.join("wal.log")).unwrap();
+    let first_line = content.lines().next().unwrap().replace("Customer1", "Customer9");
+    fs::write(dir.join("wal.log"), format!("{}\n", first_line)).unwrap();
+    assert!(storage::recover(&dir, 1000, None).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn empty_data_dir_has_nothing_to_recover() {
+    let dir = temp_data_dir("empty");
+    assert!(storage::recover(&dir, 1000, None).unwrap().is_none());
+
+    storage::initialize(&dir, 1000, Blockchain::new("Alice".to_string())).unwrap();
+    assert!(storage::initialize(&dir, 1000, Blockchain::new("Alice".to_string())).is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,141 @@
+use crate::*;
+use crate::consensus::Block as ChainBlock;
+use crate::chain_sync::ChainSync;
+use crate::executor::{self, GovernancePayload, SalePayload};
+use crate::tokenomics::Split;
+use crate::tests::fixtures::{add_validators, holder};
+
+fn royalty_policy(effective_from_height: u64, royalty: u64) -> TokenomicsPolicy {
+    let mut policy = TokenomicsPolicy::restaurant();
+    policy.effective_from_height = effective_from_height;
+    policy.franchise_node = Split::new(royalty, 49 - royalty, 3, 48);
+    policy.limits.max_owner_percentage = 60.0;
+    policy
+}
+
+#[test]
+fn policies_are_validated_and_ordered_by_height() {
+    let mut broken = TokenomicsPolicy::restaurant();
+    broken.franchise_node = Split::new(25, 24, 3, 49);
+    assert!(broken.validate().unwrap_err().contains("101%"));
+    let mut broken = TokenomicsPolicy::restaurant();
+    broken.contract_overrides.insert("Truck_Franchise".to_string(), Split::new(10, 10, 0, 10));
+    assert!(broken.validate().is_err());
+
+    assert!(TokenomicsSchedule::new(vec![]).is_err());
+    assert!(TokenomicsSchedule::new(vec![royalty_policy(3, 10)]).is_err());
+    assert!(TokenomicsSchedule::new(vec![TokenomicsPolicy::restaurant(), royalty_policy(5, 10), royalty_policy(5, 20)]).is_err());
+
+    let mut schedule = TokenomicsSchedule::new(vec![TokenomicsPolicy::restaurant(), royalty_policy(5, 10)]).unwrap();
+    assert_eq!(schedule.policy_at(4).franchise_node.network_owner, 25);
+    assert_eq!(schedule.policy_at(5).franchise_node.network_owner, 10);
+
+    // Прошлое не переписывается; более ранняя политика отменяет запланированные после нее
+    assert!(schedule.schedule(royalty_policy(3, 20), 3).is_err());
+    schedule.schedule(royalty_policy(4, 20), 3).unwrap();
+    assert_eq!(schedule.policies().len(), 2);
+    assert_eq!(schedule.policy_at(100).franchise_node.network_owner, 20);
+}
+
+#[test]
+fn config_file_overrides_genesis_policies() {
+    let path = std::env::temp_dir().join(format!("tokenomics_{}.json", std::process::id()));
+    let franchise_network = TokenomicsSchedule::new(vec![TokenomicsPolicy::franchise_network(), royalty_policy(10, 5)]).unwrap();
+    let config = serde_json::json!({ "franchise_network": franchise_network });
+    std::fs::write(&path, config.to_string()).unwrap();
+
+    let loaded = TokenomicsConfig::load(&path).unwrap();
+    assert_eq!(loaded.blockchain, TokenomicsSchedule::restaurant());
+    assert_eq!(loaded.franchise_network, franchise_network);
+
+    // Файл с долями не на 100% не загружается
+    let mut broken = serde_json::to_value(&franchise_network).unwrap();
+    broken["policies"][1]["owner_node"]["buyer"] = serde_json::json!(50);
+    std::fs::write(&path, serde_json::json!({ "franchise_network": broken }).to_string()).unwrap();
+    assert!(TokenomicsConfig::load(&path).unwrap_err().contains("owner_node"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn purchases_keep_the_split_of_their_block_height() {
+    let start = clock::now_secs() / BLOCK_TIME_SECS * BLOCK_TIME_SECS + BLOCK_TIME_SECS;
+    let mut bc = Blockchain::new(holder("Alice"));
+    add_validators(&mut bc, &["Alice", "Bob", "Customer"]);
+    bc.add_franchise_node("Truck_Franchise".to_string(), holder("Bob")).unwrap();
+    bc.add_franchise_node("Truck_Contract".to_string(), "Carol".to_string()).unwrap();
+    bc.process_purchase(holder("Customer"), "Truck1".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+
+    let mut policy = royalty_policy(2, 10);
+    policy.contract_overrides.insert("Truck_Contract".to_string(), Split::new(0, 49, 3, 48));
+    bc.schedule_tokenomics(policy).unwrap();
+    assert!(bc.schedule_tokenomics(royalty_policy(1, 10)).is_err());
+
+    // Высота 1: еще генезис-политика
+    bc.process_purchase(holder("Customer"), "Truck_Franchise".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    assert_eq!(bc.token_holders[&holder("Bob")].security_tokens, 24 * SCALE);
+    assert_eq!(bc.max_owner_percentage, 48.0);
+
+    {
+        let _clock = clock::freeze(start);
+        bc.produce_block().unwrap();
+    }
+    assert_eq!(bc.max_owner_percentage, 60.0);
+
+    // Высота 2: роялти 10%, франчайзи 39%; у договора Carol свои доли.
+    // Балансы берутся после блока: награду за него мог получить любой держатель
+    let (alice, bob) = (bc.token_holders[&holder("Alice")].security_tokens, bc.token_holders[&holder("Bob")].security_tokens);
+    bc.process_purchase(holder("Customer"), "Truck_Franchise".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    assert_eq!(bc.token_holders[&holder("Bob")].security_tokens, bob + 39 * SCALE);
+    assert_eq!(bc.token_holders[&holder("Alice")].security_tokens, alice + 10 * SCALE);
+    bc.process_purchase(holder("Customer"), "Truck_Contract".to_string(), 100.0, vec!["Meal".to_string()]).unwrap();
+    assert_eq!(bc.token_holders["Carol"].security_tokens, 49 * SCALE);
+    assert_eq!(bc.token_holders[&holder("Alice")].security_tokens, alice + 10 * SCALE);
+    bc.audit_ledger().unwrap();
+}
+
+#[test]
+fn franchise_network_royalty_changes_through_governance() {
+    let mut network = FranchiseNetwork::new("master".to_string());
+    network.whitelist_pos("POS_1".to_string());
+    let owner_node = network.register_node("master".to_string(), NodeType::OWNER, "Tbilisi".to_string()).unwrap();
+    let franchise = network.register_node("franchisee".to_string(), NodeType::FRANCHISE, "Batumi".to_string()).unwrap();
+    let (consensus, chain) = (ConsensusAlgorithm::new(), ChainSync::new());
+    let keypair = Keypair::generate();
+
+    let transaction = |node_id: u64, transaction_type: TransactionType, id: &str, data: serde_json::Value| {
+        let mut tx = ConsensusTransaction::new(node_id, transaction_type, data);
+        tx.id = id.to_string();
+        tx.sign(&keypair);
+        tx
+    };
+    let sale = |sale_id: &str| {
+        let payload = SalePayload {
+            sale_id: sale_id.to_string(),
+            price_gel: 25.5,
+            buyer_meta: "buyer".to_string(),
+            pos_id: "POS_1".to_string(),
+            items: vec![SaleItem { item_id: "khachapuri".to_string(), quantity: 1, price: 25.5 }],
+        };
+        transaction(franchise, TransactionType::Sale, sale_id, serde_json::to_value(payload).unwrap())
+    };
+
+    let mut policy = TokenomicsPolicy::franchise_network();
+    policy.effective_from_height = 1;
+    policy.franchise_node = Split::new(10, 41, 0, 49);
+    let schedule = |id: &str, node_id: u64, policy: &TokenomicsPolicy| transaction(node_id, TransactionType::Governance, id,
+        serde_json::to_value(GovernancePayload::ScheduleTokenomics { policy: policy.clone() }).unwrap());
+
+    // Франшиза не меняет токеномику; в блоке 0 продажа идет по генезис-политике
+    let block = ChainBlock::new(0, "genesis".to_string(), vec![
+        schedule("rogue", franchise, &policy), schedule("royalty_10", owner_node, &policy), sale("sale_0"),
+    ]);
+    let receipts = executor::apply_block(&mut network, &consensus, &chain, &block);
+    assert!(receipts[0].error.is_some() && receipts[1].error.is_none());
+    let block = ChainBlock::new(1, block.hash.clone(), vec![sale("sale_1")]);
+    executor::apply_block(&mut network, &consensus, &chain, &block);
+
+    let mintings = &network.token_mintings;
+    assert_eq!((mintings[0].owner_units, mintings[0].royalty_units, mintings[0].policy_height), (48, 3, 0));
+    assert_eq!((mintings[1].owner_units, mintings[1].royalty_units, mintings[1].policy_height), (41, 10, 1));
+    network.audit().unwrap();
+}
@@ -21,14 +21,14 @@ fn test_unclaimed_tokens_tracking() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let _check2 = bc.process_purchase(
         "Customer2".to_string(),
         "Truck1".to_string(),
         200.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     // Делаем покупку с привязкой к телефону (востребованная)
     // Сначала регистрируем пользователя
@@ -42,7 +42,7 @@ fn test_unclaimed_tokens_tracking() {
         "Truck1".to_string(),
         150.0,
         vec!["Salad".to_string()],
-    );
+    ).unwrap();
     
     let unclaimed_tokens = bc.get_unclaimed_tokens(Some(10));
     
@@ -52,13 +52,13 @@ fn test_unclaimed_tokens_tracking() {
     assert_eq!(unclaimed_tokens.len(), 2, "Должно быть 2 невостребованных токена");
     
     // Проверяем суммы
-    let total_unclaimed: f64 = unclaimed_tokens.iter().map(|r| r.amount).sum();
-    let expected_unclaimed = (100.0 * 0.49) + (200.0 * 0.49); // 49% от каждой покупки
+    let total_unclaimed: u64 = unclaimed_tokens.iter().map(|r| r.amount).sum();
+    let expected_unclaimed = 4900 + 9800; // 49% от каждой покупки, в subunits
     
-    println!("Общая сумма невостребованных токенов: {:.2}", total_unclaimed);
-    println!("Ожидаемая сумма: {:.2}", expected_unclaimed);
+    println!("Общая сумма невостребованных токенов: {}", format_units(total_unclaimed));
+    println!("Ожидаемая сумма: {}", format_units(expected_unclaimed));
     
-    assert!((total_unclaimed - expected_unclaimed).abs() < 0.01, "Сумма невостребованных токенов должна быть корректной");
+    assert_eq!(total_unclaimed, expected_unclaimed, "Сумма невостребованных токенов должна быть корректной");
     
     // Проверяем, что токены не распределены
     for record in &unclaimed_tokens {
@@ -75,9 +75,9 @@ fn test_annual_distribution_proportional() {
     println!("\n💰 === ТЕСТ: ГОДОВОЕ РАСПРЕДЕЛЕНИЕ ПРОПОРЦИОНАЛЬНО ДОЛЕ ВЛАДЕНИЯ ===");
     
     // Создаем несколько держателей токенов
-    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 1000.0, vec!["Burger".to_string()]);
-    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 1000.0, vec!["Pizza".to_string()]);
-    bc.process_purchase("Customer3".to_string(), "Truck1".to_string(), 1000.0, vec!["Salad".to_string()]);
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 1000.0, vec!["Burger".to_string()]).unwrap();
+    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 1000.0, vec!["Pizza".to_string()]).unwrap();
+    bc.process_purchase("Customer3".to_string(), "Truck1".to_string(), 1000.0, vec!["Salad".to_string()]).unwrap();
     
     // Получаем начальные балансы
     let owner_tokens_before = bc.token_holders.get(&owner).unwrap().security_tokens;
@@ -85,15 +85,15 @@ fn test_annual_distribution_proportional() {
     let customer1_tokens_before = bc.token_holders.get("Customer1").unwrap().security_tokens;
     
     println!("До распределения:");
-    println!("  Владелец: {:.2} токенов", owner_tokens_before);
-    println!("  Благотворительный фонд: {:.2} токенов", charity_tokens_before);
-    println!("  Customer1: {:.2} токенов", customer1_tokens_before);
+    println!("  Владелец: {} токенов", format_units(owner_tokens_before));
+    println!("  Благотворительный фонд: {} токенов", format_units(charity_tokens_before));
+    println!("  Customer1: {} токенов", format_units(customer1_tokens_before));
     
     // Симулируем истечение невостребованных токенов
     let unclaimed_tokens = bc.get_unclaimed_tokens(Some(10));
-    let total_unclaimed: f64 = unclaimed_tokens.iter().map(|r| r.amount).sum();
+    let total_unclaimed: u64 = unclaimed_tokens.iter().map(|r| r.amount).sum();
     
-    println!("Невостребованных токенов для распределения: {:.2}", total_unclaimed);
+    println!("Невостребованных токенов для распределения: {}", format_units(total_unclaimed));
     
     // Устанавливаем срок истечения в прошлое для всех невостребованных токенов
     for record in &mut bc.unclaimed_tokens {
@@ -101,7 +101,7 @@ fn test_annual_distribution_proportional() {
     }
     
     // Вычисляем общее количество токенов для пропорционального распределения
-    let _total_security_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+    let _total_security_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     
     // Выполняем годовое распределение
     let distribution_result = bc.distribute_unclaimed_tokens_annually();
@@ -110,18 +110,19 @@ fn test_annual_distribution_proportional() {
         Ok(_distribution) => {
             println!("Распределение выполнено успешно!");
             println!("Год: {}", _distribution.year);
-            println!("Общая сумма распределенных токенов: {:.2}", _distribution.total_unclaimed_tokens);
+            println!("Общая сумма распределенных токенов: {}", format_units(_distribution.total_unclaimed_tokens));
             println!("Количество получателей: {}", _distribution.distributions.len());
             
             // Проверяем, что распределение пропорционально
             for dist in &_distribution.distributions {
-                println!("  {}: {:.2} токенов ({:.2}%)", dist.recipient_address, dist.amount, dist.percentage);
+                println!("  {}: {} токенов ({:.2}%)", dist.recipient_address, format_units(dist.amount), dist.percentage);
             }
             
             // Проверяем, что общая сумма распределения равна невостребованным токенам
-            let total_distributed: f64 = _distribution.distributions.iter().map(|d| d.amount).sum();
-            assert!((total_distributed - _distribution.total_unclaimed_tokens).abs() < 0.01, 
+            let total_distributed: u64 = _distribution.distributions.iter().map(|d| d.amount).sum();
+            assert_eq!(total_distributed, _distribution.total_unclaimed_tokens,
                 "Общая сумма распределения должна равняться невостребованным токенам");
+            bc.audit_ledger().expect("распределение должно сходиться до тетри");
             
             // Проверяем, что токены добавлены к балансам
             let owner_tokens_after = bc.token_holders.get(&owner).unwrap().security_tokens;
@@ -129,9 +130,9 @@ fn test_annual_distribution_proportional() {
             let customer1_tokens_after = bc.token_holders.get("Customer1").unwrap().security_tokens;
             
             println!("После распределения:");
-            println!("  Владелец: {:.2} токенов (+{:.2})", owner_tokens_after, owner_tokens_after - owner_tokens_before);
-            println!("  Благотворительный фонд: {:.2} токенов (+{:.2})", charity_tokens_after, charity_tokens_after - charity_tokens_before);
-            println!("  Customer1: {:.2} токенов (+{:.2})", customer1_tokens_after, customer1_tokens_after - customer1_tokens_before);
+            println!("  Владелец: {} токенов (+{})", format_units(owner_tokens_after), format_units(owner_tokens_after - owner_tokens_before));
+            println!("  Благотворительный фонд: {} токенов (+{})", format_units(charity_tokens_after), format_units(charity_tokens_after - charity_tokens_before));
+            println!("  Customer1: {} токенов (+{})", format_units(customer1_tokens_after), format_units(customer1_tokens_after - customer1_tokens_before));
             
             // Проверяем, что токены действительно добавлены
             assert!(owner_tokens_after > owner_tokens_before, "Владелец должен получить дополнительные токены");
@@ -153,8 +154,8 @@ fn test_expired_tokens_check() {
     println!("\n⏰ === ТЕСТ: ПРОВЕРКА ИСТЕЧЕНИЯ НЕВОСТРЕБОВАННЫХ ТОКЕНОВ ===");
     
     // Делаем покупки
-    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 100.0, vec!["Burger".to_string()]);
-    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 200.0, vec!["Pizza".to_string()]);
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 100.0, vec!["Burger".to_string()]).unwrap();
+    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 200.0, vec!["Pizza".to_string()]).unwrap();
     
     let unclaimed_tokens = bc.get_unclaimed_tokens(Some(10));
     println!("Создано невостребованных токенов: {}", unclaimed_tokens.len());
@@ -181,8 +182,8 @@ fn test_annual_distributions_history() {
     println!("\n📊 === ТЕСТ: ИСТОРИЯ ГОДОВЫХ РАСПРЕДЕЛЕНИЙ ===");
     
     // Делаем покупки для создания невостребованных токенов
-    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 500.0, vec!["Burger".to_string()]);
-    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 500.0, vec!["Pizza".to_string()]);
+    bc.process_purchase("Customer1".to_string(), "Truck1".to_string(), 500.0, vec!["Burger".to_string()]).unwrap();
+    bc.process_purchase("Customer2".to_string(), "Truck1".to_string(), 500.0, vec!["Pizza".to_string()]).unwrap();
     
     // Устанавливаем срок истечения в прошлое для всех невостребованных токенов
     for record in &mut bc.unclaimed_tokens {
@@ -203,13 +204,13 @@ fn test_annual_distributions_history() {
             
             let distribution = &distributions[0];
             println!("Год: {}", distribution.year);
-            println!("Общая сумма: {:.2}", distribution.total_unclaimed_tokens);
+            println!("Общая сумма: {}", format_units(distribution.total_unclaimed_tokens));
             println!("Время распределения: {}", distribution.distribution_timestamp);
             println!("Количество получателей: {}", distribution.distributions.len());
             
             // Проверяем структуру распределения
             assert_eq!(distribution.year, 2024, "Год должен быть 2024");
-            assert!(distribution.total_unclaimed_tokens > 0.0, "Общая сумма должна быть положительной");
+            assert!(distribution.total_unclaimed_tokens > 0, "Общая сумма должна быть положительной");
             assert!(!distribution.distributions.is_empty(), "Должны быть получатели");
             
             // Проверяем, что все невостребованные токены помечены как распределенные
@@ -262,11 +263,11 @@ fn test_distribution_preserves_ownership_limits() {
             "Truck1".to_string(),
             100.0,
             vec!["Burger".to_string()],
-        );
+        ).unwrap();
     }
     
-    let total_unclaimed: f64 = bc.get_unclaimed_tokens(Some(100)).iter().map(|r| r.amount).sum();
-    println!("Общая сумма невостребованных токенов: {:.2}", total_unclaimed);
+    let total_unclaimed: u64 = bc.get_unclaimed_tokens(Some(100)).iter().map(|r| r.amount).sum();
+    println!("Общая сумма невостребованных токенов: {}", format_units(total_unclaimed));
     
     // Устанавливаем срок истечения в прошлое для всех невостребованных токенов
     for record in &mut bc.unclaimed_tokens {
@@ -282,8 +283,8 @@ fn test_distribution_preserves_ownership_limits() {
             
             // Проверяем, что владелец не превысил лимит
             let owner_tokens = bc.token_holders.get(&owner).unwrap().security_tokens;
-            let total_tokens: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
-            let owner_percentage = (owner_tokens / total_tokens) * 100.0;
+            let total_tokens: u64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
+            let owner_percentage = percentage_of(owner_tokens, total_tokens);
             
             println!("Процент владельца после распределения: {:.2}%", owner_percentage);
             
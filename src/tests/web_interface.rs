@@ -52,14 +52,14 @@ fn test_api_get_token_holders() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     bc.process_purchase(
         "Customer2".to_string(),
         "Truck1".to_string(),
         50.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     let request = ApiRequest::GetTokenHolders;
@@ -95,14 +95,14 @@ fn test_api_get_transactions() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     bc.process_purchase(
         "Customer2".to_string(),
         "Truck2".to_string(),
         75.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     let request = ApiRequest::GetTransactions;
@@ -135,14 +135,14 @@ fn test_api_get_checks() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let check2 = bc.process_purchase(
         "Customer2".to_string(),
         "Truck2".to_string(),
         50.0,
         vec!["Pizza".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     let request = ApiRequest::GetChecks;
@@ -180,7 +180,7 @@ fn test_api_get_network_security() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     let request = ApiRequest::GetNetworkSecurity;
@@ -214,7 +214,7 @@ fn test_api_get_monitoring_alerts() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     let request = ApiRequest::GetMonitoringAlerts { limit: Some(10) };
@@ -280,7 +280,7 @@ fn test_api_franchise_operations() {
         franchise_node.clone(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let api_server = ApiServer::new(bc);
     
@@ -320,7 +320,7 @@ fn test_api_token_emission() {
         "Truck1".to_string(),
         100.0,
         vec!["Burger".to_string()],
-    );
+    ).unwrap();
     
     let initial_total: f64 = bc.token_holders.values().map(|h| h.security_tokens).sum();
     println!("Общее количество токенов до эмиссии: {:.2}", initial_total);
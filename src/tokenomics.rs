@@ -0,0 +1,221 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use crate::franchise_network::{
+    NodeType, FRANCHISE_BUYER_SHARE, FRANCHISE_OWNER_SHARE, FRANCHISE_ROYALTY_SHARE, OWNER_BUYER_SHARE, OWNER_OWNER_SHARE,
+};
+
+// Политика токеномики: доли эмиссии при продаже и эмиссии для инвесторов, utility токены и лимиты владения.
+// Политики образуют расписание по высоте блока: продажа распределяется по политике, действующей на высоте
+// ее блока, поэтому смена ставки роялти не меняет старые продажи и при воспроизведении дает тот же результат.
+// Доли задаются по типу ноды и могут переопределяться для отдельного франшизного договора (ключ ноды).
+
+pub const CHARITY_INDEX: usize = 2; // Индекс фонда в Split::weights; ему же уходит остаток от округления
+
+// Делит total пропорционально весам с округлением вниз.
+// Остаток целиком уходит получателю dust_index, поэтому сумма частей всегда равна total.
+pub fn split_units(total: u64, weights: &[u64], dust_index: usize) -> Vec<u64> {
+    let weight_sum: u128 = weights.iter().map(|&w| w as u128).sum();
+    if weight_sum == 0 {
+        return vec![0; weights.len()];
+    }
+    let mut parts: Vec<u64> = weights.iter()
+        .map(|&w| (total as u128 * w as u128 / weight_sum) as u64)
+        .collect();
+    let distributed: u64 = parts.iter().sum();
+    parts[dust_index] += total - distributed;
+    parts
+}
+
+// Доли эмиссии в процентах, в сумме 100
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+    pub network_owner: u64, // Основатель сети (у франшизной точки — роялти)
+    pub node_owner: u64,    // Владелец точки продажи
+    pub charity: u64,       // Благотворительный фонд
+    pub buyer: u64,         // Покупатель или инвестор
+}
+
+impl Split {
+    pub fn new(network_owner: u64, node_owner: u64, charity: u64, buyer: u64) -> Self {
+        Self { network_owner, node_owner, charity, buyer }
+    }
+
+    // Веса для split_units: основатель, владелец точки, фонд, покупатель
+    pub fn weights(&self) -> [u64; 4] {
+        [self.network_owner, self.node_owner, self.charity, self.buyer]
+    }
+
+    fn validate(&self, name: &str) -> Result<(), String> {
+        let total: u64 = self.weights().iter().sum();
+        if total != 100 {
+            return Err(format!("Split {} sums to {}%, must be 100%", name, total));
+        }
+        Ok(())
+    }
+}
+
+// Лимиты доли security токенов, в процентах
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLimits {
+    pub max_owner_percentage: f64,
+    pub max_franchise_percentage: f64, // Все владельцы франшиз вместе
+    pub max_customer_percentage: f64,
+    pub charity_percentage: f64,       // Ожидаемая доля фонда (для алертов)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenomicsPolicy {
+    pub effective_from_height: u64,
+    pub owner_node: Split,
+    pub franchise_node: Split,
+    pub investor: Split, // Доля владельца точки у инвестора уходит основателю
+    pub utility_percentage: u64, // Utility токены в процентах от security эмиссии
+    pub limits: TokenLimits,
+    #[serde(default)]
+    pub contract_overrides: BTreeMap<String, Split>, // Ключ ноды -> доли по ее франшизному договору
+}
+
+impl TokenomicsPolicy {
+    // Генезис ресторанного блокчейна (Blockchain)
+    pub fn restaurant() -> Self {
+        Self {
+            effective_from_height: 0,
+            owner_node: Split::new(48, 0, 3, 49),
+            // 25% + 24% + 3% + 49% = 101% (ошибка в требованиях), покупателю 48%
+            franchise_node: Split::new(25, 24, 3, 48),
+            investor: Split::new(48, 0, 3, 49),
+            utility_percentage: 10,
+            limits: TokenLimits {
+                max_owner_percentage: 48.0,
+                max_franchise_percentage: 24.0,
+                max_customer_percentage: 49.0,
+                charity_percentage: 3.0,
+            },
+            contract_overrides: BTreeMap::new(),
+        }
+    }
+
+    // Генезис франшизной сети (FranchiseNetwork): фонда нет, 1 токен на продажу
+    pub fn franchise_network() -> Self {
+        Self {
+            owner_node: Split::new(0, OWNER_OWNER_SHARE, 0, OWNER_BUYER_SHARE),
+            franchise_node: Split::new(FRANCHISE_ROYALTY_SHARE, FRANCHISE_OWNER_SHARE, 0, FRANCHISE_BUYER_SHARE),
+            ..Self::restaurant()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.owner_node.validate("owner_node")?;
+        self.franchise_node.validate("franchise_node")?;
+        self.investor.validate("investor")?;
+        for (node, split) in &self.contract_overrides {
+            split.validate(&format!("of contract {}", node))?;
+        }
+        if self.utility_percentage > 100 {
+            return Err(format!("Utility percentage {} exceeds 100%", self.utility_percentage));
+        }
+        let limits = [
+            self.limits.max_owner_percentage,
+            self.limits.max_franchise_percentage,
+            self.limits.max_customer_percentage,
+            self.limits.charity_percentage,
+        ];
+        if limits.iter().any(|limit| !(0.0..=100.0).contains(limit)) {
+            return Err("Token limits must be between 0% and 100%".to_string());
+        }
+        Ok(())
+    }
+
+    // Доли продажи на ноде: договор ноды, иначе доли ее типа
+    pub fn split_for(&self, node_type: &NodeType, node_key: &str) -> &Split {
+        self.contract_overrides.get(node_key).unwrap_or(match node_type {
+            NodeType::OWNER => &self.owner_node,
+            NodeType::FRANCHISE => &self.franchise_node,
+        })
+    }
+}
+
+// Политики по возрастанию effective_from_height; первая действует с генезиса
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenomicsSchedule {
+    policies: Vec<TokenomicsPolicy>,
+}
+
+impl TokenomicsSchedule {
+    pub fn new(policies: Vec<TokenomicsPolicy>) -> Result<Self, String> {
+        match policies.first() {
+            None => return Err("Tokenomics schedule is empty".to_string()),
+            Some(genesis) if genesis.effective_from_height != 0 => {
+                return Err("First tokenomics policy must be effective from height 0".to_string());
+            }
+            _ => {}
+        }
+        for policy in &policies {
+            policy.validate()?;
+        }
+        if policies.windows(2).any(|pair| pair[1].effective_from_height <= pair[0].effective_from_height) {
+            return Err("Tokenomics policies must have increasing effective heights".to_string());
+        }
+        Ok(Self { policies })
+    }
+
+    pub fn restaurant() -> Self {
+        Self { policies: vec![TokenomicsPolicy::restaurant()] }
+    }
+
+    pub fn franchise_network() -> Self {
+        Self { policies: vec![TokenomicsPolicy::franchise_network()] }
+    }
+
+    pub fn policies(&self) -> &[TokenomicsPolicy] {
+        &self.policies
+    }
+
+    pub fn policy_at(&self, height: u64) -> &TokenomicsPolicy {
+        self.policies.iter()
+            .rev()
+            .find(|policy| policy.effective_from_height <= height)
+            .expect("schedule always starts at height 0")
+    }
+
+    // Новая политика; действовать она может только с высоты после current_height,
+    // поэтому уже распределенные продажи ее не видят. Запланированные позже нее политики отменяются.
+    pub fn schedule(&mut self, policy: TokenomicsPolicy, current_height: u64) -> Result<(), String> {
+        if policy.effective_from_height <= current_height {
+            return Err(format!("Tokenomics policy must take effect after height {}", current_height));
+        }
+        policy.validate()?;
+        self.policies.retain(|existing| existing.effective_from_height < policy.effective_from_height);
+        self.policies.push(policy);
+        Ok(())
+    }
+}
+
+// Конфигурация токеномики из JSON файла; отсутствующий раздел — генезис-политика модели
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenomicsConfig {
+    #[serde(default = "TokenomicsSchedule::restaurant")]
+    pub blockchain: TokenomicsSchedule,
+    #[serde(default = "TokenomicsSchedule::franchise_network")]
+    pub franchise_network: TokenomicsSchedule,
+}
+
+impl Default for TokenomicsConfig {
+    fn default() -> Self {
+        Self { blockchain: TokenomicsSchedule::restaurant(), franchise_network: TokenomicsSchedule::franchise_network() }
+    }
+}
+
+impl TokenomicsConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let config: Self = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid tokenomics config {}: {}", path.display(), e))?;
+        // Расписание из файла проходит те же проверки, что и собранное в коде
+        let blockchain = TokenomicsSchedule::new(config.blockchain.policies)?;
+        let franchise_network = TokenomicsSchedule::new(config.franchise_network.policies)?;
+        Ok(Self { blockchain, franchise_network })
+    }
+}
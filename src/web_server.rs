@@ -3,11 +3,22 @@ use std::fs;
 use std::io::{Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use crate::blob_store::Cid;
+use crate::ipfs_storage::IPFSStorage;
+
+const MAX_REQUEST_HEAD: usize = 16 * 1024;
+// Типы, которые шлюз отдает как есть; тип содержимого приходит и от пиров, остальные отдаются как двоичные данные
+const SAFE_CONTENT_TYPES: &[&str] = &[
+    "image/png", "image/jpeg", "image/gif", "image/webp", "image/svg+xml",
+    "application/json", "text/plain", "application/octet-stream",
+];
 
 pub struct WebServer {
     port: u16,
     static_dir: String,
+    storage: Option<Arc<Mutex<IPFSStorage>>>, // Содержимое для GET /ipfs/{cid}
 }
 
 impl WebServer {
@@ -15,9 +26,16 @@ impl WebServer {
         Self {
             port,
             static_dir: ".".to_string(),
+            storage: None,
         }
     }
 
+    // Локальный IPFS шлюз: страницы берут изображения меню с самой ноды, без интернета
+    pub fn with_storage(mut self, storage: Arc<Mutex<IPFSStorage>>) -> Self {
+        self.storage = Some(storage);
+        self
+    }
+
     pub fn start(&self) {
         let listener = TcpListener::bind(format!("127.0.0.1:{}", self.port))
             .expect("Failed to bind to address");
@@ -29,8 +47,9 @@ impl WebServer {
             match stream {
                 Ok(stream) => {
                     let static_dir = self.static_dir.clone();
+                    let storage = self.storage.clone();
                     thread::spawn(move || {
-                        Self::handle_connection(stream, static_dir);
+                        Self::handle_connection(stream, static_dir, storage);
                     });
                 }
                 Err(e) => {
@@ -40,15 +59,26 @@ impl WebServer {
         }
     }
 
-    fn handle_connection(mut stream: TcpStream, static_dir: String) {
-        let mut buffer = [0; 1024];
-        stream.read(&mut buffer).unwrap();
-
-        let request = String::from_utf8_lossy(&buffer[..]);
+    fn handle_connection(mut stream: TcpStream, static_dir: String, storage: Option<Arc<Mutex<IPFSStorage>>>) {
+        let request = Self::read_request_head(&mut stream);
         let request_line = request.lines().next().unwrap_or("");
 
         println!("📥 Запрос: {}", request_line);
 
+        let mut parts = request_line.split_whitespace();
+        if let (Some("GET"), Some(cid)) = (parts.next(), parts.next().and_then(|path| path.strip_prefix("/ipfs/"))) {
+            let headers = Self::parse_headers(&request);
+            let result = match &storage {
+                Some(storage) => Self::serve_content(&mut stream, storage, cid, &headers),
+                None => Self::send(&mut stream, Self::http_response("404 NOT FOUND", &[], b"Content storage is not enabled")),
+            };
+            if let Err(e) = result {
+                println!("⚠️ Failed to serve {}: {}", cid, e);
+            }
+            let _ = stream.flush();
+            return;
+        }
+
         let (status_line, filename, content_type) = Self::parse_request(request_line, &static_dir);
 
         let response = Self::build_response(status_line, &filename, content_type, &static_dir);
@@ -56,6 +86,157 @@ impl WebServer {
         stream.flush().unwrap();
     }
 
+    // Строка запроса и заголовки, до пустой строки
+    fn read_request_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut buffer = [0; 1024];
+        while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+            match stream.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => head.extend_from_slice(&buffer[..read]),
+            }
+        }
+        String::from_utf8_lossy(&head).into_owned()
+    }
+
+    fn parse_headers(request: &str) -> HashMap<String, String> {
+        request.lines()
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect()
+    }
+
+    // Ответ на GET /ipfs/{cid}: содержимое неизменно, поэтому ETag — сам CID.
+    // Содержимое чужое, поэтому браузер не угадывает тип (nosniff) и не исполняет его в контексте шлюза (sandbox).
+    // Хранилище блокируется только на время чтения описания: куски читаются и отдаются по одному уже без него.
+    fn serve_content<W: Write>(out: &mut W, storage: &Mutex<IPFSStorage>, cid: &str, headers: &HashMap<String, String>) -> Result<(), String> {
+        let (stored, store) = {
+            let storage = storage.lock().unwrap();
+            match storage.index.get(cid) {
+                Some(stored) => (stored.clone(), storage.blob_store()),
+                None => return Self::send(out, Self::http_response("404 NOT FOUND", &[], format!("Content {} is not in the local store", cid).as_bytes())),
+            }
+        };
+        let etag = format!("\"{}\"", cid);
+        let mut response_headers = vec![
+            ("ETag", etag.clone()),
+            ("Cache-Control", "public, max-age=31536000, immutable".to_string()),
+            ("Accept-Ranges", "bytes".to_string()),
+            ("X-Content-Type-Options", "nosniff".to_string()),
+            ("Content-Security-Policy", "sandbox".to_string()),
+        ];
+        if headers.get("if-none-match").is_some_and(|tags| tags.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*")) {
+            return Self::send(out, Self::http_response("304 NOT MODIFIED", &response_headers, b""));
+        }
+        let content_type = match Self::content_type_header(&stored.content_type) {
+            Ok(content_type) => content_type,
+            Err(e) => return Self::send(out, Self::http_response("500 INTERNAL SERVER ERROR", &[], e.as_bytes())),
+        };
+        let layout = match cid.parse::<Cid>().and_then(|cid| store.layout(&cid)) {
+            Ok(layout) => layout,
+            Err(e) => return Self::send(out, Self::http_response("500 INTERNAL SERVER ERROR", &[], e.as_bytes())),
+        };
+        response_headers.push(("Content-Type", content_type.to_string()));
+        let length: usize = layout.iter().map(|(_, size)| size).sum();
+
+        let (status, start, end) = match headers.get("range").and_then(|range| Self::parse_range(range, length)) {
+            Some(Ok((start, end))) => {
+                response_headers.push(("Content-Range", format!("bytes {}-{}/{}", start, end, length)));
+                ("206 PARTIAL CONTENT", start, end + 1)
+            }
+            Some(Err(())) => {
+                response_headers.push(("Content-Range", format!("bytes */{}", length)));
+                return Self::send(out, Self::http_response("416 RANGE NOT SATISFIABLE", &response_headers, b""));
+            }
+            None => ("200 OK", 0, length),
+        };
+        Self::send(out, Self::http_head(status, &response_headers, end - start))?;
+
+        // Куски проверяются по CID по мере отдачи; пропавший кусок обрывает ответ короче Content-Length
+        let mut offset = 0;
+        for (chunk, size) in layout {
+            let (chunk_start, chunk_end) = (offset, offset + size);
+            offset = chunk_end;
+            if chunk_end <= start {
+                continue;
+            }
+            if chunk_start >= end {
+                break;
+            }
+            let block = store.get_block(&chunk)?;
+            if block.len() != size {
+                return Err(format!("Chunk {} has {} bytes, expected {}", chunk, block.len(), size));
+            }
+            Self::send(out, block[start.max(chunk_start) - chunk_start..end.min(chunk_end) - chunk_start].to_vec())?;
+        }
+        Ok(())
+    }
+
+    fn send<W: Write>(out: &mut W, bytes: Vec<u8>) -> Result<(), String> {
+        out.write_all(&bytes).map_err(|e| format!("Failed to write response: {}", e))
+    }
+
+    // Content-Type из списка SAFE_CONTENT_TYPES (параметры вроде charset отбрасываются), иначе двоичные данные.
+    // Перевод строки в типе — попытка подставить заголовки, такое содержимое не отдается.
+    fn content_type_header(content_type: &str) -> Result<&'static str, String> {
+        if content_type.contains(['\r', '\n']) {
+            return Err("Stored content type contains a line break".to_string());
+        }
+        let essence = content_type.split(';').next().unwrap_or("").trim();
+        Ok(SAFE_CONTENT_TYPES.iter()
+            .find(|safe| safe.eq_ignore_ascii_case(essence))
+            .copied()
+            .unwrap_or("application/octet-stream"))
+    }
+
+    // Один диапазон "bytes=start-end", "bytes=start-" или "bytes=-suffix".
+    // None — заголовок не разобран или диапазонов несколько: отдается все содержимое.
+    fn parse_range(range: &str, length: usize) -> Option<Result<(usize, usize), ()>> {
+        let spec = range.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+        let (start, end) = spec.split_once('-')?;
+        let (start, end) = match (start.trim(), end.trim()) {
+            ("", suffix) => {
+                let suffix: usize = suffix.parse().ok()?;
+                if suffix == 0 || length == 0 {
+                    return Some(Err(()));
+                }
+                (length.saturating_sub(suffix), length - 1)
+            }
+            (start, "") => (start.parse().ok()?, length.saturating_sub(1)),
+            (start, end) => {
+                let (start, end): (usize, usize) = (start.parse().ok()?, end.parse().ok()?);
+                if start > end {
+                    return None;
+                }
+                (start, end.min(length.saturating_sub(1)))
+            }
+        };
+        if start >= length {
+            return Some(Err(()));
+        }
+        Some(Ok((start, end)))
+    }
+
+    fn http_response(status: &str, headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+        let mut response = Self::http_head(status, headers, body.len());
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn http_head(status: &str, headers: &[(&str, String)], content_length: usize) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n", status, content_length);
+        for (name, value) in headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+
     fn parse_request(request_line: &str, static_dir: &str) -> (String, String, String) {
         let parts: Vec<&str> = request_line.split_whitespace().collect();
         
@@ -171,6 +352,7 @@ impl WebServer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blob_store::CHUNK_SIZE;
 
     #[test]
     fn test_content_type_detection() {
@@ -191,4 +373,111 @@ mod tests {
         assert_eq!(status, "HTTP/1.1 200 OK\r\n");
         assert_eq!(filename, "owner_dashboard.html");
     }
+
+    fn request(storage: &Mutex<IPFSStorage>, cid: &str, headers: &[(&str, &str)]) -> String {
+        let headers = headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        let mut response = Vec::new();
+        WebServer::serve_content(&mut response, storage, cid, &headers).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    // Получатель ответа, который на каждой записи проверяет, что хранилище не заблокировано
+    struct UnlockedWriter<'a> {
+        storage: &'a Mutex<IPFSStorage>,
+        written: Vec<u8>,
+    }
+
+    impl Write for UnlockedWriter<'_> {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            assert!(self.storage.try_lock().is_ok(), "storage is locked while the response is written");
+            self.written.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ipfs_gateway() {
+        let dir = std::env::temp_dir().join(format!("web_gateway_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut storage = IPFSStorage::open(&dir, "http://127.0.0.1:8090/ipfs/".to_string()).unwrap();
+        let cid = storage.store_image(b"<svg>khinkali</svg>", "khinkali.svg").unwrap();
+        let storage = Mutex::new(storage);
+        let etag = format!("\"{}\"", cid);
+
+        let full = request(&storage, &cid, &[]);
+        assert!(full.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(full.contains("Content-Type: image/svg+xml\r\n"));
+        assert!(full.contains(&format!("ETag: {}\r\n", etag)));
+        assert!(full.ends_with("\r\n\r\n<svg>khinkali</svg>"));
+
+        let partial = request(&storage, &cid, &[("range", "bytes=5-12")]);
+        assert!(partial.starts_with("HTTP/1.1 206 PARTIAL CONTENT\r\n"));
+        assert!(partial.contains("Content-Range: bytes 5-12/19\r\n"));
+        assert!(partial.ends_with("\r\n\r\nkhinkali"));
+        assert!(request(&storage, &cid, &[("range", "bytes=-6")]).ends_with("</svg>"));
+        assert!(request(&storage, &cid, &[("range", "bytes=19-")]).starts_with("HTTP/1.1 416"));
+
+        assert!(request(&storage, &cid, &[("if-none-match", &etag)]).starts_with("HTTP/1.1 304"));
+        storage.lock().unwrap().collect_garbage().unwrap();
+        assert!(request(&storage, &cid, &[]).starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_gateway_content_types() {
+        let dir = std::env::temp_dir().join(format!("web_content_type_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut storage = IPFSStorage::open(&dir, "http://127.0.0.1:8090/ipfs/".to_string()).unwrap();
+        let png = storage.store_data("png bytes", "image/png").unwrap();
+        let html = storage.store_data("<script>alert(1)</script>", "text/html").unwrap();
+        let injected = storage.store_data("payload", "text/plain\r\nSet-Cookie: session=stolen").unwrap();
+        let storage = Mutex::new(storage);
+
+        let full = request(&storage, &png, &[]);
+        assert!(full.contains("Content-Type: image/png\r\n"));
+        assert!(full.contains("X-Content-Type-Options: nosniff\r\n"));
+        assert!(full.contains("Content-Security-Policy: sandbox\r\n"));
+
+        // Тип вне списка отдается как двоичные данные, перевод строки в типе — отказ
+        assert!(request(&storage, &html, &[]).contains("Content-Type: application/octet-stream\r\n"));
+        let rejected = request(&storage, &injected, &[]);
+        assert!(rejected.starts_with("HTTP/1.1 500"));
+        assert!(!rejected.contains("Set-Cookie"));
+    }
+
+    #[test]
+    fn test_gateway_streams_chunks_without_holding_storage() {
+        let dir = std::env::temp_dir().join(format!("web_stream_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut storage = IPFSStorage::open(&dir, "http://127.0.0.1:8090/ipfs/".to_string()).unwrap();
+        let image: Vec<u8> = (0..CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        let cid = storage.store_image(&image, "khachapuri.webp").unwrap();
+        let storage = Mutex::new(storage);
+
+        let read = |headers: &[(&str, &str)]| {
+            let headers = headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            let mut writer = UnlockedWriter { storage: &storage, written: Vec::new() };
+            WebServer::serve_content(&mut writer, &storage, &cid, &headers).unwrap();
+            let body_start = writer.written.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 4;
+            writer.written[body_start..].to_vec()
+        };
+        assert_eq!(read(&[]), image);
+        // Диапазон через границу кусков
+        let range = format!("bytes={}-{}", CHUNK_SIZE - 10, CHUNK_SIZE + 9);
+        assert_eq!(read(&[("range", &range)]), &image[CHUNK_SIZE - 10..=CHUNK_SIZE + 9]);
+    }
+
+    #[test]
+    fn test_range_parsing() {
+        assert_eq!(WebServer::parse_range("bytes=0-99", 50), Some(Ok((0, 49))));
+        assert_eq!(WebServer::parse_range("bytes=10-", 50), Some(Ok((10, 49))));
+        assert_eq!(WebServer::parse_range("bytes=-100", 50), Some(Ok((0, 49))));
+        assert_eq!(WebServer::parse_range("bytes=50-60", 50), Some(Err(())));
+        assert_eq!(WebServer::parse_range("bytes=0-1,5-6", 50), None);
+        assert_eq!(WebServer::parse_range("items=0-1", 50), None);
+        assert_eq!(WebServer::parse_range("bytes=9-3", 50), None);
+    }
 }
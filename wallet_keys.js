@@ -0,0 +1,103 @@
+// Ключ кошелька покупателя (Ed25519, WebCrypto).
+// Адрес кошелька выводится из публичного ключа так же, как на сервере:
+// "0x" + первые 20 байт SHA-256 от публичного ключа.
+// Изменяющие запросы от имени кошелька отправляются в обертке Signed с подписью и nonce.
+
+const WALLET_KEY_STORAGE = 'hotpot_wallet_key';
+const WALLET_NONCE_STORAGE = 'hotpot_wallet_nonce';
+
+function toHex(buffer) {
+    return Array.from(new Uint8Array(buffer)).map(b => b.toString(16).padStart(2, '0')).join('');
+}
+
+async function loadWalletKeys() {
+    const stored = localStorage.getItem(WALLET_KEY_STORAGE);
+    if (stored) {
+        const { privateJwk, publicJwk } = JSON.parse(stored);
+        return {
+            privateKey: await crypto.subtle.importKey('jwk', privateJwk, { name: 'Ed25519' }, true, ['sign']),
+            publicKey: await crypto.subtle.importKey('jwk', publicJwk, { name: 'Ed25519' }, true, ['verify']),
+        };
+    }
+
+    const keys = await crypto.subtle.generateKey({ name: 'Ed25519' }, true, ['sign', 'verify']);
+    localStorage.setItem(WALLET_KEY_STORAGE, JSON.stringify({
+        privateJwk: await crypto.subtle.exportKey('jwk', keys.privateKey),
+        publicJwk: await crypto.subtle.exportKey('jwk', keys.publicKey),
+    }));
+    return keys;
+}
+
+async function walletPublicKeyHex() {
+    const keys = await loadWalletKeys();
+    return toHex(await crypto.subtle.exportKey('raw', keys.publicKey));
+}
+
+async function walletAddress() {
+    const keys = await loadWalletKeys();
+    const raw = await crypto.subtle.exportKey('raw', keys.publicKey);
+    const hash = await crypto.subtle.digest('SHA-256', raw);
+    return '0x' + toHex(hash.slice(0, 20));
+}
+
+// Nonce должен строго расти для каждого кошелька
+function nextWalletNonce() {
+    const last = parseInt(localStorage.getItem(WALLET_NONCE_STORAGE) || '0', 10);
+    const nonce = Math.max(Date.now(), last + 1);
+    localStorage.setItem(WALLET_NONCE_STORAGE, String(nonce));
+    return nonce;
+}
+
+// Оборачивает запрос API в Signed: {payload, public_key, nonce, signature}
+async function signedRequest(request) {
+    const keys = await loadWalletKeys();
+    const payload = JSON.stringify(request);
+    const nonce = nextWalletNonce();
+    const message = new TextEncoder().encode(`hotpot-api|${nonce}|${payload}`);
+    const signature = await crypto.subtle.sign({ name: 'Ed25519' }, keys.privateKey, message);
+
+    return {
+        Signed: {
+            payload,
+            public_key: await walletPublicKeyHex(),
+            nonce,
+            signature: toHex(signature),
+        }
+    };
+}
+
+function fromHex(hex) {
+    return new Uint8Array(hex.match(/../g).map(byte => parseInt(byte, 16)));
+}
+
+async function sha256Parts(...parts) {
+    const bytes = new Uint8Array(parts.reduce((length, part) => length + part.length, 0));
+    let offset = 0;
+    for (const part of parts) {
+        bytes.set(part, offset);
+        offset += part.length;
+    }
+    return new Uint8Array(await crypto.subtle.digest('SHA-256', bytes));
+}
+
+// Проверка ответа GetInclusionProof без доступа к цепи: хеш заголовка считается как на сервере,
+// а лист (0x00 || leaf) через соседей (0x01 || левый || правый) ведет к merkle_root.
+// Хеш самого блока нужно сверить с доверенным источником отдельно.
+async function verifyInclusionProof({ header, leaf, proof }) {
+    const encoder = new TextEncoder();
+    // Слот и корень долей лидера есть только у PoS блоков и дописываются после nonce
+    const slot = header.slot == null ? '' : `|${header.slot}|${header.stakes_root}`;
+    const headerData = `${header.index}${header.merkle_root}${header.prev_hash}${header.timestamp}${header.validator}${header.stake_used}${header.nonce}${slot}`;
+    if (toHex(await sha256Parts(encoder.encode(headerData))) !== header.hash) {
+        return false;
+    }
+
+    let hash = await sha256Parts(new Uint8Array([0]), encoder.encode(leaf));
+    for (const step of proof.steps) {
+        const sibling = fromHex(step.hash);
+        hash = step.sibling_is_left
+            ? await sha256Parts(new Uint8Array([1]), sibling, hash)
+            : await sha256Parts(new Uint8Array([1]), hash, sibling);
+    }
+    return toHex(hash) === header.merkle_root;
+}